@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::env;
-use std::path::Path;
 use config::{Config, ConfigError, File, Environment};
-use std::convert::TryFrom;
+use std::collections::HashMap;
+
+use crate::utils::retry::RetryPolicy;
+use crate::zip_county_map::BUILT_IN_STATE;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AppConfig {
@@ -13,6 +15,16 @@ pub struct AppConfig {
     pub db_username: String,
     #[serde(default = "default_empty_string")]
     pub db_password: String,
+    // Read replica `verify` re-reads rows from (empty reads the primary), with the same credentials
+    #[serde(default = "default_empty_string")]
+    pub verify_odbc_dsn: String,
+    // How far, in seconds, the replica may lag behind the primary for its rows to be trusted
+    #[serde(default = "default_verify_max_lag_seconds")]
+    pub verify_max_lag_seconds: u64,
+    // Query run on the replica returning its current lag in seconds (empty assumes it lags by
+    // verify_max_lag_seconds)
+    #[serde(default = "default_empty_string")]
+    pub verify_lag_query: String,
     
     // Query parameters
     #[serde(default = "default_selection_query")]
@@ -21,6 +33,155 @@ pub struct AppConfig {
     pub update_query_template: String,
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
+    // Call this stored procedure per record instead of running update_query_template (empty disables)
+    #[serde(default = "default_empty_string")]
+    pub update_procedure: String,
+    // Argument templates for update_procedure, each bound as a parameter
+    #[serde(default = "default_update_procedure_arguments")]
+    pub update_procedure_arguments: Vec<String>,
+    // Transaction settings per phase; an empty isolation keeps the driver default
+    #[serde(default = "default_true")]
+    pub generation_autocommit: bool,
+    #[serde(default = "default_generation_isolation")]
+    pub generation_isolation: String,
+    #[serde(default = "default_true")]
+    pub execution_autocommit: bool,
+    #[serde(default = "default_execution_isolation")]
+    pub execution_isolation: String,
+    // With execution_autocommit off, commit this many successful statements as one transaction
+    // instead of each on its own (0 commits each)
+    #[serde(default)]
+    pub commit_batch_size: usize,
+    // Roll back the open batch and stop at the first failed statement, leaving its records Pending
+    #[serde(default)]
+    pub rollback_batch_on_error: bool,
+    // With commit_batch_size, send statements without bound values in packets of up to this many
+    // bytes of SQL per round trip (0 sends each on its own)
+    #[serde(default)]
+    pub statement_packet_bytes: usize,
+    // After execution changes more than this many rows, refresh optimizer statistics (0 disables)
+    #[serde(default)]
+    pub update_statistics_after_rows: u64,
+    // Statement run per modified table; {{table}} is replaced with the table name
+    #[serde(default = "default_update_statistics_statement")]
+    pub update_statistics_statement: String,
+    // Change request every generated query is traced to, and a free-form note (--ticket/--note override)
+    #[serde(default = "default_empty_string")]
+    pub ticket: String,
+    #[serde(default = "default_empty_string")]
+    pub note: String,
+    // Person running the tool, recorded next to the OS user and host (--operator overrides)
+    #[serde(default = "default_empty_string")]
+    pub operator: String,
+    // Retention purge run by the `purge` command
+    #[serde(default)]
+    pub purge: Option<PurgeConfig>,
+    // Compare a CSV of authoritative values with the table and generate updates for differences
+    #[serde(default)]
+    pub reconcile: Option<ReconcileConfig>,
+    // Staging copies of tables for rehearsing a run with the `trial` command
+    #[serde(default)]
+    pub trial: Option<TrialConfig>,
+    // Load the zip-county mapping from the state's workbook or a CSV instead of the built-in table
+    #[serde(default)]
+    pub zip_mapping: Option<ZipMappingConfig>,
+    // Generate upserts of the selected rows into a target table instead of templated updates
+    #[serde(default)]
+    pub upsert: Option<UpsertConfig>,
+    // Explain a sample of the pending statements before execute and stop on costly plans
+    #[serde(default)]
+    pub cost_guard: Option<CostGuardConfig>,
+    // After generation, check the selection's and statements' WHERE columns against the
+    // tables' indexes and suggest CREATE INDEX statements for unindexed ones
+    #[serde(default = "default_true")]
+    pub index_advisory: bool,
+    // Environment odbc_dsn belongs to, and the hosts allowed to change it
+    #[serde(default)]
+    pub environment: Option<EnvironmentConfig>,
+    // SQL checks that must pass before generation or execution starts
+    #[serde(default)]
+    pub preflight_queries: Vec<PreflightCheck>,
+    // Maps loaded by SQL before generation and exposed to templates by name
+    #[serde(default)]
+    pub lookups: HashMap<String, LookupConfig>,
+    // Ask for confirmation before generating when the selection matches more rows than this (0 disables)
+    #[serde(default)]
+    pub confirm_row_threshold: u64,
+    // Tables (`payroll`, `hr.payroll`) and columns (`ssn`, `employee.ssn`) that no generated or
+    // imported statement may modify; test and execute reject statements that would
+    #[serde(default)]
+    pub protected_tables: Vec<String>,
+    #[serde(default)]
+    pub protected_columns: Vec<String>,
+    // `report` flags keys of a run that the ledger shows corrected more often than this (0 disables)
+    #[serde(default = "default_recurrent_offender_threshold")]
+    pub recurrent_offender_threshold: usize,
+    // Execute reports the counts of every chunk of this many executed queries (0 disables)
+    #[serde(default)]
+    pub confirm_chunk_size: usize,
+    // Percentage of failed queries in a chunk above which execute asks whether to continue
+    #[serde(default = "default_confirm_chunk_error_rate")]
+    pub confirm_chunk_error_rate: f64,
+    // Execute refuses queries generated more than this many hours ago unless --allow-stale (0 disables)
+    #[serde(default = "default_max_query_age_hours")]
+    pub max_query_age_hours: u64,
+    // Execute re-validates each query and quarantines any whose SQL differs from what `test` passed
+    #[serde(default)]
+    pub revalidate_before_execute: bool,
+    // `test` also has the server prepare each statement, so Informix reports syntax errors and
+    // unknown tables or columns; nothing is executed
+    #[serde(default)]
+    pub test_with_prepare: bool,
+    // Journal each statement before and after it runs, and settle records an interrupted
+    // execution left InProgress from a verification read instead of quarantining them
+    #[serde(default)]
+    pub execution_journal: bool,
+    // Before each UPDATE runs, re-select the columns it assigns and write a rollback script
+    // to rollback/<key>.json for the `rollback` command
+    #[serde(default)]
+    pub capture_rollback: bool,
+    // Prefix each executed statement with a comment naming its run, key and ticket
+    #[serde(default)]
+    pub sql_provenance_comments: bool,
+    // Clauses joined by `and` a finished execution must meet, e.g. "success_rate >= 99.5 and
+    // constraint_violations == 0"; unmet ones fail the command (empty never fails)
+    #[serde(default)]
+    pub success_criteria: String,
+    // Failed executions a record gets before it's moved to dead_letter/ (0 retries it forever)
+    #[serde(default)]
+    pub max_record_attempts: u32,
+    // Lease each key in a store shared by all workers while executing it, so parallel or
+    // partitioned executions never run statements for the same key at once
+    #[serde(default)]
+    pub key_leases: bool,
+    // How long a lease lasts; a worker that dies holding one blocks its key this long
+    #[serde(default = "default_key_lease_seconds")]
+    pub key_lease_seconds: u64,
+    // What execute does with a query already applied by any earlier run: "skip", "warn" or "refuse"
+    #[serde(default = "default_replay_policy")]
+    pub replay_policy: String,
+    // What a statement that affected no rows counts as: "ok", "warn" or "fail"
+    #[serde(default = "default_zero_rows_policy")]
+    pub zero_rows_policy: String,
+    // Add the originally selected values of changed columns to each UPDATE's WHERE clause
+    #[serde(default)]
+    pub optimistic_concurrency: bool,
+    // Only move the progress bar's position per record, keeping its message (slow terminals)
+    #[serde(default)]
+    pub quiet_progress: bool,
+    // Append generated queries to queries.jsonl instead of writing one file per key
+    #[serde(default)]
+    pub query_manifest: bool,
+    // Where run state is kept: "files" (one query file per key), "jsonl" (query_manifest) or
+    // "sqlite" (processed records in state.sqlite, with the sqlite feature)
+    #[serde(default = "default_state_backend")]
+    pub state_backend: String,
+    // Write every fetched row to selection_snapshot.csv.gz during generation
+    #[serde(default)]
+    pub selection_snapshot: bool,
+    // Save generation progress every this many keys so `generate --resume` can carry on (0 disables)
+    #[serde(default = "default_generation_checkpoint_interval")]
+    pub generation_checkpoint_interval: usize,
     #[serde(default = "default_timeout_seconds")]
     pub timeout_seconds: u64,
     
@@ -30,13 +191,312 @@ pub struct AppConfig {
     #[serde(default = "default_check_again_after")]
     pub check_again_after: u64,
 
+    // Liveness heartbeat for external monitors and the status command (empty writes
+    // heartbeat.json in the results directory)
+    #[serde(default = "default_empty_string")]
+    pub heartbeat_path: String,
+    #[serde(default = "default_heartbeat_interval_seconds")]
+    pub heartbeat_interval_seconds: u64,
+    #[serde(default = "default_heartbeat_stale_seconds")]
+    pub heartbeat_stale_seconds: u64,
+
+    // Span log: append each executed record's timing and outcome as a JSON line (empty disables)
+    #[serde(default = "default_empty_string")]
+    pub span_log_path: String,
+
+    // Continuous mode only generates queries for keys that newly match after the first cycle
+    #[serde(default)]
+    pub continuous_delta: bool,
+    // Continuous mode gives up after this many failed cycles in a row
+    #[serde(default = "default_max_consecutive_failures")]
+    pub max_consecutive_failures: u32,
+    // Shell command run after each successful query with the record as JSON on stdin (empty disables)
+    #[serde(default = "default_empty_string")]
+    pub post_execution_command: String,
+    // Shell command run for operator notifications (empty disables)
+    #[serde(default = "default_empty_string")]
+    pub notify_command: String,
+
+    // Retry policies for transient failures
+    #[serde(default)]
+    pub connection_retry: RetryPolicy,
+    #[serde(default)]
+    pub execution_retry: RetryPolicy,
+    #[serde(default = "default_notification_retry")]
+    pub notification_retry: RetryPolicy,
+    // SQLSTATE prefixes that stop execution at the first failure, since every later query would fail the same way
+    #[serde(default = "default_abort_sqlstates")]
+    pub abort_sqlstates: Vec<String>,
+
     // Field name mappings (new fields)
     #[serde(default = "default_key_field_name")]
     pub key_field_name: String,
+    // Columns of a composite key, returned first by the selection in this order (overrides key_field_name)
+    #[serde(default)]
+    pub key_field_names: Vec<String>,
+    // Selected columns whose values may be substituted into statements and stored in query
+    // files (empty allows all); the others only filter the selection or feed lookups. The key
+    // columns are always allowed.
+    #[serde(default)]
+    pub substitution_columns: Vec<String>,
+    // Append ORDER BY the key columns to a selection query that has no ORDER BY
+    #[serde(default)]
+    pub order_selection_by_key: bool,
+    // Write keys of integer columns as unquoted numbers in generated WHERE clauses
+    #[serde(default = "default_true")]
+    pub typed_key_predicates: bool,
     #[serde(default = "default_zip_field_name")]
     pub zip_field_name: String,
     #[serde(default = "default_county_field_name")]
     pub county_field_name: String,
+    // State(s) whose county codes the county commands may write, comma-separated postal codes
+    #[serde(default = "default_state")]
+    pub state: String,
+    // States the county commands work on instead of `state`: postal codes or 2-digit FIPS codes,
+    // comma-separated, or "all" for every state in the zip mapping
+    #[serde(default)]
+    pub state_filter: String,
+    // update-county-codes writes 5-digit state + county FIPS codes instead of the county's 3 digits
+    #[serde(default)]
+    pub full_fips_codes: bool,
+
+    // The client sets DELIMIDENT, so double quotes delimit identifiers and generated SQL quotes names that need it
+    #[serde(default)]
+    pub delimident: bool,
+
+    // SQL dialect of the target database ("informix" or "sqlserver")
+    #[serde(default = "default_dialect")]
+    pub dialect: String,
+
+    // Server load thresholds checked via sysmaster during execution (0 disables a check)
+    #[serde(default)]
+    pub max_active_sessions: u64,
+    #[serde(default)]
+    pub max_lock_count: u64,
+    #[serde(default)]
+    pub max_log_usage_percent: f64,
+    #[serde(default = "default_load_check_interval")]
+    pub load_check_interval: usize,
+    #[serde(default = "default_load_pause_seconds")]
+    pub load_pause_seconds: u64,
+    // Commit early and pause when logical-log usage gets within this margin of LTXHWM (Informix)
+    #[serde(default)]
+    pub long_transaction_guard: bool,
+    #[serde(default = "default_long_transaction_margin_percent")]
+    pub long_transaction_margin_percent: f64,
+    // Delay statements while their rolling average latency exceeds the baseline by the multiplier
+    #[serde(default)]
+    pub adaptive_pacing: bool,
+    #[serde(default = "default_pacing_window")]
+    pub pacing_window: usize,
+    #[serde(default = "default_pacing_latency_multiplier")]
+    pub pacing_latency_multiplier: f64,
+    #[serde(default = "default_pacing_max_delay_millis")]
+    pub pacing_max_delay_millis: u64,
+    // Baseline latency; 0 learns it from the first pacing_window statements of the run
+    #[serde(default)]
+    pub pacing_baseline_millis: u64,
+
+    // Column name patterns whose values are masked in logs, progress output and error records
+    #[serde(default = "default_mask_column_patterns")]
+    pub mask_column_patterns: Vec<String>,
+    // Regular expressions whose matches are masked in the same output, whatever column they came from
+    #[serde(default)]
+    pub redaction_patterns: Vec<String>,
+
+    // Adaptive fetch batch sizing within bounds
+    #[serde(default)]
+    pub adaptive_batch_size: bool,
+    #[serde(default = "default_min_batch_size")]
+    pub min_batch_size: usize,
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    #[serde(default = "default_target_fetch_millis")]
+    pub target_fetch_millis: u64,
+    #[serde(default = "default_max_fetch_buffer_mb")]
+    pub max_fetch_buffer_mb: usize,
+    // With commit_batch_size as well, the commit interval is tuned while statements run so each
+    // batch's transaction takes about target_commit_millis
+    #[serde(default = "default_min_commit_batch_size")]
+    pub min_commit_batch_size: usize,
+    #[serde(default = "default_max_commit_batch_size")]
+    pub max_commit_batch_size: usize,
+    #[serde(default = "default_target_commit_millis")]
+    pub target_commit_millis: u64,
+
+    // Text buffer sizing for fetched columns and what to do when a value doesn't fit
+    #[serde(default = "default_max_text_length")]
+    pub max_text_length: usize,
+    #[serde(default)]
+    pub column_max_lengths: HashMap<String, usize>,
+    #[serde(default = "default_truncation_policy")]
+    pub truncation_policy: String,
+    // Template values longer than this are stored out-of-line and bound as parameters (0 disables)
+    #[serde(default = "default_inline_value_limit")]
+    pub inline_value_limit: usize,
+
+    // Timezone for log lines, record timestamps and reports ("local", "utc" or an offset like "-08:00")
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+
+    // Character set of text fetched from the database ("utf-8", "iso-8859-1" or "cp1252")
+    #[serde(default = "default_client_encoding")]
+    pub client_encoding: String,
+
+    // Normalization of numeric and date columns before template substitution
+    #[serde(default)]
+    pub column_decimal_places: HashMap<String, usize>,
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: String,
+    #[serde(default = "default_db_date_format")]
+    pub db_date_format: String,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    #[serde(default = "default_timestamp_format")]
+    pub timestamp_format: String,
+}
+
+// A lookup map loaded once per generation: the query's first column maps to its second,
+// and the entry for the row's `key` placeholder value is available to templates as {{name}}
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LookupConfig {
+    pub query: String,
+    #[serde(default = "default_lookup_key")]
+    pub key: String,
+    // Used when the row's value has no entry; without one the row is skipped
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+// A retention purge: rows of `table` matching `condition` are deleted `chunk_size` at a
+// time, each chunk committed on its own, until none remain
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PurgeConfig {
+    pub table: String,
+    pub condition: String,
+    #[serde(default = "default_purge_chunk_size")]
+    pub chunk_size: usize,
+    // "limit" deletes the first chunk_size matching rows each time; "rowid" walks rowid ranges (Informix)
+    #[serde(default = "default_purge_strategy")]
+    pub strategy: String,
+    // Pause between chunks so replication and other sessions keep up
+    #[serde(default)]
+    pub pause_millis: u64,
+    // Stop after this many chunks (0 runs until no rows remain)
+    #[serde(default)]
+    pub max_chunks: usize,
+}
+
+// The zip-county mapping as the state publishes it, read by the county commands
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ZipMappingConfig {
+    // A .xlsx workbook or .csv file with a header row
+    pub file: String,
+    // Worksheet to read; empty reads the first one
+    #[serde(default)]
+    pub sheet: String,
+    // State of rows without a state column
+    #[serde(default = "default_state")]
+    pub state: String,
+    // Header of each column; empty infers it from the header names and values
+    #[serde(default)]
+    pub zip_column: String,
+    #[serde(default)]
+    pub county_column: String,
+    #[serde(default)]
+    pub fips_column: String,
+    #[serde(default)]
+    pub name_column: String,
+    #[serde(default)]
+    pub division_column: String,
+    #[serde(default)]
+    pub state_column: String,
+    // "hud" for HUD's USPS ZIP-county crosswalk, "table" for a zip-county table; empty
+    // recognises the crosswalk by its RES_RATIO column
+    #[serde(default)]
+    pub format: String,
+}
+
+// File-vs-table reconciliation: `columns` of `table` are compared with the same-named CSV
+// columns for each key in the file, and rows that differ are corrected
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReconcileConfig {
+    pub table: String,
+    pub columns: Vec<String>,
+    // Keys per `WHERE key IN (...)` selection
+    #[serde(default = "default_reconcile_chunk_size")]
+    pub chunk_size: usize,
+}
+
+// Trial execution: statements on each production table (key) are rewritten to the staging
+// copy (value), e.g. `customers = "customers_trial"`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TrialConfig {
+    pub tables: HashMap<String, String>,
+}
+
+// Upsert generation for reconciliation loads: each selected row (key first, then one value per
+// entry of `columns`) is written into `table`, updating the row with that key or inserting it
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UpsertConfig {
+    pub table: String,
+    pub key_column: String,
+    pub columns: Vec<String>,
+    // "merge" generates a MERGE statement; "update_insert" an UPDATE with an INSERT run when it matches nothing
+    #[serde(default = "default_upsert_strategy")]
+    pub strategy: String,
+}
+
+// Optimizer estimates gathered for a sample of statements before execution, so an
+// UPDATE that would scan the whole table for every row is caught before it runs
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CostGuardConfig {
+    // File the server writes plans to (SET EXPLAIN FILE TO); must be readable from this machine
+    pub explain_file: String,
+    #[serde(default = "default_cost_guard_sample_size")]
+    pub sample_size: usize,
+    // Estimated cost above which a plan is flagged (0 only flags sequential scans)
+    #[serde(default)]
+    pub max_estimated_cost: u64,
+    // "abort" stops execute before anything runs; "warn" logs the plans and carries on
+    #[serde(default = "default_cost_guard_action")]
+    pub action: String,
+}
+
+// Label of the environment the configured DSN connects to. Commands that change a protected
+// environment's data only run when --env names it and on one of its allowed hosts.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EnvironmentConfig {
+    pub name: String,
+    // Host names allowed to change the environment's data; empty allows any non-protected one
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    // Defaults to true for "production" and "prod"
+    #[serde(default)]
+    pub protected: Option<bool>,
+}
+
+impl EnvironmentConfig {
+    pub fn is_protected(&self) -> bool {
+        self.protected.unwrap_or_else(|| matches!(self.name.trim().to_lowercase().as_str(), "production" | "prod"))
+    }
+}
+
+// A query whose first column of the first row must meet every expectation set
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PreflightCheck {
+    pub name: String,
+    pub query: String,
+    // Exact value, compared after trimming
+    #[serde(default)]
+    pub expect: Option<String>,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    // "generate" and/or "execute"; empty runs the check before both
+    #[serde(default)]
+    pub phases: Vec<String>,
 }
 
 // Default function implementations
@@ -60,6 +520,10 @@ fn default_timeout_seconds() -> u64 {
     30
 }
 
+fn default_key_lease_seconds() -> u64 {
+    900
+}
+
 fn default_data_path() -> String {
     "processed_records.json".to_string()
 }
@@ -68,6 +532,23 @@ fn default_check_again_after() -> u64 {
     1800 // 30 minutes in seconds
 }
 
+fn default_heartbeat_interval_seconds() -> u64 {
+    5
+}
+
+fn default_heartbeat_stale_seconds() -> u64 {
+    120
+}
+
+fn default_max_consecutive_failures() -> u32 {
+    5
+}
+
+// Connection exceptions: the connection is gone, so the rest of the run can only fail
+fn default_abort_sqlstates() -> Vec<String> {
+    vec!["08".to_string()]
+}
+
 fn default_key_field_name() -> String {
     "key_field".to_string()
 }
@@ -80,15 +561,202 @@ fn default_county_field_name() -> String {
     "county".to_string()
 }
 
-impl AppConfig {
-    pub fn from_env_or_file() -> Result<Self, ConfigError> {
-        let mut config = Config::default();
+// The built-in zip-county mapping is Washington's
+fn default_state() -> String {
+    BUILT_IN_STATE.to_string()
+}
 
-        // Try to load from a config file (e.g., config.toml)
-        config.merge(File::with_name("config").required(false))?;
+fn default_dialect() -> String {
+    "informix".to_string()
+}
 
-        // Override with environment variables if they exist
-        config.merge(Environment::with_prefix("IBP"))?;
+fn default_verify_max_lag_seconds() -> u64 {
+    60
+}
+
+fn default_load_check_interval() -> usize {
+    500 // records between sysmaster checks
+}
+
+fn default_long_transaction_margin_percent() -> f64 {
+    10.0
+}
+
+fn default_load_pause_seconds() -> u64 {
+    30
+}
+
+fn default_pacing_window() -> usize {
+    20
+}
+
+fn default_pacing_latency_multiplier() -> f64 {
+    3.0
+}
+
+fn default_pacing_max_delay_millis() -> u64 {
+    5000
+}
+
+fn default_min_batch_size() -> usize {
+    10
+}
+
+fn default_max_batch_size() -> usize {
+    5000
+}
+
+fn default_target_fetch_millis() -> u64 {
+    500
+}
+
+fn default_max_fetch_buffer_mb() -> usize {
+    64
+}
+
+fn default_min_commit_batch_size() -> usize {
+    10
+}
+
+fn default_max_commit_batch_size() -> usize {
+    5000
+}
+
+fn default_target_commit_millis() -> u64 {
+    2000
+}
+
+fn default_max_text_length() -> usize {
+    4096
+}
+
+fn default_truncation_policy() -> String {
+    "refetch".to_string()
+}
+
+fn default_update_statistics_statement() -> String {
+    "UPDATE STATISTICS MEDIUM FOR TABLE {{table}}".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_generation_isolation() -> String {
+    "read uncommitted".to_string()
+}
+
+fn default_execution_isolation() -> String {
+    "read committed".to_string()
+}
+
+fn default_update_procedure_arguments() -> Vec<String> {
+    vec!["{{key}}".to_string()]
+}
+
+fn default_recurrent_offender_threshold() -> usize {
+    3
+}
+
+fn default_confirm_chunk_error_rate() -> f64 {
+    5.0
+}
+
+fn default_notification_retry() -> RetryPolicy {
+    RetryPolicy::with_attempts(2)
+}
+
+fn default_purge_chunk_size() -> usize {
+    5000
+}
+
+fn default_purge_strategy() -> String {
+    "limit".to_string()
+}
+
+fn default_reconcile_chunk_size() -> usize {
+    500
+}
+
+fn default_upsert_strategy() -> String {
+    "merge".to_string()
+}
+
+fn default_cost_guard_sample_size() -> usize {
+    5
+}
+
+fn default_cost_guard_action() -> String {
+    "abort".to_string()
+}
+
+fn default_lookup_key() -> String {
+    "field1".to_string()
+}
+
+fn default_generation_checkpoint_interval() -> usize {
+    1000
+}
+
+fn default_max_query_age_hours() -> u64 {
+    72
+}
+
+fn default_replay_policy() -> String {
+    "skip".to_string()
+}
+
+fn default_state_backend() -> String {
+    "files".to_string()
+}
+
+fn default_zero_rows_policy() -> String {
+    "ok".to_string()
+}
+
+fn default_inline_value_limit() -> usize {
+    2048
+}
+
+fn default_timezone() -> String {
+    "local".to_string()
+}
+
+fn default_client_encoding() -> String {
+    "utf-8".to_string()
+}
+
+fn default_decimal_separator() -> String {
+    ".".to_string()
+}
+
+fn default_db_date_format() -> String {
+    "%m/%d/%Y".to_string() // DBDATE=MDY4/
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+fn default_timestamp_format() -> String {
+    "%Y-%m-%d %H:%M:%S%.f".to_string()
+}
+
+fn default_mask_column_patterns() -> Vec<String> {
+    ["ssn", "social_sec", "dob", "birth", "first_name", "last_name", "full_name"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+impl AppConfig {
+    pub fn from_env_or_file() -> Result<Self, ConfigError> {
+        let config = Config::builder()
+            // Try to load from a config file (e.g., config.toml)
+            .add_source(File::with_name("config").required(false))
+            // Override with environment variables if they exist
+            .add_source(Environment::with_prefix("IBP"))
+            .build()?;
 
         // Parse the config into the AppConfig struct
         let app_config: AppConfig = config.try_deserialize()?;
@@ -96,6 +764,40 @@ impl AppConfig {
         Ok(app_config)
     }
     
+    // Configured states, upper-cased: "wa, or" -> ["WA", "OR"]
+    pub fn states(&self) -> Vec<String> {
+        self.state.split(',')
+            .map(|state| state.trim().to_uppercase())
+            .filter(|state| !state.is_empty())
+            .collect()
+    }
+    
+    // Postal codes of the states in state_filter, with FIPS codes translated; empty for "all"
+    pub fn filtered_states(&self) -> Result<Vec<String>, String> {
+        let filter = self.state_filter.trim();
+        if filter.eq_ignore_ascii_case("all") || filter == "*" {
+            return Ok(Vec::new());
+        }
+        filter.split(',')
+            .map(|state| state.trim())
+            .filter(|state| !state.is_empty())
+            .map(|state| if state.chars().all(|c| c.is_ascii_digit()) {
+                crate::zip_county_map::state_for_fips(&format!("{:0>2}", state))
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("state_filter: '{}' isn't a state FIPS code", state))
+            } else if crate::zip_county_map::fips_for_state(state).is_some() {
+                Ok(state.to_uppercase())
+            } else {
+                Err(format!("state_filter: '{}' isn't a state postal code", state))
+            })
+            .collect()
+    }
+    
+    // Whether any sysmaster load threshold has been configured
+    pub fn load_monitoring_enabled(&self) -> bool {
+        self.max_active_sessions > 0 || self.max_lock_count > 0 || self.max_log_usage_percent > 0.0
+    }
+    
     // Get the ODBC DSN, preferring the config file, then environment, and failing if neither
     pub fn get_odbc_dsn(&self) -> String {
         if !self.odbc_dsn.is_empty() {
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::db::fetch::FetchStats;
+use crate::utils::time;
+
+// Batch sizes chosen by the tuner, persisted next to the processed records file
+// so later runs and continuous-mode cycles start from the tuned values
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TuningState {
+    #[serde(default)]
+    fetch_batch_size: usize,
+    #[serde(default)]
+    average_batch_millis: f64,
+    // Commit interval the last execution ended with (0 before one has been tuned)
+    #[serde(default)]
+    commit_batch_size: usize,
+    updated: String,
+}
+
+fn state_path(config: &AppConfig) -> PathBuf {
+    Path::new(&config.data_path).with_file_name("batch_tuning.json")
+}
+
+fn load_state(config: &AppConfig) -> Option<TuningState> {
+    let content = fs::read_to_string(state_path(config)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_state(config: &AppConfig, state: &TuningState) {
+    match serde_json::to_string_pretty(state) {
+        Ok(json) => {
+            if let Err(e) = fs::write(state_path(config), json) {
+                log::warn!("Failed to save batch tuning state: {}", e);
+            }
+        },
+        Err(e) => log::warn!("Failed to serialize batch tuning state: {}", e),
+    }
+}
+
+fn clamp(config: &AppConfig, batch_size: usize) -> usize {
+    let min = config.min_batch_size.max(1);
+    let max = config.max_batch_size.max(min);
+    batch_size.clamp(min, max)
+}
+
+// Batch size to use for the next fetch
+pub fn current_batch_size(config: &AppConfig) -> usize {
+    if !config.adaptive_batch_size {
+        return config.batch_size;
+    }
+    
+    let batch_size = load_state(config)
+        .map(|state| state.fetch_batch_size)
+        .filter(|&batch_size| batch_size > 0)
+        .unwrap_or(config.batch_size);
+    
+    clamp(config, batch_size)
+}
+
+// Work out the next batch size from the latency and memory of a completed fetch
+pub fn next_batch_size(config: &AppConfig, stats: &FetchStats) -> usize {
+    let current = stats.batch_size.max(1);
+    let average_millis = stats.average_batch_millis();
+    let target_millis = config.target_fetch_millis as f64;
+    
+    let mut proposed = if stats.batches < 2 {
+        // A single partial batch says little about throughput
+        current
+    } else if average_millis > target_millis {
+        current / 2
+    } else if average_millis < target_millis / 2.0 && stats.rows >= current * 2 {
+        current * 2
+    } else {
+        current
+    };
+    
+    // Keep the row buffer under the configured memory budget
+    let bytes_per_row = stats.buffer_bytes / current;
+    if let Some(max_rows_for_memory) = (config.max_fetch_buffer_mb * 1024 * 1024).checked_div(bytes_per_row) {
+        proposed = proposed.min(max_rows_for_memory.max(1));
+    }
+    
+    clamp(config, proposed)
+}
+
+// Record a completed fetch and persist the tuned batch size for the next one. The cursor's
+// row array is bound for the whole fetch, so a new size takes effect from the next fetch.
+pub fn observe(config: &AppConfig, stats: &FetchStats) {
+    if !config.adaptive_batch_size {
+        return;
+    }
+    
+    let next = next_batch_size(config, stats);
+    
+    log::info!(
+        "Fetch of {} rows in {} batches of {} took {:.1} ms/batch ({} KB buffer); next batch size {}",
+        stats.rows, stats.batches, stats.batch_size, stats.average_batch_millis(),
+        stats.buffer_bytes / 1024, next
+    );
+    
+    let mut state = load_state(config).unwrap_or_default();
+    state.fetch_batch_size = next;
+    state.average_batch_millis = stats.average_batch_millis();
+    state.updated = time::now_rfc3339();
+    save_state(config, &state);
+}
+
+fn tunes_commits(config: &AppConfig) -> bool {
+    config.adaptive_batch_size && config.commit_batch_size > 0
+}
+
+fn clamp_commit(config: &AppConfig, commit_batch_size: usize) -> usize {
+    let min = config.min_commit_batch_size.max(1);
+    let max = config.max_commit_batch_size.max(min);
+    commit_batch_size.clamp(min, max)
+}
+
+// Commit interval to start execution with: the one the last execution ended with, or
+// commit_batch_size
+pub fn initial_commit_batch_size(config: &AppConfig) -> usize {
+    if !tunes_commits(config) {
+        return config.commit_batch_size;
+    }
+    
+    let commit_batch_size = load_state(config)
+        .map(|state| state.commit_batch_size)
+        .filter(|&commit_batch_size| commit_batch_size > 0)
+        .unwrap_or(config.commit_batch_size);
+    
+    clamp_commit(config, commit_batch_size)
+}
+
+// Work out the interval for the next batch from how long a committed one held its transaction
+pub fn next_commit_batch_size(config: &AppConfig, current: usize, statements: usize, elapsed: Duration) -> usize {
+    if !tunes_commits(config) {
+        return current;
+    }
+    
+    let elapsed_millis = elapsed.as_millis() as u64;
+    let target_millis = config.target_commit_millis;
+    
+    let proposed = if elapsed_millis > target_millis {
+        // However few statements it had, a slow batch holds its locks too long
+        current / 2
+    } else if elapsed_millis < target_millis / 2 && statements >= current {
+        // Only a full batch shows there was room for more; a chunk prompt or the end of the
+        // run also commits short ones
+        current * 2
+    } else {
+        current
+    };
+    
+    clamp_commit(config, proposed)
+}
+
+// Persist the commit interval execution ended with, so the next run starts from it
+pub fn save_commit_batch_size(config: &AppConfig, commit_batch_size: usize) {
+    if !tunes_commits(config) {
+        return;
+    }
+    
+    let mut state = load_state(config).unwrap_or_default();
+    state.commit_batch_size = commit_batch_size;
+    state.updated = time::now_rfc3339();
+    save_state(config, &state);
+}
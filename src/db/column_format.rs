@@ -0,0 +1,238 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use odbc_api::DataType;
+
+use crate::config::AppConfig;
+
+// How a fetched column's text is normalized before templates see it. Values are still
+// fetched as text (so truncation can be detected from the indicators), then rewritten from
+// the driver's locale-dependent rendering into an exact, fixed representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnFormat {
+    Text,
+    // DECIMAL/NUMERIC/MONEY rendered with exactly `scale` fraction digits
+    Decimal { scale: usize },
+    Integer,
+    Date,
+    Timestamp,
+}
+
+impl ColumnFormat {
+    // Pick the format for a column from its reported type and any configured precision override
+    pub fn for_column(config: &AppConfig, column_name: &str, data_type: DataType) -> Self {
+        let override_scale = config.column_decimal_places.iter()
+            .find(|(column, _)| column.eq_ignore_ascii_case(column_name))
+            .map(|(_, places)| *places);
+        
+        match data_type {
+            DataType::Decimal { scale, .. } | DataType::Numeric { scale, .. } => match override_scale {
+                Some(places) => ColumnFormat::Decimal { scale: places },
+                None if scale <= 0 => ColumnFormat::Integer,
+                None => ColumnFormat::Decimal { scale: scale as usize },
+            },
+            DataType::Integer | DataType::SmallInt | DataType::BigInt | DataType::TinyInt => ColumnFormat::Integer,
+            DataType::Date => ColumnFormat::Date,
+            DataType::Timestamp { .. } => ColumnFormat::Timestamp,
+            _ => match override_scale {
+                Some(places) => ColumnFormat::Decimal { scale: places },
+                None => ColumnFormat::Text,
+            },
+        }
+    }
+    
+    // Normalize one fetched value. Empty (NULL) values and values that can't be parsed are
+    // passed through unchanged so nothing is silently invented.
+    pub fn apply(&self, config: &AppConfig, value: String) -> String {
+        if value.trim().is_empty() {
+            return value;
+        }
+        
+        let formatted = match self {
+            ColumnFormat::Text => return value,
+            ColumnFormat::Decimal { scale } => format_decimal(&value, &config.decimal_separator, *scale),
+            ColumnFormat::Integer => format_decimal(&value, &config.decimal_separator, 0),
+            ColumnFormat::Date => parse_date(&value, &config.db_date_format)
+                .map(|date| date.format(&config.date_format).to_string()),
+            ColumnFormat::Timestamp => parse_timestamp(&value, &config.db_date_format)
+                .map(|timestamp| timestamp.format(&config.timestamp_format).to_string()),
+        };
+        
+        match formatted {
+            Some(formatted) => formatted,
+            None => {
+                log::warn!("Could not normalize value '{}' as {:?}; using the driver's text", value, self);
+                value
+            }
+        }
+    }
+    
+    // SQL literal comparing equal to a normalized value of this column, whatever the server's
+    // DBDATE. A quoted date string is read in the DBDATE order (so '2024-01-31' fails under
+    // MDY4), so dates go through TO_DATE with an explicit format; a DATETIME string is read in
+    // its fixed ISO layout, so timestamps are rewritten to that.
+    pub fn sql_literal(&self, config: &AppConfig, value: &str) -> String {
+        let literal = match self {
+            ColumnFormat::Date => NaiveDate::parse_from_str(value.trim(), &config.date_format).ok()
+                .or_else(|| parse_date(value, &config.db_date_format))
+                .map(|date| format!("TO_DATE('{}', '%Y-%m-%d')", date.format("%Y-%m-%d"))),
+            ColumnFormat::Timestamp => NaiveDateTime::parse_from_str(value.trim(), &config.timestamp_format).ok()
+                .or_else(|| parse_timestamp(value, &config.db_date_format))
+                .map(|timestamp| format!("'{}'", timestamp.format("%Y-%m-%d %H:%M:%S%.f"))),
+            _ => None,
+        };
+        
+        literal.unwrap_or_else(|| format!("'{}'", value.replace('\'', "''")))
+    }
+}
+
+// Rewrite a driver-formatted number ("$1,234.5", "1.234,50", "-12") to plain digits with
+// exactly `scale` fraction digits, rounding half away from zero on the digit string itself
+fn format_decimal(value: &str, decimal_separator: &str, scale: usize) -> Option<String> {
+    let separator = decimal_separator.chars().next().unwrap_or('.');
+    let trimmed = value.trim();
+    let negative = trimmed.starts_with('-') || trimmed.ends_with('-') || (trimmed.starts_with('(') && trimmed.ends_with(')'));
+    
+    let mut int_digits = String::new();
+    let mut frac_digits = String::new();
+    let mut seen_separator = false;
+    
+    for c in trimmed.chars() {
+        if c.is_ascii_digit() {
+            if seen_separator { frac_digits.push(c) } else { int_digits.push(c) }
+        } else if c == separator && !seen_separator {
+            seen_separator = true;
+        } else if c.is_whitespace() || "-+(),.'$€£¥".contains(c) {
+            // sign, grouping and currency characters carry no digits
+            continue;
+        } else {
+            return None;
+        }
+    }
+    
+    if int_digits.is_empty() && frac_digits.is_empty() {
+        return None;
+    }
+    
+    // Keep scale + 1 fraction digits so the extra one decides rounding
+    let round_up = frac_digits.len() > scale && frac_digits.as_bytes()[scale] >= b'5';
+    frac_digits.truncate(scale);
+    while frac_digits.len() < scale {
+        frac_digits.push('0');
+    }
+    
+    let mut digits: Vec<u8> = format!("{}{}", int_digits, frac_digits).into_bytes();
+    if round_up {
+        let mut index = digits.len();
+        loop {
+            if index == 0 {
+                digits.insert(0, b'1');
+                break;
+            }
+            index -= 1;
+            if digits[index] == b'9' {
+                digits[index] = b'0';
+            } else {
+                digits[index] += 1;
+                break;
+            }
+        }
+    }
+    
+    let digits = String::from_utf8(digits).ok()?;
+    let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+    let int_part = int_part.trim_start_matches('0');
+    let int_part = if int_part.is_empty() { "0" } else { int_part };
+    let is_zero = int_part == "0" && frac_part.chars().all(|c| c == '0');
+    let sign = if negative && !is_zero { "-" } else { "" };
+    
+    if scale == 0 {
+        Some(format!("{}{}", sign, int_part))
+    } else {
+        Some(format!("{}{}.{}", sign, int_part, frac_part))
+    }
+}
+
+// Accept ISO dates as well as the database's DBDATE rendering
+fn parse_date(value: &str, db_date_format: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, db_date_format))
+        .ok()
+}
+
+fn parse_timestamp(value: &str, db_date_format: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M"))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, &format!("{} %H:%M:%S%.f", db_date_format)))
+        .ok()
+        .or_else(|| parse_date(value, db_date_format).and_then(|date| date.and_hms_opt(0, 0, 0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn config(db_date_format: &str) -> AppConfig {
+        let mut config: AppConfig = serde_json::from_str("{}").unwrap();
+        config.db_date_format = db_date_format.to_string();
+        config
+    }
+    
+    #[test]
+    fn decimal_keeps_exactly_the_scale() {
+        assert_eq!(format_decimal("12.5", ".", 2).as_deref(), Some("12.50"));
+        assert_eq!(format_decimal("12.345", ".", 2).as_deref(), Some("12.35"));
+        assert_eq!(format_decimal("12.344", ".", 2).as_deref(), Some("12.34"));
+        assert_eq!(format_decimal(".5", ".", 0).as_deref(), Some("1"));
+        assert_eq!(format_decimal("0012", ".", 0).as_deref(), Some("12"));
+    }
+    
+    #[test]
+    fn decimal_rounds_on_the_digit_string() {
+        // 0.125 has no exact binary form, so float rounding could give 0.12
+        assert_eq!(format_decimal("0.125", ".", 2).as_deref(), Some("0.13"));
+        assert_eq!(format_decimal("99.995", ".", 2).as_deref(), Some("100.00"));
+        assert_eq!(format_decimal("9.5", ".", 0).as_deref(), Some("10"));
+        assert_eq!(format_decimal("12345678901234567890.129", ".", 2).as_deref(), Some("12345678901234567890.13"));
+    }
+    
+    #[test]
+    fn decimal_reads_signs_grouping_and_currency() {
+        assert_eq!(format_decimal("$1,234.5", ".", 2).as_deref(), Some("1234.50"));
+        assert_eq!(format_decimal("1.234,50", ",", 2).as_deref(), Some("1234.50"));
+        assert_eq!(format_decimal("-12", ".", 2).as_deref(), Some("-12.00"));
+        assert_eq!(format_decimal("12.50-", ".", 2).as_deref(), Some("-12.50"));
+        assert_eq!(format_decimal("(3.10)", ".", 2).as_deref(), Some("-3.10"));
+        assert_eq!(format_decimal("-0.001", ".", 2).as_deref(), Some("0.00"));
+    }
+    
+    #[test]
+    fn decimal_refuses_text_that_isnt_a_number() {
+        assert_eq!(format_decimal("12a", ".", 2), None);
+        assert_eq!(format_decimal("$", ".", 2), None);
+        assert_eq!(format_decimal("1e5", ".", 2), None);
+    }
+    
+    #[test]
+    fn date_literals_dont_depend_on_dbdate() {
+        let config = config("%m/%d/%Y");
+        let date = ColumnFormat::Date.apply(&config, "01/31/2024".to_string());
+        assert_eq!(date, "2024-01-31");
+        assert_eq!(ColumnFormat::Date.sql_literal(&config, &date), "TO_DATE('2024-01-31', '%Y-%m-%d')");
+    }
+    
+    #[test]
+    fn timestamp_literals_use_the_iso_layout() {
+        let config = config("%d.%m.%Y");
+        let timestamp = ColumnFormat::Timestamp.apply(&config, "31.01.2024 10:15:00.5".to_string());
+        assert_eq!(ColumnFormat::Timestamp.sql_literal(&config, &timestamp), "'2024-01-31 10:15:00.500'");
+    }
+    
+    #[test]
+    fn other_literals_are_quoted_text() {
+        let config = config("%m/%d/%Y");
+        assert_eq!(ColumnFormat::Text.sql_literal(&config, "O'Brien"), "'O''Brien'");
+        assert_eq!(ColumnFormat::Decimal { scale: 2 }.sql_literal(&config, "12.50"), "'12.50'");
+        assert_eq!(ColumnFormat::Date.sql_literal(&config, "not a date"), "'not a date'");
+    }
+}
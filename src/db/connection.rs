@@ -1,8 +1,11 @@
-use odbc_api::{Connection, Environment};
+use odbc_api::{handles::Statement, sys, Connection, Cursor, Environment, ParameterRefCollection};
 use std::error::Error;
 use std::sync::Arc;
 
 use crate::config::AppConfig;
+use crate::db::dialect::{self, IsolationLevel};
+use crate::db::fetch::{ensure_not_truncated, text_buffers, UTILITY_MAX_TEXT_LENGTH};
+use crate::utils::encoding;
 
 // Use a global static environment to ensure it lives for the entire program
 lazy_static::lazy_static! {
@@ -10,23 +13,63 @@ lazy_static::lazy_static! {
 }
 
 pub fn create_connection(config: &AppConfig) -> Result<Connection<'static>, Box<dyn Error>> {
-    // Use the global environment
-    let connection = ENVIRONMENT.connect(
-        &config.get_odbc_dsn(),
-        &config.get_db_username(),
-        &config.get_db_password(),
+    connect(config, &config.get_odbc_dsn(), "the database")
+}
+
+// Connection for `verify`: the read replica when verify_odbc_dsn names one, so re-reading the
+// rows of a large run adds no load to the primary. The flag says whether it is the replica.
+pub fn create_verify_connection(config: &AppConfig) -> Result<(Connection<'static>, bool), Box<dyn Error>> {
+    if config.verify_odbc_dsn.trim().is_empty() {
+        return Ok((create_connection(config)?, false));
+    }
+    Ok((connect(config, config.verify_odbc_dsn.trim(), "the read replica")?, true))
+}
+
+fn connect(config: &AppConfig, dsn: &str, description: &str) -> Result<Connection<'static>, Box<dyn Error>> {
+    // Use the global environment; a server that is briefly unreachable gets a few more tries
+    let connection = config.connection_retry.run(
+        &format!("Connecting to {}", description),
+        || ENVIRONMENT.connect(
+            dsn,
+            &config.get_db_username(),
+            &config.get_db_password(),
+        ),
+        |_| true,
     )?;
     
-    log::info!("Successfully connected to the database");
+    log::info!("Successfully connected to {}", description);
     
     Ok(connection)
 }
 
+// Apply a phase's autocommit and isolation settings so behaviour doesn't depend on DSN defaults.
+// odbc-api only exposes SQL_ATTR_AUTOCOMMIT on an open connection, so isolation is set with the
+// dialect's equivalent statement, which is what the Informix driver does for SQL_ATTR_TXN_ISOLATION.
+pub fn apply_session_settings(
+    conn: &Connection,
+    config: &AppConfig,
+    phase: &str,
+    autocommit: bool,
+    isolation: &str,
+) -> Result<(), Box<dyn Error>> {
+    conn.set_autocommit(autocommit)?;
+    
+    if let Some(level) = IsolationLevel::from_name(isolation)? {
+        let statement = dialect::from_config(config)?.isolation_statement(level);
+        conn.execute(&statement, ())?;
+        log::info!("{} session: autocommit {}, {}", phase, if autocommit { "on" } else { "off" }, statement);
+    } else {
+        log::info!("{} session: autocommit {}, driver default isolation", phase, if autocommit { "on" } else { "off" });
+    }
+    
+    Ok(())
+}
+
 pub fn test_connection(config: &AppConfig) -> Result<(), Box<dyn Error>> {
     let connection = create_connection(config)?;
     
     // Test a simple query
-    let query_result = connection.execute("SELECT 1 FROM systables WHERE tabid = 1", ())?;
+    let query_result = connection.execute(dialect::current().connection_test_query(), ())?;
     
     if query_result.is_some() {
         log::info!("Database connection test successful");
@@ -36,4 +79,99 @@ pub fn test_connection(config: &AppConfig) -> Result<(), Box<dyn Error>> {
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+// Run a query expected to return a single row and return its columns as text.
+// Returns None when the query produces no rows.
+pub fn query_first_row(conn: &Connection, query: &str) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    let cursor = match conn.execute(query, ())? {
+        Some(cursor) => cursor,
+        None => return Ok(None),
+    };
+    
+    let mut buffers = text_buffers(&cursor, 1, UTILITY_MAX_TEXT_LENGTH)?;
+    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
+    
+    if let Some(batch) = row_set_cursor.fetch()? {
+        if batch.num_rows() > 0 {
+            ensure_not_truncated(batch, 0)?;
+            let values = (0..batch.num_cols())
+                .map(|col_index| encoding::decode(batch.at(col_index, 0).unwrap_or(&[])))
+                .collect();
+            return Ok(Some(values));
+        }
+    }
+    
+    Ok(None)
+}
+
+// Run a query and return every row's columns as text
+pub fn query_rows(conn: &Connection, query: &str) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let cursor = match conn.execute(query, ())? {
+        Some(cursor) => cursor,
+        None => return Ok(Vec::new()),
+    };
+    
+    let mut buffers = text_buffers(&cursor, 100, UTILITY_MAX_TEXT_LENGTH)?;
+    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
+    let mut rows = Vec::new();
+    while let Some(batch) = row_set_cursor.fetch()? {
+        for row_index in 0..batch.num_rows() {
+            ensure_not_truncated(batch, row_index)?;
+            rows.push((0..batch.num_cols()).map(|col_index| encoding::decode(batch.at(col_index, row_index).unwrap_or(&[]))).collect());
+        }
+    }
+    Ok(rows)
+}
+
+// Execute a DML statement and report how many rows it affected, if the driver can tell.
+// odbc-api doesn't expose SQLRowCount, so the statement handle is queried directly.
+pub fn execute_with_row_count(
+    conn: &Connection,
+    query: &str,
+    params: impl ParameterRefCollection,
+) -> Result<Option<usize>, odbc_api::Error> {
+    let mut preallocated = conn.preallocate()?;
+    
+    // Any cursor returned is closed right away; only the row count matters here
+    preallocated.execute(query, params)?;
+    
+    let statement = preallocated.into_statement();
+    Ok(current_row_count(&statement))
+}
+
+// Execute several statements separated by semicolons in one round trip and return each one's
+// row count, in order. A statement that fails after the first ends the results early, so fewer
+// counts than statements means the rest of the packet didn't run as sent.
+pub fn execute_packet_with_row_counts(conn: &Connection, packet: &str) -> Result<Vec<Option<usize>>, odbc_api::Error> {
+    let mut preallocated = conn.preallocate()?;
+    preallocated.execute(packet, ())?;
+    
+    let statement = preallocated.into_statement();
+    let mut row_counts = Vec::new();
+    loop {
+        row_counts.push(current_row_count(&statement));
+        
+        // NO_DATA after the last statement's result; ERROR when a later statement failed
+        let more = unsafe { sys::SQLMoreResults(statement.as_sys()) };
+        if more != sys::SqlReturn::SUCCESS && more != sys::SqlReturn::SUCCESS_WITH_INFO {
+            break;
+        }
+    }
+    Ok(row_counts)
+}
+
+// Row count of the statement's current result, None when the driver can't say (-1, as for a
+// procedure call). A dialect that answers a statement matching nothing with SQL_NO_DATA
+// leaves no count to read at all, which there means no rows changed.
+fn current_row_count(statement: &impl Statement) -> Option<usize> {
+    let mut row_count: sys::Len = 0;
+    let result = unsafe { sys::SQLRowCount(statement.as_sys(), &mut row_count) };
+    if result == sys::SqlReturn::SUCCESS || result == sys::SqlReturn::SUCCESS_WITH_INFO {
+        usize::try_from(row_count).ok()
+    } else if dialect::current().zero_rows_returns_no_data() {
+        Some(0)
+    } else {
+        None
+    }
+}
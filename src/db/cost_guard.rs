@@ -0,0 +1,191 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use regex::Regex;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{AppConfig, CostGuardConfig};
+use crate::db::dialect;
+use crate::db::query_types::{QueryRecord, QueryStatus};
+use crate::db::sql_helpers::{parse_update_statement, QualifiedName};
+use crate::files::json_handler::read_query_file;
+use crate::ui;
+
+// What the optimizer reported for one explained statement
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanEstimate {
+    pub query: String,
+    pub estimated_cost: Option<u64>,
+    // Tables the plan reads with a sequential scan, as named in the plan
+    pub sequential_scans: Vec<String>,
+}
+
+// Explain a sample of the pending UPDATE statements (plan only, nothing is executed) and
+// flag plans that exceed `max_estimated_cost` or scan the table being updated sequentially.
+// With action "abort" a flagged plan stops execute before any statement runs.
+pub fn check_query_costs(
+    conn: &Connection,
+    config: &AppConfig,
+    query_files: &[PathBuf],
+    progress_bar: &ProgressBar,
+) -> Result<(), Box<dyn Error>> {
+    let guard = match &config.cost_guard {
+        Some(guard) => guard,
+        None => return Ok(()),
+    };
+    let abort = match guard.action.trim().to_lowercase().as_str() {
+        "" | "abort" => true,
+        "warn" => false,
+        other => return Err(format!("Unknown cost_guard action '{}' (expected 'abort' or 'warn')", other).into()),
+    };
+    
+    let dialect = dialect::from_config(config)?;
+    let (explain_on, explain_off) = match dialect.explain_statements(&guard.explain_file) {
+        Some(statements) => statements,
+        None => {
+            log::warn!("The {} dialect can't explain statements; skipping the cost guard", dialect.name());
+            return Ok(());
+        }
+    };
+    
+    let sample = sample_statements(query_files, guard.sample_size);
+    if sample.is_empty() {
+        return Ok(());
+    }
+    ui::progress::print_with_progress(progress_bar, &format!("Explaining {} sample statements...", sample.len()));
+    
+    // The server appends to the file, so only what this check adds is read back
+    let offset = fs::metadata(&guard.explain_file).map(|metadata| metadata.len() as usize).unwrap_or(0);
+    explain_sample(conn, &explain_on, explain_off, &sample)?;
+    
+    let output = fs::read(&guard.explain_file)
+        .map_err(|e| format!("Cannot read the explain file {} (the server writes it; is it visible from here?): {}", guard.explain_file, e))?;
+    let plans = parse_explain_output(&String::from_utf8_lossy(&output[offset.min(output.len())..]));
+    if plans.len() < sample.len() {
+        log::warn!("Explain file {} has {} plans for {} sample statements", guard.explain_file, plans.len(), sample.len());
+    }
+    
+    let flagged: Vec<String> = sample.iter()
+        .zip(&plans)
+        .filter_map(|(record, plan)| plan_problem(guard, record, plan).map(|problem| format!("key {}: {}", record.key, problem)))
+        .collect();
+    
+    for plan in &plans {
+        log::info!("Plan estimate: cost {:?}, sequential scans {:?} for {}", plan.estimated_cost, plan.sequential_scans, plan.query);
+    }
+    if flagged.is_empty() {
+        log::info!("Cost guard: {} sample plans within limits", plans.len());
+        return Ok(());
+    }
+    
+    for problem in &flagged {
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[33mCost guard: {}\x1b[0m", problem));
+        log::warn!("Cost guard: {}", problem);
+    }
+    if abort {
+        return Err(format!(
+            "{} of {} sample statements have costly plans ({}); add an index or set cost_guard action = \"warn\"",
+            flagged.len(), sample.len(), flagged[0]
+        ).into());
+    }
+    Ok(())
+}
+
+// Up to `size` pending UPDATE statements spread evenly over the run. Statements with bound
+// parameters and procedure calls are left out: the first can't be explained as text, and
+// AVOID_EXECUTE doesn't stop a procedure from running.
+fn sample_statements(query_files: &[PathBuf], size: usize) -> Vec<QueryRecord> {
+    let candidates: Vec<QueryRecord> = query_files.iter()
+        .filter_map(|path| read_query_file(path).ok())
+        .filter(|record| record.status == QueryStatus::Pending)
+        .filter(|record| record.parameter_files.is_empty() && record.parameters.is_empty())
+        .filter(|record| parse_update_statement(&record.query).is_some())
+        .collect();
+    
+    if size == 0 || candidates.len() <= size {
+        return candidates;
+    }
+    let step = candidates.len() as f64 / size as f64;
+    (0..size).map(|i| candidates[(i as f64 * step) as usize].clone()).collect()
+}
+
+// Run the sample in plan-only mode, turning explain off again even when a statement fails
+fn explain_sample(conn: &Connection, explain_on: &[String], explain_off: &str, sample: &[QueryRecord]) -> Result<(), Box<dyn Error>> {
+    let mut result = Ok(());
+    for statement in explain_on {
+        if let Err(e) = conn.execute(statement, ()) {
+            result = Err(format!("{} failed: {}", statement, e));
+            break;
+        }
+    }
+    if result.is_ok() {
+        for record in sample {
+            if let Err(e) = conn.execute(&record.query, ()) {
+                result = Err(format!("Explaining the query for key {} failed: {}", record.key, e));
+                break;
+            }
+        }
+    }
+    
+    if let Err(e) = conn.execute(explain_off, ()) {
+        log::error!("{} failed: {}", explain_off, e);
+    }
+    result.map_err(|e| e.into())
+}
+
+// Why a plan is flagged, if it is
+fn plan_problem(guard: &CostGuardConfig, record: &QueryRecord, plan: &PlanEstimate) -> Option<String> {
+    if let Some(table) = parse_update_statement(&record.query).and_then(|parts| QualifiedName::parse(&parts.table)) {
+        let scanned = plan.sequential_scans.iter()
+            .any(|scan| QualifiedName::parse(scan).is_some_and(|scan| scan.name == table.name));
+        if scanned {
+            return Some(format!("sequential scan of {} (estimated cost {})", table.name, plan.estimated_cost.unwrap_or(0)));
+        }
+    }
+    match plan.estimated_cost {
+        Some(cost) if guard.max_estimated_cost > 0 && cost > guard.max_estimated_cost => {
+            Some(format!("estimated cost {} exceeds max_estimated_cost {}", cost, guard.max_estimated_cost))
+        },
+        _ => None,
+    }
+}
+
+// Split Informix SET EXPLAIN output into one estimate per QUERY: section
+pub fn parse_explain_output(text: &str) -> Vec<PlanEstimate> {
+    let cost_pattern = Regex::new(r"(?i)^\s*Estimated Cost:\s*(\d+)").unwrap();
+    let scan_pattern = Regex::new(r"(?i)^\s*\d+\)\s+(\S+):\s+SEQUENTIAL SCAN").unwrap();
+    
+    let mut plans: Vec<PlanEstimate> = Vec::new();
+    let mut in_query_text = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("QUERY:") {
+            plans.push(PlanEstimate::default());
+            in_query_text = false;
+            continue;
+        }
+        let plan = match plans.last_mut() {
+            Some(plan) => plan,
+            None => continue,
+        };
+        
+        // The statement follows a "------" rule and ends at the first blank line
+        if line.trim_start().starts_with("------") && plan.query.is_empty() {
+            in_query_text = true;
+        } else if in_query_text {
+            if line.trim().is_empty() {
+                in_query_text = false;
+            } else {
+                if !plan.query.is_empty() {
+                    plan.query.push(' ');
+                }
+                plan.query.push_str(line.trim());
+            }
+        } else if let Some(captures) = cost_pattern.captures(line) {
+            plan.estimated_cost = captures[1].parse().ok();
+        } else if let Some(captures) = scan_pattern.captures(line) {
+            plan.sequential_scans.push(captures[1].to_string());
+        }
+    }
+    plans
+}
@@ -1,12 +1,21 @@
-use odbc_api::{buffers::TextRowSet, Connection, Cursor};
+use odbc_api::Connection;
 use indicatif::ProgressBar;
 use std::error::Error;
 
 use crate::config::AppConfig;
-use crate::db::query_types::{QueryRecord, QueryStatus};
-use crate::db::sql_helpers::{find_column_index_by_name, extract_table_name};
+use crate::db::fetch::for_each_row;
+use crate::db::keys::key_literal;
+use crate::db::query_types::{CountyChange, QueryRecord};
+use crate::db::result_columns::ResultColumns;
+use crate::db::sql_helpers::{
+    add_concurrency_guard, extract_table_name, format_identifier, normalize_identifier,
+    parse_set_assignments, parse_update_statement, QualifiedName,
+};
 use crate::files::json_handler::save_query_file;
+use crate::files::refused_zips::{RefusedZips, REFUSED_ZIPS_FILE_NAME};
 use crate::ui;
+use crate::utils::memo::Memo;
+use crate::zip_county_map::{zip_county_map, ZipCountyInfo};
 
 pub fn update_county_by_zip(
     conn: &Connection,
@@ -16,101 +25,89 @@ pub fn update_county_by_zip(
 ) -> Result<(usize, usize), Box<dyn Error>> {
     ui::progress::print_with_progress(progress_bar, "Finding records with mismatched county and zip codes...");
     
-    // Load the zip-county mapping
-    let zip_county_map = crate::zip_county_map::load_zip_county_map();
+    // Zip codes repeat across rows, so each distinct one is mapped once
+    let mut zip_lookups = Memo::new("zip lookups");
+    let states = configured_states(config, config.full_fips_codes)?;
+    let mut refused = RefusedZips::default();
     
     // Query to find records with zip codes but potentially incorrect county codes
     let selection_query = "SELECT key_field, zip_code, county FROM table_name WHERE zip_code IS NOT NULL";
     
-    // Execute the selection query
-    let cursor = match conn.execute(selection_query, ())? {
-        Some(cursor) => cursor,
-        None => {
-            ui::progress::print_with_progress(progress_bar, "No records found with zip codes.");
-            log::warn!("Selection query returned no results");
-            return Ok((0, 0));
-        }
-    };
-    
-    // Set up buffer for fetching rows
-    let mut buffers = TextRowSet::for_cursor(config.batch_size, &cursor, Some(4096))?;
-    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
-    
     let mut count = 0;
     let mut mismatch_count = 0;
-    let mut total_records = 0;
     
     ui::progress::print_with_progress(progress_bar, "Generating update queries for records with mismatched county codes...");
     
-    // Process each batch of rows
-    while let Some(batch) = row_set_cursor.fetch()? {
-        total_records += batch.num_rows();
-        progress_bar.set_length(total_records as u64);
+    // Process each fetched row
+    let stats = for_each_row(conn, selection_query, config, progress_bar, |row| {
+        progress_bar.set_position(count as u64);
         
-        for row_index in 0..batch.num_rows() {
-            progress_bar.set_position(count as u64);
-            
-            // Get key field value
-            let key_field = String::from_utf8_lossy(batch.at(0, row_index).unwrap_or(&[])).to_string();
-            
-            // Get zip code
-            let zip_code = String::from_utf8_lossy(batch.at(1, row_index).unwrap_or(&[])).to_string();
-            
-            // Get current county code
-            let current_county = String::from_utf8_lossy(batch.at(2, row_index).unwrap_or(&[])).to_string();
+        // Get key field value
+        let key_field = row[0].clone();
+        
+        // Get zip code
+        let zip_code = row[1].clone();
+        
+        // Get current county code
+        let current_county = row[2].clone();
+        
+        // 5-digit zip and its mapping, worked out once per distinct zip code
+        let (zip5, zip_info) = zip_lookups.get_or_insert_with(zip_code.clone(), |zip_code| lookup_zip(zip_code)).clone();
+        let zip_info = within_states(zip_info, &states, &zip5, &key_field, &mut refused);
+        
+        // Look up the correct FIPS code for this zip
+        if let Some(zip_info) = zip_info {
+            // A 3-digit county code only means something within one state
+            let correct_fips = &if config.full_fips_codes { zip_info.full_fips_code() } else { zip_info.fips_code.clone() };
             
-            // Extract 5-digit zip from zip+4 if needed
-            let zip5 = if zip_code.contains('-') {
-                zip_code.split('-').next().unwrap_or("").to_string()
-            } else if zip_code.len() >= 5 {
-                zip_code[0..5].to_string()
-            } else {
-                zip_code.clone()
-            };
+            // Update progress bar message but don't print to console
+            ui::progress::update_message(progress_bar, format!("Checking key: {}, zip: {}, county: {}", key_field, zip5, current_county));
             
-            // Look up the correct FIPS code for this zip
-            if let Some(zip_info) = zip_county_map.get(&zip5) {
-                let correct_fips = &zip_info.fips_code;
+            // Only generate update query if county code doesn't match the correct FIPS code
+            if current_county != *correct_fips {
+                mismatch_count += 1;
+                
+                // Generate update query
+                let query = format!(
+                    "UPDATE table_name SET county = '{}' WHERE key_field = '{}'",
+                    correct_fips, key_field
+                );
                 
-                // Update progress bar message but don't print to console
-                ui::progress::update_message(progress_bar, format!("Checking key: {}, zip: {}, county: {}", key_field, zip5, current_county));
+                // Create query record, guarded against the county having changed since selection
+                let mut query_record = QueryRecord::new(key_field.clone(), query);
+                query_record.annotate(config);
+                query_record.county_change = Some(CountyChange {
+                    zip: zip_code.clone(),
+                    old_county: current_county.clone(),
+                    new_county: correct_fips.clone(),
+                });
+                guard_county_update(config, &mut query_record, &current_county);
                 
-                // Only generate update query if county code doesn't match the correct FIPS code
-                if current_county != *correct_fips {
-                    mismatch_count += 1;
-                    
-                    // Generate update query
-                    let query = format!(
-                        "UPDATE table_name SET county = '{}' WHERE key_field = '{}'",
-                        correct_fips, key_field
-                    );
-                    
-                    // Create query record
-                    let query_record = QueryRecord {
-                        key: key_field.clone(),
-                        query,
-                        status: QueryStatus::Pending,
-                        result: None,
-                        timestamp: None,
-                    };
-                    
-                    // Save query to file
-                    let file_path = format!("{}/{}.json", results_dir, key_field);
-                    save_query_file(&file_path, &query_record)?;
-                    
-                    log::info!("Generated update query for key: {}, changing county from '{}' to '{}'", 
-                               key_field, current_county, correct_fips);
-                }
+                // Save query to file
+                let file_path = format!("{}/{}.json", results_dir, key_field);
+                save_query_file(&file_path, &query_record)?;
+                
+                log::info!("Generated update query for key: {}, changing county from '{}' to '{}'", 
+                           key_field, current_county, correct_fips);
             }
-            
-            count += 1;
         }
+        
+        count += 1;
+        Ok(())
+    })?;
+    
+    if stats.is_none() {
+        ui::progress::print_with_progress(progress_bar, "No records found with zip codes.");
+        log::warn!("Selection query returned no results");
+        return Ok((0, 0));
     }
+    report_refused_zips(&refused, &states, results_dir, progress_bar)?;
     
     // Print summary
     let summary = format!("Checked {} records, found {} with mismatched county codes", count, mismatch_count);
     ui::progress::print_with_progress(progress_bar, &format!("\x1b[32m{}\x1b[0m", summary));
     log::info!("{}", summary);
+    zip_lookups.log_metrics();
     
     Ok((count, mismatch_count))
 }
@@ -123,120 +120,203 @@ pub fn update_county_code_from_countyfp(
 ) -> Result<(usize, usize), Box<dyn Error>> {
     ui::progress::update_message(progress_bar, "Finding records with county codes to update...");
     
-    // Load the zip-county mapping
-    let zip_county_map = crate::zip_county_map::load_zip_county_map();
+    // Zip codes repeat across rows, so each distinct one is mapped once
+    let mut zip_lookups = Memo::new("zip lookups");
+    let states = configured_states(config, false)?;
+    let mut refused = RefusedZips::default();
     
     // Query to find records with zip codes but potentially incorrect county codes
     let selection_query = &config.selection_query;
     
-    // Execute the selection query
-    let cursor = match conn.execute(selection_query, ())? {
-        Some(cursor) => cursor,
-        None => {
-            ui::progress::print_with_progress(progress_bar, "No records found with selection query.");
-            log::warn!("Selection query returned no results");
-            return Ok((0, 0));
-        }
-    };
-    
-    // Set up buffer for fetching rows
-    let mut buffers = TextRowSet::for_cursor(config.batch_size, &cursor, Some(4096))?;
-    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
-    
     let mut count = 0;
     let mut mismatch_count = 0;
-    let mut total_records = 0;
     
     ui::progress::print_with_progress(progress_bar, "Generating update queries for records with county codes...");
     
-    // Process each batch of rows
-    while let Some(batch) = row_set_cursor.fetch()? {
-        total_records += batch.num_rows();
-        progress_bar.set_length(total_records as u64);
+    // Locate the columns by the names the driver reports for the selection, and the table to
+    // update from the query, so a query they can't be found in fails before the fetch starts
+    let columns = ResultColumns::resolve(conn, config, selection_query, progress_bar)?;
+    let key_col_idx = columns.require(&config.key_field_name, "key_field_name")?;
+    let county_col_idx = columns.require(&config.county_field_name, "county_field_name")?;
+    let zip_col_idx = columns.require(&config.zip_field_name, "zip_field_name")?;
+    
+    // Keep any owner prefix and quote names that need it
+    let table_name = extract_table_name(&config.selection_query)
+        .map_err(|e| format!("Can't determine the table to update from selection_query: {}", e))?;
+    let table_name = QualifiedName::parse(&table_name)
+        .map(|name| name.to_sql(config.delimident))
+        .unwrap_or(table_name);
+    
+    log::info!("Using column indices: key_field={}, county_field={}, zip_field={} (from {:?})",
+              key_col_idx, county_col_idx, zip_col_idx, columns.source);
+    
+    // Process each fetched row
+    let stats = for_each_row(conn, selection_query, config, progress_bar, |row| {
+        progress_bar.set_position(count as u64);
         
-        // Parse column indices from the selection query
-        let key_col_idx = find_column_index_by_name(&config.selection_query, &config.key_field_name);
-        let county_col_idx = find_column_index_by_name(&config.selection_query, &config.county_field_name);
-        let zip_col_idx = find_column_index_by_name(&config.selection_query, &config.zip_field_name);
+        // Get key field value (always use the first column as the key)
+        let key_field = row[key_col_idx].clone();
         
-        log::info!("Using column indices: key_field={}, county_field={}, zip_field={}",
-                  key_col_idx, county_col_idx, zip_col_idx);
+        // Get zip code and current county code from the determined column indices
+        let zip_code = row[zip_col_idx].clone();
+        let current_county = row[county_col_idx].clone();
         
-        for row_index in 0..batch.num_rows() {
-            progress_bar.set_position(count as u64);
-            
-            // Get key field value (always use the first column as the key)
-            let key_field = String::from_utf8_lossy(batch.at(key_col_idx, row_index).unwrap_or(&[])).to_string();
-            
-            // Get zip code and current county code from the determined column indices
-            let zip_code = String::from_utf8_lossy(batch.at(zip_col_idx, row_index).unwrap_or(&[])).to_string();
-            let current_county = String::from_utf8_lossy(batch.at(county_col_idx, row_index).unwrap_or(&[])).to_string();
-            
-            // Skip if no zip code
-            if zip_code.is_empty() {
-                continue;
-            }
-            
-            // Extract 5-digit zip from zip+4 if needed
-            let zip5 = if zip_code.contains('-') {
-                zip_code.split('-').next().unwrap_or("").to_string()
-            } else if zip_code.len() >= 5 {
-                zip_code[0..5].to_string()
-            } else {
-                zip_code.clone()
-            };
-            
-            // Update progress bar message but don't print to console
-            ui::progress::update_message(progress_bar, 
-                format!("Checking key: {}, zip: {}, county: {}", key_field, zip5, current_county));
+        // Skip if no zip code
+        if zip_code.is_empty() {
+            return Ok(());
+        }
+        
+        // 5-digit zip and its mapping, worked out once per distinct zip code
+        let (zip5, zip_info) = zip_lookups.get_or_insert_with(zip_code.clone(), |zip_code| lookup_zip(zip_code)).clone();
+        let zip_info = within_states(zip_info, &states, &zip5, &key_field, &mut refused);
+        
+        // Update progress bar message but don't print to console
+        ui::progress::update_message(progress_bar, 
+            format!("Checking key: {}, zip: {}, county: {}", key_field, zip5, current_county));
+        
+        // Look up the correct county code for this zip
+        if let Some(zip_info) = zip_info {
+            let correct_county_code = &zip_info.county_code;
             
-            // Look up the correct county code for this zip
-            if let Some(zip_info) = zip_county_map.get(&zip5) {
-                let correct_county_code = &zip_info.county_code;
+            // Only generate update query if county code doesn't match the correct county code
+            if current_county != *correct_county_code {
+                mismatch_count += 1;
+                
+                // Generate update query with the correct field names from config
+                let query = format!(
+                    "UPDATE {} SET {} = '{}' WHERE {} = {}",
+                    table_name, 
+                    format_identifier(&normalize_identifier(&config.county_field_name), config.delimident),
+                    correct_county_code, 
+                    format_identifier(&normalize_identifier(&config.key_field_name), config.delimident),
+                    key_literal(config, &key_field, columns.is_integer(key_col_idx))
+                );
+                
+                // Create query record, guarded against the county having changed since selection
+                let mut query_record = QueryRecord::new(key_field.clone(), query);
+                query_record.annotate(config);
+                query_record.county_change = Some(CountyChange {
+                    zip: zip_code.clone(),
+                    old_county: current_county.clone(),
+                    new_county: correct_county_code.clone(),
+                });
+                guard_county_update(config, &mut query_record, &current_county);
                 
-                // Only generate update query if county code doesn't match the correct county code
-                if current_county != *correct_county_code {
-                    mismatch_count += 1;
-                    
-                    // Generate update query using the table name from the selection_query
-                    let table_name = extract_table_name(&config.selection_query);
-                    
-                    // Generate update query with the correct field names from config
-                    let query = format!(
-                        "UPDATE {} SET {} = '{}' WHERE {} = '{}'",
-                        table_name, 
-                        config.county_field_name,
-                        correct_county_code, 
-                        config.key_field_name, 
-                        key_field
-                    );
-                    
-                    // Create query record
-                    let query_record = QueryRecord {
-                        key: key_field.clone(),
-                        query,
-                        status: QueryStatus::Pending,
-                        result: None,
-                        timestamp: None,
-                    };
-                    
-                    // Save query to file
-                    let file_path = format!("{}/{}.json", results_dir, key_field);
-                    save_query_file(&file_path, &query_record)?;
-                    
-                    log::info!("Generated update query for key: {}, changing county from '{}' to '{}' where zip starts with '{}'", 
-                              key_field, current_county, correct_county_code, zip5);
-                }
+                // Save query to file
+                let file_path = format!("{}/{}.json", results_dir, key_field);
+                save_query_file(&file_path, &query_record)?;
+                
+                log::info!("Generated update query for key: {}, changing county from '{}' to '{}' where zip starts with '{}'", 
+                          key_field, current_county, correct_county_code, zip5);
             }
-            
-            count += 1;
         }
+        
+        count += 1;
+        Ok(())
+    })?;
+    
+    if stats.is_none() {
+        ui::progress::print_with_progress(progress_bar, "No records found with selection query.");
+        log::warn!("Selection query returned no results");
+        return Ok((0, 0));
     }
+    report_refused_zips(&refused, &states, results_dir, progress_bar)?;
     
     // Print summary
     let summary = format!("Checked {} records, found {} with county codes to update", count, mismatch_count);
     ui::progress::print_with_progress(progress_bar, &format!("\x1b[32m{}\x1b[0m", summary));
     log::info!("{}", summary);
+    zip_lookups.log_metrics();
     
     Ok((count, mismatch_count))
-}
\ No newline at end of file
+}
+
+// Keep the selected county for the diff report and, with optimistic concurrency enabled,
+// only update the county if it still holds the selected value
+fn guard_county_update(config: &AppConfig, query_record: &mut QueryRecord, current_county: &str) {
+    if let Some(parts) = parse_update_statement(&query_record.query) {
+        for (column, _) in parse_set_assignments(&parts.set_clause) {
+            query_record.original_values.insert(column, current_county.to_string());
+        }
+    }
+    
+    if !config.optimistic_concurrency {
+        return;
+    }
+    
+    if let Some(guarded_query) = add_concurrency_guard(&query_record.query, |_| Some(current_county.to_string())) {
+        query_record.query = guarded_query;
+        query_record.concurrency_guard = true;
+    }
+}
+
+// The states county codes may be written for, from state_filter or else state; county codes
+// collide across states. Empty means every state, with state_filter = "all". A state_filter
+// covering several states needs codes that carry their state (`state_qualified_codes`),
+// since bare county codes from different states would be indistinguishable in the table.
+fn configured_states(config: &AppConfig, state_qualified_codes: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    if !config.state_filter.trim().is_empty() {
+        let states = config.filtered_states()?;
+        if states.len() != 1 && !state_qualified_codes {
+            return Err(format!(
+                "state_filter '{}' covers more than one state, whose county codes collide; set full_fips_codes = true and run update-county-codes, or filter on one state",
+                config.state_filter.trim()
+            ).into());
+        }
+        if states.is_empty() {
+            log::info!("state_filter covers every state in the zip mapping");
+        }
+        return Ok(states);
+    }
+    let states = config.states();
+    if states.is_empty() {
+        return Err("No state configured; set state to the postal code(s) of the table's state(s), e.g. \"WA\"".into());
+    }
+    Ok(states)
+}
+
+// The zip's mapping if it's in one of the configured states; otherwise the zip is refused
+fn within_states(
+    zip_info: Option<&'static ZipCountyInfo>,
+    states: &[String],
+    zip5: &str,
+    key: &str,
+    refused: &mut RefusedZips,
+) -> Option<&'static ZipCountyInfo> {
+    match zip_info {
+        Some(info) if !states.is_empty() && !states.contains(&info.state) => {
+            log::warn!("Refusing key {}: zip {} is in {}, county {} is outside {}", key, zip5, info.state, info.namespaced_code(), states.join(", "));
+            refused.record(zip5, &info.state, key);
+            None
+        },
+        other => other,
+    }
+}
+
+// Warn about the refused zips and list them in the results directory
+fn report_refused_zips(refused: &RefusedZips, states: &[String], results_dir: &str, progress_bar: &ProgressBar) -> Result<(), Box<dyn Error>> {
+    if refused.is_empty() {
+        return Ok(());
+    }
+    refused.write(results_dir)?;
+    let message = format!(
+        "Refused {} records whose zip is outside {} (see {})",
+        refused.records(), states.join(", "), REFUSED_ZIPS_FILE_NAME
+    );
+    ui::progress::print_with_progress(progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+    log::warn!("{}", message);
+    Ok(())
+}
+
+// The 5-digit zip of a zip or zip+4 code and the county it maps to, if any
+fn lookup_zip(zip_code: &str) -> (String, Option<&'static ZipCountyInfo>) {
+    let zip5 = if zip_code.contains('-') {
+        zip_code.split('-').next().unwrap_or("").to_string()
+    } else if zip_code.len() >= 5 {
+        zip_code[0..5].to_string()
+    } else {
+        zip_code.to_string()
+    };
+    let zip_info = zip_county_map().get(&zip5);
+    (zip5, zip_info)
+}
@@ -0,0 +1,306 @@
+use std::error::Error;
+use std::sync::OnceLock;
+
+use crate::config::AppConfig;
+
+// Dialect of this run, installed from config by `init`
+static CURRENT: OnceLock<&'static (dyn SqlDialect + Sync)> = OnceLock::new();
+
+// Transaction isolation levels a phase can request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    // Parse an isolation setting, accepting ANSI and Informix names. Empty means driver default.
+    pub fn from_name(name: &str) -> Result<Option<Self>, Box<dyn Error>> {
+        match name.trim().to_lowercase().replace(['_', '-'], " ").as_str() {
+            "" | "default" => Ok(None),
+            "read uncommitted" | "dirty read" => Ok(Some(IsolationLevel::ReadUncommitted)),
+            "read committed" | "committed read" => Ok(Some(IsolationLevel::ReadCommitted)),
+            "repeatable read" => Ok(Some(IsolationLevel::RepeatableRead)),
+            "serializable" => Ok(Some(IsolationLevel::Serializable)),
+            _ => Err(format!(
+                "Unknown isolation level '{}' (expected 'read uncommitted', 'read committed', 'repeatable read' or 'serializable')",
+                name
+            ).into()),
+        }
+    }
+}
+
+// Database-specific SQL syntax and behaviour. Informix is the default; other
+// implementations let the same batch pipeline run against other ODBC sources.
+pub trait SqlDialect {
+    // Name used for the `dialect` config setting
+    fn name(&self) -> &'static str;
+
+    // Quote an identifier so reserved words and mixed case survive
+    fn quote_identifier(&self, identifier: &str) -> String;
+
+    // Skip the first `offset` rows of an ordered SELECT and return at most `limit` of the rest
+    fn paginate(&self, query: &str, offset: usize, limit: Option<usize>) -> String;
+
+    // Whether a DML statement that matches no rows comes back as SQL_NO_DATA, leaving no
+    // row count to read, rather than as a success with a zero row count
+    fn zero_rows_returns_no_data(&self) -> bool;
+
+    // Cheap query used to check that a connection is usable
+    fn connection_test_query(&self) -> &'static str;
+
+    // Catalog query returning (column name, type) for a table, in column order
+    fn table_columns_query(&self, table_name: &str) -> String;
+
+    // Catalog query returning the index names defined on a table
+    fn table_indexes_query(&self, table_name: &str) -> String;
+
+    // Catalog query returning (index name, leading column name) for each index on a table
+    fn index_leading_columns_query(&self, table_name: &str) -> String;
+
+    // Statement switching the session to an isolation level
+    fn isolation_statement(&self, level: IsolationLevel) -> String;
+
+    // DELETE removing at most `limit` rows of `table` that match `condition`
+    fn chunked_delete(&self, table: &str, condition: &str, limit: usize) -> String;
+
+    // A one-row derived table for a MERGE source, from a select list of literals
+    fn single_row_source(&self, select_list: &str) -> String;
+
+    // Statements that make the session write query plans to `explain_file` without executing
+    // anything, and the statement that turns them off again; None when unsupported
+    fn explain_statements(&self, explain_file: &str) -> Option<(Vec<String>, &'static str)>;
+}
+
+pub struct InformixDialect;
+
+impl SqlDialect for InformixDialect {
+    fn name(&self) -> &'static str {
+        "informix"
+    }
+
+    fn quote_identifier(&self, identifier: &str) -> String {
+        // Delimited identifiers require DELIMIDENT to be set on the client
+        format!("\"{}\"", identifier.replace('"', "\"\""))
+    }
+
+    fn paginate(&self, query: &str, offset: usize, limit: Option<usize>) -> String {
+        // Informix puts SKIP/FIRST directly after the SELECT keyword
+        let trimmed = query.trim_start();
+        if trimmed.len() < 6 || !trimmed[..6].eq_ignore_ascii_case("SELECT") {
+            return query.to_string();
+        }
+        let mut paginated = "SELECT".to_string();
+        if offset > 0 {
+            paginated.push_str(&format!(" SKIP {}", offset));
+        }
+        if let Some(limit) = limit {
+            paginated.push_str(&format!(" FIRST {}", limit));
+        }
+        paginated.push_str(&trimmed[6..]);
+        paginated
+    }
+
+    fn zero_rows_returns_no_data(&self) -> bool {
+        true
+    }
+
+    fn connection_test_query(&self) -> &'static str {
+        "SELECT 1 FROM systables WHERE tabid = 1"
+    }
+
+    fn table_columns_query(&self, table_name: &str) -> String {
+        format!(
+            "SELECT c.colname, c.coltype FROM syscolumns c, systables t \
+             WHERE c.tabid = t.tabid AND t.tabname = '{}' ORDER BY c.colno",
+            table_name.to_lowercase().replace('\'', "''")
+        )
+    }
+
+    fn table_indexes_query(&self, table_name: &str) -> String {
+        format!(
+            "SELECT i.idxname FROM sysindexes i, systables t \
+             WHERE i.tabid = t.tabid AND t.tabname = '{}'",
+            table_name.to_lowercase().replace('\'', "''")
+        )
+    }
+
+    fn index_leading_columns_query(&self, table_name: &str) -> String {
+        // A descending key part is stored as the negated column number
+        format!(
+            "SELECT i.idxname, c.colname FROM sysindexes i, systables t, syscolumns c \
+             WHERE i.tabid = t.tabid AND c.tabid = t.tabid AND c.colno = ABS(i.part1) AND t.tabname = '{}'",
+            table_name.to_lowercase().replace('\'', "''")
+        )
+    }
+
+    fn isolation_statement(&self, level: IsolationLevel) -> String {
+        // Informix has no separate serializable level; repeatable read is equivalent
+        match level {
+            IsolationLevel::ReadUncommitted => "SET ISOLATION TO DIRTY READ",
+            IsolationLevel::ReadCommitted => "SET ISOLATION TO COMMITTED READ",
+            IsolationLevel::RepeatableRead | IsolationLevel::Serializable => "SET ISOLATION TO REPEATABLE READ",
+        }.to_string()
+    }
+
+    fn chunked_delete(&self, table: &str, condition: &str, limit: usize) -> String {
+        // DELETE has no FIRST clause, so the chunk is chosen by rowid in a subquery
+        format!(
+            "DELETE FROM {table} WHERE rowid IN (SELECT FIRST {limit} rowid FROM {table} WHERE {condition})",
+            table = table, limit = limit, condition = condition
+        )
+    }
+
+    fn single_row_source(&self, select_list: &str) -> String {
+        format!("(SELECT {} FROM sysmaster:sysdual)", select_list)
+    }
+
+    fn explain_statements(&self, explain_file: &str) -> Option<(Vec<String>, &'static str)> {
+        Some((
+            vec![
+                format!("SET EXPLAIN FILE TO '{}'", explain_file.replace('\'', "''")),
+                "SET EXPLAIN ON AVOID_EXECUTE".to_string(),
+            ],
+            "SET EXPLAIN OFF",
+        ))
+    }
+}
+
+pub struct SqlServerDialect;
+
+impl SqlDialect for SqlServerDialect {
+    fn name(&self) -> &'static str {
+        "sqlserver"
+    }
+
+    fn quote_identifier(&self, identifier: &str) -> String {
+        format!("[{}]", identifier.replace(']', "]]"))
+    }
+
+    fn paginate(&self, query: &str, offset: usize, limit: Option<usize>) -> String {
+        // OFFSET/FETCH is only valid after an ORDER BY clause
+        let query = query.trim_end().trim_end_matches(';');
+        let order_by = if query.to_uppercase().contains(" ORDER BY ") { "" } else { " ORDER BY (SELECT NULL)" };
+        let fetch = limit.map(|limit| format!(" FETCH NEXT {} ROWS ONLY", limit)).unwrap_or_default();
+        format!("{}{} OFFSET {} ROWS{}", query, order_by, offset, fetch)
+    }
+
+    fn zero_rows_returns_no_data(&self) -> bool {
+        false
+    }
+
+    fn connection_test_query(&self) -> &'static str {
+        "SELECT 1"
+    }
+
+    fn table_columns_query(&self, table_name: &str) -> String {
+        format!(
+            "SELECT COLUMN_NAME, DATA_TYPE FROM INFORMATION_SCHEMA.COLUMNS \
+             WHERE TABLE_NAME = '{}' ORDER BY ORDINAL_POSITION",
+            table_name.replace('\'', "''")
+        )
+    }
+
+    fn table_indexes_query(&self, table_name: &str) -> String {
+        format!(
+            "SELECT i.name FROM sys.indexes i WHERE i.object_id = OBJECT_ID('{}') AND i.name IS NOT NULL",
+            table_name.replace('\'', "''")
+        )
+    }
+
+    fn index_leading_columns_query(&self, table_name: &str) -> String {
+        format!(
+            "SELECT i.name, c.name FROM sys.indexes i \
+             JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id AND ic.key_ordinal = 1 \
+             JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id \
+             WHERE i.object_id = OBJECT_ID('{}')",
+            table_name.replace('\'', "''")
+        )
+    }
+
+    fn isolation_statement(&self, level: IsolationLevel) -> String {
+        let level = match level {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        };
+        format!("SET TRANSACTION ISOLATION LEVEL {}", level)
+    }
+
+    fn chunked_delete(&self, table: &str, condition: &str, limit: usize) -> String {
+        format!("DELETE TOP ({}) FROM {} WHERE {}", limit, table, condition)
+    }
+
+    fn single_row_source(&self, select_list: &str) -> String {
+        format!("(SELECT {})", select_list)
+    }
+
+    fn explain_statements(&self, _explain_file: &str) -> Option<(Vec<String>, &'static str)> {
+        // SHOWPLAN returns plans as result sets rather than writing a file
+        None
+    }
+}
+
+// Look up a dialect by its config name
+pub fn dialect_for_name(name: &str) -> Option<Box<dyn SqlDialect>> {
+    match name.trim().to_lowercase().as_str() {
+        "" | "informix" => Some(Box::new(InformixDialect)),
+        "sqlserver" | "mssql" => Some(Box::new(SqlServerDialect)),
+        _ => None,
+    }
+}
+
+// Get the dialect configured for this run
+pub fn from_config(config: &AppConfig) -> Result<Box<dyn SqlDialect>, Box<dyn Error>> {
+    dialect_for_name(&config.dialect)
+        .ok_or_else(|| format!("Unknown SQL dialect '{}' (expected 'informix' or 'sqlserver')", config.dialect).into())
+}
+
+// Install the process-wide dialect from config. Later calls are ignored.
+pub fn init(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let dialect: &'static (dyn SqlDialect + Sync) = match from_config(config)?.name() {
+        "sqlserver" => &SqlServerDialect,
+        _ => &InformixDialect,
+    };
+    let _ = CURRENT.set(dialect);
+    Ok(())
+}
+
+// The dialect of this run (Informix if not initialized)
+pub fn current() -> &'static dyn SqlDialect {
+    CURRENT.get().copied().unwrap_or(&InformixDialect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn informix_pages_after_the_select_keyword() {
+        let query = "SELECT zip, county FROM customer ORDER BY zip";
+        assert_eq!(InformixDialect.paginate(query, 0, Some(10)), "SELECT FIRST 10 zip, county FROM customer ORDER BY zip");
+        assert_eq!(InformixDialect.paginate(query, 500, None), "SELECT SKIP 500 zip, county FROM customer ORDER BY zip");
+        assert_eq!(InformixDialect.paginate("EXECUTE PROCEDURE p()", 5, None), "EXECUTE PROCEDURE p()");
+    }
+
+    #[test]
+    fn sqlserver_pages_with_offset_fetch() {
+        assert_eq!(
+            SqlServerDialect.paginate("SELECT zip FROM customer ORDER BY zip;", 500, Some(10)),
+            "SELECT zip FROM customer ORDER BY zip OFFSET 500 ROWS FETCH NEXT 10 ROWS ONLY"
+        );
+        assert_eq!(
+            SqlServerDialect.paginate("SELECT zip FROM customer", 0, Some(10)),
+            "SELECT zip FROM customer ORDER BY (SELECT NULL) OFFSET 0 ROWS FETCH NEXT 10 ROWS ONLY"
+        );
+    }
+
+    #[test]
+    fn identifiers_are_quoted_per_dialect() {
+        assert_eq!(InformixDialect.quote_identifier("Zip \"Code\""), "\"Zip \"\"Code\"\"\"");
+        assert_eq!(SqlServerDialect.quote_identifier("Zip [Code]"), "[Zip [Code]]]");
+    }
+}
@@ -0,0 +1,310 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::db::query_types::ErrorRecord;
+use crate::utils::{masking, run_id};
+
+// "ISAM error: -107" in a driver message, compiled on first use
+static ISAM_ERROR_PATTERN: OnceLock<Regex> = OnceLock::new();
+// "constraint (owner.name)" in a violation message
+static CONSTRAINT_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+fn isam_error_pattern() -> &'static Regex {
+    ISAM_ERROR_PATTERN.get_or_init(|| Regex::new(r"(?i)ISAM error[^-0-9]*(-?\d+)").unwrap())
+}
+
+fn constraint_pattern() -> &'static Regex {
+    CONSTRAINT_PATTERN.get_or_init(|| Regex::new(r"(?i)constraint\s*\(([^)]+)\)").unwrap())
+}
+
+// SQLCODE and ISAM error extracted from an ODBC error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ErrorClass {
+    pub sqlcode: Option<i32>,
+    pub isam_error: Option<i32>,
+}
+
+impl ErrorClass {
+    pub fn label(&self) -> String {
+        match (self.sqlcode, self.isam_error) {
+            (Some(sqlcode), Some(isam)) => format!("{} (ISAM {})", sqlcode, isam),
+            (Some(sqlcode), None) => sqlcode.to_string(),
+            (None, Some(isam)) => format!("ISAM {}", isam),
+            (None, None) => "unclassified".to_string(),
+        }
+    }
+}
+
+// The diagnostic record of a failed ODBC call. odbc-api keeps the first record of the call
+// with the error and logs any further ones as warnings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OdbcDiagnostics {
+    // Five characters; the first two are the SQLSTATE class, e.g. 40 for transaction rollback
+    pub sqlstate: Option<String>,
+    // The driver's native error, for Informix the SQLCODE
+    pub native_error: Option<i32>,
+    pub isam_error: Option<i32>,
+    // The ODBC function that failed, e.g. SQLExecDirect
+    pub function: Option<String>,
+    // The components that passed the message on, outermost first, then the message text:
+    // [Informix][Informix ODBC Driver][Informix]Could not position ... becomes
+    // ["Informix", "Informix ODBC Driver", "Informix", "Could not position ..."]
+    pub message_chain: Vec<String>,
+    // The complete message as the driver sent it
+    pub message: String,
+}
+
+impl OdbcDiagnostics {
+    pub fn from_odbc_error(err: &odbc_api::Error) -> Self {
+        match err {
+            odbc_api::Error::Diagnostics { record, function } => {
+                let message = String::from_utf16_lossy(&record.message);
+                let isam_error = isam_error_pattern()
+                    .captures(&message)
+                    .and_then(|caps| caps[1].parse::<i32>().ok())
+                    .map(|code| -code.abs());
+                let sqlstate = Some(record.state.as_str().trim_matches(char::from(0)).trim().to_string())
+                    .filter(|state| !state.is_empty());
+                OdbcDiagnostics {
+                    sqlstate,
+                    native_error: if record.native_error != 0 { Some(record.native_error) } else { None },
+                    isam_error,
+                    function: Some(function.to_string()),
+                    message_chain: message_chain(&message),
+                    message,
+                }
+            },
+            odbc_api::Error::NoDiagnostics { function } => OdbcDiagnostics {
+                function: Some(function.to_string()),
+                message: err.to_string(),
+                ..OdbcDiagnostics::default()
+            },
+            other => OdbcDiagnostics { message: other.to_string(), ..OdbcDiagnostics::default() },
+        }
+    }
+    
+    // Classify by the driver's native error code (the Informix SQLCODE) and any ISAM error
+    // mentioned in the message
+    pub fn class(&self) -> ErrorClass {
+        ErrorClass { sqlcode: self.native_error, isam_error: self.isam_error }
+    }
+    
+    // Whether the SQLSTATE starts with one of `prefixes`, so "08" matches the whole
+    // connection exception class and "HYT00" only the timeout
+    pub fn sqlstate_in(&self, prefixes: &[String]) -> bool {
+        self.sqlstate.as_ref().is_some_and(|state| {
+            prefixes.iter()
+                .map(|prefix| prefix.trim())
+                .any(|prefix| !prefix.is_empty() && state.to_uppercase().starts_with(&prefix.to_uppercase()))
+        })
+    }
+    
+    // "Could not position within a table (SQLSTATE S1000, native error -243, ISAM -107, in SQLExecDirect)"
+    pub fn summary(&self) -> String {
+        let text = self.message_chain.last().map(String::as_str).unwrap_or(&self.message).trim();
+        let details: Vec<String> = [
+            self.sqlstate.as_ref().map(|state| format!("SQLSTATE {}", state)),
+            self.native_error.map(|code| format!("native error {}", code)),
+            self.isam_error.map(|code| format!("ISAM {}", code)),
+            self.function.as_ref().map(|function| format!("in {}", function)),
+        ].into_iter().flatten().collect();
+        if details.is_empty() {
+            text.to_string()
+        } else {
+            format!("{} ({})", text, details.join(", "))
+        }
+    }
+    
+    // The errors.json entry for a failed key, masked like the log
+    pub fn error_record(&self, key: &str, file: &str, timestamp: &str) -> ErrorRecord {
+        ErrorRecord {
+            key: key.to_string(),
+            file: file.to_string(),
+            error: masking::mask(&self.summary()),
+            timestamp: timestamp.to_string(),
+            run_id: Some(run_id::current().to_string()),
+            sqlcode: self.native_error,
+            isam_error: self.isam_error,
+            sqlstate: self.sqlstate.clone(),
+            odbc_function: self.function.clone(),
+            message_chain: self.message_chain.iter().map(|part| masking::mask(part)).collect(),
+        }
+    }
+    
+    // Why execution has to stop, when the SQLSTATE is one of `abort_sqlstates`
+    pub fn abort_reason(&self, abort_sqlstates: &[String], key: &str) -> Option<String> {
+        self.sqlstate_in(abort_sqlstates).then(|| format!(
+            "SQLSTATE {} at key {} is in abort_sqlstates: {}",
+            self.sqlstate.as_deref().unwrap_or_default(), key, masking::mask(&self.summary())
+        ))
+    }
+}
+
+// Split the leading [component] prefixes off a message
+fn message_chain(message: &str) -> Vec<String> {
+    let mut chain = Vec::new();
+    let mut rest = message.trim_start();
+    while let Some(inner) = rest.strip_prefix('[') {
+        match inner.find(']') {
+            Some(end) => {
+                chain.push(inner[..end].trim().to_string());
+                rest = inner[end + 1..].trim_start();
+            },
+            None => break,
+        }
+    }
+    chain.push(rest.trim_end().to_string());
+    chain
+}
+
+// Built-in knowledge of common Informix errors seen in batch updates: (code, meaning, recommendation).
+// SQLCODEs and ISAM errors are numbered independently, so each kind has its own table.
+const SQLCODE_ERRORS: &[(i32, &str, &str)] = &[
+    (-201, "syntax error", "check update_query_template; run the test command to validate generated SQL"),
+    (-206, "table not in database", "check the table name in the template and the DSN's database"),
+    (-217, "column not found", "check column names in update_query_template against the table"),
+    (-239, "duplicate value for a unique index", "the new value collides with an existing row; review the keys involved"),
+    (-243, "could not position within a table", "usually a lock conflict; see the ISAM error, increase lock wait or lower concurrency"),
+    (-244, "could not do a physical-order read", "rows are locked by other sessions: increase lock wait (SET LOCK MODE TO WAIT n) or lower workers/commit_batch_size"),
+    (-245, "could not position within a file via an index", "lock conflict on an index; run off-peak or lower concurrency"),
+    (-246, "could not do an indexed read", "lock conflict on an index read; increase lock wait or lower concurrency"),
+    (-263, "could not lock row for update", "another session holds the row; increase lock wait or retry the failed keys later"),
+    (-268, "unique constraint violated", "the new value duplicates another row under a unique constraint"),
+    (-271, "could not insert new row into the table", "see the ISAM error; often locks or a full dbspace"),
+    (-346, "could not update a row in the table", "see the ISAM error; often locks or a full dbspace"),
+    (-391, "cannot insert a null into a not-null column", "the template produced NULL/empty for a NOT NULL column; check source values"),
+    (-458, "long transaction aborted", "the logical logs filled up; commit more often (smaller batches) or add log space"),
+    (-691, "missing key in referenced table", "foreign key violation: the new value has no parent row"),
+    (-692, "key value is still referenced", "foreign key violation: dependent rows reference the old value"),
+    (-1205, "invalid month in date", "source date values don't match DBDATE; check db_date_format"),
+    (-1213, "character to numeric conversion error", "a non-numeric value was assigned to a numeric column; check source values"),
+    (-1218, "string to date conversion error", "source date values don't match DBDATE; check db_date_format"),
+    (-1226, "decimal or money value exceeds maximum precision", "the new value doesn't fit the column's precision"),
+    (-1262, "non-numeric character in datetime or interval", "check the datetime literal format in the template"),
+    (-25580, "system error occurred in network function", "the connection dropped; re-run to retry failed keys"),
+    (-25582, "network connection is broken", "the connection dropped; re-run to retry failed keys"),
+];
+
+const ISAM_ERRORS: &[(i32, &str, &str)] = &[
+    (-107, "record is locked", "another session holds the row lock; increase lock wait or run off-peak"),
+    (-113, "the file is locked", "a table lock is held by another session; run when it's released"),
+    (-143, "deadlock detected", "lower concurrency or keep transactions shorter"),
+    (-154, "lock timeout expired", "increase lock wait or lower concurrency"),
+];
+
+// Duplicate values for unique indexes and constraints, NULL into NOT NULL, and foreign keys
+const CONSTRAINT_SQLCODES: &[i32] = &[-239, -268, -391, -691, -692];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CodeKind {
+    Sqlcode,
+    Isam,
+}
+
+fn lookup(kind: CodeKind, code: i32) -> Option<&'static (i32, &'static str, &'static str)> {
+    let table = match kind {
+        CodeKind::Sqlcode => SQLCODE_ERRORS,
+        CodeKind::Isam => ISAM_ERRORS,
+    };
+    table.iter().find(|(known, _, _)| *known == code)
+}
+
+// What is known about a class: its SQLCODE's entry, or for an unknown SQLCODE its ISAM error's
+fn known_error(class: &ErrorClass) -> Option<&'static (i32, &'static str, &'static str)> {
+    class.sqlcode.and_then(|code| lookup(CodeKind::Sqlcode, code))
+        .or_else(|| class.isam_error.and_then(|code| lookup(CodeKind::Isam, code)))
+}
+
+#[derive(Debug, Default)]
+struct ClassTotals {
+    count: usize,
+    example_key: String,
+    example_message: String,
+}
+
+// Execution errors aggregated by class, for the end-of-run report
+#[derive(Debug, Default)]
+pub struct ErrorStatistics {
+    classes: BTreeMap<ErrorClass, ClassTotals>,
+}
+
+impl ErrorStatistics {
+    pub fn record(&mut self, class: ErrorClass, key: &str, message: &str) {
+        let totals = self.classes.entry(class).or_default();
+        if totals.count == 0 {
+            totals.example_key = key.to_string();
+            totals.example_message = message.to_string();
+        }
+        totals.count += 1;
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+    
+    // Failures from unique, not-null and referential constraints
+    pub fn constraint_violations(&self) -> usize {
+        self.classes.iter()
+            .filter(|(class, _)| class.sqlcode.is_some_and(|code| CONSTRAINT_SQLCODES.contains(&code)))
+            .map(|(_, totals)| totals.count)
+            .sum()
+    }
+    
+    // One line per error class, most frequent first, with a recommendation where one is known
+    pub fn report(&self) -> Vec<String> {
+        let mut classes: Vec<(&ErrorClass, &ClassTotals)> = self.classes.iter().collect();
+        classes.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.count));
+        
+        classes.iter()
+            .map(|(class, totals)| {
+                let mut line = match known_error(class) {
+                    Some((_, meaning, recommendation)) => format!(
+                        "{}: {} x {} - {}", class.label(), totals.count, meaning, recommendation
+                    ),
+                    None => format!("{}: {} x {}", class.label(), totals.count, totals.example_message.trim()),
+                };
+                
+                // Name the constraint for referential and unique violations
+                if let Some(constraint) = constraint_pattern()
+                    .captures(&totals.example_message)
+                    .map(|caps| caps[1].to_string())
+                {
+                    line.push_str(&format!(", see constraint {}", constraint));
+                }
+                
+                line.push_str(&format!(" (e.g. key {})", totals.example_key));
+                line
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn isam_codes_are_not_read_as_sqlcodes() {
+        // -243 is a SQLCODE; as an ISAM error it means something else entirely
+        let class = ErrorClass { sqlcode: None, isam_error: Some(-243) };
+        assert_eq!(known_error(&class), None);
+        
+        let class = ErrorClass { sqlcode: Some(-999), isam_error: Some(-107) };
+        assert_eq!(known_error(&class).map(|(_, meaning, _)| *meaning), Some("record is locked"));
+    }
+    
+    #[test]
+    fn sqlcode_comes_before_its_isam_error() {
+        let class = ErrorClass { sqlcode: Some(-244), isam_error: Some(-107) };
+        assert_eq!(known_error(&class).map(|(code, _, _)| *code), Some(-244));
+    }
+    
+    #[test]
+    fn patterns_find_isam_errors_and_constraints() {
+        let caps = isam_error_pattern().captures("Could not position within a table (ISAM error: record is locked. -107)").unwrap();
+        assert_eq!(&caps[1], "-107");
+        let caps = constraint_pattern().captures("Missing key in referenced table for referential constraint (informix.r104_12)").unwrap();
+        assert_eq!(&caps[1], "informix.r104_12");
+    }
+}
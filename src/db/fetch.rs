@@ -0,0 +1,218 @@
+use odbc_api::{buffers::{Indicator, TextRowSet}, Connection, Cursor, ResultSetMetadata};
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::config::AppConfig;
+use crate::db::batch_tuning;
+use crate::db::column_format::ColumnFormat;
+use crate::db::sql_helpers::{extract_table_alias, extract_table_name, select_list};
+use crate::ui;
+use crate::utils::encoding;
+
+// Longest value a column gets room for in the buffers of `text_buffers`. Catalog reads,
+// verification and rollback pre-images fail on a longer value instead of using it truncated.
+pub const UTILITY_MAX_TEXT_LENGTH: usize = 65536;
+
+// Timing and sizing of a completed fetch, used to tune the next one
+#[derive(Debug, Clone, Default)]
+pub struct FetchStats {
+    pub batch_size: usize,
+    pub batches: usize,
+    pub rows: usize,
+    pub fetch_time: Duration,
+    pub buffer_bytes: usize,
+    pub refetched_cells: usize,
+    pub failed_rows: usize,
+}
+
+impl FetchStats {
+    pub fn average_batch_millis(&self) -> f64 {
+        if self.batches == 0 {
+            0.0
+        } else {
+            self.fetch_time.as_secs_f64() * 1000.0 / self.batches as f64
+        }
+    }
+}
+
+// Execute a selection query and call `on_row` with each row's column values as text.
+// Returns None when the query produces no result set. The progress bar length grows as
+// batches arrive. All selection paths go through here so buffer sizing, batch tuning and
+// truncation handling live in one place.
+pub fn for_each_row(
+    conn: &Connection,
+    query: &str,
+    config: &AppConfig,
+    progress_bar: &ProgressBar,
+    mut on_row: impl FnMut(&[String]) -> Result<(), Box<dyn Error>>,
+) -> Result<Option<FetchStats>, Box<dyn Error>> {
+    let cursor = match conn.execute(query, ())? {
+        Some(cursor) => cursor,
+        None => return Ok(None),
+    };
+    
+    let batch_size = batch_tuning::current_batch_size(config);
+    let columns = describe_columns(&cursor, config)?;
+    let max_lengths: Vec<usize> = columns.iter().map(|column| column.max_length).collect();
+    
+    // Set up buffer for fetching rows
+    let mut buffers = TextRowSet::from_max_str_lens(batch_size, max_lengths.iter().copied())?;
+    let buffer_bytes = max_lengths.iter().map(|len| len * batch_size).sum();
+    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
+    
+    let mut stats = FetchStats { batch_size, buffer_bytes, ..FetchStats::default() };
+    
+    loop {
+        let started = Instant::now();
+        let batch = match row_set_cursor.fetch()? {
+            Some(batch) => batch,
+            None => break,
+        };
+        stats.fetch_time += started.elapsed();
+        stats.batches += 1;
+        stats.rows += batch.num_rows();
+        progress_bar.set_length(stats.rows as u64);
+        
+        'rows: for row_index in 0..batch.num_rows() {
+            let mut row: Vec<String> = (0..batch.num_cols())
+                .map(|col_index| encoding::decode(batch.at(col_index, row_index).unwrap_or(&[])))
+                .collect();
+            
+            for (value, column) in row.iter_mut().zip(&columns) {
+                *value = column.format.apply(config, std::mem::take(value));
+            }
+            
+            // Never generate updates from silently truncated values
+            for col_index in 0..batch.num_cols() {
+                if !is_truncated(batch, col_index, row_index) {
+                    continue;
+                }
+                
+                let refetched = if config.truncation_policy.eq_ignore_ascii_case("refetch") {
+                    refetch_cell(conn, query, col_index, &row[0])
+                } else {
+                    Err("truncation_policy is 'fail'".into())
+                };
+                
+                match refetched {
+                    Ok(value) => {
+                        let value = columns[col_index].format.apply(config, value);
+                        log::info!("Re-fetched truncated column {} for key {} ({} bytes)", col_index + 1, row[0], value.len());
+                        row[col_index] = value;
+                        stats.refetched_cells += 1;
+                    },
+                    Err(e) => {
+                        let message = format!(
+                            "Skipping key {}: column {} exceeds its {}-byte buffer and could not be re-fetched ({})",
+                            row[0], col_index + 1, batch.max_len(col_index), e
+                        );
+                        ui::progress::print_with_progress(progress_bar, &message);
+                        log::error!("{}", message);
+                        stats.failed_rows += 1;
+                        continue 'rows;
+                    }
+                }
+            }
+            
+            on_row(&row)?;
+        }
+    }
+    
+    batch_tuning::observe(config, &stats);
+    
+    Ok(Some(stats))
+}
+
+// Buffer size and value format of a result column
+struct FetchColumn {
+    max_length: usize,
+    format: ColumnFormat,
+}
+
+// Describe each result column: its buffer length (a per-column override from config,
+// otherwise the driver-reported size capped at `max_text_length`) and how its text is
+// normalized
+fn describe_columns(cursor: &impl ResultSetMetadata, config: &AppConfig) -> Result<Vec<FetchColumn>, Box<dyn Error>> {
+    let num_cols = cursor.num_result_cols()? as u16;
+    let mut columns = Vec::with_capacity(num_cols as usize);
+    
+    for col_number in 1..=num_cols {
+        let name = cursor.col_name(col_number)?.to_lowercase();
+        let data_type = cursor.col_data_type(col_number)?;
+        
+        let max_length = match config.column_max_lengths.iter().find(|(column, _)| column.to_lowercase() == name) {
+            Some((_, length)) => *length,
+            None => reported_length(cursor, col_number)?.min(config.max_text_length),
+        };
+        
+        columns.push(FetchColumn {
+            max_length: max_length.max(1),
+            format: ColumnFormat::for_column(config, &name, data_type),
+        });
+    }
+    
+    Ok(columns)
+}
+
+// The length the driver reports for a column's values as text
+fn reported_length(cursor: &impl ResultSetMetadata, col_number: u16) -> Result<usize, Box<dyn Error>> {
+    match cursor.col_data_type(col_number)?.utf8_len() {
+        Some(len) => Ok(len),
+        None => Ok(cursor.col_display_size(col_number)? as usize),
+    }
+}
+
+// Text buffers for `batch_size` rows of a result set, each column sized from its reported
+// length up to `max_text_length`. Pair with `ensure_not_truncated` on every row read.
+pub fn text_buffers(cursor: &impl ResultSetMetadata, batch_size: usize, max_text_length: usize) -> Result<TextRowSet, Box<dyn Error>> {
+    let num_cols = cursor.num_result_cols()? as u16;
+    let mut max_lengths = Vec::with_capacity(num_cols as usize);
+    for col_number in 1..=num_cols {
+        max_lengths.push(reported_length(cursor, col_number)?.min(max_text_length).max(1));
+    }
+    Ok(TextRowSet::from_max_str_lens(batch_size, max_lengths.into_iter())?)
+}
+
+// Fail when a value of a row came back longer than the buffer that received it
+pub fn ensure_not_truncated(batch: &TextRowSet, row_index: usize) -> Result<(), Box<dyn Error>> {
+    match (0..batch.num_cols()).find(|&col_index| is_truncated(batch, col_index, row_index)) {
+        Some(col_index) => Err(format!("column {} exceeds its {}-byte buffer", col_index + 1, batch.max_len(col_index)).into()),
+        None => Ok(()),
+    }
+}
+
+// Whether the driver reported a value longer than the buffer that received it
+fn is_truncated(batch: &TextRowSet, col_index: usize, row_index: usize) -> bool {
+    match batch.indicator_at(col_index, row_index) {
+        Indicator::Null => false,
+        Indicator::NoTotal => true,
+        Indicator::Length(total_length) => total_length > batch.max_len(col_index),
+    }
+}
+
+// Re-read a single cell without a size limit, locating the row by the first selected column
+fn refetch_cell(conn: &Connection, query: &str, col_index: usize, key: &str) -> Result<String, Box<dyn Error>> {
+    let columns = select_list(query).ok_or("cannot parse the selection column list")?;
+    let column = columns.get(col_index).ok_or("column not found in the selection list")?;
+    let key_column = columns.first().ok_or("selection has no key column")?;
+    
+    // Keep the table's alias so qualified column references still resolve
+    let table = match extract_table_alias(query) {
+        Some(alias) => format!("{} {}", extract_table_name(query)?, alias),
+        None => extract_table_name(query)?,
+    };
+    let refetch_query = format!(
+        "SELECT {} FROM {} WHERE {} = '{}'",
+        column, table, key_column, key.replace('\'', "''")
+    );
+    
+    let mut cursor = conn.execute(&refetch_query, ())?.ok_or("re-fetch returned no result set")?;
+    let mut row = cursor.next_row()?.ok_or("row no longer exists")?;
+    
+    // get_text keeps calling SQLGetData until the whole value has been read
+    let mut buffer = Vec::new();
+    row.get_text(1, &mut buffer)?;
+    
+    Ok(encoding::decode(&buffer))
+}
@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::config::AppConfig;
+use crate::db::query_types::QueryRecord;
+use crate::utils::run_id;
+
+// Side effect run after each successfully executed query (cache busting, enqueueing
+// downstream recalculation, ...). Implement this and register it in `from_config` to add
+// site-specific behaviour in Rust.
+pub trait PostExecutionHook {
+    fn name(&self) -> &str;
+    
+    fn after_success(&self, record: &QueryRecord) -> Result<(), Box<dyn Error>>;
+}
+
+// Runs an external command per record, passing the record as JSON on stdin
+pub struct CommandHook {
+    command: String,
+}
+
+impl CommandHook {
+    pub fn new(command: &str) -> Self {
+        CommandHook { command: command.to_string() }
+    }
+}
+
+impl PostExecutionHook for CommandHook {
+    fn name(&self) -> &str {
+        &self.command
+    }
+    
+    fn after_success(&self, record: &QueryRecord) -> Result<(), Box<dyn Error>> {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(&self.command);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.command);
+            command
+        };
+        
+        let mut child = command
+            .env("IBP_RUN_ID", run_id::current())
+            .env("IBP_RECORD_KEY", &record.key)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(serde_json::to_string(record)?.as_bytes())?;
+        }
+        
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("exited with {}", status).into());
+        }
+        
+        Ok(())
+    }
+}
+
+// The hooks configured for this run
+pub fn from_config(config: &AppConfig) -> Vec<Box<dyn PostExecutionHook>> {
+    let mut hooks: Vec<Box<dyn PostExecutionHook>> = Vec::new();
+    
+    if !config.post_execution_command.trim().is_empty() {
+        hooks.push(Box::new(CommandHook::new(&config.post_execution_command)));
+    }
+    
+    hooks
+}
+
+// Run every hook for a record. A failing hook is logged but never fails the record,
+// since the database change has already been applied.
+pub fn run_hooks(hooks: &[Box<dyn PostExecutionHook>], record: &QueryRecord) {
+    for hook in hooks {
+        if let Err(e) = hook.after_success(record) {
+            log::warn!("Post-execution hook '{}' failed for key {}: {}", hook.name(), record.key, e);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use crate::config::AppConfig;
+use crate::db::connection::query_rows;
+use crate::db::dialect::{self, SqlDialect};
+use crate::db::sql_helpers::{extract_table_alias, extract_table_name, find_keyword_outside_quotes, normalize_identifier, parse_update_statement, QualifiedName};
+use crate::db::verification::unguarded_condition;
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::manifest::IndexAdvice;
+use crate::ui;
+
+// Generated statements whose WHERE clauses are checked; a template gives them all one shape
+const UPDATE_SAMPLE_SIZE: usize = 20;
+
+// A table as written and the columns a statement's condition compares
+type TablePredicate = (String, Vec<String>);
+
+// Check the columns the selection query and the generated UPDATE statements filter on against
+// the indexes of their tables. A predicate set none of whose columns leads an index means a
+// sequential scan per statement (or for the whole selection), so it's reported with a
+// CREATE INDEX suggestion. Catalog errors are logged and leave the table out; the advisory
+// never fails a run.
+pub fn advise_indexes(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    progress_bar: &ProgressBar,
+) -> Result<Vec<IndexAdvice>, Box<dyn Error>> {
+    if !config.index_advisory {
+        return Ok(Vec::new());
+    }
+    
+    // (table as written, source) -> columns filtered on, in order
+    let mut predicates: BTreeMap<(String, &'static str), Vec<String>> = BTreeMap::new();
+    if let Some((table, columns)) = selection_predicates(&config.selection_query) {
+        predicates.insert((table, "selection"), columns);
+    }
+    for (table, columns) in update_predicates(results_dir)? {
+        let entry = predicates.entry((table, "update")).or_default();
+        for column in columns {
+            if !entry.contains(&column) {
+                entry.push(column);
+            }
+        }
+    }
+    predicates.retain(|_, columns| !columns.is_empty());
+    if predicates.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let dialect = dialect::from_config(config)?;
+    let mut leading_columns: BTreeMap<String, Option<BTreeSet<String>>> = BTreeMap::new();
+    let mut advice = Vec::new();
+    for ((table, source), columns) in predicates {
+        let indexed = leading_columns.entry(table.clone()).or_insert_with(|| {
+            let name = QualifiedName::parse(&table).map(|name| name.name).unwrap_or_else(|| table.clone());
+            match query_rows(conn, &dialect.index_leading_columns_query(&name)) {
+                Ok(rows) => Some(rows.into_iter().filter_map(|row| row.into_iter().nth(1)).map(|column| normalize_identifier(column.trim())).collect()),
+                Err(e) => {
+                    log::warn!("Index advisory skipped {}: its indexes couldn't be read: {}", table, e);
+                    None
+                }
+            }
+        });
+        let indexed = match indexed {
+            Some(indexed) => indexed,
+            None => continue,
+        };
+        if columns.iter().any(|column| indexed.contains(column)) {
+            log::info!("Index advisory: the {} predicates on {} ({}) are covered by an index", source, table, columns.join(", "));
+            continue;
+        }
+        
+        let name = QualifiedName::parse(&table).map(|name| name.name).unwrap_or_else(|| table.clone());
+        let index_name = unused_index_name(conn, dialect.as_ref(), &name, &format!("ix_{}_{}", name, columns.join("_")));
+        let suggestion = format!("CREATE INDEX {} ON {} ({})", index_name, table, columns.join(", "));
+        let message = format!("No index on {} leads with any {} predicate column ({}); consider: {}", table, source, columns.join(", "), suggestion);
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[33mIndex advisory: {}\x1b[0m", message));
+        log::warn!("Index advisory: {}", message);
+        advice.push(IndexAdvice { table, source: source.to_string(), columns, suggestion });
+    }
+    Ok(advice)
+}
+
+// `candidate`, or it with a numeric suffix when the table already has an index of that name
+fn unused_index_name(conn: &Connection, dialect: &dyn SqlDialect, table: &str, candidate: &str) -> String {
+    let existing: BTreeSet<String> = query_rows(conn, &dialect.table_indexes_query(table))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|row| row.into_iter().next())
+        .map(|name| normalize_identifier(name.trim()))
+        .collect();
+    (1..)
+        .map(|suffix| if suffix == 1 { candidate.to_string() } else { format!("{}_{}", candidate, suffix) })
+        .find(|name| !existing.contains(name))
+        .unwrap_or_else(|| candidate.to_string())
+}
+
+// The first table of the selection and the columns its WHERE clause filters it on
+fn selection_predicates(selection_query: &str) -> Option<TablePredicate> {
+    let query = selection_query.trim().trim_end_matches(';').trim();
+    let table = extract_table_name(query).ok()?;
+    let where_position = find_keyword_outside_quotes(query, "WHERE")?;
+    let mut condition = &query[where_position + 5..];
+    for keyword in ["GROUP", "ORDER", "HAVING", "UNION", "INTO"] {
+        if let Some(position) = find_keyword_outside_quotes(condition, keyword) {
+            condition = &condition[..position];
+        }
+    }
+    
+    // Columns qualified by another table of a join are that table's business
+    let qualifiers: Vec<String> = extract_table_alias(query).into_iter()
+        .chain(QualifiedName::parse(&table).map(|name| name.name))
+        .map(|qualifier| normalize_identifier(&qualifier))
+        .collect();
+    let columns = predicate_columns(condition).into_iter()
+        .filter(|(qualifier, _)| qualifier.as_ref().is_none_or(|qualifier| qualifiers.contains(qualifier)))
+        .map(|(_, column)| column)
+        .collect();
+    Some((table, columns))
+}
+
+// Tables and WHERE columns of a sample of the generated UPDATE statements
+fn update_predicates(results_dir: &str) -> Result<Vec<TablePredicate>, Box<dyn Error>> {
+    let files = read_query_files(results_dir)?;
+    let step = (files.len() / UPDATE_SAMPLE_SIZE).max(1);
+    let mut predicates = Vec::new();
+    for file_path in files.iter().step_by(step).take(UPDATE_SAMPLE_SIZE) {
+        let record = match read_query_file(file_path) {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        let parts = match parse_update_statement(&record.query) {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let condition = match parts.where_clause.as_deref() {
+            Some(condition) if record.concurrency_guard => unguarded_condition(condition),
+            Some(condition) => condition,
+            None => continue,
+        };
+        predicates.push((parts.table, predicate_columns(condition).into_iter().map(|(_, column)| column).collect()));
+    }
+    Ok(predicates)
+}
+
+// Columns compared by the top-level AND terms of a condition, with any qualifier. A term with a
+// top-level OR is left out, since no single index serves both sides.
+fn predicate_columns(condition: &str) -> Vec<(Option<String>, String)> {
+    let comparison = Regex::new(r#"(?i)^\s*(?:([A-Za-z_][\w$]*|"[^"]+")\.)?([A-Za-z_][\w$]*|"[^"]+")\s*(?:=|<=|>=|<>|!=|<|>|\bIN\b|\bBETWEEN\b|\bLIKE\b|\bMATCHES\b)"#)
+        .expect("valid predicate pattern");
+    let mut columns: Vec<(Option<String>, String)> = Vec::new();
+    for term in split_top_level(condition, "AND") {
+        let term = strip_parentheses(&term);
+        if split_top_level(term, "OR").len() > 1 {
+            continue;
+        }
+        if let Some(captures) = comparison.captures(term) {
+            let column = (captures.get(1).map(|qualifier| normalize_identifier(qualifier.as_str())), normalize_identifier(&captures[2]));
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+    }
+    columns
+}
+
+// Split a condition on a keyword outside quotes and parentheses. BETWEEN's own AND stays with it.
+fn split_top_level(condition: &str, keyword: &str) -> Vec<String> {
+    let upper = condition.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut pending_between = false;
+    let is_word_at = |i: usize, word: &str| {
+        upper[i..].starts_with(word)
+            && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_'))
+            && bytes.get(i + word.len()).is_none_or(|c| !(c.is_ascii_alphanumeric() || *c == b'_'))
+    };
+    
+    for (i, c) in upper.char_indices() {
+        match c {
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '(' if !in_single_quote && !in_double_quote => depth += 1,
+            ')' if !in_single_quote && !in_double_quote => depth -= 1,
+            _ => {}
+        }
+        if in_single_quote || in_double_quote || depth != 0 {
+            continue;
+        }
+        if is_word_at(i, "BETWEEN") {
+            pending_between = true;
+        } else if is_word_at(i, keyword) {
+            if keyword == "AND" && pending_between {
+                pending_between = false;
+                continue;
+            }
+            parts.push(condition[start..i].trim().to_string());
+            start = i + keyword.len();
+        }
+    }
+    parts.push(condition[start..].trim().to_string());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+// A term wrapped in parentheses that enclose all of it, without them
+fn strip_parentheses(term: &str) -> &str {
+    let mut term = term.trim();
+    while term.starts_with('(') && encloses(term) {
+        term = term[1..term.len() - 1].trim();
+    }
+    term
+}
+
+// Whether the opening parenthesis of a term closes at its last character
+fn encloses(term: &str) -> bool {
+    let mut depth = 0;
+    let mut in_quote = false;
+    for (index, c) in term.char_indices() {
+        match c {
+            '\'' => in_quote = !in_quote,
+            '(' if !in_quote => depth += 1,
+            ')' if !in_quote => {
+                depth -= 1;
+                if depth == 0 {
+                    return index == term.len() - 1;
+                }
+            },
+            _ => {}
+        }
+    }
+    false
+}
@@ -0,0 +1,69 @@
+use crate::config::AppConfig;
+use crate::db::sql_helpers::{format_identifier, normalize_identifier};
+
+// Joins the parts of a composite key into the record's key, e.g. `1042|A`
+pub const KEY_SEPARATOR: &str = "|";
+
+// The key columns the selection returns first, in order: `key_field_names` for a composite
+// key, otherwise the single `key_field_name`
+pub fn key_columns(config: &AppConfig) -> Vec<String> {
+    let columns: Vec<String> = config.key_field_names.iter()
+        .map(|column| column.trim().to_string())
+        .filter(|column| !column.is_empty())
+        .collect();
+    if columns.is_empty() {
+        vec![config.key_field_name.clone()]
+    } else {
+        columns
+    }
+}
+
+// The record key of a row whose first `key_count` values are its key columns
+pub fn row_key(row: &[String], key_count: usize) -> String {
+    row.iter().take(key_count.max(1)).cloned().collect::<Vec<_>>().join(KEY_SEPARATOR)
+}
+
+// `col1 = 'v1' AND col2 = 2`, matching exactly the row the key values identify. `integer`
+// tells which key columns have an integer type, so their values can be written unquoted.
+pub fn key_condition(config: &AppConfig, columns: &[String], values: &[String], integer: &[bool]) -> String {
+    columns.iter()
+        .zip(values)
+        .enumerate()
+        .map(|(index, (column, value))| format!(
+            "{} = {}",
+            format_identifier(&normalize_identifier(column), config.delimident),
+            key_literal(config, value, integer.get(index).copied().unwrap_or(false))
+        ))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+// A key value as SQL: a plain number for an integer column (with typed_key_predicates on),
+// so the comparison can use the column's index, and a quoted string otherwise
+pub fn key_literal(config: &AppConfig, value: &str, integer: bool) -> String {
+    match integer_key(config, value, integer) {
+        Some(number) => number,
+        None => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+// The canonical integer a key of an integer column holds: surrounding blanks, a `+` sign
+// and zero padding (`00042`) removed. None when the value isn't an integer, the column
+// isn't an integer column, or typed_key_predicates is off.
+pub fn integer_key(config: &AppConfig, value: &str, integer: bool) -> Option<String> {
+    if !integer || !config.typed_key_predicates {
+        return None;
+    }
+    
+    let value = value.trim();
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value.strip_prefix('+').unwrap_or(value)),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    
+    let digits = digits.trim_start_matches('0');
+    Some(if digits.is_empty() { "0".to_string() } else { format!("{}{}", sign, digits) })
+}
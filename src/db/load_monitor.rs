@@ -0,0 +1,198 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::db::connection::query_first_row;
+use crate::db::dialect;
+use crate::files::heartbeat;
+use crate::ui;
+use crate::utils::{notify, time};
+
+// Server load as reported by sysmaster
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoadReading {
+    pub sessions: u64,
+    pub locks: u64,
+    pub log_usage_percent: f64,
+    // When sysmaster was read
+    #[serde(default)]
+    pub read_at: String,
+}
+
+impl LoadReading {
+    pub fn summary(&self) -> String {
+        format!(
+            "sessions: {}, locks: {}, logical log usage: {:.1}%",
+            self.sessions, self.locks, self.log_usage_percent
+        )
+    }
+}
+
+// Read current session, lock and logical-log usage from sysmaster
+pub fn read_load(conn: &Connection) -> Result<LoadReading, Box<dyn Error>> {
+    let sessions = query_count(conn, "SELECT COUNT(*) FROM sysmaster:syssessions")?;
+    let locks = query_count(conn, "SELECT COUNT(*) FROM sysmaster:syslocks")?;
+
+    // Logs that are in use and not yet backed up cannot be reused
+    let log_row = query_first_row(
+        conn,
+        "SELECT SUM(CASE WHEN is_used = 1 AND is_backed_up = 0 THEN size ELSE 0 END), SUM(size) \
+         FROM sysmaster:syslogs",
+    )?.unwrap_or_default();
+
+    let used: f64 = log_row.first().and_then(|v| v.trim().parse().ok()).unwrap_or(0.0);
+    let total: f64 = log_row.get(1).and_then(|v| v.trim().parse().ok()).unwrap_or(0.0);
+    let log_usage_percent = if total > 0.0 { used * 100.0 / total } else { 0.0 };
+
+    Ok(LoadReading { sessions, locks, log_usage_percent, read_at: time::now_rfc3339() })
+}
+
+fn query_count(conn: &Connection, query: &str) -> Result<u64, Box<dyn Error>> {
+    let row = query_first_row(conn, query)?.unwrap_or_default();
+    Ok(row.first().and_then(|v| v.trim().parse().ok()).unwrap_or(0))
+}
+
+// Describe which configured threshold a reading exceeds, if any
+pub fn threshold_violation(config: &AppConfig, reading: &LoadReading) -> Option<String> {
+    if config.max_active_sessions > 0 && reading.sessions > config.max_active_sessions {
+        return Some(format!("{} sessions exceeds max_active_sessions {}", reading.sessions, config.max_active_sessions));
+    }
+    if config.max_lock_count > 0 && reading.locks > config.max_lock_count {
+        return Some(format!("{} locks exceeds max_lock_count {}", reading.locks, config.max_lock_count));
+    }
+    if config.max_log_usage_percent > 0.0 && reading.log_usage_percent > config.max_log_usage_percent {
+        return Some(format!(
+            "logical log usage {:.1}% exceeds max_log_usage_percent {:.1}%",
+            reading.log_usage_percent, config.max_log_usage_percent
+        ));
+    }
+    None
+}
+
+// Long-transaction high-water mark assumed when sysconfig can't be read (the Informix default)
+const DEFAULT_LTXHWM_PERCENT: f64 = 70.0;
+
+// Logical-log usage (percent) at which the long-transaction guard acts: the server's LTXHWM
+// minus the configured margin. None when the guard is disabled or not applicable.
+pub fn long_transaction_limit(conn: &Connection, config: &AppConfig) -> Option<f64> {
+    if !config.long_transaction_guard {
+        return None;
+    }
+
+    if !has_sysmaster(config) {
+        log::warn!("The long-transaction guard requires sysmaster and is skipped for dialect '{}'", config.dialect);
+        return None;
+    }
+
+    let ltxhwm = match query_first_row(conn, "SELECT cf_effective FROM sysmaster:sysconfig WHERE cf_name = 'LTXHWM'") {
+        Ok(row) => row.unwrap_or_default().first().and_then(|v| v.trim().parse::<f64>().ok()),
+        Err(e) => {
+            log::warn!("Could not read LTXHWM from sysmaster: {}", e);
+            None
+        }
+    }.unwrap_or(DEFAULT_LTXHWM_PERCENT);
+
+    let limit = (ltxhwm - config.long_transaction_margin_percent).max(1.0);
+    log::info!("Long-transaction guard active: LTXHWM {:.0}%, acting at {:.1}% logical log usage", ltxhwm, limit);
+    Some(limit)
+}
+
+// When logical-log usage is at or above `limit`, commit the open transaction (with
+// `commit_open_work`, in manual-commit mode) and pause until usage falls back below it, so
+// Informix never has to roll back a long transaction (-458). Returns whether the guard had to act.
+pub fn guard_long_transaction(
+    conn: &Connection,
+    config: &AppConfig,
+    limit: f64,
+    mut commit_open_work: Option<&mut dyn FnMut() -> Result<(), Box<dyn Error>>>,
+    progress_bar: &ProgressBar,
+) -> Result<bool, Box<dyn Error>> {
+    let mut acted = false;
+
+    loop {
+        let reading = match read_load(conn) {
+            Ok(reading) => reading,
+            Err(e) => {
+                log::warn!("Long-transaction guard could not read logical log usage: {}", e);
+                return Ok(acted);
+            }
+        };
+
+        if reading.log_usage_percent < limit {
+            if acted {
+                let message = format!("Logical log usage back to {:.1}%, resuming execution", reading.log_usage_percent);
+                ui::progress::print_with_progress(progress_bar, &message);
+                log::info!("{}", message);
+            }
+            return Ok(acted);
+        }
+
+        if !acted {
+            let manual_commit = commit_open_work.is_some();
+            if let Some(commit_open_work) = commit_open_work.as_mut() {
+                commit_open_work()?;
+            }
+            let message = format!(
+                "Long-transaction guard: logical log usage {:.1}% reached the {:.1}% limit; {}pausing until logs are freed",
+                reading.log_usage_percent, limit, if manual_commit { "committed open work, " } else { "" }
+            );
+            ui::progress::print_with_progress(progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+            log::warn!("{}", message);
+            notify::notify(config, "Long-transaction guard triggered", &message);
+            acted = true;
+        }
+
+        thread::sleep(Duration::from_secs(config.load_pause_seconds));
+    }
+}
+
+// Whether the configured dialect is Informix, whose sysmaster the readings come from
+fn has_sysmaster(config: &AppConfig) -> bool {
+    dialect::from_config(config).is_ok_and(|dialect| dialect.name() == "informix")
+}
+
+// Check server load and pause until it is back under the configured thresholds.
+// Failures to read sysmaster are logged but never block execution.
+pub fn wait_for_acceptable_load(conn: &Connection, config: &AppConfig, progress_bar: &ProgressBar) -> Option<LoadReading> {
+    if !config.load_monitoring_enabled() {
+        return None;
+    }
+
+    if !has_sysmaster(config) {
+        log::warn!("Load monitoring requires sysmaster and is skipped for dialect '{}'", config.dialect);
+        return None;
+    }
+
+    loop {
+        let reading = match read_load(conn) {
+            Ok(reading) => reading,
+            Err(e) => {
+                log::warn!("Could not read server load from sysmaster: {}", e);
+                return None;
+            }
+        };
+
+        log::info!("Server load: {}", reading.summary());
+        heartbeat::record_load(&reading);
+
+        match threshold_violation(config, &reading) {
+            Some(reason) => {
+                let message = format!(
+                    "Pausing {}s for server load ({}): {}",
+                    config.load_pause_seconds, reason, reading.summary()
+                );
+                ui::progress::print_with_progress(progress_bar, &message);
+                log::warn!("{}", message);
+                thread::sleep(Duration::from_secs(config.load_pause_seconds));
+            },
+            None => {
+                ui::progress::update_message(progress_bar, format!("Server load OK ({})", reading.summary()));
+                return Some(reading);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::config::{AppConfig, LookupConfig};
+use crate::db::fetch::for_each_row;
+use crate::ui;
+
+// One loaded lookup map and how rows find their entry in it
+#[derive(Debug, Clone)]
+struct LookupTable {
+    name: String,
+    key: String,
+    default: Option<String>,
+    entries: HashMap<String, String>,
+}
+
+// The lookup maps declared under `[lookups]`, loaded once at the start of generation so
+// templates can use values the selection query can't join in
+#[derive(Debug, Clone, Default)]
+pub struct LookupTables {
+    tables: Vec<LookupTable>,
+}
+
+impl LookupTables {
+    // Run each configured lookup query; the first column is the lookup key, the second the value
+    pub fn load(conn: &Connection, config: &AppConfig, progress_bar: &ProgressBar) -> Result<Self, Box<dyn Error>> {
+        let mut maps = HashMap::new();
+        
+        for (name, lookup) in sorted_lookups(config) {
+            let mut entries = HashMap::new();
+            for_each_row(conn, &lookup.query, config, progress_bar, |row| {
+                if row.len() < 2 {
+                    return Err(format!("Lookup '{}' query must return two columns (key, value)", name).into());
+                }
+                if let Some(previous) = entries.insert(row[0].trim().to_string(), row[1].clone()) {
+                    log::warn!("Lookup '{}' has more than one value for '{}'; using the last ('{}' was replaced)", name, row[0].trim(), previous);
+                }
+                Ok(())
+            }).map_err(|e| format!("Failed to load lookup '{}': {}", name, e))?;
+            
+            let message = format!("Loaded lookup '{}' with {} entries", name, entries.len());
+            ui::progress::print_with_progress(progress_bar, &message);
+            log::info!("{}", message);
+            maps.insert(name.clone(), entries);
+        }
+        
+        Ok(LookupTables::from_maps(config, maps))
+    }
+    
+    // Build the tables from already loaded maps (template fixtures supply them this way);
+    // a configured lookup missing from `maps` is empty
+    pub fn from_maps(config: &AppConfig, mut maps: HashMap<String, HashMap<String, String>>) -> Self {
+        let tables = sorted_lookups(config)
+            .into_iter()
+            .map(|(name, lookup)| LookupTable {
+                name: name.clone(),
+                key: lookup.key.clone(),
+                default: lookup.default.clone(),
+                entries: maps.remove(name).unwrap_or_default(),
+            })
+            .collect();
+        
+        LookupTables { tables }
+    }
+    
+    // Add each lookup's value for this row to the template values. Returns the reason the
+    // row must be skipped when a value has no entry and the lookup has no default.
+    pub fn apply(&self, values: &mut HashMap<String, String>) -> Result<(), String> {
+        for table in &self.tables {
+            let lookup_key = values.get(&table.key)
+                .ok_or_else(|| format!("lookup '{}' uses {{{{{}}}}}, which the selection doesn't provide", table.name, table.key))?
+                .trim()
+                .to_string();
+            
+            let value = match (table.entries.get(&lookup_key), &table.default) {
+                (Some(value), _) => value.clone(),
+                (None, Some(default)) => default.clone(),
+                (None, None) => return Err(format!("lookup '{}' has no entry for '{}'", table.name, lookup_key)),
+            };
+            values.insert(table.name.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+// Lookups in name order so loading and logging are deterministic
+fn sorted_lookups(config: &AppConfig) -> Vec<(&String, &LookupConfig)> {
+    let mut lookups: Vec<_> = config.lookups.iter().collect();
+    lookups.sort_by(|a, b| a.0.cmp(b.0));
+    lookups
+}
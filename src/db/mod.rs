@@ -1,11 +1,41 @@
 // Public modules - these are exposed to other parts of the application
 pub mod connection;
 pub mod query;
+pub mod dialect;
+pub mod load_monitor;
+pub mod hooks;
 
 // Private submodules - these are only used internally by the query module
 mod query_types;
 mod query_generation;
 mod query_execution;
 mod query_testing;
+mod query_ingestion;
 mod county_operations;
-mod sql_helpers;
\ No newline at end of file
+mod sql_helpers;
+mod sql_parser;
+mod keys;
+mod result_columns;
+mod cost_guard;
+mod preflight;
+mod protection;
+mod upsert;
+mod reconcile;
+mod verification;
+mod fetch;
+mod batch_tuning;
+mod pacing;
+mod column_format;
+mod error_classes;
+mod statistics;
+mod lookups;
+mod purge;
+mod streaming;
+mod sweep;
+mod template_fixtures;
+mod template_lint;
+mod trial;
+mod success_criteria;
+mod run_verification;
+mod rollback;
+mod index_advisory;
\ No newline at end of file
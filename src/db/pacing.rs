@@ -0,0 +1,103 @@
+use indicatif::ProgressBar;
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::ui;
+
+// Smallest delay added once latency rises, and the step it is halved back to zero from
+const MIN_DELAY: Duration = Duration::from_millis(50);
+
+// Paces execution by statement latency. The baseline is the average of the first
+// `pacing_window` statements (or `pacing_baseline_millis`); whenever the rolling average of
+// the last `pacing_window` statements exceeds it by `pacing_latency_multiplier`, the delay
+// between statements doubles up to `pacing_max_delay_millis`, and it halves again once the
+// server recovers.
+#[derive(Debug)]
+pub struct Pacer {
+    enabled: bool,
+    window: usize,
+    multiplier: f64,
+    max_delay: Duration,
+    baseline_millis: Option<f64>,
+    recent: VecDeque<f64>,
+    delay: Duration,
+    // Statements delayed and the longest delay used, for the end-of-run summary
+    paused: usize,
+    longest_delay: Duration,
+}
+
+impl Pacer {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Pacer {
+            enabled: config.adaptive_pacing,
+            window: config.pacing_window.max(1),
+            multiplier: config.pacing_latency_multiplier.max(1.0),
+            max_delay: Duration::from_millis(config.pacing_max_delay_millis),
+            baseline_millis: (config.pacing_baseline_millis > 0).then_some(config.pacing_baseline_millis as f64),
+            recent: VecDeque::new(),
+            delay: Duration::ZERO,
+            paused: 0,
+            longest_delay: Duration::ZERO,
+        }
+    }
+    
+    // Account for one statement's round trip and adjust the delay
+    pub fn record(&mut self, latency: Duration) {
+        if !self.enabled {
+            return;
+        }
+        
+        self.recent.push_back(latency.as_secs_f64() * 1000.0);
+        if self.recent.len() > self.window {
+            self.recent.pop_front();
+        }
+        let average = self.recent.iter().sum::<f64>() / self.recent.len() as f64;
+        
+        let baseline = match self.baseline_millis {
+            Some(baseline) => baseline,
+            None if self.recent.len() >= self.window => {
+                log::info!("Pacing baseline: {:.1}ms average statement latency", average);
+                self.baseline_millis = Some(average);
+                return;
+            },
+            None => return,
+        };
+        
+        let previous = self.delay;
+        if average > baseline * self.multiplier {
+            self.delay = (self.delay * 2).max(MIN_DELAY).min(self.max_delay);
+        } else if self.delay > MIN_DELAY {
+            self.delay /= 2;
+        } else {
+            self.delay = Duration::ZERO;
+        }
+        
+        if self.delay != previous {
+            log::info!(
+                "Pacing: average latency {:.1}ms (baseline {:.1}ms), delay now {}ms",
+                average, baseline, self.delay.as_millis()
+            );
+        }
+    }
+    
+    // Wait the current delay before the next statement
+    pub fn pause(&mut self, progress_bar: &ProgressBar) {
+        if self.delay.is_zero() {
+            return;
+        }
+        self.paused += 1;
+        self.longest_delay = self.longest_delay.max(self.delay);
+        ui::progress::update_message(progress_bar, format!("Pacing: waiting {}ms for the server", self.delay.as_millis()));
+        thread::sleep(self.delay);
+    }
+    
+    // "Pacing delayed 120 statements, up to 800ms" when it had to act
+    pub fn summary(&self) -> Option<String> {
+        (self.paused > 0).then(|| format!(
+            "Pacing delayed {} statements, up to {}ms, for rising statement latency",
+            self.paused, self.longest_delay.as_millis()
+        ))
+    }
+}
@@ -0,0 +1,133 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{AppConfig, PreflightCheck};
+use crate::db::connection::query_first_row;
+use crate::ui;
+use crate::utils::{masking, time};
+
+// Outcome of every check of the last phase that ran them, read back by `report`
+pub const PREFLIGHT_FILE_NAME: &str = "preflight.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PreflightResult {
+    pub name: String,
+    pub phase: String,
+    pub passed: bool,
+    // First column of the first row, if the query returned one
+    pub value: Option<String>,
+    // Why the check failed
+    pub reason: Option<String>,
+    pub timestamp: String,
+}
+
+// Run the `preflight_queries` that apply to `phase` ("generate" or "execute") and fail, naming
+// every failed check, unless all of them pass. The results are saved to preflight.json.
+pub fn run_preflight_checks(
+    conn: &Connection,
+    config: &AppConfig,
+    phase: &str,
+    results_dir: &str,
+    progress_bar: &ProgressBar,
+) -> Result<(), Box<dyn Error>> {
+    let checks: Vec<&PreflightCheck> = config.preflight_queries.iter()
+        .filter(|check| check.phases.is_empty() || check.phases.iter().any(|p| p.eq_ignore_ascii_case(phase)))
+        .collect();
+    if checks.is_empty() {
+        return Ok(());
+    }
+    
+    ui::progress::print_with_progress(progress_bar, &format!("Running {} pre-flight checks...", checks.len()));
+    let mut results = Vec::new();
+    for check in checks {
+        let result = run_check(conn, check, phase);
+        match &result.reason {
+            Some(reason) => {
+                ui::progress::print_with_progress(progress_bar, &format!("\x1b[31mPre-flight check '{}' failed: {}\x1b[0m", check.name, reason));
+                log::error!("Pre-flight check '{}' failed: {}", check.name, reason);
+            },
+            None => {
+                ui::progress::print_with_progress(progress_bar, &format!("Pre-flight check '{}' passed", check.name));
+                log::info!("Pre-flight check '{}' passed (value {:?})", check.name, result.value);
+            },
+        }
+        results.push(result);
+    }
+    
+    let path = Path::new(results_dir).join(PREFLIGHT_FILE_NAME);
+    fs::write(&path, serde_json::to_string_pretty(&results)?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    
+    let failed: Vec<&str> = results.iter().filter(|result| !result.passed).map(|result| result.name.as_str()).collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(format!("{} pre-flight checks failed: {}; {} aborted", failed.len(), failed.join(", "), phase).into())
+}
+
+fn run_check(conn: &Connection, check: &PreflightCheck, phase: &str) -> PreflightResult {
+    let mut result = PreflightResult {
+        name: check.name.clone(),
+        phase: phase.to_string(),
+        timestamp: time::now_rfc3339(),
+        ..PreflightResult::default()
+    };
+    
+    match query_first_row(conn, &check.query) {
+        Ok(row) => {
+            result.value = row.and_then(|row| row.into_iter().next()).map(|value| value.trim().to_string());
+            result.reason = failure(check, result.value.as_deref());
+        },
+        Err(e) => result.reason = Some(masking::mask(&format!("query failed: {}", e))),
+    }
+    result.passed = result.reason.is_none();
+    result
+}
+
+// Why a check's value doesn't meet its expectations, if it doesn't. A query returning no row
+// has no value, which only passes `expect = ""`.
+fn failure(check: &PreflightCheck, value: Option<&str>) -> Option<String> {
+    if let Some(expected) = &check.expect {
+        if value.unwrap_or_default() != expected.trim() {
+            return Some(format!("expected '{}', got {}", expected.trim(), describe(value)));
+        }
+    }
+    
+    if check.min.is_none() && check.max.is_none() {
+        return None;
+    }
+    let number = match value.and_then(|value| value.parse::<f64>().ok()) {
+        Some(number) => number,
+        None => return Some(format!("expected a number, got {}", describe(value))),
+    };
+    if let Some(min) = check.min {
+        if number < min {
+            return Some(format!("{} is below the minimum {}", number, min));
+        }
+    }
+    if let Some(max) = check.max {
+        if number > max {
+            return Some(format!("{} is above the maximum {}", number, max));
+        }
+    }
+    None
+}
+
+fn describe(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("'{}'", value),
+        None => "no row".to_string(),
+    }
+}
+
+// Results saved by the last phase that ran checks in `run_dir`
+pub fn load_preflight_results(run_dir: &str) -> Vec<PreflightResult> {
+    fs::read_to_string(Path::new(run_dir).join(PREFLIGHT_FILE_NAME))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,125 @@
+use regex::Regex;
+use std::path::PathBuf;
+
+use crate::config::AppConfig;
+use crate::db::query_types::QueryRecord;
+use crate::db::sql_helpers::{column_label, find_keyword_outside_quotes, parse_set_assignments, parse_update_statement, QualifiedName};
+use crate::files::json_handler::read_query_file;
+
+// Tables and columns no statement run by this tool may modify, from `protected_tables`
+// and `protected_columns`
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedObjects {
+    tables: Vec<QualifiedName>,
+    // (table, column); a column listed without a table is protected in every table
+    columns: Vec<(Option<QualifiedName>, String)>,
+}
+
+impl ProtectedObjects {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let tables = config.protected_tables.iter()
+            .filter_map(|table| QualifiedName::parse(table))
+            .collect();
+        let columns = config.protected_columns.iter()
+            .filter(|column| !column.trim().is_empty())
+            .map(|column| match column.trim().rsplit_once('.') {
+                Some((table, column)) => (QualifiedName::parse(table), column_label(column)),
+                None => (None, column_label(column)),
+            })
+            .collect();
+        ProtectedObjects { tables, columns }
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty() && self.columns.is_empty()
+    }
+    
+    // Why a statement must not run, if it modifies a protected table or column.
+    // Procedure calls can't be inspected and are not checked.
+    pub fn violation(&self, query: &str) -> Option<String> {
+        let (table, columns) = modified_objects(query)?;
+        let table = QualifiedName::parse(&table)?;
+        
+        if let Some(protected) = self.tables.iter().find(|protected| same_table(protected, &table)) {
+            return Some(format!("modifies protected table {}", protected.normalized()));
+        }
+        
+        for column in columns.iter().map(|column| column_label(column)) {
+            let protected = self.columns.iter().any(|(protected_table, protected_column)| {
+                *protected_column == column && protected_table.as_ref().is_none_or(|protected| same_table(protected, &table))
+            });
+            if protected {
+                return Some(format!("modifies protected column {}.{}", table.normalized(), column));
+            }
+        }
+        None
+    }
+    
+    // The violation of a record's statement or its INSERT fallback
+    pub fn record_violation(&self, query_record: &QueryRecord) -> Option<String> {
+        self.violation(&query_record.query)
+            .or_else(|| query_record.insert_fallback.as_deref().and_then(|insert| self.violation(insert)))
+    }
+}
+
+// A protected name without an owner matches the table under any owner
+fn same_table(protected: &QualifiedName, table: &QualifiedName) -> bool {
+    protected.name == table.name && protected.owner.as_ref().is_none_or(|owner| Some(owner) == table.owner.as_ref())
+}
+
+// The table a statement modifies and the columns it names, for UPDATE, DELETE, INSERT and MERGE.
+// DELETE removes whole rows, so it reports no columns and only protected tables reject it.
+fn modified_objects(query: &str) -> Option<(String, Vec<String>)> {
+    if let Some(parts) = parse_update_statement(query) {
+        let columns = parse_set_assignments(&parts.set_clause).into_iter().map(|(column, _)| column).collect();
+        return Some((parts.table, columns));
+    }
+    
+    let target = Regex::new(r#"(?is)^\s*(DELETE\s+FROM|DELETE|INSERT\s+INTO|MERGE\s+INTO)\s+("[^"]*"|[^\s(]+)"#).unwrap();
+    let captures = target.captures(query)?;
+    let statement = captures[1].to_uppercase();
+    let table = captures[2].to_string();
+    if statement.starts_with("DELETE") {
+        return Some((table, Vec::new()));
+    }
+    
+    let mut columns = Vec::new();
+    let insert_list = Regex::new(r"(?is)\bINSERT\s+(?:INTO\s+\S+\s*)?\(([^)]*)\)").unwrap();
+    if let Some(list) = insert_list.captures(query) {
+        columns.extend(list[1].split(',').map(|column| column.trim().to_string()));
+    }
+    
+    // MERGE ... WHEN MATCHED THEN UPDATE SET a = s.a, b = s.b WHEN NOT MATCHED ...
+    if let Some(position) = find_keyword_outside_quotes(query, "UPDATE SET") {
+        let assignments = &query[position + "UPDATE SET".len()..];
+        let end = find_keyword_outside_quotes(assignments, "WHEN").unwrap_or(assignments.len());
+        columns.extend(parse_set_assignments(&assignments[..end]).into_iter().map(|(column, _)| column));
+    }
+    Some((table, columns))
+}
+
+// Refuse to start when any pending query modifies a protected table or column, logging each
+// one so the template (or the ingested batch) can be corrected
+pub fn refuse_protected_queries(config: &AppConfig, query_files: &[PathBuf]) -> Result<(), String> {
+    let protected = ProtectedObjects::from_config(config);
+    if protected.is_empty() {
+        return Ok(());
+    }
+    
+    let violations: Vec<String> = query_files.iter()
+        .filter_map(|path| read_query_file(path).ok())
+        .filter(|record| !record.status.is_settled())
+        .filter_map(|record| protected.record_violation(&record).map(|reason| format!("key {}: {}", record.key, reason)))
+        .collect();
+    if violations.is_empty() {
+        return Ok(());
+    }
+    
+    for violation in &violations {
+        log::error!("Protected object: {}", violation);
+    }
+    Err(format!(
+        "{} pending queries modify protected tables or columns, e.g. {}; refusing to execute",
+        violations.len(), violations[0]
+    ))
+}
@@ -0,0 +1,280 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{AppConfig, PurgeConfig};
+use crate::db::connection::{execute_with_row_count, query_first_row};
+use crate::db::dialect;
+use crate::db::error_classes::OdbcDiagnostics;
+use crate::db::load_monitor::wait_for_acceptable_load;
+use crate::db::query_execution::finish_transaction;
+use crate::ui;
+use crate::utils::{masking, run_id, time};
+
+// Summary of a purge, written to purge.json in the results directory
+pub const PURGE_SUMMARY_FILE_NAME: &str = "purge.json";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeSummary {
+    pub run_id: String,
+    pub table: String,
+    pub condition: String,
+    pub strategy: String,
+    pub started: String,
+    pub duration_seconds: f64,
+    pub chunks: usize,
+    pub rows_deleted: u64,
+    // Why the purge ended before every matching row was deleted, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_reason: Option<String>,
+}
+
+// Delete the rows matching the purge condition one chunk at a time, committing each chunk
+// (when autocommit is off) so no single transaction grows large, until none remain.
+pub fn purge_rows(
+    conn: &Connection,
+    config: &AppConfig,
+    purge: &PurgeConfig,
+    results_dir: &str,
+    progress_bar: &ProgressBar,
+) -> Result<PurgeSummary, Box<dyn Error>> {
+    if purge.table.trim().is_empty() || purge.condition.trim().is_empty() {
+        return Err("purge requires both a table and a condition; refusing to delete without a WHERE clause".into());
+    }
+    if purge.chunk_size == 0 {
+        return Err("purge chunk_size must be greater than 0".into());
+    }
+    
+    let timer = Instant::now();
+    let mut summary = PurgeSummary {
+        run_id: run_id::current().to_string(),
+        table: purge.table.clone(),
+        condition: purge.condition.clone(),
+        strategy: purge.strategy.clone(),
+        started: time::now_rfc3339(),
+        ..PurgeSummary::default()
+    };
+    
+    // Size the progress bar from the number of matching rows
+    let count_query = format!("SELECT COUNT(*) FROM {} WHERE {}", purge.table, purge.condition);
+    let expected = query_first_row(conn, &count_query)?
+        .and_then(|row| row.first().and_then(|count| count.trim().parse::<u64>().ok()))
+        .unwrap_or(0);
+    progress_bar.set_length(expected);
+    
+    let message = format!("Purging {} rows from {} in chunks of {}", expected, purge.table, purge.chunk_size);
+    ui::progress::print_with_progress(progress_bar, &message);
+    log::info!("{} ({} strategy, condition: {})", message, purge.strategy, purge.condition);
+    
+    let mut chunks = ChunkStatements::new(conn, config, purge)?;
+    
+    while let Some(statement) = chunks.next_statement() {
+        if purge.max_chunks > 0 && summary.chunks >= purge.max_chunks {
+            summary.stopped_reason = Some(format!("max_chunks reached ({} chunks)", summary.chunks));
+            break;
+        }
+        
+        // Give way to a busy server between chunks
+        if summary.chunks > 0 && config.load_check_interval > 0 && summary.chunks.is_multiple_of(config.load_check_interval) {
+            wait_for_acceptable_load(conn, config, progress_bar);
+        }
+        
+        let deleted = config.execution_retry.run(
+            &format!("Purge chunk {}", summary.chunks + 1),
+            || {
+                let execution = execute_with_row_count(conn, &statement, ());
+                if config.execution_autocommit {
+                    execution
+                } else {
+                    finish_transaction(conn, execution)
+                }
+            },
+            |err| {
+                let diagnostics = OdbcDiagnostics::from_odbc_error(err);
+                config.execution_retry.is_retryable(diagnostics.sqlstate.as_deref(), diagnostics.native_error, diagnostics.isam_error)
+            },
+        ).map_err(|e| {
+            summary.duration_seconds = timer.elapsed().as_secs_f64();
+            summary.stopped_reason = Some(masking::mask(&format!("chunk {} failed: {}", summary.chunks + 1, e)));
+            let _ = save_summary(results_dir, &summary);
+            format!("Purge stopped after {} rows: {}", summary.rows_deleted, e)
+        })?;
+        
+        summary.chunks += 1;
+        let deleted = match deleted {
+            Some(deleted) => deleted as u64,
+            None => {
+                summary.stopped_reason = Some("the driver reports no row counts, so progress can't be tracked".to_string());
+                break;
+            }
+        };
+        summary.rows_deleted += deleted;
+        progress_bar.set_position(summary.rows_deleted);
+        ui::progress::update_message(progress_bar, format!("Purged {} rows from {}", summary.rows_deleted, purge.table));
+        log::info!("Purge chunk {} deleted {} rows ({} total)", summary.chunks, deleted, summary.rows_deleted);
+        
+        if !chunks.record_deleted(deleted) {
+            break;
+        }
+        if purge.pause_millis > 0 {
+            thread::sleep(Duration::from_millis(purge.pause_millis));
+        }
+    }
+    
+    summary.duration_seconds = timer.elapsed().as_secs_f64();
+    save_summary(results_dir, &summary)?;
+    
+    let message = format!(
+        "Purged {} rows from {} in {} chunks ({:.1}s)",
+        summary.rows_deleted, purge.table, summary.chunks, summary.duration_seconds
+    );
+    ui::progress::print_with_progress(progress_bar, &format!("\x1b[32m{}\x1b[0m", message));
+    log::info!("{}", message);
+    if let Some(reason) = &summary.stopped_reason {
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[33mStopped early: {}\x1b[0m", reason));
+        log::warn!("Purge stopped early: {}", reason);
+    }
+    
+    Ok(summary)
+}
+
+// Produces the DELETE for each chunk according to the purge strategy
+enum ChunkStatements {
+    // Delete the first chunk_size matching rows until a chunk deletes nothing
+    Limit { statement: String },
+    // Walk rowid ranges from the lowest to the highest matching rowid
+    Rowid { table: String, condition: String, chunk_size: usize, next: i64, last: i64 },
+}
+
+impl ChunkStatements {
+    fn new(conn: &Connection, config: &AppConfig, purge: &PurgeConfig) -> Result<Self, Box<dyn Error>> {
+        match purge.strategy.trim().to_lowercase().as_str() {
+            "" | "limit" => Self::limit(config, purge),
+            "rowid" => {
+                let range_query = format!("SELECT MIN(rowid), MAX(rowid) FROM {} WHERE {}", purge.table, purge.condition);
+                let row = query_first_row(conn, &range_query)?.unwrap_or_default();
+                let bound = |index: usize| row.get(index).and_then(|value| value.trim().parse::<i64>().ok());
+                Ok(Self::rowid_range(purge, bound(0), bound(1)))
+            },
+            other => Err(format!("Unknown purge strategy '{}' (expected 'limit' or 'rowid')", other).into()),
+        }
+    }
+    
+    fn limit(config: &AppConfig, purge: &PurgeConfig) -> Result<Self, Box<dyn Error>> {
+        Ok(ChunkStatements::Limit {
+            statement: dialect::from_config(config)?.chunked_delete(&purge.table, &purge.condition, purge.chunk_size),
+        })
+    }
+    
+    // Ranges from the lowest to the highest matching rowid; none when no rows match
+    fn rowid_range(purge: &PurgeConfig, first: Option<i64>, last: Option<i64>) -> Self {
+        let (next, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => (1, 0),
+        };
+        ChunkStatements::Rowid {
+            table: purge.table.clone(),
+            condition: purge.condition.clone(),
+            chunk_size: purge.chunk_size,
+            next,
+            last,
+        }
+    }
+    
+    fn next_statement(&mut self) -> Option<String> {
+        match self {
+            ChunkStatements::Limit { statement } => Some(statement.clone()),
+            ChunkStatements::Rowid { table, condition, chunk_size, next, last } => {
+                if *next > *last {
+                    return None;
+                }
+                let end = next.saturating_add(*chunk_size as i64);
+                let statement = format!(
+                    "DELETE FROM {} WHERE rowid >= {} AND rowid < {} AND ({})",
+                    table, next, end, condition
+                );
+                *next = end;
+                Some(statement)
+            },
+        }
+    }
+    
+    // Whether to keep going after a chunk deleted `deleted` rows
+    fn record_deleted(&self, deleted: u64) -> bool {
+        match self {
+            ChunkStatements::Limit { .. } => deleted > 0,
+            // A rowid range can be empty while later ranges still hold matching rows
+            ChunkStatements::Rowid { .. } => true,
+        }
+    }
+}
+
+fn save_summary(results_dir: &str, summary: &PurgeSummary) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(summary)?;
+    fs::write(Path::new(results_dir).join(PURGE_SUMMARY_FILE_NAME), json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn purge(strategy: &str) -> PurgeConfig {
+        PurgeConfig {
+            table: "audit_log".to_string(),
+            condition: "logged < TODAY - 90".to_string(),
+            chunk_size: 100,
+            strategy: strategy.to_string(),
+            pause_millis: 0,
+            max_chunks: 0,
+        }
+    }
+    
+    #[test]
+    fn limit_repeats_one_statement_until_a_chunk_deletes_nothing() {
+        let config: AppConfig = serde_json::from_str("{}").unwrap();
+        let mut chunks = ChunkStatements::limit(&config, &purge("limit")).unwrap();
+        let expected = "DELETE FROM audit_log WHERE rowid IN (SELECT FIRST 100 rowid FROM audit_log WHERE logged < TODAY - 90)";
+        
+        assert_eq!(chunks.next_statement().as_deref(), Some(expected));
+        assert!(chunks.record_deleted(100));
+        assert_eq!(chunks.next_statement().as_deref(), Some(expected));
+        assert!(chunks.record_deleted(7));
+        assert!(!chunks.record_deleted(0));
+    }
+    
+    #[test]
+    fn rowid_ranges_cover_the_matching_rowids_once() {
+        let mut chunks = ChunkStatements::rowid_range(&purge("rowid"), Some(150), Some(400));
+        let mut statements = Vec::new();
+        while let Some(statement) = chunks.next_statement() {
+            statements.push(statement);
+            // An empty range doesn't end the walk; later ranges can still match
+            assert!(chunks.record_deleted(0));
+        }
+        
+        assert_eq!(statements, vec![
+            "DELETE FROM audit_log WHERE rowid >= 150 AND rowid < 250 AND (logged < TODAY - 90)",
+            "DELETE FROM audit_log WHERE rowid >= 250 AND rowid < 350 AND (logged < TODAY - 90)",
+            "DELETE FROM audit_log WHERE rowid >= 350 AND rowid < 450 AND (logged < TODAY - 90)",
+        ]);
+    }
+    
+    #[test]
+    fn rowid_walk_of_a_single_row_is_one_chunk() {
+        let mut chunks = ChunkStatements::rowid_range(&purge("rowid"), Some(42), Some(42));
+        assert!(chunks.next_statement().is_some());
+        assert_eq!(chunks.next_statement(), None);
+    }
+    
+    #[test]
+    fn rowid_walk_without_matching_rows_has_no_chunks() {
+        let mut chunks = ChunkStatements::rowid_range(&purge("rowid"), None, None);
+        assert_eq!(chunks.next_statement(), None);
+    }
+}
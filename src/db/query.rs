@@ -3,8 +3,20 @@ pub use crate::db::query_types::*;
 pub use crate::db::query_generation::*;
 pub use crate::db::query_execution::*;
 pub use crate::db::query_testing::*;
+pub use crate::db::query_ingestion::*;
 pub use crate::db::county_operations::*;
 pub use crate::db::sql_helpers::*;
+pub use crate::db::template_fixtures::*;
+pub use crate::db::template_lint::*;
+pub use crate::db::purge::*;
+pub use crate::db::reconcile::*;
+pub use crate::db::preflight::*;
+pub use crate::db::sweep::*;
+pub use crate::db::streaming::*;
+pub use crate::db::trial::*;
+pub use crate::db::success_criteria::*;
+pub use crate::db::run_verification::*;
+pub use crate::db::rollback::*;
 
 // This module is now a facade that re-exports functionality from the more specialized modules
 // This maintains backward compatibility while allowing for better organization
\ No newline at end of file
@@ -1,119 +1,1647 @@
-use odbc_api::Connection;
+use odbc_api::{parameter::VarCharBox, Connection};
+use chrono::{DateTime, FixedOffset, Utc};
 use indicatif::ProgressBar;
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
 use std::error::Error;
-use std::fs;
-use chrono::prelude::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use crate::db::query_types::{QueryRecord, QueryStatus, ErrorRecord};
-use crate::files::json_handler::{save_query_file, read_query_files, save_error_file};
+use crate::config::AppConfig;
+use crate::db::batch_tuning;
+use crate::db::connection::{execute_packet_with_row_counts, execute_with_row_count};
+use crate::db::cost_guard::check_query_costs;
+use crate::db::error_classes::{ErrorStatistics, OdbcDiagnostics};
+use crate::db::hooks::{self, PostExecutionHook};
+use crate::db::protection::refuse_protected_queries;
+use crate::db::rollback::capture_rollback;
+use crate::db::load_monitor::{guard_long_transaction, long_transaction_limit, wait_for_acceptable_load};
+use crate::db::pacing::Pacer;
+use crate::db::preflight::run_preflight_checks;
+use crate::db::query_testing::basic_sql_validation;
+use crate::db::query_types::{prompt_user, DryRunResult, ErrorRecord, ExecutionAttempt, QueryRecord, QueryStatus, ZeroRowsPolicy};
+use crate::db::sql_helpers::parse_update_statement;
+use crate::db::statistics::update_statistics_after_run;
+use crate::db::success_criteria::RunMetrics;
+use crate::db::verification::{verify_query, VerificationOutcome};
+use crate::files::checkpoint::GenerationCheckpoint;
+use crate::files::dead_letter::move_to_dead_letter;
+use crate::files::journal::ExecutionJournal;
+use crate::files::json_handler::{save_query_file, read_query_file, read_query_files, save_error_file};
+use crate::files::manifest::RunManifest;
+use crate::files::leases::{HeldLease, LeaseOutcome, LeaseStore};
+use crate::files::ledger::{AppliedLedger, LedgerEntry, ReplayPolicy};
+use crate::files::processed::ProcessedRecords;
 use crate::ui;
+use crate::utils::operator;
+use crate::utils::retry::RetryPolicy;
+use crate::utils::span::RecordSpan;
+use crate::utils::{encoding, masking, run_id, time};
+
+// Options controlling a single execution phase
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionOptions {
+    // Execute and verify this many randomly chosen queries before the full run
+    pub canary: Option<usize>,
+    // Stop cleanly after executing this many queries, leaving the rest Pending
+    pub max_rows: Option<usize>,
+    // Stop cleanly once this much time has passed; a running statement is allowed to finish
+    pub max_duration: Option<Duration>,
+    // Only execute the queries whose key hashes into this partition
+    pub partition: Option<Partition>,
+    // Execute queries older than max_query_age_hours anyway
+    pub allow_stale: bool,
+    // Ask whether to continue when a chunk's error rate exceeds confirm_chunk_error_rate
+    pub interactive: bool,
+}
+
+// One of `count` disjoint slices of a run, selected by a hash of each query's key so
+// independent invocations on different hosts agree on the split without coordinating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition {
+    // 1-based
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Partition {
+    // Parse "2/4" (the second of four partitions)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (index, count) = text.split_once('/')
+            .ok_or_else(|| format!("invalid partition '{}' (expected N/M, e.g. 2/4)", text))?;
+        let index: u64 = index.trim().parse().map_err(|_| format!("invalid partition index '{}'", index))?;
+        let count: u64 = count.trim().parse().map_err(|_| format!("invalid partition count '{}'", count))?;
+        
+        if count == 0 || index == 0 || index > count {
+            return Err(format!("invalid partition '{}': N must be between 1 and M", text));
+        }
+        
+        Ok(Partition { index, count })
+    }
+    
+    // Whether a query key belongs to this partition
+    pub fn contains(&self, key: &str) -> bool {
+        let digest = Sha256::digest(key.as_bytes());
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&digest[..8]);
+        u64::from_be_bytes(prefix) % self.count == self.index - 1
+    }
+}
+
+// "2/4", as given on the command line
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl ExecutionOptions {
+    // Why the run's budget is used up, if it is
+    fn budget_exhausted(&self, executed: usize, elapsed: Duration) -> Option<String> {
+        if let Some(max_rows) = self.max_rows {
+            if executed >= max_rows {
+                return Some(format!("max rows reached ({} queries executed)", executed));
+            }
+        }
+        if let Some(max_duration) = self.max_duration {
+            if elapsed >= max_duration {
+                return Some(format!("max duration reached ({}s elapsed)", elapsed.as_secs()));
+            }
+        }
+        None
+    }
+}
+
+// What happened to a single query file during execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordOutcome {
+    Succeeded,
+    Failed,
+    AlreadyCompleted,
+    AlreadyApplied,
+    Quarantined,
+    Conflict,
+    // Another worker holds the key's lease; the record is left for it
+    Leased,
+    // Waiting to be sent in the next statement packet; its outcome is counted once it has run
+    Queued,
+}
+
+// Shared state for executing the records of one phase
+struct ExecutionContext<'a> {
+    conn: &'a Connection<'a>,
+    config: &'a AppConfig,
+    results_dir: &'a str,
+    hooks: &'a [Box<dyn PostExecutionHook>],
+    progress_bar: &'a ProgressBar,
+    // Autocommit is off, so each record is committed or rolled back here
+    manual_commit: bool,
+    replay_policy: ReplayPolicy,
+    retry_policy: &'a RetryPolicy,
+    abort_sqlstates: &'a [String],
+    // Re-run the offline validation and require the SQL `test` passed
+    revalidate: bool,
+    // Prefix statements with their run, key and ticket for DBAs tracing sessions
+    provenance_comments: bool,
+    // Failed executions before a record is dead-lettered (0 never)
+    max_record_attempts: u32,
+    // Key leases shared with other workers, when key_leases is on
+    leases: Option<&'a LeaseStore>,
+    // Successful statements committed together as one transaction; 0 commits each on its own
+    commit_batch_size: usize,
+    // Roll back the open batch and stop when a statement fails
+    rollback_batch_on_error: bool,
+    // Intent and completion of every statement, when execution_journal is on
+    journal: Option<&'a ExecutionJournal>,
+    // Write each UPDATE's rollback script before it runs
+    capture_rollback: bool,
+    zero_rows_policy: ZeroRowsPolicy,
+    // Bytes of SQL sent per round trip in a statement packet; 0 sends each statement on its own
+    statement_packet_bytes: usize,
+}
+
+// A successful statement of the open batch, settled once its transaction commits. The lease on
+// its key is held, and renewed as later statements settle, until then, so no other worker
+// finds the record InProgress meanwhile.
+struct UncommittedRecord<'a> {
+    file_path: PathBuf,
+    record: QueryRecord,
+    change_hash: String,
+    rows_affected: u64,
+    lease: Option<HeldLease<'a>>,
+}
+
+// A record whose statement is about to reach, or has reached, the server: saved InProgress
+// with its journal intent written
+struct StatementAttempt {
+    file_path: PathBuf,
+    change_hash: String,
+    current_time: String,
+}
+
+// A statement waiting for the next packet
+struct QueuedStatement {
+    attempt: StatementAttempt,
+    record: QueryRecord,
+    statement: String,
+}
+
+// Running totals for an execution phase
+#[derive(Default)]
+struct ExecutionTotals<'a> {
+    success_count: usize,
+    error_count: usize,
+    already_applied_count: usize,
+    quarantined_count: usize,
+    conflict_count: usize,
+    leased_count: usize,
+    long_transaction_pauses: usize,
+    error_statistics: ErrorStatistics,
+    // Rows changed by successful statements; a statement whose driver reports no count counts once
+    rows_affected: u64,
+    // Unguarded statements that affected no rows, whatever zero_rows_policy made of them
+    no_rows_count: usize,
+    // Tables changed by successful UPDATE statements
+    modified_tables: BTreeSet<String>,
+    // Set when a failure's SQLSTATE is in abort_sqlstates
+    abort_reason: Option<String>,
+    // Successful statements of the open transaction, with commit_batch_size
+    uncommitted: Vec<UncommittedRecord<'a>>,
+    batches_committed: usize,
+    // Statements per batch, commit_batch_size or as tuned with adaptive_batch_size
+    commit_interval: usize,
+    // When the first statement of the open batch started
+    batch_started: Option<Instant>,
+    // Successful statements undone with their batch and left Pending
+    rolled_back_count: usize,
+    // Statements waiting to be sent together, with statement_packet_bytes
+    packet: Vec<QueuedStatement>,
+    packets_sent: usize,
+    // The driver refused a packet whose statements all ran on their own
+    packets_unsupported: bool,
+}
+
+impl ExecutionTotals<'_> {
+    fn record(&mut self, outcome: RecordOutcome) {
+        match outcome {
+            RecordOutcome::Succeeded => self.success_count += 1,
+            RecordOutcome::Failed => self.error_count += 1,
+            RecordOutcome::AlreadyApplied => self.already_applied_count += 1,
+            RecordOutcome::Quarantined => self.quarantined_count += 1,
+            RecordOutcome::Conflict => self.conflict_count += 1,
+            RecordOutcome::Leased => self.leased_count += 1,
+            RecordOutcome::AlreadyCompleted | RecordOutcome::Queued => {},
+        }
+    }
+    
+    // Statements sent to the database so far, counting those queued for the next packet so
+    // max_rows isn't overshot by a packet's worth
+    fn executed(&self) -> usize {
+        self.success_count + self.error_count + self.conflict_count + self.packet.len()
+    }
+}
 
 pub fn execute_queries(
     conn: &Connection,
+    config: &AppConfig,
     results_dir: &str,
+    processed_records: &mut ProcessedRecords,
+    options: &ExecutionOptions,
     progress_bar: &ProgressBar,
-) -> Result<(usize, usize), Box<dyn Error>> {
+) -> Result<RunMetrics, Box<dyn Error>> {
     ui::progress::print_with_progress(progress_bar, "Executing update queries...");
     
     // Find all query files in the results directory
-    let query_files = read_query_files(results_dir)?;
+    let query_files = partition_query_files(results_dir, options, progress_bar)?;
+    
     let total_files = query_files.len();
     
     if total_files == 0 {
         ui::progress::print_with_progress(progress_bar, "No queries found to execute.");
-        return Ok((0, 0));
+        return Ok(RunMetrics::default());
+    }
+    
+    // A half-generated run would silently apply only part of the correction
+    if GenerationCheckpoint::load(results_dir).is_some() {
+        return Err(format!(
+            "Generation of {} was interrupted and never finished; run `generate --resume` before executing",
+            results_dir
+        ).into());
+    }
+    
+    // Never apply updates computed from a selection that is too old to trust
+    if config.max_query_age_hours > 0 && !options.allow_stale {
+        refuse_stale_queries(&query_files, results_dir, config.max_query_age_hours)?;
+    }
+    
+    // Nothing runs while any statement would touch a protected table or column
+    refuse_protected_queries(config, &query_files)?;
+    
+    // Changes applied by any earlier run, including ones from other results directories
+    let replay_policy = ReplayPolicy::from_name(&config.replay_policy)?;
+    let zero_rows_policy = ZeroRowsPolicy::from_name(&config.zero_rows_policy)?;
+    let mut ledger = AppliedLedger::load(&config.data_path);
+    if replay_policy == ReplayPolicy::Refuse {
+        refuse_replayed_queries(&query_files, processed_records, &ledger)?;
+    }
+    
+    // Autocommit would commit each statement before a batch could be rolled back
+    if config.commit_batch_size > 0 && config.execution_autocommit {
+        return Err("commit_batch_size groups statements into transactions, which needs execution_autocommit = false".into());
     }
     
     progress_bar.set_length(total_files as u64);
     
-    let mut success_count = 0;
-    let mut error_count = 0;
+    // Operational checks (no archival job running, table sizes, ...) come before anything else touches the table
+    run_preflight_checks(conn, config, "execute", results_dir, progress_bar)?;
+    
+    // Catch statements whose plans would scan the whole table before any of them runs
+    check_query_costs(conn, config, &query_files, progress_bar)?;
+    
+    // Attribute this execution to its operator in the run's manifest
+    RunManifest::record_execution(results_dir, options.partition.map(|partition| partition.to_string()))?;
+    
+    // Check server load before starting so we don't add to an already busy system
+    if let Some(reading) = wait_for_acceptable_load(conn, config, progress_bar) {
+        ui::progress::print_with_progress(progress_bar, &format!("Server load before execution: {}", reading.summary()));
+    }
+    
+    // Keep clear of Informix's long-transaction high-water mark before and during the run
+    let long_transaction_limit = long_transaction_limit(conn, config);
+    let manual_commit = !config.execution_autocommit;
+    
+    let started = Instant::now();
+    let mut stopped_reason = None;
+    let hooks = hooks::from_config(config);
+    let leases = if config.key_leases {
+        Some(LeaseStore::open(&config.data_path, results_dir, config.key_lease_seconds)?)
+    } else {
+        None
+    };
+    let journal = if config.execution_journal {
+        Some(ExecutionJournal::open(results_dir)?)
+    } else {
+        None
+    };
+    // A packet is undone with its batch when one of its statements fails, and a leased key's
+    // lease is settled statement by statement
+    let statement_packet_bytes = if config.statement_packet_bytes > 0 && (config.commit_batch_size == 0 || config.key_leases) {
+        log::warn!("Ignoring statement_packet_bytes: statement packets need commit_batch_size and can't be used with key_leases");
+        0
+    } else {
+        config.statement_packet_bytes
+    };
+    let context = ExecutionContext {
+        conn,
+        config,
+        results_dir,
+        hooks: &hooks,
+        progress_bar,
+        manual_commit,
+        replay_policy,
+        zero_rows_policy,
+        retry_policy: &config.execution_retry,
+        abort_sqlstates: &config.abort_sqlstates,
+        revalidate: config.revalidate_before_execute,
+        provenance_comments: config.sql_provenance_comments,
+        max_record_attempts: config.max_record_attempts,
+        leases: leases.as_ref(),
+        commit_batch_size: config.commit_batch_size,
+        rollback_batch_on_error: config.rollback_batch_on_error,
+        journal: journal.as_ref(),
+        capture_rollback: config.capture_rollback,
+        statement_packet_bytes,
+    };
+    let mut totals = ExecutionTotals {
+        commit_interval: batch_tuning::initial_commit_batch_size(config),
+        ..ExecutionTotals::default()
+    };
+    
+    // Run the canary first; the main loop skips the files it completed. A failed canary keeps
+    // none of its batch, but without commit batches its statements are already committed, so
+    // their processed records are saved before the run aborts.
+    if let Some(canary_size) = options.canary {
+        if let Err(e) = run_canary(&context, &query_files, canary_size, processed_records, &mut ledger, &mut totals) {
+            roll_back_batch(&context, &mut totals, "the canary failed")?;
+            if let Err(save_error) = processed_records.save(&config.data_path) {
+                log::error!("Failed to save processed records after the canary failed: {}", save_error);
+            }
+            return Err(e);
+        }
+        commit_batch(&context, processed_records, &mut ledger, &mut totals)?;
+    }
+    
+    // Successes and failures when the current confirmation chunk started
+    let mut chunk_start = (totals.success_count, totals.error_count);
+    let mut chunk_number = 0;
+    let mut pacer = Pacer::from_config(config);
     
     for (index, file_path) in query_files.iter().enumerate() {
         progress_bar.set_position(index as u64);
+        ui::progress::update_rows_affected(progress_bar, totals.rows_affected);
         
-        // Read query record from file
-        let file_content = match fs::read_to_string(file_path) {
-            Ok(content) => content,
-            Err(e) => {
-                log::error!("Failed to read file {}: {}", file_path.display(), e);
-                error_count += 1;
-                continue;
+        // Stop cleanly once the authorized budget is used; the rest stays Pending for the next window
+        if let Some(reason) = options.budget_exhausted(totals.executed(), started.elapsed()) {
+            stopped_reason = Some(reason);
+            break;
+        }
+        
+        // Re-check server load periodically during long runs
+        if index > 0 && config.load_check_interval > 0 && index % config.load_check_interval == 0 {
+            wait_for_acceptable_load(conn, config, progress_bar);
+        }
+        if let Some(limit) = long_transaction_limit {
+            if index == 0 || (config.load_check_interval > 0 && index % config.load_check_interval == 0) {
+                // A batch's records are settled along with the open work the guard commits
+                let mut commit = || commit_open_work(&context, processed_records, &mut ledger, &mut totals);
+                let commit: Option<&mut dyn FnMut() -> Result<(), Box<dyn Error>>> = if manual_commit { Some(&mut commit) } else { None };
+                if guard_long_transaction(conn, config, limit, commit, progress_bar)? {
+                    totals.long_transaction_pauses += 1;
+                }
+                if let Some(reason) = totals.abort_reason.take() {
+                    stopped_reason = Some(reason);
+                    break;
+                }
             }
+        }
+        
+        pacer.pause(progress_bar);
+        let statement_started = Instant::now();
+        if totals.uncommitted.is_empty() && totals.packet.is_empty() {
+            totals.batch_started = Some(statement_started);
+        }
+        let outcome = match load_query_record(file_path) {
+            Some(mut query_record) => {
+                let span = record_span(&query_record);
+                let outcome = execute_record(&context, file_path, &mut query_record, processed_records, &mut ledger, &mut totals)?;
+                finish_span(span, outcome);
+                outcome
+            },
+            None => RecordOutcome::Failed,
         };
-            
-        let mut query_record: QueryRecord = match serde_json::from_str(&file_content) {
-            Ok(record) => record,
-            Err(e) => {
-                log::error!("Failed to parse JSON from file {}: {}", file_path.display(), e);
-                error_count += 1;
-                continue;
+        totals.record(outcome);
+        if context.commit_batch_size > 0 && totals.uncommitted.len() + totals.packet.len() >= totals.commit_interval {
+            commit_batch(&context, processed_records, &mut ledger, &mut totals)?;
+        }
+        if let Some(reason) = totals.abort_reason.take() {
+            stopped_reason = Some(reason);
+            break;
+        }
+        
+        // Only statements that reached the database say anything about its latency
+        if matches!(outcome, RecordOutcome::Succeeded | RecordOutcome::Failed | RecordOutcome::Conflict) {
+            pacer.record(statement_started.elapsed());
+        }
+        
+        // Every statement of a finished chunk is committed; check its error rate before the next
+        let (succeeded, failed) = (totals.success_count - chunk_start.0, totals.error_count - chunk_start.1);
+        if config.confirm_chunk_size > 0 && succeeded + failed >= config.confirm_chunk_size {
+            commit_batch(&context, processed_records, &mut ledger, &mut totals)?;
+            chunk_number += 1;
+            chunk_start = (totals.success_count, totals.error_count);
+            if !confirm_chunk(config, options, chunk_number, succeeded, failed, progress_bar) {
+                stopped_reason = Some(format!("operator stopped after chunk {}", chunk_number));
+                break;
             }
+        }
+    }
+    
+    // Statements still waiting for their packet aren't sent once execution has stopped
+    if let Some(reason) = &stopped_reason {
+        let queued = std::mem::take(&mut totals.packet);
+        release_packet(context.journal, &mut totals, queued, &format!("not sent - execution stopped: {}", reason))?;
+    }
+    
+    // Whatever stopped the loop, the statements it ran so far are committed (or, if the commit
+    // fails, rolled back and left Pending)
+    commit_batch(&context, processed_records, &mut ledger, &mut totals)?;
+    if let Some(reason) = totals.abort_reason.take() {
+        stopped_reason.get_or_insert(reason);
+    }
+    ui::progress::update_rows_affected(progress_bar, totals.rows_affected);
+    
+    // Print summary at the end
+    let summary = format!(
+        "Executed {} queries: {} successful, {} failed, {} conflicts, {} skipped as already applied, {} quarantined; {} rows affected",
+        total_files, totals.success_count, totals.error_count, totals.conflict_count,
+        totals.already_applied_count, totals.quarantined_count, totals.rows_affected
+    );
+    ui::progress::print_with_progress(progress_bar, &summary);
+    log::info!("{}", summary);
+    
+    if totals.no_rows_count > 0 {
+        let message = format!(
+            "{} statements affected no rows (zero_rows_policy \"{}\": {})",
+            totals.no_rows_count, config.zero_rows_policy.trim(),
+            if zero_rows_policy == ZeroRowsPolicy::Fail { "counted as failed" } else { "counted as successful" }
+        );
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::warn!("{}", message);
+    }
+    
+    if context.commit_batch_size > 0 {
+        batch_tuning::save_commit_batch_size(config, totals.commit_interval);
+        let message = format!(
+            "Committed {} batches, ending at {} statements per batch; {} statements rolled back and left Pending",
+            totals.batches_committed, totals.commit_interval, totals.rolled_back_count
+        );
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+    }
+    
+    if context.statement_packet_bytes > 0 {
+        let message = if totals.packets_unsupported {
+            format!("Sent {} statement packets before the driver refused one; the rest went one statement at a time", totals.packets_sent)
+        } else {
+            format!("Sent {} statement packets of up to {} bytes", totals.packets_sent, context.statement_packet_bytes)
         };
-            
-        // Skip already executed queries
-        if query_record.status == QueryStatus::Completed {
-            ui::progress::update_message(progress_bar, format!("Skipping already completed query for key: {}", query_record.key));
-            continue;
-        }
-        
-        // Update progress bar message
-        ui::progress::update_message(progress_bar, format!("Executing query for key: {}", query_record.key));
-        
-        // Execute the query
-        let current_time = Utc::now().to_rfc3339();
-        match conn.execute(&query_record.query, ()) {
-            Ok(Some(_cursor)) => {
-                // For UPDATE, INSERT, DELETE, assume success if we got a cursor without error
-                // Success case - we assume rows were affected
-                query_record.status = QueryStatus::Completed;
-                query_record.result = Some("success - operation completed".to_string());
-                query_record.timestamp = Some(current_time.clone());
-                success_count += 1;
-                
-                log::info!("Query execution successful for key {}", query_record.key);
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+    }
+    
+    if totals.leased_count > 0 {
+        let message = format!("{} queries were left Pending while another worker held their key's lease", totals.leased_count);
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::warn!("{}", message);
+    }
+    
+    if totals.long_transaction_pauses > 0 {
+        let message = format!("Long-transaction guard paused execution {} times", totals.long_transaction_pauses);
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::warn!("{}", message);
+    }
+    
+    if let Some(message) = pacer.summary() {
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::warn!("{}", message);
+    }
+    
+    if let Some(reason) = stopped_reason {
+        let message = format!("Stopped early: {}; remaining queries stay Pending for the next run", reason);
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+        log::warn!("{}", message);
+    }
+    
+    // Break failures down by error class with targeted recommendations
+    if !totals.error_statistics.is_empty() {
+        ui::progress::print_with_progress(progress_bar, "Errors by class:");
+        for line in totals.error_statistics.report() {
+            ui::progress::print_with_progress(progress_bar, &format!("  {}", line));
+            log::warn!("Error class {}", line);
+        }
+    }
+    
+    // Large corrections can leave the optimizer with stale distributions
+    update_statistics_after_run(conn, config, results_dir, &totals.modified_tables, totals.rows_affected, progress_bar)?;
+    
+    Ok(RunMetrics {
+        succeeded: totals.success_count,
+        failed: totals.error_count,
+        conflicts: totals.conflict_count,
+        skipped: totals.already_applied_count,
+        quarantined: totals.quarantined_count,
+        constraint_violations: totals.error_statistics.constraint_violations(),
+        rows_affected: totals.rows_affected,
+    })
+}
+
+// The query files of the results directory, keeping only this invocation's share of the run
+// with --partition; query files are named after their key
+fn partition_query_files(results_dir: &str, options: &ExecutionOptions, progress_bar: &ProgressBar) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut query_files = read_query_files(results_dir)?;
+    if let Some(partition) = options.partition {
+        let before = query_files.len();
+        query_files.retain(|path| {
+            path.file_stem().is_some_and(|key| partition.contains(&key.to_string_lossy()))
+        });
+        let message = format!("Partition {}/{}: {} of {} queries", partition.index, partition.count, query_files.len(), before);
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+    }
+    Ok(query_files)
+}
+
+// Outcome of an `execute --dry-run`
+#[derive(Debug, Default)]
+pub struct DryRunSummary {
+    // Statements executed and rolled back
+    pub executed: usize,
+    pub failed: usize,
+    // Guarded statements that matched no row because it changed since selection
+    pub conflicts: usize,
+    // Successful statements that would change no row
+    pub no_rows: usize,
+    pub rows_affected: u64,
+    pub error_statistics: ErrorStatistics,
+}
+
+// Execute each Pending statement against the real tables inside a transaction and roll it back,
+// storing the server's response and row count in the record's `dry_run`. Unlike `test`, this
+// catches constraint violations, locks, permissions and statements that match no row. Records
+// keep their status, nothing is added to the applied ledger and no hooks run. The connection
+// must have autocommit off.
+pub fn dry_run_queries(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    options: &ExecutionOptions,
+    progress_bar: &ProgressBar,
+) -> Result<DryRunSummary, Box<dyn Error>> {
+    ui::progress::print_with_progress(progress_bar, "Dry run: executing update queries and rolling each one back...");
+    
+    let query_files = partition_query_files(results_dir, options, progress_bar)?;
+    
+    // Even a rolled-back statement locks and logs the rows it touches
+    refuse_protected_queries(config, &query_files)?;
+    let zero_rows_policy = ZeroRowsPolicy::from_name(&config.zero_rows_policy)?;
+    
+    progress_bar.set_length(query_files.len() as u64);
+    let started = Instant::now();
+    let mut summary = DryRunSummary::default();
+    for (index, file_path) in query_files.iter().enumerate() {
+        progress_bar.set_position(index as u64);
+        ui::progress::update_rows_affected(progress_bar, summary.rows_affected);
+        if let Some(reason) = options.budget_exhausted(summary.executed, started.elapsed()) {
+            let message = format!("Dry run stopped: {}", reason);
+            ui::progress::print_with_progress(progress_bar, &message);
+            log::info!("{}", message);
+            break;
+        }
+        
+        let mut query_record = match load_query_record(file_path) {
+            Some(record) if record.status == QueryStatus::Pending => record,
+            _ => continue,
+        };
+        ui::progress::update_message(progress_bar, format!("Dry run of query for key: {}", query_record.key));
+        
+        let parameters: Vec<VarCharBox> = query_record.bound_values(results_dir)?
+            .iter()
+            .map(|value| VarCharBox::from_vec(encoding::encode(value)))
+            .collect();
+        let sent = |sql: &str| if config.sql_provenance_comments { query_record.with_provenance_comment(sql) } else { sql.to_string() };
+        let statement = sent(&query_record.query);
+        let insert_fallback = query_record.insert_fallback.as_deref().map(sent);
+        
+        let mut execution = if parameters.is_empty() {
+            execute_with_row_count(conn, &statement, ())
+        } else {
+            execute_with_row_count(conn, &statement, parameters.as_slice())
+        };
+        if let (Ok(Some(0)), Some(insert)) = (&execution, &insert_fallback) {
+            execution = execute_with_row_count(conn, insert, ());
+        }
+        // A change that couldn't be undone must stop everything before it is repeated
+        if let Err(e) = conn.rollback() {
+            return Err(format!(
+                "Rollback after the dry run of key {} failed, so its change may have been applied: {}",
+                query_record.key, OdbcDiagnostics::from_odbc_error(&e).summary()
+            ).into());
+        }
+        summary.executed += 1;
+        
+        let result = match execution {
+            Ok(Some(0)) if query_record.concurrency_guard => {
+                summary.conflicts += 1;
+                DryRunResult::new(Some(0), "conflict - row changed since selection".to_string(), None)
             },
-            Ok(None) => {
-                // No cursor returned, but no error - could be 0 rows affected
-                // This is still considered a success, not an error
-                query_record.status = QueryStatus::Completed;
-                query_record.result = Some("success".to_string());
-                query_record.timestamp = Some(current_time.clone());
-                success_count += 1;
-                
-                // Just log as info, not as error
-                log::info!("Query execution completed for key {} but no rows were affected", query_record.key);
+            Ok(Some(0)) if zero_rows_policy == ZeroRowsPolicy::Fail => {
+                summary.failed += 1;
+                DryRunResult::new(Some(0), "error: no rows affected (zero_rows_policy \"fail\")".to_string(), None)
             },
-            Err(err) => {
-                // Only this case is a true error - when ODBC returns an error
-                query_record.status = QueryStatus::Failed;
-                query_record.result = Some(format!("error: {:?}", err));
-                query_record.timestamp = Some(current_time.clone());
-                
-                // Add to error log
-                let error_record = ErrorRecord {
-                    key: query_record.key.clone(),
-                    file: file_path.file_name().unwrap().to_string_lossy().to_string(),
-                    error: format!("{:?}", err),
-                    timestamp: current_time.clone(),
+            Ok(row_count) => {
+                if row_count == Some(0) {
+                    summary.no_rows += 1;
+                }
+                summary.rows_affected += row_count.unwrap_or(1) as u64;
+                let response = match row_count {
+                    Some(rows) => format!("success - {} rows affected", rows),
+                    None => "success - operation completed".to_string(),
                 };
-                
-                save_error_file(&format!("{}/errors.json", results_dir), &error_record)?;
-                error_count += 1;
-                
-                // Only log actual ODBC errors
-                log::error!("Query execution failed for key {}: {:?}", query_record.key, err);
+                DryRunResult::new(row_count, response, None)
+            },
+            Err(err) => {
+                summary.failed += 1;
+                let diagnostics = OdbcDiagnostics::from_odbc_error(&err);
+                summary.error_statistics.record(diagnostics.class(), &query_record.key, &masking::mask(&diagnostics.message));
+                DryRunResult::new(None, format!("error: {}", masking::mask(&diagnostics.summary())), diagnostics.sqlstate.clone())
+            },
+        };
+        log::info!("Dry run of key {}{}: {} (rolled back)", query_record.key, query_record.ticket_suffix(), result.response);
+        query_record.dry_run = Some(result);
+        save_query_file(file_path, &query_record)?;
+    }
+    
+    let summary_line = format!(
+        "Dry run executed and rolled back {} queries: {} would succeed ({} changing no rows), {} would fail, {} would conflict; {} rows would change",
+        summary.executed, summary.executed - summary.failed - summary.conflicts, summary.no_rows,
+        summary.failed, summary.conflicts, summary.rows_affected
+    );
+    ui::progress::print_with_progress(progress_bar, &summary_line);
+    log::info!("{}", summary_line);
+    
+    if !summary.error_statistics.is_empty() {
+        ui::progress::print_with_progress(progress_bar, "Errors by class:");
+        for line in summary.error_statistics.report() {
+            ui::progress::print_with_progress(progress_bar, &format!("  {}", line));
+            log::warn!("Error class {}", line);
+        }
+    }
+    Ok(summary)
+}
+
+// Show a finished chunk's counts and, when its error rate exceeds confirm_chunk_error_rate,
+// ask whether to go on. Returns false when the operator declines; without a terminal to ask
+// (--yes, continuous mode) the warning is logged and execution continues.
+fn confirm_chunk(
+    config: &AppConfig,
+    options: &ExecutionOptions,
+    chunk_number: usize,
+    succeeded: usize,
+    failed: usize,
+    progress_bar: &ProgressBar,
+) -> bool {
+    let error_rate = failed as f64 * 100.0 / (succeeded + failed).max(1) as f64;
+    let message = format!("Chunk {}: {} successful, {} failed ({:.1}% errors)", chunk_number, succeeded, failed, error_rate);
+    if error_rate <= config.confirm_chunk_error_rate {
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+        return true;
+    }
+    
+    ui::progress::print_with_progress(progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+    log::warn!("{}, above confirm_chunk_error_rate {}%", message, config.confirm_chunk_error_rate);
+    if !options.interactive {
+        return true;
+    }
+    
+    let response = progress_bar.suspend(|| prompt_user(&format!(
+        "Chunk {} failed {:.1}% of its queries (limit {}%). Continue executing?",
+        chunk_number, error_rate, config.confirm_chunk_error_rate
+    )));
+    let proceed = response.eq_ignore_ascii_case("y");
+    log::info!("Operator {} execution after chunk {}", if proceed { "continued" } else { "stopped" }, chunk_number);
+    proceed
+}
+
+// Fail before anything executes if a pending query was generated more than `max_age_hours` ago.
+// Records without a generation time fall back to the run manifest's creation time.
+fn refuse_stale_queries(query_files: &[PathBuf], results_dir: &str, max_age_hours: u64) -> Result<(), Box<dyn Error>> {
+    let manifest_created = RunManifest::load(results_dir).map(|manifest| manifest.created);
+    let cutoff = Utc::now() - chrono::Duration::hours(max_age_hours as i64);
+    
+    let mut stale_count = 0;
+    let mut oldest: Option<(DateTime<FixedOffset>, String)> = None;
+    for file_path in query_files {
+        let record = match load_query_record(file_path) {
+            Some(record) if record.status == QueryStatus::Pending => record,
+            _ => continue,
+        };
+        let generated = record.generated_at.as_ref()
+            .or(manifest_created.as_ref())
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok());
+        
+        if let Some(generated) = generated {
+            if generated < cutoff {
+                stale_count += 1;
+                if oldest.as_ref().is_none_or(|(time, _)| generated < *time) {
+                    oldest = Some((generated, record.key.clone()));
+                }
             }
         }
+    }
+    
+    match oldest {
+        None => Ok(()),
+        Some((generated, key)) => {
+            let age_hours = (Utc::now() - generated.with_timezone(&Utc)).num_hours();
+            log::error!("{} pending queries are older than {} hours; oldest is key {} ({} hours)", stale_count, max_age_hours, key, age_hours);
+            Err(format!(
+                "{} pending queries were generated more than max_query_age_hours ({}) ago, the oldest {} hours ago (key {}); \
+                 regenerate them from a fresh selection or pass --allow-stale",
+                stale_count, max_age_hours, age_hours, key
+            ).into())
+        }
+    }
+}
+
+// Fail before anything executes if a pending query repeats a change an earlier run applied
+fn refuse_replayed_queries(
+    query_files: &[PathBuf],
+    processed_records: &ProcessedRecords,
+    ledger: &AppliedLedger,
+) -> Result<(), Box<dyn Error>> {
+    let mut replayed = Vec::new();
+    for file_path in query_files {
+        let record = match load_query_record(file_path) {
+            Some(record) if record.status == QueryStatus::Pending => record,
+            _ => continue,
+        };
+        let change_hash = record.change_hash();
+        if let Some(entry) = ledger.lookup(&change_hash) {
+            replayed.push(format!("{} (applied {} by run {} in {})", record.key, entry.timestamp, entry.run_id, entry.results_dir));
+        } else if processed_records.has_applied_hash(&change_hash) {
+            replayed.push(format!("{} (applied by an earlier run)", record.key));
+        }
+    }
+    
+    if replayed.is_empty() {
+        return Ok(());
+    }
+    
+    for line in &replayed {
+        log::error!("Replayed query: {}", line);
+    }
+    Err(format!(
+        "{} pending queries repeat changes that were already applied, e.g. key {}; refusing to execute \
+         (set replay_policy = \"skip\" to skip them instead)",
+        replayed.len(), replayed[0]
+    ).into())
+}
+
+// Open the span carrying a record's key and table for the duration of its execution
+fn record_span(query_record: &QueryRecord) -> RecordSpan {
+    let table = parse_update_statement(&query_record.query)
+        .map(|parts| parts.table)
+        .unwrap_or_default();
+    RecordSpan::enter(&query_record.key, &table)
+}
+
+// Close a record's span; records that were already settled aren't worth a span
+fn finish_span(span: RecordSpan, outcome: RecordOutcome) {
+    if outcome != RecordOutcome::AlreadyCompleted {
+        span.finish(&format!("{:?}", outcome));
+    }
+}
+
+fn record_modified_table(totals: &mut ExecutionTotals, query_record: &QueryRecord) {
+    if let Some(parts) = parse_update_statement(&query_record.query) {
+        totals.modified_tables.insert(parts.table);
+    }
+}
+
+// The errors.json entry of a statement zero_rows_policy "fail" failed; the server reported no error
+pub(crate) fn zero_rows_error(key: &str, file: &str, error: &str, timestamp: &str) -> ErrorRecord {
+    ErrorRecord {
+        key: key.to_string(),
+        file: file.to_string(),
+        error: error.to_string(),
+        timestamp: timestamp.to_string(),
+        run_id: Some(run_id::current().to_string()),
+        sqlcode: None,
+        isam_error: None,
+        sqlstate: None,
+        odbc_function: None,
+        message_chain: Vec::new(),
+    }
+}
+
+// Read a query record, logging (rather than propagating) unreadable files
+fn load_query_record(file_path: &Path) -> Option<QueryRecord> {
+    match read_query_file(file_path) {
+        Ok(record) => Some(record),
+        Err(e) => {
+            log::error!("Failed to read query file {}: {}", file_path.display(), e);
+            None
+        }
+    }
+}
+
+// Why a record must not run under revalidate_before_execute
+fn revalidation_failure(query_record: &QueryRecord) -> Option<String> {
+    if !basic_sql_validation(&query_record.query) {
+        return Some("failed revalidation: query syntax error".to_string());
+    }
+    match &query_record.tested_hash {
+        None => Some("failed revalidation: not tested - run the test command first".to_string()),
+        Some(hash) if *hash != query_record.sql_hash() => Some(format!(
+            "failed revalidation: SQL changed since it was tested{} - review the edit and test again",
+            query_record.tested_at.as_ref().map(|at| format!(" at {}", at)).unwrap_or_default()
+        )),
+        Some(_) => None,
+    }
+}
+
+// Execute a single query record while holding its key's lease, when leases are on. Between
+// reading the record and taking the lease another worker may have executed it, so the record
+// is read again once the lease is held.
+fn execute_record<'a>(
+    context: &ExecutionContext<'a>,
+    file_path: &Path,
+    query_record: &mut QueryRecord,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+) -> Result<RecordOutcome, Box<dyn Error>> {
+    let leases = match context.leases {
+        Some(leases) => leases,
+        None => return execute_unleased_record(context, file_path, query_record, processed_records, ledger, totals),
+    };
+    
+    let lease = match leases.acquire(&query_record.key)? {
+        LeaseOutcome::Acquired(lease) => lease,
+        LeaseOutcome::HeldBy(holder) => {
+            ui::progress::update_message(context.progress_bar, format!("Key {} is leased by another worker", query_record.key));
+            log::info!("Leaving key {} Pending: its lease is held by {}", query_record.key, holder);
+            return Ok(RecordOutcome::Leased);
+        },
+    };
+    *query_record = load_query_record(file_path).ok_or_else(|| format!("Failed to re-read {}", file_path.display()))?;
+    let outcome = execute_unleased_record(context, file_path, query_record, processed_records, ledger, totals)?;
+    
+    // A statement waiting for its batch to commit keeps the lease until then
+    match totals.uncommitted.last_mut() {
+        Some(uncommitted) if outcome == RecordOutcome::Succeeded && uncommitted.file_path == file_path => {
+            uncommitted.lease = Some(lease);
+        },
+        _ => {},
+    }
+    
+    // Each settled statement extends the leases the open batch holds, so a batch running
+    // past key_lease_seconds doesn't hand its keys to another worker before it commits
+    for uncommitted in &mut totals.uncommitted {
+        if let Some(lease) = uncommitted.lease.as_mut() {
+            lease.renew_if_due();
+        }
+    }
+    Ok(outcome)
+}
+
+// Execute a single query record and persist its outcome
+fn execute_unleased_record<'a>(
+    context: &ExecutionContext<'a>,
+    file_path: &Path,
+    query_record: &mut QueryRecord,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+) -> Result<RecordOutcome, Box<dyn Error>> {
+    let ExecutionContext { conn, results_dir, progress_bar, .. } = *context;
+    
+    // A record left InProgress was interrupted mid-execution. Its journal intent, if still open,
+    // lets a verification read settle whether the statement committed; otherwise its outcome is unknown.
+    let mut unknown_outcome = String::new();
+    if query_record.status == QueryStatus::InProgress {
+        let change_hash = query_record.change_hash();
+        let journal = context.journal.filter(|journal| journal.open_intent(&query_record.key, &change_hash).is_some());
+        if let Some(journal) = journal {
+            let verification = verify_query(conn, query_record)
+                .unwrap_or_else(|e| VerificationOutcome::Unverifiable(format!("verification read failed: {}", e)));
+            match verification {
+                VerificationOutcome::Verified => {
+                    let current_time = time::now_rfc3339();
+                    query_record.status = QueryStatus::Completed;
+                    query_record.result = Some("success - interrupted statement found committed".to_string());
+                    query_record.timestamp = Some(current_time.clone());
+                    processed_records.add_applied(query_record.key.clone(), current_time.clone(), change_hash.clone());
+                    record_in_ledger(ledger, results_dir, query_record, &change_hash, &current_time);
+                    save_query_file(file_path, query_record)?;
+                    journal.record_completion(&query_record.key, &change_hash, "committed before interruption")?;
+                    log::info!("Key {}: interrupted statement found committed by a verification read", query_record.key);
+                    hooks::run_hooks(context.hooks, query_record);
+                    return Ok(RecordOutcome::Succeeded);
+                },
+                VerificationOutcome::Mismatch(reason) => {
+                    // It never committed, so it runs again like any Pending record
+                    log::warn!("Re-executing key {}: interrupted statement didn't commit ({})", query_record.key, masking::mask(&reason));
+                    journal.record_completion(&query_record.key, &change_hash, "not committed")?;
+                    query_record.status = QueryStatus::Pending;
+                },
+                VerificationOutcome::Unverifiable(reason) => {
+                    unknown_outcome = format!(" (journal intent open, but {})", reason);
+                },
+            }
+        }
+    }
+    if query_record.status == QueryStatus::InProgress {
+        let message = format!(
+            "interrupted during execution by run {}; outcome unknown{} - verify the row and reset status to Pending to retry",
+            query_record.execution_run_id.as_deref().unwrap_or("unknown"), unknown_outcome
+        );
+        ui::progress::print_with_progress(progress_bar, &format!("Quarantining key {}: {}", query_record.key, message));
+        log::warn!("Quarantining key {}: {}", query_record.key, message);
+        query_record.status = QueryStatus::Quarantined;
+        query_record.result = Some(message);
+        save_query_file(file_path, query_record)?;
+        return Ok(RecordOutcome::Quarantined);
+    }
+    
+    // Skip already executed, skipped and quarantined queries
+    if query_record.status.is_settled() {
+        ui::progress::update_message(progress_bar, format!("Skipping {:?} query for key: {}", query_record.status, query_record.key));
+        return Ok(RecordOutcome::AlreadyCompleted);
+    }
+    
+    // Refuse statements that fail validation now or were edited after `test` passed them
+    if context.revalidate {
+        if let Some(reason) = revalidation_failure(query_record) {
+            ui::progress::print_with_progress(progress_bar, &format!("Quarantining key {}: {}", query_record.key, reason));
+            log::warn!("Quarantining key {}: {}", query_record.key, reason);
+            query_record.status = QueryStatus::Quarantined;
+            query_record.result = Some(reason);
+            save_query_file(file_path, query_record)?;
+            return Ok(RecordOutcome::Quarantined);
+        }
+    }
+    
+    // Never apply the same change twice, even from a different results directory,
+    // unless replay_policy allows it
+    let change_hash = query_record.change_hash();
+    let previously_applied = ledger.lookup(&change_hash)
+        .map(|entry| format!("by run {} in {}", entry.run_id, entry.results_dir))
+        .or_else(|| processed_records.has_applied_hash(&change_hash).then(|| "by an earlier run".to_string()));
+    if let (Some(previous), ReplayPolicy::Warn) = (&previously_applied, context.replay_policy) {
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[33mRe-applying change for key {} already applied {}\x1b[0m", query_record.key, previous));
+        log::warn!("Re-applying change for key {}: identical change already applied {}", query_record.key, previous);
+    } else if previously_applied.is_some() {
+        ui::progress::update_message(progress_bar, format!("Skipping already applied change for key: {}", query_record.key));
+        log::info!("Skipping key {}: identical change already applied", query_record.key);
+        query_record.status = QueryStatus::Skipped;
+        query_record.result = Some("skipped - identical change already applied".to_string());
+        save_query_file(file_path, query_record)?;
+        return Ok(RecordOutcome::AlreadyApplied);
+    }
+    
+    // A change that couldn't be undone isn't made
+    if context.capture_rollback {
+        if let Err(e) = capture_rollback(conn, results_dir, query_record) {
+            let reason = format!("rollback script not captured: {}", masking::mask(&e.to_string()));
+            ui::progress::print_with_progress(progress_bar, &format!("Quarantining key {}: {}", query_record.key, reason));
+            log::warn!("Quarantining key {}: {}", query_record.key, reason);
+            query_record.status = QueryStatus::Quarantined;
+            query_record.result = Some(reason);
+            save_query_file(file_path, query_record)?;
+            return Ok(RecordOutcome::Quarantined);
+        }
+    }
+    
+    // Update progress bar message
+    ui::progress::update_message(progress_bar, format!("Executing query for key: {}", query_record.key));
+    
+    // Load any values bound to the query's placeholders
+    let parameters: Vec<VarCharBox> = query_record.bound_values(results_dir)?
+        .iter()
+        .map(|value| VarCharBox::from_vec(encoding::encode(value)))
+        .collect();
+    
+    // Record the attempt before sending the statement so a crash can't look like "never attempted"
+    let current_time = time::now_rfc3339();
+    query_record.status = QueryStatus::InProgress;
+    query_record.timestamp = Some(current_time.clone());
+    query_record.execution_run_id = Some(run_id::current().to_string());
+    save_query_file(file_path, query_record)?;
+    if let Some(journal) = context.journal {
+        journal.record_intent(&query_record.key, &change_hash)?;
+    }
+    
+    // The statements as sent to the server; the stored query stays as tested and hashed
+    let sent = |sql: &str| if context.provenance_comments { query_record.with_provenance_comment(sql) } else { sql.to_string() };
+    let statement = sent(&query_record.query);
+    let insert_fallback = query_record.insert_fallback.as_deref().map(sent);
+    let attempt = StatementAttempt { file_path: file_path.to_path_buf(), change_hash, current_time };
+    
+    // A statement with nothing to bind and no INSERT to follow up with can share a round trip
+    if context.statement_packet_bytes > 0 && !totals.packets_unsupported && parameters.is_empty() && insert_fallback.is_none() {
+        let packet_bytes: usize = totals.packet.iter().map(|queued| queued.statement.len() + 2).sum();
+        if !totals.packet.is_empty() && packet_bytes + statement.len() > context.statement_packet_bytes {
+            flush_packet(context, processed_records, ledger, totals)?;
+        }
+        totals.packet.push(QueuedStatement { attempt, record: query_record.clone(), statement });
+        return Ok(RecordOutcome::Queued);
+    }
+    
+    let execution = send_statement(context, &query_record.key, &statement, insert_fallback.as_deref(), &parameters);
+    settle_record(context, attempt, query_record, processed_records, ledger, totals, execution)
+}
+
+// Execute a statement, retrying lock conflicts and other transient errors the policy allows.
+// A failed attempt has already been rolled back (in a batch, Informix undoes just the failed
+// statement), so retrying can't apply the change twice.
+fn send_statement(
+    context: &ExecutionContext,
+    key: &str,
+    statement: &str,
+    insert_fallback: Option<&str>,
+    parameters: &[VarCharBox],
+) -> Result<Option<usize>, odbc_api::Error> {
+    let conn = context.conn;
+    context.retry_policy.run(
+        &format!("Query for key {}", key),
+        || {
+            let mut execution = if parameters.is_empty() {
+                execute_with_row_count(conn, statement, ())
+            } else {
+                execute_with_row_count(conn, statement, parameters)
+            };
+            // An UPDATE + INSERT upsert inserts the row when the UPDATE matched none, in the same transaction
+            if let (Ok(Some(0)), Some(insert)) = (&execution, insert_fallback) {
+                execution = execute_with_row_count(conn, insert, ());
+            }
+            if context.manual_commit && context.commit_batch_size == 0 {
+                finish_transaction(conn, execution)
+            } else {
+                execution
+            }
+        },
+        |err| {
+            let diagnostics = OdbcDiagnostics::from_odbc_error(err);
+            context.retry_policy.is_retryable(diagnostics.sqlstate.as_deref(), diagnostics.native_error, diagnostics.isam_error)
+        },
+    )
+}
+
+// Record how a sent statement ended: its status and result, the error log, the open batch or
+// the ledger, the journal, the dead letter directory and the hooks
+fn settle_record<'a>(
+    context: &ExecutionContext<'a>,
+    attempt: StatementAttempt,
+    query_record: &mut QueryRecord,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+    execution: Result<Option<usize>, odbc_api::Error>,
+) -> Result<RecordOutcome, Box<dyn Error>> {
+    let ExecutionContext { results_dir, progress_bar, .. } = *context;
+    let StatementAttempt { file_path, change_hash, current_time } = attempt;
+    let file_path = file_path.as_path();
+    
+    query_record.executed_by = Some(operator::current().clone());
+    query_record.rows_affected = execution.as_ref().ok().copied().flatten();
+    let rows_before = totals.rows_affected;
+    let outcome = match execution {
+        Ok(Some(0)) if query_record.concurrency_guard => {
+            // The guarded WHERE clause matched nothing: the row changed after it was selected
+            query_record.status = QueryStatus::Conflict;
+            query_record.result = Some("conflict - row changed since selection, not overwritten".to_string());
+            query_record.timestamp = Some(current_time.clone());
+            
+            ui::progress::print_with_progress(progress_bar, &format!("Conflict for key {}: row changed since selection", query_record.key));
+            log::warn!("Concurrency conflict for key {}: row changed since selection, update not applied", query_record.key);
+            RecordOutcome::Conflict
+        },
+        Ok(Some(0)) if context.zero_rows_policy == ZeroRowsPolicy::Fail => {
+            // The statement ran, but changing nothing counts as a failure for this job
+            totals.no_rows_count += 1;
+            let error = "no rows affected (zero_rows_policy \"fail\")".to_string();
+            query_record.status = QueryStatus::Failed;
+            query_record.result = Some(format!("error: {}", error));
+            query_record.timestamp = Some(current_time.clone());
+            
+            let file_name = file_path.file_name().unwrap().to_string_lossy().to_string();
+            save_error_file(format!("{}/errors.json", results_dir), &zero_rows_error(&query_record.key, &file_name, &error, &current_time))?;
+            log::error!("Query execution failed for key {}{}: {}", query_record.key, query_record.ticket_suffix(), error);
+            
+            query_record.attempt_history.push(ExecutionAttempt {
+                run_id: run_id::current().to_string(),
+                timestamp: current_time.clone(),
+                error,
+                sqlstate: None,
+            });
+            query_record.failed_attempts += 1;
+            RecordOutcome::Failed
+        },
+        Ok(Some(0)) => {
+            // Zero rows affected is still considered a success, not an error
+            totals.no_rows_count += 1;
+            query_record.status = QueryStatus::Completed;
+            query_record.result = Some("success - no rows affected".to_string());
+            query_record.timestamp = Some(current_time.clone());
+            record_modified_table(totals, query_record);
+            
+            if context.zero_rows_policy == ZeroRowsPolicy::Warn {
+                log::warn!("Query execution completed for key {}{} but no rows were affected", query_record.key, query_record.ticket_suffix());
+            } else {
+                log::info!("Query execution completed for key {}{} but no rows were affected", query_record.key, query_record.ticket_suffix());
+            }
+            RecordOutcome::Succeeded
+        },
+        Ok(row_count) => {
+            // Success case - rows were affected, or the driver couldn't say how many
+            query_record.status = QueryStatus::Completed;
+            query_record.result = Some(match row_count {
+                Some(rows) => format!("success - {} rows affected", rows),
+                None => "success - operation completed".to_string(),
+            });
+            query_record.timestamp = Some(current_time.clone());
+            totals.rows_affected += row_count.unwrap_or(1) as u64;
+            record_modified_table(totals, query_record);
+            
+            log::info!("Query execution successful for key {}{}", query_record.key, query_record.ticket_suffix());
+            RecordOutcome::Succeeded
+        },
+        Err(err) => {
+            // Only this case is a true error - when ODBC returns an error
+            query_record.status = QueryStatus::Failed;
+            let diagnostics = OdbcDiagnostics::from_odbc_error(&err);
+            query_record.result = Some(format!("error: {}", masking::mask(&diagnostics.summary())));
+            query_record.timestamp = Some(current_time.clone());
+            
+            // Classify by SQLCODE/ISAM error for the end-of-run breakdown
+            totals.error_statistics.record(diagnostics.class(), &query_record.key, &masking::mask(&diagnostics.message));
+            
+            // Add to error log
+            let file_name = file_path.file_name().unwrap().to_string_lossy().to_string();
+            save_error_file(format!("{}/errors.json", results_dir), &diagnostics.error_record(&query_record.key, &file_name, &current_time))?;
+            
+            // Only log actual ODBC errors
+            log::error!("Query execution failed for key {}{}: {}", query_record.key, query_record.ticket_suffix(), masking::mask(&diagnostics.summary()));
+            
+            // A connection exception (by default) fails every later query too
+            totals.abort_reason = diagnostics.abort_reason(context.abort_sqlstates, &query_record.key);
+            
+            query_record.attempt_history.push(ExecutionAttempt {
+                run_id: run_id::current().to_string(),
+                timestamp: current_time.clone(),
+                error: masking::mask(&diagnostics.summary()),
+                sqlstate: diagnostics.sqlstate.clone(),
+            });
+            query_record.failed_attempts += 1;
+            RecordOutcome::Failed
+        }
+    };
+    
+    // Until its batch commits the change isn't applied; the file stays InProgress, so a crash
+    // quarantines the record instead of claiming it applied
+    if outcome == RecordOutcome::Succeeded && context.commit_batch_size > 0 {
+        totals.uncommitted.push(UncommittedRecord {
+            file_path: file_path.to_path_buf(),
+            record: query_record.clone(),
+            change_hash,
+            rows_affected: totals.rows_affected - rows_before,
+            lease: None,
+        });
+        return Ok(outcome);
+    }
+    if outcome == RecordOutcome::Succeeded {
+        processed_records.add_applied(query_record.key.clone(), current_time.clone(), change_hash.clone());
+        record_in_ledger(ledger, results_dir, query_record, &change_hash, &current_time);
+    }
+    
+    // Save updated query record
+    save_query_file(file_path, query_record)?;
+    if let Some(journal) = context.journal {
+        let ended = match outcome {
+            RecordOutcome::Succeeded => "committed",
+            RecordOutcome::Conflict => "conflict",
+            _ => "failed",
+        };
+        journal.record_completion(&query_record.key, &change_hash, ended)?;
+    }
+    
+    // A record that keeps failing stops being retried automatically
+    if outcome == RecordOutcome::Failed && context.max_record_attempts > 0 && query_record.failed_attempts >= context.max_record_attempts {
+        let target = move_to_dead_letter(file_path)?;
+        let message = format!(
+            "Key {} failed {} times; moved to {} (retry-failed --include-dead-letter re-enqueues it)",
+            query_record.key, query_record.failed_attempts, target.display()
+        );
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::warn!("{}", message);
+    }
+    
+    // Nothing of a batch with a failed statement is kept
+    if outcome == RecordOutcome::Failed && context.commit_batch_size > 0 && context.rollback_batch_on_error {
+        let rolled_back = roll_back_batch(context, totals, &format!("key {} failed in the same batch", query_record.key))?;
+        totals.abort_reason.get_or_insert_with(|| format!(
+            "key {} failed; rolled back the {} other statements of its batch", query_record.key, rolled_back
+        ));
+    }
+    
+    if outcome == RecordOutcome::Succeeded {
+        hooks::run_hooks(context.hooks, query_record);
+    }
+    
+    Ok(outcome)
+}
+
+// Send the queued statements in one round trip and settle each from its row count. When the
+// packet fails, or returns fewer results than it had statements, it's rolled back with the rest
+// of the open batch and its statements are sent one at a time, so each error is attributed to
+// its own record. A packet refused although every statement then ran on its own means the driver
+// doesn't take several statements at once, and the rest of the run sends them singly.
+fn flush_packet<'a>(
+    context: &ExecutionContext<'a>,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+) -> Result<(), Box<dyn Error>> {
+    let packet = std::mem::take(&mut totals.packet);
+    if packet.is_empty() {
+        return Ok(());
+    }
+    
+    ui::progress::update_message(context.progress_bar, format!("Executing a packet of {} statements", packet.len()));
+    let sql = packet.iter().map(|queued| queued.statement.as_str()).collect::<Vec<_>>().join(";\n");
+    let row_counts = match execute_packet_with_row_counts(context.conn, &sql) {
+        Ok(row_counts) if row_counts.len() == packet.len() => {
+            totals.packets_sent += 1;
+            Some(row_counts)
+        },
+        Ok(row_counts) => {
+            log::warn!("A packet of {} statements returned {} results; sending them one at a time", packet.len(), row_counts.len());
+            None
+        },
+        Err(e) => {
+            log::warn!(
+                "A packet of {} statements failed ({}); sending them one at a time",
+                packet.len(), masking::mask(&OdbcDiagnostics::from_odbc_error(&e).summary())
+            );
+            None
+        },
+    };
+    if row_counts.is_none() {
+        roll_back_batch(context, totals, "a statement packet failed")?;
+    }
+    
+    let errors_before = totals.error_count;
+    let mut remaining = packet.into_iter().enumerate();
+    while let Some((index, mut queued)) = remaining.next() {
+        let execution = match &row_counts {
+            Some(row_counts) => Ok(row_counts[index]),
+            None => send_statement(context, &queued.record.key, &queued.statement, None, &[]),
+        };
+        let outcome = settle_record(context, queued.attempt, &mut queued.record, processed_records, ledger, totals, execution)?;
+        totals.record(outcome);
         
-        // Save updated query record
-        save_query_file(file_path, &query_record)?;
+        // A batch rolled back or execution stopping leaves the rest of the packet undone
+        if let Some(reason) = totals.abort_reason.clone() {
+            release_packet(context.journal, totals, remaining.map(|(_, queued)| queued).collect(), &format!("rolled back - {}", reason))?;
+            return Ok(());
+        }
+    }
+    if row_counts.is_none() && totals.error_count == errors_before {
+        totals.packets_unsupported = true;
+        log::warn!("The driver refused a packet whose statements all succeeded on their own; sending statements one at a time for the rest of the run");
+    }
+    Ok(())
+}
+
+// Put queued statements that won't be applied back to Pending, with `result` saying why
+fn release_packet(journal: Option<&ExecutionJournal>, totals: &mut ExecutionTotals, queued: Vec<QueuedStatement>, result: &str) -> Result<(), Box<dyn Error>> {
+    for QueuedStatement { attempt, mut record, .. } in queued {
+        record.status = QueryStatus::Pending;
+        record.result = Some(result.to_string());
+        save_query_file(&attempt.file_path, &record)?;
+        if let Some(journal) = journal {
+            journal.record_completion(&record.key, &attempt.change_hash, "not applied")?;
+        }
+        totals.rolled_back_count += 1;
+    }
+    Ok(())
+}
+
+// Commit the open transaction the long-transaction guard found too large: the batch, with
+// commit_batch_size, or else the work autocommit-off execution has outstanding
+fn commit_open_work<'a>(
+    context: &ExecutionContext<'a>,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+) -> Result<(), Box<dyn Error>> {
+    if context.commit_batch_size > 0 {
+        commit_batch(context, processed_records, ledger, totals)
+    } else {
+        Ok(context.conn.commit()?)
+    }
+}
+
+// Commit the open batch and settle its records: Completed, applied, in the ledger and passed to
+// the hooks. A commit the server refuses rolls the batch back and stops execution.
+fn commit_batch<'a>(
+    context: &ExecutionContext<'a>,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+) -> Result<(), Box<dyn Error>> {
+    // Queued statements belong to the batch
+    flush_packet(context, processed_records, ledger, totals)?;
+    if totals.uncommitted.is_empty() {
+        return Ok(());
+    }
+    if let Err(e) = context.conn.commit() {
+        let reason = format!("commit of a batch of {} statements failed: {}", totals.uncommitted.len(), OdbcDiagnostics::from_odbc_error(&e).summary());
+        log::error!("{}", reason);
+        roll_back_batch(context, totals, &reason)?;
+        totals.abort_reason.get_or_insert(reason);
+        return Ok(());
     }
     
-    // Print summary at the end
-    let summary = format!("Executed {} queries: {} successful, {} failed", total_files, success_count, error_count);
-    ui::progress::print_with_progress(progress_bar, &summary);
-    log::info!("{}", summary);
+    let batch = std::mem::take(&mut totals.uncommitted);
+    totals.batches_committed += 1;
+    let elapsed = totals.batch_started.take().map(|started| started.elapsed()).unwrap_or_default();
+    let next_interval = batch_tuning::next_commit_batch_size(context.config, totals.commit_interval, batch.len(), elapsed);
+    log::info!(
+        "Committed batch {} of {} statements in {} ms; next batch of up to {}",
+        totals.batches_committed, batch.len(), elapsed.as_millis(), next_interval
+    );
+    totals.commit_interval = next_interval;
+    for uncommitted in batch {
+        let record = &uncommitted.record;
+        let timestamp = record.timestamp.clone().unwrap_or_else(time::now_rfc3339);
+        processed_records.add_applied(record.key.clone(), timestamp.clone(), uncommitted.change_hash.clone());
+        record_in_ledger(ledger, context.results_dir, record, &uncommitted.change_hash, &timestamp);
+        save_query_file(&uncommitted.file_path, record)?;
+        if let Some(journal) = context.journal {
+            journal.record_completion(&record.key, &uncommitted.change_hash, "committed")?;
+        }
+        hooks::run_hooks(context.hooks, record);
+    }
+    Ok(())
+}
+
+// Roll back the open batch and put its records back to Pending for the next run. Returns the
+// number of statements undone.
+fn roll_back_batch(context: &ExecutionContext, totals: &mut ExecutionTotals, reason: &str) -> Result<usize, Box<dyn Error>> {
+    if let Err(e) = context.conn.rollback() {
+        log::error!("Rollback of the open batch failed: {}", e);
+    }
+    let rolled_back = revert_batch(context.journal, totals, reason)?;
+    if rolled_back > 0 {
+        let message = format!("Rolled back {} statements: {}", rolled_back, reason);
+        ui::progress::print_with_progress(context.progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+        log::warn!("{}", message);
+    }
+    Ok(rolled_back)
+}
+
+// Take back the successes of a batch the server rolled back: each record goes back to
+// Pending and out of the totals
+fn revert_batch(journal: Option<&ExecutionJournal>, totals: &mut ExecutionTotals, reason: &str) -> Result<usize, Box<dyn Error>> {
+    let batch = std::mem::take(&mut totals.uncommitted);
+    for uncommitted in &batch {
+        let mut record = uncommitted.record.clone();
+        record.status = QueryStatus::Pending;
+        record.result = Some(format!("rolled back - {}", reason));
+        save_query_file(&uncommitted.file_path, &record)?;
+        if let Some(journal) = journal {
+            journal.record_completion(&record.key, &uncommitted.change_hash, "rolled back")?;
+        }
+        totals.success_count -= 1;
+        totals.rows_affected -= uncommitted.rows_affected;
+    }
+    totals.rolled_back_count += batch.len();
+    Ok(batch.len())
+}
+
+// Append a successful change to the global ledger; a failed write is logged, not fatal,
+// since the change itself is already committed
+fn record_in_ledger(ledger: &mut AppliedLedger, results_dir: &str, query_record: &QueryRecord, change_hash: &str, timestamp: &str) {
+    let entry = LedgerEntry {
+        hash: change_hash.to_string(),
+        key: query_record.key.clone(),
+        results_dir: results_dir.to_string(),
+        run_id: run_id::current().to_string(),
+        timestamp: timestamp.to_string(),
+        ticket: query_record.ticket.clone(),
+        operator: Some(operator::current().to_string()),
+    };
+    if let Err(e) = ledger.record(entry) {
+        log::error!("Failed to record key {} in the applied ledger: {}", query_record.key, e);
+    }
+}
+
+// Commit a successful statement or roll back a failed one when autocommit is off
+pub fn finish_transaction(conn: &Connection, execution: Result<Option<usize>, odbc_api::Error>) -> Result<Option<usize>, odbc_api::Error> {
+    match execution {
+        Ok(row_count) => match conn.commit() {
+            Ok(()) => Ok(row_count),
+            Err(e) => {
+                if let Err(rollback_error) = conn.rollback() {
+                    log::error!("Rollback after failed commit also failed: {}", rollback_error);
+                }
+                Err(e)
+            }
+        },
+        Err(e) => {
+            if let Err(rollback_error) = conn.rollback() {
+                log::error!("Rollback after failed statement failed: {}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+// Execute a random sample of pending queries and verify the rows they changed.
+// Returns an error (aborting the run) if any canary query fails or doesn't verify.
+fn run_canary<'a>(
+    context: &ExecutionContext<'a>,
+    query_files: &[PathBuf],
+    canary_size: usize,
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    totals: &mut ExecutionTotals<'a>,
+) -> Result<(), Box<dyn Error>> {
+    let ExecutionContext { conn, progress_bar, .. } = *context;
+    // Each canary statement is verified as soon as it runs, so none waits for a packet
+    let context = &ExecutionContext { statement_packet_bytes: 0, ..*context };
+    
+    let pending: Vec<(PathBuf, QueryRecord)> = query_files.iter()
+        .filter_map(|path| load_query_record(path).map(|record| (path.clone(), record)))
+        .filter(|(_, record)| record.status == QueryStatus::Pending)
+        .collect();
+    
+    let sample: Vec<&(PathBuf, QueryRecord)> = pending.choose_multiple(&mut rand::thread_rng(), canary_size).collect();
+    
+    ui::progress::print_with_progress(
+        progress_bar,
+        &format!("Running canary: executing and verifying {} of {} pending queries", sample.len(), pending.len()),
+    );
+    log::info!("Running canary with {} queries", sample.len());
+    
+    let mut failures = Vec::new();
     
-    Ok((success_count, error_count))
-}
\ No newline at end of file
+    for (file_path, record) in sample {
+        let mut query_record = record.clone();
+        let span = record_span(&query_record);
+        let outcome = execute_record(context, file_path, &mut query_record, processed_records, ledger, totals)?;
+        finish_span(span, outcome);
+        totals.record(outcome);
+        
+        match outcome {
+            RecordOutcome::Failed => {
+                failures.push(format!("{}: execution failed", query_record.key));
+            },
+            RecordOutcome::Succeeded => {
+                match verify_query(conn, &query_record)? {
+                    VerificationOutcome::Verified => {
+                        log::info!("Canary verified for key {}", query_record.key);
+                    },
+                    // A canary that can't be checked proves nothing, so it doesn't pass either
+                    VerificationOutcome::Unverifiable(reason) => {
+                        failures.push(format!("{}: could not be verified: {}", query_record.key, reason));
+                    },
+                    VerificationOutcome::Mismatch(reason) => {
+                        failures.push(format!("{}: {}", query_record.key, reason));
+                    },
+                }
+            },
+            _ => {}
+        }
+    }
+    
+    if !failures.is_empty() {
+        let report = format!("Canary verification failed for {} queries:\n  {}", failures.len(), failures.join("\n  "));
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[31m{}\x1b[0m", report));
+        log::error!("{}", report);
+        return Err(format!("Canary verification failed for {} queries; full run aborted", failures.len()).into());
+    }
+    
+    ui::progress::print_with_progress(progress_bar, "\x1b[32mCanary verification passed, continuing with full run\x1b[0m");
+    log::info!("Canary verification passed");
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::json_handler::read_query_file;
+    use std::fs;
+    
+    fn results_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ibp-execution-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    fn queued_in(dir: &Path, key: &str) -> QueuedStatement {
+        let statement = format!("UPDATE customer SET county = '001' WHERE zip = '{}'", key);
+        let mut record = QueryRecord::new(key.to_string(), statement.clone());
+        record.status = QueryStatus::InProgress;
+        QueuedStatement {
+            attempt: StatementAttempt {
+                file_path: dir.join(format!("{}.json", key)),
+                change_hash: record.change_hash(),
+                current_time: "2026-01-01T00:00:00Z".to_string(),
+            },
+            record,
+            statement,
+        }
+    }
+    
+    fn queued(key: &str) -> QueuedStatement {
+        queued_in(Path::new("results_1"), key)
+    }
+    
+    #[test]
+    fn a_failed_packet_puts_its_batch_and_its_statements_back_to_pending() {
+        let dir = results_dir("packet-rollback");
+        let journal = ExecutionJournal::open(&dir.to_string_lossy()).unwrap();
+        let mut totals = ExecutionTotals::default();
+        
+        // Two statements of the open batch succeeded before the packet, which holds two more
+        for (key, rows) in [("1001", 1), ("1002", 2)] {
+            let QueuedStatement { attempt, mut record, .. } = queued_in(&dir, key);
+            save_query_file(&attempt.file_path, &record).unwrap();
+            journal.record_intent(key, &attempt.change_hash).unwrap();
+            record.status = QueryStatus::Completed;
+            totals.success_count += 1;
+            totals.rows_affected += rows;
+            totals.uncommitted.push(UncommittedRecord {
+                file_path: attempt.file_path,
+                record,
+                change_hash: attempt.change_hash,
+                rows_affected: rows,
+                lease: None,
+            });
+        }
+        for key in ["1003", "1004"] {
+            let queued = queued_in(&dir, key);
+            save_query_file(&queued.attempt.file_path, &queued.record).unwrap();
+            journal.record_intent(key, &queued.attempt.change_hash).unwrap();
+            totals.packet.push(queued);
+        }
+        
+        assert_eq!(revert_batch(Some(&journal), &mut totals, "a statement packet failed").unwrap(), 2);
+        let packet = std::mem::take(&mut totals.packet);
+        release_packet(Some(&journal), &mut totals, packet, "rolled back - a statement packet failed").unwrap();
+        
+        assert_eq!((totals.success_count, totals.rows_affected, totals.rolled_back_count), (0, 0, 4));
+        assert!(totals.uncommitted.is_empty());
+        for key in ["1001", "1002", "1003", "1004"] {
+            let record = read_query_file(dir.join(format!("{}.json", key))).unwrap();
+            assert_eq!(record.status, QueryStatus::Pending, "key {}", key);
+            assert_eq!(record.result.as_deref(), Some("rolled back - a statement packet failed"));
+        }
+        
+        // Every intent got its completion, so a later execution has nothing to replay
+        let reopened = ExecutionJournal::open(&dir.to_string_lossy()).unwrap();
+        for key in ["1001", "1003"] {
+            let change_hash = queued_in(&dir, key).attempt.change_hash;
+            assert!(reopened.open_intent(key, &change_hash).is_none(), "key {}", key);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn partitions_split_keys_without_overlap() {
+        assert_eq!(Partition::parse("2/4"), Ok(Partition { index: 2, count: 4 }));
+        assert!(Partition::parse("0/4").is_err());
+        assert!(Partition::parse("5/4").is_err());
+        assert!(Partition::parse("2").is_err());
+        
+        let partitions: Vec<Partition> = (1..=3).map(|index| Partition { index, count: 3 }).collect();
+        for key in (1000..1100).map(|key| key.to_string()) {
+            assert_eq!(partitions.iter().filter(|partition| partition.contains(&key)).count(), 1, "key {}", key);
+        }
+    }
+    
+    #[test]
+    fn queued_packet_statements_count_against_max_rows() {
+        let options = ExecutionOptions { max_rows: Some(3), ..ExecutionOptions::default() };
+        let mut totals = ExecutionTotals { success_count: 2, ..ExecutionTotals::default() };
+        assert_eq!(options.budget_exhausted(totals.executed(), Duration::ZERO), None);
+        
+        totals.packet.push(queued("1003"));
+        assert!(options.budget_exhausted(totals.executed(), Duration::ZERO).is_some());
+    }
+    
+    #[test]
+    fn max_duration_is_measured_against_elapsed_time() {
+        let options = ExecutionOptions { max_duration: Some(Duration::from_secs(60)), ..ExecutionOptions::default() };
+        assert_eq!(options.budget_exhausted(0, Duration::from_secs(59)), None);
+        assert!(options.budget_exhausted(0, Duration::from_secs(60)).is_some());
+    }
+}
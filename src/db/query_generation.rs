@@ -1,93 +1,818 @@
-use odbc_api::{buffers::TextRowSet, Connection, Cursor};
+use odbc_api::Connection;
 use indicatif::ProgressBar;
 use std::error::Error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, IsTerminal};
 
 use crate::config::AppConfig;
-use crate::db::query_types::QueryRecord;
-use crate::files::json_handler::save_query_file;
+use crate::db::column_format::ColumnFormat;
+use crate::db::fetch::for_each_row;
+use crate::db::connection::query_first_row;
+use crate::db::index_advisory::advise_indexes;
+use crate::db::keys::{integer_key, key_columns, key_condition, key_literal, row_key};
+use crate::db::lookups::LookupTables;
+use crate::db::preflight::run_preflight_checks;
+use crate::db::query_types::{prompt_user, QueryRecord, QueryStatus};
+use crate::db::result_columns::{self, ResultColumns};
+use crate::db::upsert::{build_upsert, UpsertStrategy};
+use crate::db::dialect;
+use crate::db::sql_helpers::{
+    add_typed_concurrency_guard, column_label, count_query, find_keyword_outside_quotes, format_identifier, normalize_identifier,
+    parse_set_assignments, parse_update_statement, select_list,
+};
+use crate::files::applied_comparison::{write_applied_comparison, AppliedRun, Regression, APPLIED_COMPARISON_FILE_NAME};
+use crate::files::checkpoint::GenerationCheckpoint;
+use crate::files::json_handler::{enable_query_manifest, query_file_exists, read_query_file, save_query_file};
+use crate::files::manifest::{load_selection_keys, save_selection_keys, RunManifest};
+use crate::files::parameter_values::save_parameter_values;
+use crate::files::snapshot::{SelectionSnapshot, SNAPSHOT_FILE_NAME};
+use crate::files::processed::ProcessedRecords;
 use crate::ui;
+use crate::utils::memo::Memo;
+use crate::utils::operator;
+use crate::utils::{run_id, time, version};
+
+// Placeholder for the WHERE condition matching a composite key; it is SQL, so never bound
+const KEY_CONDITION: &str = "key_condition";
+
+// Options controlling a single generation phase
+#[derive(Debug, Clone, Default)]
+pub struct GenerationOptions {
+    // Only generate queries for keys not matched by this previous run
+    pub delta_from: Option<String>,
+    // Keys the previous run's selection matched
+    pub previous_keys: Option<HashSet<String>>,
+    // Proceed without prompting when the selection exceeds confirm_row_threshold
+    pub assume_yes: bool,
+    // No one is there to answer a prompt (continuous mode), so never ask
+    pub unattended: bool,
+    // Carry on from the results directory's generation checkpoint instead of starting over
+    pub resume: bool,
+    // Flag keys this earlier run corrected that need a change again
+    pub compare_applied: Option<AppliedRun>,
+    // Generate queries for keys the processed records already hold too
+    pub reprocess: bool,
+}
+
+// Derivations every row repeats, worked out once per generation run
+#[derive(Debug)]
+pub struct GenerationCache {
+    // Position in the selection of each SET column, or None when the selection lacks it
+    set_columns: Memo<String, Option<usize>>,
+}
+
+impl Default for GenerationCache {
+    fn default() -> Self {
+        GenerationCache { set_columns: Memo::new("SET column positions") }
+    }
+}
+
+impl GenerationCache {
+    pub fn log_metrics(&self) {
+        self.set_columns.log_metrics();
+    }
+}
+
+impl GenerationOptions {
+    // Generate only for keys the given previous run's selection didn't match
+    pub fn delta_from(previous_results_dir: &str) -> Result<Self, Box<dyn Error>> {
+        let previous_keys = load_selection_keys(previous_results_dir)?;
+        log::info!("Delta generation against {} ({} previously matched keys)", previous_results_dir, previous_keys.len());
+        
+        Ok(GenerationOptions {
+            delta_from: Some(previous_results_dir.to_string()),
+            previous_keys: Some(previous_keys),
+            ..GenerationOptions::default()
+        })
+    }
+}
 
 pub fn generate_queries(
     conn: &Connection,
     config: &AppConfig,
     results_dir: &str,
+    processed_records: &mut ProcessedRecords,
+    options: &GenerationOptions,
     progress_bar: &ProgressBar,
 ) -> Result<usize, Box<dyn Error>> {
+    // Nothing is selected while any configured check fails
+    run_preflight_checks(conn, config, "generate", results_dir, progress_bar)?;
+    
     ui::progress::print_with_progress(progress_bar, "Finding records requiring updates...");
     
-    // Execute the selection query to find records requiring updates
-    let cursor = match conn.execute(&config.selection_query, ())? {
-        Some(cursor) => cursor,
-        None => {
-            ui::progress::print_with_progress(progress_bar, "No records found requiring updates.");
-            log::warn!("Selection query returned no results");
-            return Ok(0);
+    // Make sure a bad WHERE clause can't silently queue a huge batch
+    confirm_selection_volume(conn, config, options, progress_bar)?;
+    
+    // Lookup maps the templates can use alongside the selected columns
+    let lookups = LookupTables::load(conn, config, progress_bar)?;
+    
+    // Millions of tiny files make listing a results directory slow; one manifest doesn't
+    if config.query_manifest {
+        enable_query_manifest(results_dir)?;
+    }
+    
+    // An interrupted generation carries on with its counts and the keys it already handled
+    let mut checkpoint = if options.resume {
+        let checkpoint = GenerationCheckpoint::load(results_dir)
+            .ok_or_else(|| format!("{} has no generation checkpoint to resume", results_dir))?;
+        if checkpoint.selection_query != config.selection_query {
+            return Err("selection_query changed since the interrupted generation; start a new run instead of resuming".into());
         }
+        checkpoint
+    } else {
+        GenerationCheckpoint::remove(results_dir)?;
+        GenerationCheckpoint { selection_query: config.selection_query.clone(), ..GenerationCheckpoint::default() }
+    };
+    let mut matched_keys = if options.resume { GenerationCheckpoint::load_keys(results_dir)? } else { Vec::new() };
+    let resumed_keys: HashSet<String> = matched_keys.iter().cloned().collect();
+    let mut checkpointed_keys = matched_keys.len();
+    
+    let mut count = checkpoint.generated;
+    let mut already_applied_count = checkpoint.already_applied;
+    let mut lookup_skipped_count = checkpoint.lookup_skipped;
+    let mut delta_skipped_count = checkpoint.delta_skipped;
+    let mut processed_skipped_count = 0;
+    
+    // Column names as the driver reports them, so `SELECT *` selections get real names too
+    let columns = ResultColumns::resolve(conn, config, &config.selection_query, progress_bar)?;
+    let column_names = &columns.names;
+    check_selection_shape(config, &columns)?;
+    let key_count = key_columns(config).len();
+    let withheld = withheld_columns(config, column_names);
+    let mut regressions = Vec::new();
+    
+    let mut cache = GenerationCache::default();
+    
+    // Optionally keep an exact copy of everything the selection returned; a resumed run can't
+    // reproduce the rows fetched before the interruption
+    if options.resume && config.selection_snapshot {
+        log::warn!("Resumed generation does not write {}; it would miss the rows fetched before the interruption", SNAPSHOT_FILE_NAME);
+    }
+    let mut snapshot = if config.selection_snapshot && !options.resume {
+        Some(SelectionSnapshot::create(results_dir, column_names)?)
+    } else {
+        None
+    };
+    
+    let selection = if options.resume {
+        let selection = resumed_selection(config, &columns, &checkpoint);
+        let message = format!(
+            "Resuming generation after {} keys ({} queries generated){}",
+            resumed_keys.len(), count,
+            if selection == config.selection_query { "; re-reading the selection and skipping handled keys" } else { " from the last checkpointed key" }
+        );
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+        selection
+    } else {
+        ui::progress::print_with_progress(progress_bar, "Generating update queries for all matching records...");
+        config.selection_query.clone()
     };
     
-    // Set up buffer for fetching rows
-    let mut buffers = TextRowSet::for_cursor(config.batch_size, &cursor, Some(4096))?;
-    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
-    
-    let mut count = 0;
-    let mut total_records = 0;
-    
-    ui::progress::print_with_progress(progress_bar, "Generating update queries for all matching records...");
-    
-    // Process each batch of rows
-    while let Some(batch) = row_set_cursor.fetch()? {
-        total_records += batch.num_rows();
-        progress_bar.set_length(total_records as u64);
-        
-        for row_index in 0..batch.num_rows() {
-            progress_bar.set_position(count as u64);
-            
-            // Get key field value (assuming first column is key)
-            let key_field = String::from_utf8_lossy(batch.at(0, row_index).unwrap_or(&[])).to_string();
-            
-            // Update progress bar message but don't print to console
-            ui::progress::update_message(progress_bar, format!("Generating query for key: {}", key_field));
-            
-            // Create a map of values for template substitution
-            let mut values = HashMap::new();
-            values.insert("key".to_string(), key_field.clone());
-            
-            // Add all other columns to the values map
-            for col_index in 1..batch.num_cols() {
-                let col_name = format!("field{}", col_index);
-                let value = String::from_utf8_lossy(batch.at(col_index, row_index).unwrap_or(&[])).to_string();
-                values.insert(col_name, value);
+    // Process each fetched row
+    let stats = for_each_row(conn, &selection, config, progress_bar, |row| {
+        progress_bar.set_position((count + already_applied_count + delta_skipped_count + lookup_skipped_count + processed_skipped_count) as u64);
+        
+        // Everything before this row is handled, so it's a safe point to checkpoint
+        let pending_keys = matched_keys.len() - checkpointed_keys;
+        if config.generation_checkpoint_interval > 0 && pending_keys >= config.generation_checkpoint_interval {
+            checkpoint.last_key = matched_keys.last().cloned();
+            checkpoint.rows = matched_keys.len();
+            checkpoint.generated = count;
+            checkpoint.already_applied = already_applied_count;
+            checkpoint.lookup_skipped = lookup_skipped_count;
+            checkpoint.delta_skipped = delta_skipped_count;
+            checkpoint.save(results_dir, &matched_keys[checkpointed_keys..])?;
+            checkpointed_keys = matched_keys.len();
+        }
+        
+        if let Some(snapshot) = snapshot.as_mut() {
+            if withheld.is_empty() {
+                snapshot.write_row(row)?;
+            } else {
+                snapshot.write_row(&without_withheld(row, &withheld))?;
             }
-            
-            // Generate update query by replacing template placeholders
-            let mut query = config.update_query_template.clone();
-            for (key, value) in &values {
-                let placeholder = format!("{{{{{}}}}}", key);
-                query = query.replace(&placeholder, value);
+        }
+        
+        // Get key field value (the first column, or the first columns of a composite key)
+        let key_field = row_key(row, key_count);
+        if resumed_keys.contains(&key_field) {
+            return Ok(());
+        }
+        matched_keys.push(key_field.clone());
+        
+        // In delta mode, keys the previous run already matched are not regenerated
+        if let Some(previous_keys) = &options.previous_keys {
+            if previous_keys.contains(&key_field) {
+                delta_skipped_count += 1;
+                return Ok(());
+            }
+        }
+        
+        // Update progress bar message but don't print to console
+        ui::progress::update_message(progress_bar, format!("Generating query for key: {}", key_field));
+        
+        // Render the update (or procedure call) for this row
+        let (mut query_record, long_values) = match build_query_record(config, &columns, &lookups, &mut cache, row) {
+            Ok(built) => built,
+            Err(reason) => {
+                log::warn!("Skipping key {}: {}", key_field, reason);
+                lookup_skipped_count += 1;
+                return Ok(());
+            }
+        };
+        
+        // A key corrected before that needs a change again regressed, unless the first change was wrong
+        if let Some(regression) = options.compare_applied.as_ref().and_then(|applied| applied.compare(&query_record)) {
+            regressions.push(regression);
+        }
+        
+        // Skip changes that an earlier run already applied, even if the row still matches
+        if processed_records.has_applied_hash(&query_record.change_hash()) {
+            log::info!("Skipping key {}: identical change already applied in a previous run", key_field);
+            already_applied_count += 1;
+            return Ok(());
+        }
+        
+        // Keys an earlier run updated, or whose query file still waits to run, are left alone
+        // unless --reprocess
+        if !options.reprocess {
+            if let Some(reason) = previously_handled(processed_records, &key_field) {
+                log::info!("Skipping key {}: {} (--reprocess generates it anyway)", key_field, reason);
+                processed_skipped_count += 1;
+                return Ok(());
             }
-            
-            // Create query record
-            let query_record = QueryRecord {
-                key: key_field.clone(),
-                query,
-                status: crate::db::query_types::QueryStatus::Pending,
-                result: None,
-                timestamp: None,
-            };
-            
-            // Save query to file
-            let file_path = format!("{}/{}.json", results_dir, key_field);
-            save_query_file(&file_path, &query_record)?;
-            
-            count += 1;
         }
+        
+        // Store long values next to the query record
+        query_record.parameter_files = save_parameter_values(results_dir, &query_record.file_stem(), &long_values)?;
+        
+        // Save query to file
+        let file_path = format!("{}/{}.json", results_dir, query_record.file_stem());
+        save_query_file(&file_path, &query_record)?;
+        processed_records.add_generated(key_field, time::now_rfc3339(), file_path);
+        
+        count += 1;
+        Ok(())
+    })?;
+    
+    if let Some(snapshot) = snapshot {
+        let rows = snapshot.finish()?;
+        log::info!("Wrote {} rows to selection snapshot {}/{}", rows, results_dir, SNAPSHOT_FILE_NAME);
     }
     
+    let stats = match stats {
+        Some(stats) => stats,
+        None => {
+            ui::progress::print_with_progress(progress_bar, "No records found requiring updates.");
+            log::warn!("Selection query returned no results");
+            return Ok(0);
+        }
+    };
+    
+    // Record what this run matched so a later run can generate a delta against it
+    save_selection_keys(results_dir, &matched_keys)?;
+    let manifest = RunManifest {
+        run_id: run_id::current().to_string(),
+        created: time::now_rfc3339(),
+        selection_query: config.selection_query.clone(),
+        matched_keys: matched_keys.len(),
+        generated: count,
+        delta_from: options.delta_from.clone(),
+        delta_skipped: delta_skipped_count,
+        expires: (config.max_query_age_hours > 0)
+            .then(|| time::rfc3339(time::now() + chrono::Duration::hours(config.max_query_age_hours as i64))),
+        ticket: Some(config.ticket.trim().to_string()).filter(|ticket| !ticket.is_empty()),
+        generated_by: Some(operator::current().clone()),
+        executions: Vec::new(),
+        ingested_from: None,
+        reconciled_from: None,
+        statistics_updates: Vec::new(),
+        // Unindexed key and zip predicates dominate slow runs; say so before anyone executes.
+        // The files are written by now, so advice that can't be worked out only costs a warning.
+        index_advice: advise_indexes(conn, config, results_dir, progress_bar).unwrap_or_else(|e| {
+            log::warn!("Index advisory skipped: {}", e);
+            Vec::new()
+        }),
+        tool_version: Some(version::build()),
+    };
+    manifest.save(results_dir)?;
+    
+    // The run is complete; a later --resume has nothing to carry on from
+    GenerationCheckpoint::remove(results_dir)?;
+    
     // Only print the summary at the end
-    let summary = format!("Generated {} update queries", count);
+    let summary = format!(
+        "Generated {} update queries ({} skipped as already applied, {} skipped as already processed, {} skipped for truncated values, {} skipped as matched by the previous run, {} skipped for missing lookup values)",
+        count, already_applied_count, processed_skipped_count, stats.failed_rows, delta_skipped_count, lookup_skipped_count
+    );
     ui::progress::print_with_progress(progress_bar, &format!("\x1b[32m{}\x1b[0m", summary));
     log::info!("{}", summary);
+    cache.log_metrics();
+    
+    if let Some(applied_run) = &options.compare_applied {
+        report_regressions(results_dir, applied_run, &regressions, progress_bar)?;
+    }
+    result_columns::log_cache_metrics();
     
     Ok(count)
-}
\ No newline at end of file
+}
+
+// Summarize the keys an earlier run corrected that the selection matched again, and write them
+// all to applied_comparison.csv. Rows that keep needing a fix usually point at an upstream bug.
+fn report_regressions(
+    results_dir: &str,
+    applied_run: &AppliedRun,
+    regressions: &[Regression],
+    progress_bar: &ProgressBar,
+) -> Result<(), Box<dyn Error>> {
+    write_applied_comparison(results_dir, applied_run, regressions)?;
+    if regressions.is_empty() {
+        let message = format!("None of the {} keys {} corrected needs a change again", applied_run.len(), applied_run.results_dir);
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+        return Ok(());
+    }
+    
+    let same = regressions.iter().filter(|regression| regression.same_change).count();
+    let message = format!(
+        "{} of the {} keys {} corrected need a change again: {} the same change (their rows went back; skipped as already applied), \
+         {} a different one; see {}",
+        regressions.len(), applied_run.len(), applied_run.results_dir, same, regressions.len() - same, APPLIED_COMPARISON_FILE_NAME
+    );
+    ui::progress::print_with_progress(progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+    log::warn!("{}", message);
+    for regression in regressions.iter().take(10) {
+        let line = format!(
+            "  {} (corrected {}): {}",
+            regression.key, regression.applied_at, if regression.same_change { "same change again" } else { "different change" }
+        );
+        ui::progress::print_with_progress(progress_bar, &line);
+    }
+    Ok(())
+}
+
+// Fail when the selection's columns can't supply the key columns or the upsert's columns
+pub fn check_selection_shape(config: &AppConfig, columns: &ResultColumns) -> Result<(), Box<dyn Error>> {
+    let column_names = &columns.names;
+    
+    // A composite key takes the selection's first columns, one per key column
+    let key_count = key_columns(config).len();
+    if column_names.len() < key_count {
+        return Err(format!(
+            "The selection returns {} columns but the composite key needs the first {} ({})",
+            column_names.len(), key_count, key_columns(config).join(", ")
+        ).into());
+    }
+    
+    // Withheld columns must not be what a statement is built from
+    let withheld = withheld_columns(config, column_names);
+    if !config.substitution_columns.is_empty() {
+        check_substitution_columns(config, columns, &withheld)?;
+    }
+    
+    // An upsert maps the key and each selected value to a target column, so the shapes must agree
+    if let Some(upsert) = &config.upsert {
+        if key_count > 1 {
+            return Err("upsert supports a single key column; remove key_field_names or [upsert]".into());
+        }
+        UpsertStrategy::from_name(&upsert.strategy)?;
+        if column_names.len() != upsert.columns.len() + 1 {
+            return Err(format!(
+                "The selection returns {} columns but upsert needs {} (the key, then {})",
+                column_names.len(), upsert.columns.len() + 1, upsert.columns.join(", ")
+            ).into());
+        }
+    }
+    Ok(())
+}
+
+// Indexes of the selected columns substitution_columns doesn't allow, compared without case;
+// none when the list is empty. The key columns are always allowed.
+pub fn withheld_columns(config: &AppConfig, column_names: &[String]) -> Vec<usize> {
+    if config.substitution_columns.is_empty() {
+        return Vec::new();
+    }
+    let key_count = key_columns(config).len();
+    column_names.iter()
+        .enumerate()
+        .skip(key_count)
+        .filter(|(_, name)| !config.substitution_columns.iter().any(|allowed| allowed.trim().eq_ignore_ascii_case(name)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// A row with the withheld columns' values blanked, for files that keep the selection's shape
+fn without_withheld(row: &[String], withheld: &[usize]) -> Vec<String> {
+    row.iter()
+        .enumerate()
+        .map(|(index, value)| if withheld.contains(&index) { String::new() } else { value.clone() })
+        .collect()
+}
+
+// Fail when substitution_columns names no selected column, or when the template, a procedure
+// argument or the upsert would need a withheld column's value
+fn check_substitution_columns(config: &AppConfig, columns: &ResultColumns, withheld: &[usize]) -> Result<(), Box<dyn Error>> {
+    for allowed in &config.substitution_columns {
+        if !columns.names.iter().any(|name| name.eq_ignore_ascii_case(allowed.trim())) {
+            return Err(format!(
+                "substitution_columns names {}, which the selection doesn't return (columns: {})",
+                allowed.trim(), columns.names.join(", ")
+            ).into());
+        }
+    }
+    if withheld.is_empty() {
+        return Ok(());
+    }
+    
+    let names: Vec<&str> = withheld.iter().filter_map(|index| columns.names.get(*index).map(String::as_str)).collect();
+    if config.upsert.is_some() {
+        return Err(format!("[upsert] writes every selected column, but substitution_columns withholds {}", names.join(", ")).into());
+    }
+    let templates = std::iter::once(&config.update_query_template).chain(&config.update_procedure_arguments);
+    for template in templates {
+        for (index, name) in withheld.iter().zip(&names) {
+            let placeholder = [format!("{{{{field{}}}}}", index), format!("{{{{{}}}}}", name)]
+                .into_iter()
+                .find(|placeholder| template.contains(placeholder.as_str()));
+            if let Some(placeholder) = placeholder {
+                if !config.lookups.contains_key(*name) || placeholder.starts_with("{{field") {
+                    return Err(format!(
+                        "{} substitutes column {}, which isn't in substitution_columns; add it there or stop using it",
+                        placeholder, name
+                    ).into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The selection query with `ORDER BY` its key columns appended when it has no ORDER BY of its
+// own, so reruns, resumed generations and delta comparisons read rows in the same sequence
+pub fn ordered_selection(config: &AppConfig) -> String {
+    let query = config.selection_query.trim().trim_end_matches(';').trim();
+    if find_keyword_outside_quotes(query, "ORDER BY").is_some() {
+        return config.selection_query.clone();
+    }
+    let order = key_columns(config).iter()
+        .map(|column| format_identifier(&normalize_identifier(column), config.delimident))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{} ORDER BY {}", query, order)
+}
+
+// The selection a resumed generation runs. When the selection is ordered by its (single) key
+// column, only the rows after the last checkpointed key are fetched again. An order that
+// covers every key column is total, so the dialect pages past the rows already handled.
+// Otherwise the whole selection is re-read and the handled keys are skipped, which still
+// avoids regenerating and rewriting their files.
+fn resumed_selection(config: &AppConfig, columns: &ResultColumns, checkpoint: &GenerationCheckpoint) -> String {
+    let query = config.selection_query.trim().trim_end_matches(';').trim();
+    let order_position = match find_keyword_outside_quotes(query, "ORDER BY") {
+        Some(position) => position,
+        None => return config.selection_query.clone(),
+    };
+    let order_terms: Vec<(String, String)> = query[order_position + "ORDER BY".len()..]
+        .split(',')
+        .map(|term| {
+            let mut words = term.split_whitespace();
+            (column_label(words.next().unwrap_or_default()), words.next().unwrap_or("ASC").to_string())
+        })
+        .collect();
+    
+    let key_label = columns.names.first().cloned().unwrap_or_default();
+    let last_key = match &checkpoint.last_key {
+        Some(last_key) if key_columns(config).len() == 1 => last_key,
+        _ => return paged_selection(config, query, &order_terms, checkpoint.rows),
+    };
+    let (order_column, direction) = &order_terms[0];
+    if *order_column != column_label(&key_label) || !direction.eq_ignore_ascii_case("ASC") {
+        return paged_selection(config, query, &order_terms, checkpoint.rows);
+    }
+    
+    format!(
+        "SELECT * FROM ({}) resumed WHERE {} > {} ORDER BY {}",
+        query[..order_position].trim_end(),
+        key_label,
+        key_literal(config, last_key, columns.is_integer(0)),
+        key_label
+    )
+}
+
+// Why an earlier run's record of a key keeps it from being generated again: the key was
+// updated, or its query file is still pending. A key whose generated statement failed, or
+// whose file is gone, is generated again.
+fn previously_handled(processed_records: &ProcessedRecords, key: &str) -> Option<String> {
+    match processed_records.get_action(key)?.as_str() {
+        "updated" => Some("already updated in a previous run".to_string()),
+        "generated" => {
+            let file = processed_records.generated_file(key)?;
+            let pending = query_file_exists(file)
+                && read_query_file(file).is_ok_and(|record| record.status == QueryStatus::Pending);
+            pending.then(|| format!("its query file {} is still pending", file))
+        },
+        _ => None,
+    }
+}
+
+// The ordered selection without its first `rows` rows, when its ORDER BY names every key
+// column; a partial order could put an unhandled row among the skipped ones
+fn paged_selection(config: &AppConfig, query: &str, order_terms: &[(String, String)], rows: usize) -> String {
+    let total = key_columns(config).iter().all(|column| order_terms.iter().any(|(label, _)| *label == column_label(column)));
+    if rows == 0 || !total {
+        return config.selection_query.clone();
+    }
+    dialect::current().paginate(query, rows, None)
+}
+
+// Count the rows the selection will return and, above confirm_row_threshold, ask before
+// generating. A count that can't be run is logged and doesn't block generation. Unattended
+// runs and runs whose stdin isn't a terminal log a warning instead of asking.
+pub fn confirm_selection_volume(
+    conn: &Connection,
+    config: &AppConfig,
+    options: &GenerationOptions,
+    progress_bar: &ProgressBar,
+) -> Result<(), Box<dyn Error>> {
+    let query = match count_query(&config.selection_query) {
+        Some(query) => query,
+        None => {
+            log::warn!("Could not build a count query for the selection; skipping volume preflight");
+            return Ok(());
+        }
+    };
+    
+    let expected = match query_first_row(conn, &query) {
+        Ok(row) => row.unwrap_or_default().first().and_then(|v| v.trim().parse::<u64>().ok()).unwrap_or(0),
+        Err(e) => {
+            log::warn!("Selection count preflight failed ({}): {}", query, e);
+            return Ok(());
+        }
+    };
+    
+    let message = format!("Selection matches {} records", expected);
+    ui::progress::print_with_progress(progress_bar, &message);
+    log::info!("{}", message);
+    
+    if config.confirm_row_threshold == 0 || expected <= config.confirm_row_threshold {
+        return Ok(());
+    }
+    
+    if options.assume_yes {
+        log::warn!("{} records exceeds confirm_row_threshold {}; continuing because --yes was given", expected, config.confirm_row_threshold);
+        return Ok(());
+    }
+    if options.unattended || !io::stdin().is_terminal() {
+        let message = format!("{} records exceeds confirm_row_threshold {}; continuing without asking, since no one can answer", expected, config.confirm_row_threshold);
+        ui::progress::print_with_progress(progress_bar, &format!("\x1b[33m{}\x1b[0m", message));
+        log::warn!("{}", message);
+        return Ok(());
+    }
+    
+    let response = progress_bar.suspend(|| prompt_user(&format!(
+        "Selection matches {} records, more than confirm_row_threshold {}. Generate update queries for all of them?",
+        expected, config.confirm_row_threshold
+    )));
+    if response.to_uppercase().starts_with('Y') {
+        log::info!("Operator confirmed generation for {} records", expected);
+        return Ok(());
+    }
+    
+    log::warn!("Generation cancelled: {} records exceeds confirm_row_threshold {}", expected, config.confirm_row_threshold);
+    Err(format!(
+        "Generation cancelled: selection matches {} records (confirm_row_threshold {})",
+        expected, config.confirm_row_threshold
+    ).into())
+}
+
+// Build the query record for one selected row (key first, then field1..fieldN, each also
+// under its column name), along with values too long to inline, which the caller stores and
+// binds as parameters. A composite key also provides key1..keyN and key_condition. Fails
+// with the reason when a lookup has no value for the row.
+pub fn build_query_record(
+    config: &AppConfig,
+    columns: &ResultColumns,
+    lookups: &LookupTables,
+    cache: &mut GenerationCache,
+    row: &[String],
+) -> Result<(QueryRecord, Vec<String>), String> {
+    let key_names = key_columns(config);
+    let key_count = key_names.len();
+    let key_field = row_key(row, key_count);
+    let key_values: Vec<String> = if key_count > 1 { row.iter().take(key_count).cloned().collect() } else { Vec::new() };
+    
+    let withheld = withheld_columns(config, &columns.names);
+    
+    // Create a map of values for template substitution
+    let mut values = HashMap::new();
+    values.insert("key".to_string(), key_field.clone());
+    for (index, value) in key_values.iter().enumerate() {
+        values.insert(format!("key{}", index + 1), value.clone());
+    }
+    if !key_values.is_empty() {
+        let integer: Vec<bool> = (0..key_count).map(|index| columns.is_integer(index)).collect();
+        values.insert(KEY_CONDITION.to_string(), key_condition(config, &key_names, &key_values, &integer));
+    }
+    
+    // Add all other columns to the values map
+    for (col_index, value) in row.iter().enumerate().skip(1) {
+        let col_name = format!("field{}", col_index);
+        values.insert(col_name, value.clone());
+    }
+    
+    // Each column is also available by its name, unless that clashes with the names above
+    let mut withheld_names = Vec::new();
+    for (index, (name, value)) in columns.names.iter().zip(row).enumerate() {
+        if !values.contains_key(name) {
+            values.insert(name.clone(), value.clone());
+            if withheld.contains(&index) {
+                withheld_names.push(name.clone());
+            }
+        }
+    }
+    
+    // Add the values looked up for this row
+    lookups.apply(&mut values)?;
+    
+    // Columns left out of substitution_columns served the selection and the lookups; nothing
+    // after this sees their values
+    for index in &withheld {
+        values.remove(&format!("field{}", index));
+    }
+    for name in withheld_names.iter().filter(|name| !config.lookups.contains_key(*name)) {
+        values.remove(name);
+    }
+    
+    // Upsert the row into the configured table instead of rendering the update template
+    if let Some(upsert) = &config.upsert {
+        let dialect = dialect::from_config(config).map_err(|e| e.to_string())?;
+        let (statement, insert_fallback) = build_upsert(config, upsert, dialect.as_ref(), row)?;
+        let mut query_record = QueryRecord::new(key_field, statement);
+        query_record.insert_fallback = insert_fallback;
+        query_record.annotate(config);
+        return Ok((query_record, Vec::new()));
+    }
+    
+    // Route the change through a stored procedure when one is configured
+    if !config.update_procedure.is_empty() {
+        let arguments = config.update_procedure_arguments.iter()
+            .map(|argument| render_template(argument, &values, 0).0)
+            .collect();
+        let mut query_record = QueryRecord::procedure_call(key_field, &config.update_procedure, arguments);
+        query_record.key_values = key_values;
+        query_record.annotate(config);
+        return Ok((query_record, Vec::new()));
+    }
+    
+    // Keys of integer columns are compared as numbers, so `'{{key}}'` loses its quotes
+    let mut template = config.update_query_template.clone();
+    let key_placeholders: Vec<String> = if key_count > 1 {
+        (1..=key_count).map(|index| format!("key{}", index)).collect()
+    } else {
+        vec!["key".to_string()]
+    };
+    for (index, placeholder) in key_placeholders.into_iter().enumerate() {
+        let value = row.get(index).map(String::as_str).unwrap_or_default();
+        if let Some(number) = integer_key(config, value, columns.is_integer(index)) {
+            template = template.replace(&format!("'{{{{{}}}}}'", placeholder), &format!("{{{{{}}}}}", placeholder));
+            values.insert(placeholder, number);
+        }
+    }
+    
+    // Generate update query by replacing template placeholders; long values become bound parameters
+    let (query, long_values) = render_template(&template, &values, config.inline_value_limit);
+    
+    // Keep the selected values of the SET columns the selection returned, for the
+    // before/after diff report
+    let set_columns = parse_update_statement(&query)
+        .map(|parts| parse_set_assignments(&parts.set_clause))
+        .unwrap_or_default();
+    let mut set_formats = HashMap::new();
+    let original_values: BTreeMap<String, String> = set_columns.into_iter()
+        .filter_map(|(column, _)| {
+            let index = *cache.set_columns.get_or_insert_with(column.clone(), |column| {
+                let label = column_label(column);
+                columns.names.iter().position(|name| name.eq_ignore_ascii_case(&label))
+            });
+            let index = index.filter(|index| !withheld.contains(index))?;
+            let value = row.get(index).cloned()?;
+            set_formats.insert(column.clone(), columns.format(index));
+            Some((column, value))
+        })
+        .collect();
+    
+    // Only overwrite rows whose changed columns still hold the values we selected, compared
+    // through literals the server reads the same way under any DBDATE
+    let guarded_query = if config.optimistic_concurrency {
+        add_typed_concurrency_guard(
+            &query,
+            |column| original_values.get(column).cloned(),
+            |column, value| set_formats.get(column).cloned().unwrap_or(ColumnFormat::Text).sql_literal(config, value),
+        )
+    } else {
+        None
+    };
+    
+    // Create query record
+    let mut query_record = if long_values.is_empty() {
+        QueryRecord::new(key_field, query)
+    } else {
+        QueryRecord::with_parameters(key_field, query, &long_values)
+    };
+    if let Some(guarded_query) = guarded_query {
+        query_record.query = guarded_query;
+        query_record.concurrency_guard = true;
+    }
+    query_record.original_values = original_values;
+    query_record.key_values = key_values;
+    query_record.annotate(config);
+    
+    Ok((query_record, long_values))
+}
+
+// Substitute `{{name}}` placeholders in a template. Values longer than `inline_limit`
+// (LVARCHAR/CLOB content) are replaced by a `?` placeholder, dropping the surrounding
+// quotes, and returned in placeholder order so they can be bound at execution.
+fn render_template(template: &str, values: &HashMap<String, String>, inline_limit: usize) -> (String, Vec<String>) {
+    let mut query = String::with_capacity(template.len());
+    let mut long_values = Vec::new();
+    let mut rest = template;
+    
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start + 2..].find("}}") {
+            Some(end) => start + 2 + end,
+            None => break,
+        };
+        
+        query.push_str(&rest[..start]);
+        let name = &rest[start + 2..end];
+        let mut after = &rest[end + 2..];
+        
+        match values.get(name) {
+            Some(value) if inline_limit > 0 && value.len() > inline_limit && name != KEY_CONDITION => {
+                if query.ends_with('\'') && after.starts_with('\'') {
+                    query.pop();
+                    after = &after[1..];
+                }
+                query.push('?');
+                long_values.push(value.clone());
+            },
+            Some(value) => query.push_str(value),
+            None => query.push_str(&rest[start..end + 2]),
+        }
+        
+        rest = after;
+    }
+    
+    query.push_str(rest);
+    (query, long_values)
+}
+
+// Each selected column's alias or name, used as the snapshot header and to match SET columns
+pub fn selection_column_names(selection_query: &str) -> Vec<String> {
+    select_list(selection_query)
+        .unwrap_or_default()
+        .iter()
+        .map(|item| column_label(item))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    #[test]
+    fn a_generated_key_whose_statement_failed_is_generated_again() {
+        let dir = std::env::temp_dir().join(format!("ibp-previously-handled-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("1001.json").to_string_lossy().to_string();
+        let mut record = QueryRecord::new("1001".to_string(), "UPDATE customer SET county = '001' WHERE zip = '1001'".to_string());
+        save_query_file(&file, &record).unwrap();
+        
+        let mut processed = ProcessedRecords::default();
+        processed.add_generated("1001".to_string(), "2026-01-01T00:00:00Z".to_string(), file.clone());
+        assert!(previously_handled(&processed, "1001").is_some(), "a pending file is left to run");
+        
+        record.status = QueryStatus::Failed;
+        save_query_file(&file, &record).unwrap();
+        assert_eq!(previously_handled(&processed, "1001"), None, "a failed statement is generated again");
+        
+        processed.add_generated("1001".to_string(), "2026-01-02T00:00:00Z".to_string(), file.clone());
+        record.status = QueryStatus::Completed;
+        save_query_file(&file, &record).unwrap();
+        processed.add_applied("1001".to_string(), "2026-01-02T00:05:00Z".to_string(), record.change_hash());
+        assert!(previously_handled(&processed, "1001").is_some(), "an updated key stays skipped");
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn keys_generated_without_a_query_file_are_generated_again() {
+        let mut processed = ProcessedRecords::default();
+        processed.add("1002".to_string(), "2026-01-01T00:00:00Z".to_string(), "generated".to_string());
+        assert_eq!(previously_handled(&processed, "1002"), None);
+        assert_eq!(previously_handled(&processed, "1003"), None);
+    }
+}
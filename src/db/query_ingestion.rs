@@ -0,0 +1,155 @@
+use indicatif::ProgressBar;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+
+use crate::db::query_testing::basic_sql_validation;
+use crate::db::query_types::{ErrorRecord, QueryRecord, QueryStatus};
+use crate::files::json_handler::{is_query_file_name, save_error_file, save_query_file};
+use crate::files::manifest::{save_selection_keys, RunManifest};
+use crate::files::processed::ProcessedRecords;
+use crate::ui;
+use crate::utils::operator;
+use crate::utils::{run_id, time, version};
+
+// One query handed over by another team's tooling
+#[derive(Debug, Clone, Deserialize)]
+pub struct IngestEntry {
+    pub key: String,
+    pub query: String,
+}
+
+// What an ingestion did with the entries it was given
+#[derive(Debug, Clone, Default)]
+pub struct IngestSummary {
+    // Valid queries placed into the run as Pending
+    pub accepted: usize,
+    // Queries that failed validation, kept as Quarantined for review
+    pub quarantined: usize,
+    // Identical changes already applied by an earlier run, kept as Skipped
+    pub already_applied: usize,
+    // Entries that couldn't become a query file at all (bad or duplicate key)
+    pub rejected: usize,
+}
+
+// Ingest a JSON array of `{"key": ..., "query": ...}` objects into a results directory
+// so the executor can apply and track them like generated queries. Every query goes
+// through the test pipeline's validation: valid ones become Pending, invalid ones are
+// Quarantined with the reason, and changes already applied are marked Skipped.
+pub fn ingest_queries(
+    file: &str,
+    results_dir: &str,
+    processed_records: &ProcessedRecords,
+    progress_bar: &ProgressBar,
+) -> Result<IngestSummary, Box<dyn Error>> {
+    ui::progress::print_with_progress(progress_bar, &format!("Reading queries from {}...", file));
+    
+    let content = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read ingest file {}: {}", file, e))?;
+    let entries: Vec<IngestEntry> = serde_json::from_str(&content)
+        .map_err(|e| format!("Ingest file {} is not a JSON array of {{key, query}} objects: {}", file, e))?;
+    
+    progress_bar.set_length(entries.len() as u64);
+    
+    let mut summary = IngestSummary::default();
+    let mut seen_keys = HashSet::new();
+    let mut keys = Vec::new();
+    
+    for (index, entry) in entries.into_iter().enumerate() {
+        progress_bar.set_position(index as u64);
+        ui::progress::update_message(progress_bar, format!("Ingesting query for key: {}", entry.key));
+        
+        // The key names the query file, so it has to be a safe and unique file name
+        let key = entry.key.trim().to_string();
+        if let Some(reason) = invalid_key_reason(&key, &seen_keys) {
+            log::error!("Rejected ingest entry {} (key '{}'): {}", index + 1, entry.key, reason);
+            let error_record = ErrorRecord {
+                key: entry.key.clone(),
+                file: file.to_string(),
+                error: format!("rejected at ingest: {}", reason),
+                timestamp: time::now_rfc3339(),
+                run_id: Some(run_id::current().to_string()),
+                sqlcode: None,
+                isam_error: None,
+                sqlstate: None,
+                odbc_function: None,
+                message_chain: Vec::new(),
+            };
+            save_error_file(format!("{}/errors.json", results_dir), &error_record)?;
+            summary.rejected += 1;
+            continue;
+        }
+        seen_keys.insert(key.clone());
+        keys.push(key.clone());
+        
+        let mut query_record = QueryRecord::new(key.clone(), entry.query.trim().to_string());
+        
+        if !basic_sql_validation(&query_record.query) {
+            log::error!("Quarantined ingested query for key {}: failed syntax validation", key);
+            query_record.status = QueryStatus::Quarantined;
+            query_record.result = Some("quarantined - failed syntax validation at ingest".to_string());
+            query_record.timestamp = Some(time::now_rfc3339());
+            summary.quarantined += 1;
+        } else if processed_records.has_applied_hash(&query_record.change_hash()) {
+            log::info!("Ingested query for key {} was already applied by an earlier run", key);
+            query_record.status = QueryStatus::Skipped;
+            query_record.result = Some("skipped - identical change already applied".to_string());
+            query_record.timestamp = Some(time::now_rfc3339());
+            summary.already_applied += 1;
+        } else {
+            summary.accepted += 1;
+        }
+        
+        let file_path = format!("{}/{}.json", results_dir, key);
+        save_query_file(&file_path, &query_record)?;
+    }
+    
+    // Ingested runs get the same manifest and key list as generated ones
+    save_selection_keys(results_dir, &keys)?;
+    let manifest = RunManifest {
+        run_id: run_id::current().to_string(),
+        created: time::now_rfc3339(),
+        selection_query: String::new(),
+        matched_keys: keys.len(),
+        generated: summary.accepted,
+        delta_from: None,
+        delta_skipped: 0,
+        expires: None,
+        ticket: None,
+        generated_by: Some(operator::current().clone()),
+        executions: Vec::new(),
+        ingested_from: Some(file.to_string()),
+        reconciled_from: None,
+        statistics_updates: Vec::new(),
+        index_advice: Vec::new(),
+        tool_version: Some(version::build()),
+    };
+    manifest.save(results_dir)?;
+    
+    let message = format!(
+        "Ingested {} queries from {} ({} quarantined as invalid, {} already applied, {} rejected)",
+        summary.accepted, file, summary.quarantined, summary.already_applied, summary.rejected
+    );
+    ui::progress::print_with_progress(progress_bar, &format!("\x1b[32m{}\x1b[0m", message));
+    log::info!("{}", message);
+    
+    Ok(summary)
+}
+
+// Why a key can't be used as a query file name, if it can't
+fn invalid_key_reason(key: &str, seen_keys: &HashSet<String>) -> Option<String> {
+    if key.is_empty() {
+        return Some("key is empty".to_string());
+    }
+    if key.contains(['/', '\\', ':']) || key == "." || key == ".." {
+        return Some("key contains path characters".to_string());
+    }
+    if !is_query_file_name(&format!("{}.json", key)) {
+        return Some("key collides with a reserved results file".to_string());
+    }
+    if seen_keys.contains(key) {
+        return Some("duplicate key".to_string());
+    }
+    None
+}
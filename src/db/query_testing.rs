@@ -1,14 +1,18 @@
 use odbc_api::Connection;
 use indicatif::ProgressBar;
 use std::error::Error;
-use std::fs;
 
+use crate::config::AppConfig;
+use crate::db::error_classes::OdbcDiagnostics;
+use crate::db::protection::ProtectedObjects;
 use crate::db::query_types::QueryRecord;
-use crate::files::json_handler::read_query_files;
+use crate::files::json_handler::{read_query_file, read_query_files, save_query_file};
 use crate::ui;
+use crate::utils::{masking, time};
 
 pub fn test_queries(
     conn: &Connection,
+    config: &AppConfig,
     results_dir: &str,
     progress_bar: &ProgressBar,
 ) -> Result<(usize, usize), Box<dyn Error>> {
@@ -27,25 +31,17 @@ pub fn test_queries(
     
     let mut valid_count = 0;
     let mut invalid_count = 0;
+    let protected = ProtectedObjects::from_config(config);
     
     // No longer using transactions for testing, just test each query independently
     for (index, file_path) in query_files.iter().enumerate() {
         progress_bar.set_position(index as u64);
         
         // Read query record from file
-        let file_content = match fs::read_to_string(file_path) {
-            Ok(content) => content,
-            Err(e) => {
-                log::error!("Failed to read file {}: {}", file_path.display(), e);
-                invalid_count += 1;
-                continue;
-            }
-        };
-            
-        let query_record: QueryRecord = match serde_json::from_str(&file_content) {
+        let mut query_record: QueryRecord = match read_query_file(file_path) {
             Ok(record) => record,
             Err(e) => {
-                log::error!("Failed to parse JSON from file {}: {}", file_path.display(), e);
+                log::error!("Failed to read query file {}: {}", file_path.display(), e);
                 invalid_count += 1;
                 continue;
             }
@@ -57,27 +53,64 @@ pub fn test_queries(
         // Update progress message but don't print to console
         ui::progress::update_message(progress_bar, format!("Testing query for key: {}", key));
         
-        // Very basic SQL syntax validation without using ODBC
-        let is_valid = basic_sql_validation(query);
+        // Statements touching protected tables or columns are rejected whatever their syntax
+        let is_valid = if let Some(reason) = protected.record_violation(&query_record) {
+            log::error!("Query for key {} rejected: {}", key, reason);
+            log::error!("Query: {}", query);
+            false
+        } else if !basic_sql_validation(query) {
+            log::error!("Query syntax error for key: {}", key);
+            log::error!("Query: {}", query);
+            false
+        } else if let Some(error) = config.test_with_prepare.then(|| prepare_failure(conn, &query_record)).flatten() {
+            log::error!("Server rejected the query for key {}: {}", key, error);
+            log::error!("Query: {}", query);
+            false
+        } else {
+            // Very basic SQL syntax validation without using ODBC, unless the server prepared it
+            log::info!("Query syntax looks valid for key: {}", key);
+            true
+        };
         
         if is_valid {
-            log::info!("Query syntax looks valid for key: {}", key);
             valid_count += 1;
         } else {
-            log::error!("Query syntax error for key: {}", key);
-            log::error!("Query: {}", query);
             invalid_count += 1;
         }
+        
+        // Remember what was tested so execute can refuse statements edited since
+        let tested_hash = is_valid.then(|| query_record.sql_hash());
+        if !query_record.status.is_settled() && query_record.tested_hash != tested_hash {
+            query_record.tested_at = tested_hash.is_some().then(time::now_rfc3339);
+            query_record.tested_hash = tested_hash;
+            save_query_file(file_path, &query_record)?;
+        }
     }
     
     // Print summary only at the end
-    let summary = format!("Tested {} queries: {} valid, {} invalid", total_files, valid_count, invalid_count);
+    let summary = format!(
+        "Tested {} queries{}: {} valid, {} invalid",
+        total_files, if config.test_with_prepare { " (prepared by the server)" } else { "" }, valid_count, invalid_count
+    );
     ui::progress::print_with_progress(progress_bar, &summary);
     log::info!("{}", summary);
     
     Ok((valid_count, invalid_count))
 }
 
+// Have the server prepare the record's statement and its INSERT fallback without executing
+// them. Informix parses and resolves a prepared statement, so the error is the one execution
+// would get for bad syntax, unknown tables or columns, or missing privileges.
+fn prepare_failure(conn: &Connection, query_record: &QueryRecord) -> Option<String> {
+    let statements = std::iter::once(query_record.query.as_str()).chain(query_record.insert_fallback.as_deref());
+    for statement in statements {
+        if let Err(e) = conn.prepare(statement) {
+            return Some(masking::mask(&OdbcDiagnostics::from_odbc_error(&e).summary()));
+        }
+    }
+    None
+}
+
 // A simple SQL validator that doesn't use the ODBC API at all
 pub fn basic_sql_validation(query: &str) -> bool {
     let query = query.trim().to_uppercase();
@@ -88,7 +121,13 @@ pub fn basic_sql_validation(query: &str) -> bool {
     }
     
     // Basic CHECK: Must start with a valid SQL command for this application
-    if !query.starts_with("UPDATE") && !query.starts_with("INSERT") && !query.starts_with("DELETE") {
+    if !query.starts_with("UPDATE") && !query.starts_with("INSERT") && !query.starts_with("DELETE")
+        && !query.starts_with("EXECUTE PROCEDURE") {
+        return false;
+    }
+    
+    // Basic CHECK: For procedure calls, must have an argument list
+    if query.starts_with("EXECUTE PROCEDURE") && !query.contains('(') {
         return false;
     }
     
@@ -113,16 +152,8 @@ pub fn basic_sql_validation(query: &str) -> bool {
     
     for c in query.chars() {
         match c {
-            '\'' => {
-                if !in_double_quote {
-                    in_single_quote = !in_single_quote;
-                }
-            },
-            '"' => {
-                if !in_single_quote {
-                    in_double_quote = !in_double_quote;
-                }
-            },
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
             _ => {}
         }
     }
@@ -1,20 +1,288 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::io::{self, Write};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+use crate::config::AppConfig;
+use crate::db::sql_helpers::{idempotency_hash, inline_parameters};
+use crate::files::parameter_values::load_parameter_values;
+use crate::utils::operator::OperatorIdentity;
+use crate::utils::{run_id, time, version};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum QueryStatus {
+    // Generated, never attempted
     Pending,
+    // Execution started; persisted before the statement is sent so a crash leaves a trace
+    InProgress,
     Completed,
     Failed,
+    // Not executed because an identical change was already applied
+    Skipped,
+    // Set aside for manual review (e.g. interrupted with an unknown outcome)
+    Quarantined,
+    // The row changed since it was selected, so the guarded update matched nothing
+    Conflict,
+    // Left Pending or InProgress by an abandoned run and not found applied by `sweep`
+    Stale,
+    // Applied, then undone from its rollback script by `rollback`
+    RolledBack,
+}
+
+impl QueryStatus {
+    // Whether execution should leave the record alone
+    pub fn is_settled(&self) -> bool {
+        matches!(self, QueryStatus::Completed | QueryStatus::Skipped | QueryStatus::Quarantined | QueryStatus::Conflict | QueryStatus::Stale | QueryStatus::RolledBack)
+    }
+}
+
+// What execution makes of a statement the server says affected no rows. A statement whose
+// driver reports no count succeeds under every policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroRowsPolicy {
+    // Completed
+    Ok,
+    // Completed, with a warning in the log
+    Warn,
+    // Failed, like a statement the server rejected
+    Fail,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ZeroRowsPolicy {
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "ok" => Ok(ZeroRowsPolicy::Ok),
+            "warn" => Ok(ZeroRowsPolicy::Warn),
+            "fail" => Ok(ZeroRowsPolicy::Fail),
+            _ => Err(format!("Unknown zero_rows_policy '{}' (expected 'ok', 'warn' or 'fail')", name).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryRecord {
     pub key: String,
     pub query: String,
     pub status: QueryStatus,
     pub result: Option<String>,
     pub timestamp: Option<String>,
+    #[serde(default)]
+    pub idempotency_hash: Option<String>,
+    // Out-of-line values bound to the query's `?` placeholders, relative to the results directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameter_files: Vec<String>,
+    // Values bound to the query's `?` placeholders, stored in the record itself (procedure calls)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<String>,
+    // Runs that generated and last executed this query
+    #[serde(default)]
+    pub run_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>,
+    #[serde(default)]
+    pub execution_run_id: Option<String>,
+    // The WHERE clause also checks the originally selected values of the changed columns
+    #[serde(default)]
+    pub concurrency_guard: bool,
+    // Selected values of the columns the query changes, for before/after reports
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub original_values: BTreeMap<String, String>,
+    // Change request this query implements, and an optional note for reviewers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    // Operator, user and host of the process that last executed this query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executed_by: Option<OperatorIdentity>,
+    // The correction a county command generated this query for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub county_change: Option<CountyChange>,
+    // INSERT run in the same transaction when the UPDATE of an update_insert upsert matches no row
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insert_fallback: Option<String>,
+    // Values of the key columns, in key_field_names order, when the key is composite
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_values: Vec<String>,
+    // Version and commit of the build that generated this query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    // sql_hash() when `test` last found the statement valid, for revalidate_before_execute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tested_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tested_at: Option<String>,
+    // Every failed execution, oldest first, kept when the record is dead-lettered and re-enqueued
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempt_history: Vec<ExecutionAttempt>,
+    // Failed executions counted against max_record_attempts since the record was last re-enqueued
+    #[serde(default)]
+    pub failed_attempts: u32,
+    // What the statement did in the last `execute --dry-run`, which rolled it back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<DryRunResult>,
+    // Rows the last execution changed, when the driver reported a count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows_affected: Option<usize>,
+}
+
+// One failed execution of a query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionAttempt {
+    pub run_id: String,
+    pub timestamp: String,
+    pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sqlstate: Option<String>,
+}
+
+// Response of the server to a statement executed and rolled back by `execute --dry-run`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunResult {
+    pub run_id: String,
+    pub timestamp: String,
+    // Rows the statement would change, when the driver reports a count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows_affected: Option<usize>,
+    // "success - 1 rows affected", "conflict - ...", or the error the server returned
+    pub response: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sqlstate: Option<String>,
+}
+
+impl DryRunResult {
+    pub fn new(rows_affected: Option<usize>, response: String, sqlstate: Option<String>) -> Self {
+        DryRunResult { run_id: run_id::current().to_string(), timestamp: time::now_rfc3339(), rows_affected, response, sqlstate }
+    }
+}
+
+// Zip code and county values behind a county correction, for the county results sheet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CountyChange {
+    pub zip: String,
+    pub old_county: String,
+    pub new_county: String,
+}
+
+impl QueryRecord {
+    // Create a pending query record, computing its idempotency hash
+    pub fn new(key: String, query: String) -> Self {
+        let idempotency_hash = Some(idempotency_hash(&key, &query));
+        QueryRecord {
+            key,
+            query,
+            status: QueryStatus::Pending,
+            result: None,
+            timestamp: None,
+            idempotency_hash,
+            parameter_files: Vec::new(),
+            parameters: Vec::new(),
+            run_id: Some(run_id::current().to_string()),
+            generated_at: Some(time::now_rfc3339()),
+            execution_run_id: None,
+            concurrency_guard: false,
+            original_values: BTreeMap::new(),
+            ticket: None,
+            note: None,
+            executed_by: None,
+            county_change: None,
+            insert_fallback: None,
+            key_values: Vec::new(),
+            tool_version: Some(version::build()),
+            tested_hash: None,
+            tested_at: None,
+            attempt_history: Vec::new(),
+            failed_attempts: 0,
+            dry_run: None,
+            rows_affected: None,
+        }
+    }
+    
+    // SHA-256 of exactly the SQL the record would run: the statement, its INSERT fallback
+    // and its inline parameters. Unlike the idempotency hash, any edit changes it.
+    pub fn sql_hash(&self) -> String {
+        let payload = format!(
+            "{}\n{}\n{}",
+            self.query, self.insert_fallback.as_deref().unwrap_or_default(), self.parameters.join("\n")
+        );
+        let digest = Sha256::digest(payload.as_bytes());
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+    
+    // Name of the record's file (and its parameter files) without extension: the key itself,
+    // or for a composite key a hash of it, since key values may hold characters file names can't
+    pub fn file_stem(&self) -> String {
+        if self.key_values.len() > 1 {
+            let digest = Sha256::digest(self.key.as_bytes());
+            let hex: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+            format!("key_{}", hex)
+        } else {
+            self.key.clone()
+        }
+    }
+    
+    // Attach the configured ticket and note
+    pub fn annotate(&mut self, config: &AppConfig) {
+        let non_empty = |value: &str| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        self.ticket = non_empty(&config.ticket);
+        self.note = non_empty(&config.note);
+    }
+    
+    // " (ticket CHG-1234)" for log lines, or nothing when the query has no ticket
+    pub fn ticket_suffix(&self) -> String {
+        self.ticket.as_ref().map(|ticket| format!(" (ticket {})", ticket)).unwrap_or_default()
+    }
+    
+    // `sql` preceded by a `-- run:<id> key:<key> ticket:<ticket>` comment naming the batch and
+    // record, so a statement seen in onstat or sqltrace can be traced back to its query file
+    pub fn with_provenance_comment(&self, sql: &str) -> String {
+        // A line break in a key or ticket would end the comment and run the rest as SQL
+        let single_line = |value: &str| value.replace(['\r', '\n'], " ");
+        let run = self.run_id.clone().unwrap_or_else(|| run_id::current().to_string());
+        let ticket = self.ticket.as_ref().map(|ticket| format!(" ticket:{}", single_line(ticket))).unwrap_or_default();
+        format!("-- run:{} key:{}{}\n{}", single_line(&run), single_line(&self.key), ticket, sql)
+    }
+    
+    // Create a pending record whose query binds `values` to `?` placeholders. The hash is
+    // computed as if the values were inline so it matches an equivalent literal query.
+    pub fn with_parameters(key: String, query: String, values: &[String]) -> Self {
+        let idempotency_hash = Some(idempotency_hash(&key, &inline_parameters(&query, values)));
+        QueryRecord {
+            idempotency_hash,
+            ..QueryRecord::new(key, query)
+        }
+    }
+    
+    // Create a pending `EXECUTE PROCEDURE` call binding each argument as a parameter
+    pub fn procedure_call(key: String, procedure: &str, arguments: Vec<String>) -> Self {
+        let placeholders = vec!["?"; arguments.len()].join(", ");
+        let query = format!("EXECUTE PROCEDURE {}({})", procedure.trim(), placeholders);
+        QueryRecord {
+            parameters: arguments.clone(),
+            ..QueryRecord::with_parameters(key, query, &arguments)
+        }
+    }
+    
+    // Whether any values are bound to placeholders rather than written into the SQL
+    pub fn has_bound_values(&self) -> bool {
+        !self.parameter_files.is_empty() || !self.parameters.is_empty()
+    }
+    
+    // The values to bind, in placeholder order, reading out-of-line values from the results directory
+    pub fn bound_values(&self, results_dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        if self.parameter_files.is_empty() {
+            Ok(self.parameters.clone())
+        } else {
+            load_parameter_values(results_dir, &self.parameter_files)
+        }
+    }
+    
+    // The stored idempotency hash, or one computed from the query for older records
+    pub fn change_hash(&self) -> String {
+        self.idempotency_hash.clone()
+            .unwrap_or_else(|| idempotency_hash(&self.key, &self.query))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +291,21 @@ pub struct ErrorRecord {
     pub file: String,
     pub error: String,
     pub timestamp: String,
+    #[serde(default)]
+    pub run_id: Option<String>,
+    #[serde(default)]
+    pub sqlcode: Option<i32>,
+    #[serde(default)]
+    pub isam_error: Option<i32>,
+    // SQLSTATE of the failed call, e.g. 40001
+    #[serde(default)]
+    pub sqlstate: Option<String>,
+    // The ODBC function that failed, e.g. SQLExecDirect
+    #[serde(default)]
+    pub odbc_function: Option<String>,
+    // The components that passed the message on, outermost first, then the message text
+    #[serde(default)]
+    pub message_chain: Vec<String>,
 }
 
 // Utility function for user prompts
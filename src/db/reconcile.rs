@@ -0,0 +1,199 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+
+use crate::config::AppConfig;
+use crate::db::fetch::for_each_row;
+use crate::db::query_types::QueryRecord;
+use crate::db::sql_helpers::{add_concurrency_guard, format_identifier, normalize_identifier, QualifiedName};
+use crate::files::json_handler::save_query_file;
+use crate::files::manifest::{save_selection_keys, RunManifest};
+use crate::files::processed::ProcessedRecords;
+use crate::files::review::parse_csv;
+use crate::ui;
+use crate::utils::operator;
+use crate::utils::{run_id, time, version};
+
+// Outcome of reconciling a file against the table
+#[derive(Debug, Clone, Default)]
+pub struct ReconcileSummary {
+    // Keys in the file
+    pub file_keys: usize,
+    // Keys found in the table
+    pub matched: usize,
+    // Rows that differ in at least one column, one update generated each
+    pub generated: usize,
+    // Rows that already hold the file's values
+    pub unchanged: usize,
+    // Keys in the file the table doesn't have
+    pub missing: usize,
+    // Differences an earlier run already corrected identically
+    pub already_applied: usize,
+}
+
+// Read a CSV of authoritative values (a `key_field_name` column plus each reconciled column),
+// select the same keys from the table in chunks, and generate an UPDATE for every row whose
+// values differ, setting only the columns that differ. Values are compared after the column
+// formatting fetches apply, so `1234.50` in the file matches a MONEY column.
+pub fn reconcile_file(
+    conn: &Connection,
+    config: &AppConfig,
+    file: &str,
+    results_dir: &str,
+    processed_records: &ProcessedRecords,
+    progress_bar: &ProgressBar,
+) -> Result<ReconcileSummary, Box<dyn Error>> {
+    let reconcile = config.reconcile.as_ref().ok_or("No [reconcile] section in the configuration")?;
+    if reconcile.columns.is_empty() {
+        return Err("[reconcile] lists no columns to compare".into());
+    }
+    
+    let expected = read_authoritative_values(file, &config.key_field_name, &reconcile.columns)?;
+    let mut summary = ReconcileSummary { file_keys: expected.len(), ..ReconcileSummary::default() };
+    ui::progress::print_with_progress(progress_bar, &format!("Comparing {} keys from {} with {}...", expected.len(), file, reconcile.table));
+    progress_bar.set_length(expected.len() as u64);
+    
+    let identifier = |name: &str| format_identifier(&normalize_identifier(name), config.delimident);
+    let table = QualifiedName::parse(&reconcile.table)
+        .map(|name| name.to_sql(config.delimident))
+        .ok_or_else(|| format!("[reconcile] table '{}' is not a valid table name", reconcile.table))?;
+    let key_column = identifier(&config.key_field_name);
+    let columns: Vec<String> = reconcile.columns.iter().map(|column| identifier(column)).collect();
+    
+    let keys: Vec<&String> = expected.keys().collect();
+    let mut matched_keys = Vec::new();
+    for chunk in keys.chunks(reconcile.chunk_size.max(1)) {
+        let query = format!(
+            "SELECT {}, {} FROM {} WHERE {} IN ({})",
+            key_column,
+            columns.join(", "),
+            table,
+            key_column,
+            chunk.iter().map(|key| format!("'{}'", key.replace('\'', "''"))).collect::<Vec<_>>().join(", ")
+        );
+        
+        for_each_row(conn, &query, config, progress_bar, |row| {
+            let key = row[0].trim().to_string();
+            let wanted = match expected.get(&key) {
+                Some(wanted) => wanted,
+                None => return Ok(()),
+            };
+            matched_keys.push(key.clone());
+            
+            // (column, current value, authoritative value) for each column that differs; an
+            // empty cell in the file means it has no value for that column
+            let differences: Vec<(&String, &str, &str)> = columns.iter()
+                .zip(&row[1..])
+                .zip(wanted)
+                .filter(|((_, current), wanted)| !wanted.trim().is_empty() && current.trim() != wanted.trim())
+                .map(|((column, current), wanted)| (column, current.as_str(), wanted.as_str()))
+                .collect();
+            
+            if differences.is_empty() {
+                summary.unchanged += 1;
+                return Ok(());
+            }
+            
+            let query_record = reconcile_record(config, &table, &key_column, &key, &differences);
+            if processed_records.has_applied_hash(&query_record.change_hash()) {
+                log::info!("Skipping key {}: identical change already applied in a previous run", key);
+                summary.already_applied += 1;
+                return Ok(());
+            }
+            
+            save_query_file(format!("{}/{}.json", results_dir, key), &query_record)?;
+            log::info!(
+                "Reconcile: key {} differs in {}",
+                key, differences.iter().map(|(column, _, _)| column.as_str()).collect::<Vec<_>>().join(", ")
+            );
+            summary.generated += 1;
+            Ok(())
+        })?;
+        progress_bar.set_position(matched_keys.len() as u64);
+    }
+    
+    summary.matched = matched_keys.len();
+    summary.missing = summary.file_keys - summary.matched;
+    if summary.missing > 0 {
+        let matched: HashSet<&String> = matched_keys.iter().collect();
+        let missing: Vec<&&String> = keys.iter().filter(|key| !matched.contains(**key)).take(10).collect();
+        log::warn!("{} keys in {} are not in {} (e.g. {:?})", summary.missing, file, reconcile.table, missing);
+    }
+    
+    save_selection_keys(results_dir, &matched_keys)?;
+    let manifest = RunManifest {
+        run_id: run_id::current().to_string(),
+        created: time::now_rfc3339(),
+        selection_query: format!("SELECT {}, {} FROM {} WHERE {} IN (<keys from file>)", key_column, columns.join(", "), table, key_column),
+        matched_keys: summary.matched,
+        generated: summary.generated,
+        expires: (config.max_query_age_hours > 0)
+            .then(|| time::rfc3339(time::now() + chrono::Duration::hours(config.max_query_age_hours as i64))),
+        ticket: Some(config.ticket.trim().to_string()).filter(|ticket| !ticket.is_empty()),
+        generated_by: Some(operator::current().clone()),
+        reconciled_from: Some(file.to_string()),
+        tool_version: Some(version::build()),
+        ..RunManifest::default()
+    };
+    manifest.save(results_dir)?;
+    
+    Ok(summary)
+}
+
+// The UPDATE correcting one row, keeping the replaced values for the diff report and, with
+// optimistic concurrency, only applying while the row still holds them
+fn reconcile_record(config: &AppConfig, table: &str, key_column: &str, key: &str, differences: &[(&String, &str, &str)]) -> QueryRecord {
+    let assignments = differences.iter()
+        .map(|(column, _, wanted)| format!("{} = '{}'", column, wanted.trim().replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let query = format!("UPDATE {} SET {} WHERE {} = '{}'", table, assignments, key_column, key.replace('\'', "''"));
+    
+    let originals: BTreeMap<String, String> = differences.iter()
+        .map(|(column, current, _)| (column.to_string(), current.to_string()))
+        .collect();
+    
+    let mut query_record = QueryRecord::new(key.to_string(), query);
+    if config.optimistic_concurrency {
+        if let Some(guarded_query) = add_concurrency_guard(&query_record.query, |column| originals.get(column).cloned()) {
+            query_record.query = guarded_query;
+            query_record.concurrency_guard = true;
+        }
+    }
+    query_record.original_values = originals;
+    query_record.annotate(config);
+    query_record
+}
+
+// Map each key in the file to its values for `columns`, in that order
+fn read_authoritative_values(file: &str, key_field: &str, columns: &[String]) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+    let content = fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+    let rows = parse_csv(content.trim_start_matches('\u{feff}'));
+    
+    let header = rows.first().ok_or_else(|| format!("{} is empty", file))?;
+    let column_index = |name: &str| {
+        let name = normalize_identifier(name);
+        header.iter()
+            .position(|cell| normalize_identifier(cell) == name)
+            .ok_or_else(|| format!("{} has no '{}' column", file, name))
+    };
+    let key_index = column_index(key_field)?;
+    let value_indexes = columns.iter().map(|column| column_index(column)).collect::<Result<Vec<_>, _>>()?;
+    
+    let mut values = HashMap::new();
+    for (line, row) in rows.iter().enumerate().skip(1) {
+        let key = match row.get(key_index) {
+            Some(key) if !key.trim().is_empty() => key.trim().to_string(),
+            _ => continue,
+        };
+        let row_values = value_indexes.iter()
+            .map(|&index| row.get(index).cloned().unwrap_or_default())
+            .collect();
+        if values.insert(key.clone(), row_values).is_some() {
+            return Err(format!("Key {} appears more than once in {} (again in row {})", key, file, line + 1).into());
+        }
+    }
+    Ok(values)
+}
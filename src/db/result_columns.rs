@@ -0,0 +1,158 @@
+use odbc_api::{Connection, DataType, ResultSetMetadata};
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::AppConfig;
+use crate::db::column_format::ColumnFormat;
+use crate::db::dialect;
+use crate::db::fetch::for_each_row;
+use crate::db::sql_helpers::{column_label, QualifiedName};
+use crate::db::sql_parser::SelectStatement;
+use crate::utils::memo::Memo;
+
+// Descriptions already resolved in this process, by query text, so continuous mode and the
+// commands that describe the same selection twice only ask the driver once
+static RESOLVED: OnceLock<Mutex<Memo<String, ResultColumns>>> = OnceLock::new();
+
+// Where the column names of a selection came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnSource {
+    // The driver's description of the prepared statement's result set
+    ResultMetadata,
+    // The system catalog, for a `SELECT *` the driver couldn't describe
+    Catalog,
+    // The select list as written, when neither of the above was usable
+    SelectList,
+}
+
+// The normalized names of the columns a selection query returns, in result order
+#[derive(Debug, Clone)]
+pub struct ResultColumns {
+    pub names: Vec<String>,
+    pub source: ColumnSource,
+    // Whether each column has an integer type, and how its fetched text is normalized; only
+    // the driver's metadata tells, so these are empty for the other sources
+    pub integer: Vec<bool>,
+    pub formats: Vec<ColumnFormat>,
+}
+
+impl ResultColumns {
+    // Describe a selection query's result columns without fetching any rows. The driver's
+    // metadata comes first; a driver that leaves names blank falls back to the catalog for
+    // `SELECT * FROM table`, and to the parsed select list otherwise. The result is cached
+    // for the rest of the process.
+    // Columns known only by name, such as a template fixture's
+    pub fn from_names(names: Vec<String>) -> Self {
+        ResultColumns { names, source: ColumnSource::SelectList, integer: Vec::new(), formats: Vec::new() }
+    }
+    
+    pub fn resolve(conn: &Connection, config: &AppConfig, query: &str, progress_bar: &ProgressBar) -> Result<Self, Box<dyn Error>> {
+        let mut resolved = RESOLVED.get_or_init(|| Mutex::new(Memo::new("result columns"))).lock().unwrap();
+        resolved.try_get_or_insert_with(query.to_string(), |query| Self::describe(conn, config, query, progress_bar))
+            .cloned()
+    }
+    
+    fn describe(conn: &Connection, config: &AppConfig, query: &str, progress_bar: &ProgressBar) -> Result<Self, Box<dyn Error>> {
+        match metadata_columns(conn, query) {
+            Ok(columns) if !columns.is_empty() && columns.iter().all(|(name, _)| !name.is_empty()) => {
+                let integer = columns.iter().map(|(_, data_type)| is_integer_type(*data_type)).collect();
+                let formats = columns.iter().map(|(name, data_type)| ColumnFormat::for_column(config, name, *data_type)).collect();
+                let names = columns.into_iter().map(|(name, _)| name).collect();
+                return Ok(ResultColumns { names, source: ColumnSource::ResultMetadata, integer, formats });
+            },
+            Ok(_) => log::warn!("The driver didn't report column names for the selection query"),
+            Err(e) => log::warn!("Could not describe the selection query's result columns: {}", e),
+        }
+        
+        let statement = SelectStatement::parse(query)?;
+        if let ([item], Some(table)) = (statement.items.as_slice(), &statement.table.name) {
+            if item.is_wildcard() {
+                let names = catalog_column_names(conn, config, table, progress_bar)?;
+                if names.is_empty() {
+                    return Err(format!("The catalog lists no columns for table {}", table).into());
+                }
+                return Ok(ResultColumns { names, source: ColumnSource::Catalog, integer: Vec::new(), formats: Vec::new() });
+            }
+        }
+        
+        Ok(ResultColumns {
+            names: statement.items.iter().map(|item| item.label()).collect(),
+            source: ColumnSource::SelectList,
+            integer: Vec::new(),
+            formats: Vec::new(),
+        })
+    }
+    
+    // Whether the column at `index` is known to have an integer type
+    pub fn is_integer(&self, index: usize) -> bool {
+        self.integer.get(index).copied().unwrap_or(false)
+    }
+    
+    // How the column at `index` is normalized; plain text when the driver didn't describe it
+    pub fn format(&self, index: usize) -> ColumnFormat {
+        self.formats.get(index).cloned().unwrap_or(ColumnFormat::Text)
+    }
+    
+    // Position of a column by name; qualifiers and quotes on `field_name` are ignored
+    pub fn index_of(&self, field_name: &str) -> Option<usize> {
+        let field_name = column_label(field_name);
+        self.names.iter().position(|name| *name == field_name)
+    }
+    
+    // Position of the column a setting names, or an error listing what the query returns
+    pub fn require(&self, field_name: &str, setting: &str) -> Result<usize, String> {
+        self.index_of(field_name).ok_or_else(|| format!(
+            "{} '{}' is not among the selection query's columns ({}; from {:?})",
+            setting, field_name, self.names.join(", "), self.source
+        ))
+    }
+}
+
+// Log how often resolve was answered from the cache
+pub fn log_cache_metrics() {
+    if let Some(resolved) = RESOLVED.get() {
+        resolved.lock().unwrap().log_metrics();
+    }
+}
+
+// Column names of the prepared query as reported by the driver, lower-cased the way
+// Informix returns unquoted names, with their types
+fn metadata_columns(conn: &Connection, query: &str) -> Result<Vec<(String, DataType)>, Box<dyn Error>> {
+    let prepared = conn.prepare(query)?;
+    let num_cols = prepared.num_result_cols()?.max(0) as u16;
+    
+    let mut columns = Vec::with_capacity(num_cols as usize);
+    for col_number in 1..=num_cols {
+        let name = prepared.col_name(col_number)?.trim().to_lowercase();
+        columns.push((name, prepared.col_data_type(col_number)?));
+    }
+    Ok(columns)
+}
+
+fn is_integer_type(data_type: DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Integer | DataType::SmallInt | DataType::BigInt | DataType::TinyInt
+            | DataType::Numeric { scale: 0, .. } | DataType::Decimal { scale: 0, .. }
+    )
+}
+
+// Column names of a table from the system catalog, in column order
+fn catalog_column_names(conn: &Connection, config: &AppConfig, table: &str, progress_bar: &ProgressBar) -> Result<Vec<String>, Box<dyn Error>> {
+    let table_name = QualifiedName::parse(table)
+        .map(|name| name.name)
+        .ok_or_else(|| format!("Can't read a table name from '{}'", table))?;
+    let query = dialect::from_config(config)?.table_columns_query(&table_name);
+    
+    let mut names = Vec::new();
+    for_each_row(conn, &query, config, progress_bar, |row| {
+        if let Some(name) = row.first() {
+            names.push(name.trim().to_lowercase());
+        }
+        Ok(())
+    })?;
+    
+    log::info!("Read {} column names for {} from the catalog", names.len(), table);
+    Ok(names)
+}
@@ -0,0 +1,206 @@
+use odbc_api::{Connection, Cursor};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::db::connection::execute_with_row_count;
+use crate::db::error_classes::OdbcDiagnostics;
+use crate::db::fetch::{ensure_not_truncated, text_buffers, UTILITY_MAX_TEXT_LENGTH};
+use crate::db::query_execution::finish_transaction;
+use crate::db::query_types::{QueryRecord, QueryStatus};
+use crate::db::sql_helpers::{add_concurrency_guard, parse_set_assignments, parse_update_statement};
+use crate::db::verification::unguarded_condition;
+use crate::files::json_handler::{read_query_file, read_query_files, save_query_file};
+use crate::ui;
+use crate::utils::{encoding, masking, run_id, time};
+
+// Directory inside a results directory holding the rollback script of each executed UPDATE
+pub const ROLLBACK_DIR_NAME: &str = "rollback";
+
+// The values an UPDATE's row held just before it ran, and the statement that restores them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackScript {
+    pub key: String,
+    pub table: String,
+    pub captured_at: String,
+    pub run_id: String,
+    // Value of each column the UPDATE assigns; None was NULL
+    pub previous_values: BTreeMap<String, Option<String>>,
+    // Restores the previous values, guarded by the literals the UPDATE assigned so a row
+    // someone changed since is left alone
+    pub rollback_query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+// Outcome of applying a run's rollback scripts
+#[derive(Debug, Clone, Default)]
+pub struct RollbackSummary {
+    pub rolled_back: usize,
+    // The row no longer holds what the UPDATE set, so it was left alone
+    pub conflicts: usize,
+    pub failed: usize,
+    // Completed records without a rollback script (not an UPDATE, or executed without capture_rollback)
+    pub without_script: usize,
+}
+
+fn script_path(results_dir: &str, record: &QueryRecord) -> PathBuf {
+    Path::new(results_dir).join(ROLLBACK_DIR_NAME).join(format!("{}.json", record.file_stem()))
+}
+
+// Re-select the columns an UPDATE assigns and write the statement that restores them to
+// rollback/<key>.json, before the UPDATE runs. Returns false for statements that aren't an
+// UPDATE, which get no script. An UPDATE with an INSERT fallback whose row doesn't exist yet
+// is rolled back by deleting the row it will insert. Fails when the row can't be read or the
+// WHERE clause matches more than one row, since one script can't restore several.
+pub fn capture_rollback(conn: &Connection, results_dir: &str, record: &QueryRecord) -> Result<bool, Box<dyn Error>> {
+    let parts = match parse_update_statement(&record.query) {
+        Some(parts) => parts,
+        None => return Ok(false),
+    };
+    let where_clause = parts.where_clause.as_deref().ok_or("UPDATE has no WHERE clause")?;
+    let condition = if record.concurrency_guard { unguarded_condition(where_clause) } else { where_clause };
+    let assignments = parse_set_assignments(&parts.set_clause);
+    if assignments.is_empty() {
+        return Err("no SET assignments recognised".into());
+    }
+    
+    let columns = assignments.iter().map(|(column, _)| column.as_str()).collect::<Vec<_>>().join(", ");
+    let rows = select_nullable(conn, &format!("SELECT {} FROM {} WHERE {}", columns, parts.table, condition))?;
+    let (previous_values, rollback_query) = match rows.as_slice() {
+        [] if record.insert_fallback.is_some() => (BTreeMap::new(), format!("DELETE FROM {} WHERE {}", parts.table, condition)),
+        [] => return Err("no row matches the WHERE clause".into()),
+        [row] => {
+            let previous_values: BTreeMap<String, Option<String>> = assignments.iter()
+                .zip(row)
+                .map(|((column, _), value)| (column.clone(), value.clone()))
+                .collect();
+            let set_clause = assignments.iter()
+                .zip(row)
+                .map(|((column, _), value)| match value {
+                    Some(value) => format!("{} = '{}'", column, value.replace('\'', "''")),
+                    None => format!("{} = NULL", column),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let restore = format!("UPDATE {} SET {} WHERE {}", parts.table, set_clause, condition);
+            // Only restore the row while it still holds what the UPDATE assigned
+            let guarded = add_concurrency_guard(&restore, |column| {
+                assignments.iter().find(|(assigned, _)| assigned == column).and_then(|(_, value)| value.clone())
+            });
+            (previous_values, guarded.unwrap_or(restore))
+        },
+        _ => return Err(format!("{} rows match the WHERE clause; a rollback script restores one", rows.len()).into()),
+    };
+    
+    let script = RollbackScript {
+        key: record.key.clone(),
+        table: parts.table.clone(),
+        captured_at: time::now_rfc3339(),
+        run_id: run_id::current().to_string(),
+        previous_values,
+        rollback_query,
+        applied_at: None,
+        result: None,
+    };
+    let path = script_path(results_dir, record);
+    fs::create_dir_all(path.parent().unwrap_or_else(|| Path::new(results_dir)))?;
+    fs::write(&path, serde_json::to_string_pretty(&script)?)?;
+    Ok(true)
+}
+
+// Every row of a query, with NULLs kept apart from empty strings. A value longer than its
+// buffer fails the capture, since restoring a truncated pre-image would lose data.
+fn select_nullable(conn: &Connection, query: &str) -> Result<Vec<Vec<Option<String>>>, Box<dyn Error>> {
+    let cursor = match conn.execute(query, ())? {
+        Some(cursor) => cursor,
+        None => return Ok(Vec::new()),
+    };
+    let mut buffers = text_buffers(&cursor, 100, UTILITY_MAX_TEXT_LENGTH)?;
+    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
+    let mut rows = Vec::new();
+    while let Some(batch) = row_set_cursor.fetch()? {
+        for row_index in 0..batch.num_rows() {
+            ensure_not_truncated(batch, row_index)?;
+            rows.push((0..batch.num_cols()).map(|col_index| batch.at(col_index, row_index).map(encoding::decode)).collect());
+        }
+    }
+    Ok(rows)
+}
+
+// Undo a run's completed UPDATEs from their rollback scripts, latest first so several changes
+// to one row unwind in order. `keys` limits it to those keys. A restored record becomes
+// RolledBack; a script whose row changed since is left unapplied and counted as a conflict.
+pub fn apply_rollback(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    keys: &[String],
+    progress_bar: &ProgressBar,
+) -> Result<RollbackSummary, Box<dyn Error>> {
+    let mut summary = RollbackSummary::default();
+    let mut pending = Vec::new();
+    for file_path in read_query_files(results_dir)? {
+        let record = match read_query_file(&file_path) {
+            Ok(record) if record.status == QueryStatus::Completed => record,
+            _ => continue,
+        };
+        if !keys.is_empty() && !keys.contains(&record.key) {
+            continue;
+        }
+        let path = script_path(results_dir, &record);
+        let script: RollbackScript = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+            Err(_) => {
+                summary.without_script += 1;
+                continue;
+            },
+        };
+        if script.applied_at.is_none() {
+            pending.push((file_path, record, path, script));
+        }
+    }
+    pending.sort_by(|a, b| b.1.timestamp.cmp(&a.1.timestamp));
+    
+    progress_bar.set_length(pending.len() as u64);
+    ui::progress::print_with_progress(progress_bar, &format!("Rolling back {} changes of {}", pending.len(), results_dir));
+    for (index, (file_path, mut record, path, mut script)) in pending.into_iter().enumerate() {
+        progress_bar.set_position(index as u64);
+        ui::progress::update_message(progress_bar, format!("Rolling back key: {}", record.key));
+        
+        let execution = execute_with_row_count(conn, &script.rollback_query, ());
+        let execution = if config.execution_autocommit { execution } else { finish_transaction(conn, execution) };
+        match execution {
+            Ok(Some(0)) => {
+                summary.conflicts += 1;
+                script.result = Some("conflict - the row changed since the update; not rolled back".to_string());
+                log::warn!("Not rolling back key {}: its row no longer holds the change", record.key);
+            },
+            Ok(_) => {
+                summary.rolled_back += 1;
+                let now = time::now_rfc3339();
+                script.applied_at = Some(now.clone());
+                script.result = Some("rolled back".to_string());
+                record.status = QueryStatus::RolledBack;
+                record.result = Some(format!("rolled back by run {}", run_id::current()));
+                record.timestamp = Some(now);
+                save_query_file(&file_path, &record)?;
+                log::info!("Rolled back key {}{}", record.key, record.ticket_suffix());
+            },
+            Err(e) => {
+                summary.failed += 1;
+                let error = masking::mask(&OdbcDiagnostics::from_odbc_error(&e).summary());
+                script.result = Some(format!("error: {}", error));
+                log::error!("Rollback of key {} failed: {}", record.key, error);
+            },
+        }
+        fs::write(&path, serde_json::to_string_pretty(&script)?)?;
+    }
+    Ok(summary)
+}
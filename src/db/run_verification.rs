@@ -0,0 +1,126 @@
+use odbc_api::Connection;
+use chrono::{DateTime, Utc};
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::db::connection::query_first_row;
+use crate::db::query_types::QueryStatus;
+use crate::db::verification::{verify_query, VerificationOutcome};
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::snapshot::csv_field;
+use crate::ui;
+use crate::utils::masking;
+
+// Outcome of each verified record, written to the results directory by `verify`
+pub const VERIFICATION_FILE_NAME: &str = "verification.csv";
+
+// Outcome of verifying a run's completed queries
+#[derive(Debug, Clone, Default)]
+pub struct RunVerificationSummary {
+    pub verified: usize,
+    pub mismatched: usize,
+    pub unverifiable: usize,
+    // Seconds the replica was behind the primary when verification started
+    pub replica_lag_seconds: Option<u64>,
+}
+
+// Re-read the row of every Completed query of a run and check it holds the values the query
+// assigned. On the read replica, its lag is checked against verify_max_lag_seconds first, and
+// verification waits until the latest execution is older than the lag so every change has had
+// time to arrive. Records keep their status; the outcomes go to verification.csv.
+pub fn verify_run(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    on_replica: bool,
+    progress_bar: &ProgressBar,
+) -> Result<RunVerificationSummary, Box<dyn Error>> {
+    let mut records = Vec::new();
+    for file_path in read_query_files(results_dir)? {
+        match read_query_file(&file_path) {
+            Ok(record) if record.status == QueryStatus::Completed => records.push(record),
+            Ok(_) => {},
+            Err(e) => log::error!("Failed to read {}: {}", file_path.display(), e),
+        }
+    }
+    
+    let mut summary = RunVerificationSummary::default();
+    if on_replica {
+        let lag = replica_lag(conn, config)?;
+        summary.replica_lag_seconds = Some(lag);
+        let newest = records.iter()
+            .filter_map(|record| record.timestamp.as_deref())
+            .filter_map(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .max();
+        if let Some(newest) = newest {
+            let age = (Utc::now() - newest.with_timezone(&Utc)).num_seconds().max(0) as u64;
+            if age < lag {
+                let message = format!("Waiting {}s for the replica to receive the latest changes", lag - age);
+                ui::progress::print_with_progress(progress_bar, &message);
+                log::info!("{}", message);
+                thread::sleep(Duration::from_secs(lag - age));
+            }
+        }
+    }
+    
+    progress_bar.set_length(records.len() as u64);
+    ui::progress::print_with_progress(progress_bar, &format!(
+        "Verifying {} completed queries on the {}", records.len(), if on_replica { "read replica" } else { "primary" }
+    ));
+    
+    let mut content = String::from("\u{feff}key,outcome,detail\r\n");
+    for (index, record) in records.iter().enumerate() {
+        progress_bar.set_position(index as u64);
+        ui::progress::update_message(progress_bar, format!("Verifying key: {}", record.key));
+        let outcome = match verify_query(conn, record) {
+            Ok(outcome) => outcome,
+            Err(e) => VerificationOutcome::Unverifiable(format!("verification read failed: {}", e)),
+        };
+        let (name, detail) = match outcome {
+            VerificationOutcome::Verified => {
+                summary.verified += 1;
+                ("verified", String::new())
+            },
+            VerificationOutcome::Mismatch(reason) => {
+                summary.mismatched += 1;
+                log::warn!("Key {} doesn't hold its change: {}", record.key, masking::mask(&reason));
+                ("mismatch", masking::mask(&reason))
+            },
+            VerificationOutcome::Unverifiable(reason) => {
+                summary.unverifiable += 1;
+                ("unverifiable", masking::mask(&reason))
+            },
+        };
+        content.push_str(&format!("{},{},{}\r\n", csv_field(&record.key), name, csv_field(&detail)));
+    }
+    fs::write(Path::new(results_dir).join(VERIFICATION_FILE_NAME), content)?;
+    
+    Ok(summary)
+}
+
+// The replica's lag from verify_lag_query, refused when it's more than verify_max_lag_seconds.
+// Without a lag query the replica is assumed to lag by the tolerance itself.
+fn replica_lag(conn: &Connection, config: &AppConfig) -> Result<u64, Box<dyn Error>> {
+    if config.verify_lag_query.trim().is_empty() {
+        return Ok(config.verify_max_lag_seconds);
+    }
+    let value = query_first_row(conn, &config.verify_lag_query)?
+        .and_then(|row| row.into_iter().next())
+        .ok_or("verify_lag_query returned no rows")?;
+    let lag = value.trim().parse::<f64>()
+        .map_err(|_| format!("verify_lag_query returned '{}', not a number of seconds", value.trim()))?;
+    let lag = lag.max(0.0).ceil() as u64;
+    if lag > config.verify_max_lag_seconds {
+        return Err(format!(
+            "The read replica is {}s behind the primary, more than verify_max_lag_seconds ({}s); try again later",
+            lag, config.verify_max_lag_seconds
+        ).into());
+    }
+    log::info!("Read replica lag: {}s (tolerance {}s)", lag, config.verify_max_lag_seconds);
+    Ok(lag)
+}
@@ -1,133 +1,449 @@
-use odbc_api::buffers::TextRowSet;
-
-// Helper function to find column index by position (for key field)
-pub fn find_column_index_by_position(batch: &TextRowSet, default_position: usize) -> usize {
-    // Return the default position, but make sure it's within the valid range
-    let num_cols = batch.num_cols();
-    if default_position < num_cols {
-        default_position
-    } else {
-        0 // Fallback to first column if default is out of range
-    }
-}
-
-// Find column index based on field name and query structure
-pub fn find_column_index_by_name(query: &str, field_name: &str) -> usize {
-    // Parse the SELECT statement to extract column names
-    if let Some(select_pos) = query.to_uppercase().find("SELECT ") {
-        if let Some(from_pos) = query.to_uppercase().find(" FROM ") {
-            let columns_str = &query[(select_pos + 7)..from_pos];
-            let columns: Vec<&str> = columns_str.split(',').map(|s| s.trim()).collect();
-            
-            // Find the position of the field name in the columns list
-            for (i, col) in columns.iter().enumerate() {
-                // Check if the column name exactly matches the field name
-                // or if it ends with the field name (e.g., "table.field_name")
-                if col.to_lowercase() == field_name.to_lowercase() || 
-                   col.to_lowercase().ends_with(&format!(".{}", field_name.to_lowercase())) {
-                    return i;
-                }
-            }
-            
-            // If we got here, the field name wasn't found in the columns
-            panic!("Field name '{}' not found in SELECT statement: {}", field_name, columns_str);
-        } else {
-            panic!("Invalid SELECT statement: FROM clause not found in query: {}", query);
+use sha2::{Digest, Sha256};
+
+use crate::db::dialect;
+use crate::db::sql_parser::{SelectStatement, SqlParseError};
+
+// Extract the first table name from a query's FROM clause, as written (owner prefix and
+// quotes included), without any alias or join that follows it
+pub fn extract_table_name(query: &str) -> Result<String, SqlParseError> {
+    // Example: "SELECT c.field1 FROM informix.customer c WHERE condition" -> "informix.customer"
+    let statement = SelectStatement::parse(query)?;
+    statement.table.name.ok_or_else(|| SqlParseError {
+        message: "the first FROM item is a derived table, not a table that can be updated".to_string(),
+        position: statement.table.position,
+        statement: query.to_string(),
+    })
+}
+
+// The alias given to the first table of a query's FROM clause (`customer c`, `customer AS c`)
+pub fn extract_table_alias(query: &str) -> Option<String> {
+    SelectStatement::parse(query).ok()?.table.alias
+}
+
+// An identifier that may be qualified by owner and database, such as `customer`,
+// `informix.customer`, `"Informix"."Customer"` or `stores@srv:informix.customer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualifiedName {
+    // Database (and server) prefix before the colon, as written
+    pub database: Option<String>,
+    pub owner: Option<String>,
+    pub name: String,
+}
+
+impl QualifiedName {
+    // Split a name into its parts. Quoted parts keep their case; unquoted ones are folded to
+    // lower case the way Informix stores them.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (database, rest) = match find_outside_double_quotes(text, ':') {
+            Some(colon) => (Some(text[..colon].trim().to_string()), &text[colon + 1..]),
+            None => (None, text),
+        };
+        
+        let mut parts: Vec<String> = split_outside_double_quotes(rest, '.')
+            .iter()
+            .map(|part| normalize_identifier(part))
+            .collect();
+        let name = parts.pop().filter(|name| !name.is_empty())?;
+        let owner = parts.pop().filter(|owner| !owner.is_empty());
+        if !parts.is_empty() {
+            return None;
+        }
+        
+        Some(QualifiedName { database, owner, name })
+    }
+    
+    // Comparable form: `owner.name`, or just `name` without an owner
+    pub fn normalized(&self) -> String {
+        match &self.owner {
+            Some(owner) => format!("{}.{}", owner, self.name),
+            None => self.name.clone(),
         }
+    }
+    
+    // Render for SQL, quoting each part that needs it when delimited identifiers are enabled
+    pub fn to_sql(&self, delimident: bool) -> String {
+        let mut sql = String::new();
+        if let Some(database) = &self.database {
+            sql.push_str(database);
+            sql.push(':');
+        }
+        if let Some(owner) = &self.owner {
+            sql.push_str(&format_identifier(owner, delimident));
+            sql.push('.');
+        }
+        sql.push_str(&format_identifier(&self.name, delimident));
+        sql
+    }
+}
+
+// The case-normalized form of one identifier: the contents of a quoted identifier as-is
+// (with doubled quotes unescaped), an unquoted one in lower case
+pub fn normalize_identifier(identifier: &str) -> String {
+    let identifier = identifier.trim();
+    if identifier.len() >= 2 && identifier.starts_with('"') && identifier.ends_with('"') {
+        identifier[1..identifier.len() - 1].replace("\"\"", "\"")
     } else {
-        panic!("Invalid query: SELECT statement not found in query: {}", query);
+        identifier.to_lowercase()
     }
 }
 
-// Helper function to find column index by data pattern or position
-pub fn find_column_index_by_pattern(batch: &TextRowSet, field_name: &str, default_position: usize) -> usize {
-    let num_cols = batch.num_cols();
-    
-    // If the default position is valid, use it as a fallback
-    let fallback = if default_position < num_cols { default_position } else { 0 };
+// The column name an expression like `c.county`, `"Zip_Code"` or `zip AS z` refers to or
+// is labelled with, normalized
+pub fn column_label(expression: &str) -> String {
+    let expression = expression.trim();
+    let label = split_outside_double_quotes(expression, ' ')
+        .into_iter()
+        .rfind(|word| !word.trim().is_empty())
+        .unwrap_or_else(|| expression.to_string());
+    let label = split_outside_double_quotes(&label, '.').pop().unwrap_or(label);
+    normalize_identifier(&label)
+}
+
+// Whether an identifier must be delimited: anything but a lower-case name of letters,
+// digits, `_` and `$` starting with a letter or `_`, or one of the reserved words that
+// commonly collide with column names
+pub fn needs_quoting(identifier: &str) -> bool {
+    const RESERVED: &[&str] = &["select", "from", "where", "order", "group", "table", "user", "date", "value", "key", "type"];
     
-    // For zip fields, look for a column that contains zip-like data (5 digits, maybe a dash)
-    if field_name.contains("zip") && batch.num_rows() > 0 {
-        for i in 0..num_cols {
-            let value = String::from_utf8_lossy(batch.at(i, 0).unwrap_or(&[])).to_string();
-            if value.len() >= 5 && value.chars().take(5).all(|c| c.is_digit(10)) {
-                return i;
-            }
+    let mut chars = identifier.chars();
+    let starts_ok = chars.next().is_some_and(|c| c.is_ascii_lowercase() || c == '_');
+    let rest_ok = chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+    !(starts_ok && rest_ok) || RESERVED.contains(&identifier)
+}
+
+// Write an identifier for SQL. With DELIMIDENT set, names that need it are quoted the way the
+// run's dialect quotes them; without it Informix reads double quotes as strings, so the
+// identifier is left bare.
+pub fn format_identifier(identifier: &str, delimident: bool) -> String {
+    if delimident && needs_quoting(identifier) {
+        dialect::current().quote_identifier(identifier)
+    } else {
+        identifier.to_string()
+    }
+}
+
+// Position of `separator` outside double quotes
+fn find_outside_double_quotes(text: &str, separator: char) -> Option<usize> {
+    let mut in_double_quote = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_double_quote = !in_double_quote,
+            c if c == separator && !in_double_quote => return Some(i),
+            _ => {}
         }
     }
+    None
+}
+
+// Split on `separator` outside double quotes, keeping the quotes in each part
+fn split_outside_double_quotes(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(i) = find_outside_double_quotes(rest, separator) {
+        parts.push(rest[..i].to_string());
+        rest = &rest[i + separator.len_utf8()..];
+    }
+    parts.push(rest.to_string());
+    parts
+}
+
+// The pieces of an `UPDATE <table> SET <assignments> WHERE <condition>` statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateParts {
+    pub table: String,
+    pub set_clause: String,
+    pub where_clause: Option<String>,
+}
+
+// Find a keyword in a query, ignoring anything inside single or double quotes.
+// The keyword is matched case-insensitively and must be delimited by whitespace.
+pub fn find_keyword_outside_quotes(query: &str, keyword: &str) -> Option<usize> {
+    // ASCII uppercasing keeps byte offsets identical to the original query
+    let upper = query.to_ascii_uppercase();
+    let keyword = keyword.trim().to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
     
-    // For county fields, look for a column with values that match county code patterns (2-3 digits)
-    if field_name.contains("county") && batch.num_rows() > 0 {
-        for i in 0..num_cols {
-            let value = String::from_utf8_lossy(batch.at(i, 0).unwrap_or(&[])).to_string();
-            if (value.len() == 2 || value.len() == 3) && value.chars().all(|c| c.is_digit(10)) {
-                return i;
-            }
+    for (i, c) in upper.char_indices() {
+        match c {
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            _ => {}
+        }
+        
+        if in_single_quote || in_double_quote || !upper[i..].starts_with(&keyword) {
+            continue;
+        }
+        
+        let before_ok = i == 0 || bytes[i - 1].is_ascii_whitespace();
+        let after = i + keyword.len();
+        let after_ok = after == bytes.len() || bytes[after].is_ascii_whitespace();
+        
+        if before_ok && after_ok {
+            return Some(i);
         }
     }
     
-    // If we couldn't identify the column by pattern, return the fallback position
-    fallback
+    None
 }
 
-// Extract table name from SQL query
-pub fn extract_table_name(query: &str) -> String {
-    // Simple implementation to extract table name from SELECT query
-    // Example: "SELECT field1, field2 FROM table_name WHERE condition"
-    let query = query.trim().to_uppercase();
+// Split an UPDATE statement into table, SET assignments and WHERE condition
+pub fn parse_update_statement(query: &str) -> Option<UpdateParts> {
+    let query = query.trim().trim_end_matches(';').trim();
     
-    if let Some(from_pos) = query.find(" FROM ") {
-        let after_from = &query[from_pos + 6..];
-        if let Some(where_pos) = after_from.find(" WHERE ") {
-            return after_from[..where_pos].trim().to_string();
-        } else if let Some(limit_pos) = after_from.find(" LIMIT ") {
-            return after_from[..limit_pos].trim().to_string();
-        } else {
-            return after_from.trim().to_string();
-        }
+    if query.len() < 7 || !query[..6].eq_ignore_ascii_case("UPDATE") {
+        return None;
+    }
+    
+    let set_pos = find_keyword_outside_quotes(query, "SET")?;
+    let table = query[6..set_pos].trim().to_string();
+    let after_set = &query[set_pos + 3..];
+    
+    let (set_clause, where_clause) = match find_keyword_outside_quotes(after_set, "WHERE") {
+        Some(where_pos) => (
+            after_set[..where_pos].trim().to_string(),
+            Some(after_set[where_pos + 5..].trim().to_string()),
+        ),
+        None => (after_set.trim().to_string(), None),
+    };
+    
+    if table.is_empty() || set_clause.is_empty() {
+        return None;
     }
     
-    // Fallback to "table_name" if we can't extract it
-    "table_name".to_string()
+    Some(UpdateParts { table, set_clause, where_clause })
+}
+
+
+// Collapse runs of whitespace so formatting differences don't change a hash
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-// Helper function to find column index by name - more robust approach
-pub fn find_column_index(batch: &TextRowSet, column_name: &str) -> Option<usize> {
-    // Get the number of columns in the result set
-    let num_cols = batch.num_cols();
+// Deterministic hash identifying the change a query makes (table + key + SET payload).
+// Identical corrections generated in different runs produce the same hash.
+pub fn idempotency_hash(key: &str, query: &str) -> String {
+    let payload = match parse_update_statement(query) {
+        Some(parts) => format!(
+            "{}\n{}\n{}",
+            QualifiedName::parse(&parts.table)
+                .map(|name| name.normalized())
+                .unwrap_or_else(|| normalize_whitespace(&parts.table).to_lowercase()),
+            key,
+            normalize_whitespace(&parts.set_clause)
+        ),
+        None => format!("{}\n{}", key, normalize_whitespace(query)),
+    };
     
-    // We can't directly access column names, so we'll need an alternative approach
-    // First, check if we're looking for specific columns we know we need
-    if column_name == "zip" || column_name == "zip_code" {
-        // Try to identify the zip column by looking at a sample row
-        for i in 0..num_cols {
-            // Look at the first row (if available)
-            if batch.num_rows() > 0 {
-                let value = String::from_utf8_lossy(batch.at(i, 0).unwrap_or(&[])).to_string();
-                // Check if this looks like a zip code (5 digits, possibly followed by a dash and 4 digits)
-                if value.len() >= 5 && value.chars().take(5).all(|c| c.is_digit(10)) {
-                    return Some(i);
-                }
-            }
+    let digest = Sha256::digest(payload.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Split text on commas that are not inside quotes or parentheses
+pub fn split_top_level_commas(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    
+    for c in text.chars() {
+        match c {
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '(' if !in_single_quote && !in_double_quote => depth += 1,
+            ')' if !in_single_quote && !in_double_quote => depth -= 1,
+            ',' if depth == 0 && !in_single_quote && !in_double_quote => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            },
+            _ => {}
         }
-    } else if column_name == "county" || column_name == "county_code" {
-        // Try to identify the county column by looking at a sample row
-        for i in 0..num_cols {
-            // Look at the first row (if available)
-            if batch.num_rows() > 0 {
-                let value = String::from_utf8_lossy(batch.at(i, 0).unwrap_or(&[])).to_string();
-                // Check if this looks like a county code (2 or 3 digits)
-                if value.len() <= 3 && value.chars().all(|c| c.is_digit(10)) {
-                    return Some(i);
+        current.push(c);
+    }
+    
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    
+    parts
+}
+
+// Parse a SQL literal: quoted strings are unquoted, bare numbers returned as-is,
+// NULL becomes an empty string. Anything else (expressions) returns None.
+pub fn parse_sql_literal(text: &str) -> Option<String> {
+    let text = text.trim();
+    
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        return Some(text[1..text.len() - 1].replace("''", "'"));
+    }
+    
+    if text.eq_ignore_ascii_case("NULL") {
+        return Some(String::new());
+    }
+    
+    if !text.is_empty() && text.parse::<f64>().is_ok() {
+        return Some(text.to_string());
+    }
+    
+    None
+}
+
+// Parse the assignments of a SET clause into (column, literal value).
+// The value is None when the right-hand side is an expression rather than a literal.
+pub fn parse_set_assignments(set_clause: &str) -> Vec<(String, Option<String>)> {
+    split_top_level_commas(set_clause)
+        .iter()
+        .filter_map(|assignment| {
+            let eq_pos = assignment.find('=')?;
+            let column = assignment[..eq_pos].trim().to_string();
+            let value = parse_sql_literal(&assignment[eq_pos + 1..]);
+            Some((column, value))
+        })
+        .collect()
+}
+
+// The items of a SELECT statement's column list as written, in order
+pub fn select_list(query: &str) -> Option<Vec<String>> {
+    let statement = SelectStatement::parse(query).ok()?;
+    Some(statement.items.into_iter().map(|item| item.text).collect())
+}
+
+// Replace each `?` placeholder outside quotes with the matching value as a quoted literal
+pub fn inline_parameters(query: &str, values: &[String]) -> String {
+    let mut result = String::with_capacity(query.len());
+    let mut values = values.iter();
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    
+    for c in query.chars() {
+        match c {
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '?' if !in_single_quote && !in_double_quote => {
+                if let Some(value) = values.next() {
+                    result.push_str(&format!("'{}'", value.replace('\'', "''")));
+                    continue;
                 }
-            }
+            },
+            _ => {}
         }
-    } else if column_name == "key_field" {
-        // Assuming key_field is the first column
-        return Some(0);
+        result.push(c);
     }
     
-    // If we couldn't find a match through pattern recognition,
-    // we'll fall back to a more generic approach
-    None
-}
\ No newline at end of file
+    result
+}
+
+// Guard an UPDATE so it only applies while each changed column still holds the value
+// originally selected. `original_value` returns the selected value for a SET column, if known.
+// Returns None when the query isn't an UPDATE with a WHERE clause or no column could be guarded.
+pub fn add_concurrency_guard(query: &str, original_value: impl Fn(&str) -> Option<String>) -> Option<String> {
+    add_typed_concurrency_guard(query, original_value, |_, value| format!("'{}'", value.replace('\'', "''")))
+}
+
+// The same, with `literal` writing the SQL literal a column's value is compared against
+pub fn add_typed_concurrency_guard(
+    query: &str,
+    original_value: impl Fn(&str) -> Option<String>,
+    literal: impl Fn(&str, &str) -> String,
+) -> Option<String> {
+    let parts = parse_update_statement(query)?;
+    let where_clause = parts.where_clause.as_ref()?;
+    
+    let guards: Vec<String> = parse_set_assignments(&parts.set_clause)
+        .iter()
+        .filter_map(|(column, _)| {
+            let value = original_value(column)?;
+            Some(if value.is_empty() {
+                format!("({} IS NULL OR {} = '')", column, column)
+            } else {
+                format!("{} = {}", column, literal(column, &value))
+            })
+        })
+        .collect();
+    
+    if guards.is_empty() {
+        return None;
+    }
+    
+    Some(format!(
+        "UPDATE {} SET {} WHERE ({}) AND {}",
+        parts.table, parts.set_clause, where_clause, guards.join(" AND ")
+    ))
+}
+
+// Turn a selection query into one that counts the rows it would return.
+// ORDER BY is dropped; DISTINCT and GROUP BY selections are counted as a derived table.
+pub fn count_query(query: &str) -> Option<String> {
+    let query = query.trim().trim_end_matches(';').trim();
+    let query = match find_keyword_outside_quotes(query, "ORDER BY") {
+        Some(order_pos) => query[..order_pos].trim_end(),
+        None => query,
+    };
+    
+    let statement = SelectStatement::parse(query).ok()?;
+    if statement.distinct || find_keyword_outside_quotes(query, "GROUP BY").is_some() {
+        return Some(format!("SELECT COUNT(*) FROM ({})", query));
+    }
+    
+    Some(format!("SELECT COUNT(*) {}", &query[statement.from_position..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn qualified_names_split_outside_quotes() {
+        assert_eq!(QualifiedName::parse("stores@srv:Informix.Customer"), Some(QualifiedName {
+            database: Some("stores@srv".to_string()),
+            owner: Some("informix".to_string()),
+            name: "customer".to_string(),
+        }));
+        assert_eq!(QualifiedName::parse("\"Own.er\".\"Cust:omer\""), Some(QualifiedName {
+            database: None,
+            owner: Some("Own.er".to_string()),
+            name: "Cust:omer".to_string(),
+        }));
+        assert_eq!(QualifiedName::parse("a.b.c"), None);
+        assert_eq!(QualifiedName::parse("owner."), None);
+    }
+    
+    #[test]
+    fn identifiers_normalize_like_informix_stores_them() {
+        assert_eq!(normalize_identifier("Zip_Code"), "zip_code");
+        assert_eq!(normalize_identifier(" \"Zip_Code\" "), "Zip_Code");
+        assert_eq!(normalize_identifier("\"say \"\"hi\"\"\""), "say \"hi\"");
+        
+        assert_eq!(column_label("c.County"), "county");
+        assert_eq!(column_label("zip AS z"), "z");
+        assert_eq!(column_label("t.\"Tax Status\""), "Tax Status");
+    }
+    
+    #[test]
+    fn only_names_outside_the_plain_form_need_quoting() {
+        assert!(!needs_quoting("zip_code"));
+        assert!(!needs_quoting("_tmp$1"));
+        assert!(needs_quoting("ZipCode"));
+        assert!(needs_quoting("1st"));
+        assert!(needs_quoting("tax status"));
+        assert!(needs_quoting("date"));
+        assert!(needs_quoting(""));
+    }
+    
+    #[test]
+    fn identifiers_are_only_delimited_with_delimident() {
+        assert_eq!(format_identifier("zip_code", true), "zip_code");
+        assert_eq!(format_identifier("Zip Code", true), "\"Zip Code\"");
+        assert_eq!(format_identifier("say \"hi\"", true), "\"say \"\"hi\"\"\"");
+        assert_eq!(format_identifier("Zip Code", false), "Zip Code");
+        
+        let name = QualifiedName::parse("db:\"Owner\".customer").unwrap();
+        assert_eq!(name.to_sql(true), "db:\"Owner\".customer");
+        assert_eq!(name.to_sql(false), "db:Owner.customer");
+        assert_eq!(name.normalized(), "Owner.customer");
+    }
+}
@@ -0,0 +1,480 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::db::sql_helpers::{column_label, normalize_identifier};
+
+// Words that end a table reference instead of naming its alias
+const NOT_ALIASES: &[&str] = &[
+    "WHERE", "GROUP", "ORDER", "HAVING", "UNION", "INNER", "LEFT", "RIGHT", "FULL", "CROSS", "JOIN",
+    "OUTER", "INTO", "FOR", "LIMIT", "ON",
+];
+
+// Words that can end a select item without being its alias (`CASE ... END`, `... IS NULL`)
+const NOT_COLUMN_ALIASES: &[&str] = &["END", "NULL", "TRUE", "FALSE"];
+
+// A problem found while parsing a statement, with the character it was found at
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlParseError {
+    pub message: String,
+    // Byte offset into the statement
+    pub position: usize,
+    pub statement: String,
+}
+
+impl SqlParseError {
+    fn new(message: impl Into<String>, position: usize, statement: &str) -> Self {
+        SqlParseError { message: message.into(), position, statement: statement.to_string() }
+    }
+    
+    // The statement around the error position, with `<here>` marking it
+    fn excerpt(&self) -> String {
+        let position = self.position.min(self.statement.len());
+        let mut start = position.saturating_sub(30);
+        while !self.statement.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (position + 30).min(self.statement.len());
+        while !self.statement.is_char_boundary(end) {
+            end += 1;
+        }
+        let collapse = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+        format!(
+            "{}{} <here> {}{}",
+            if start > 0 { "..." } else { "" },
+            collapse(&self.statement[start..position]),
+            collapse(&self.statement[position..end]),
+            if end < self.statement.len() { "..." } else { "" }
+        )
+    }
+}
+
+impl fmt::Display for SqlParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = self.statement[..self.position.min(self.statement.len())].chars().count() + 1;
+        write!(f, "{} (character {}: {})", self.message, column, self.excerpt())
+    }
+}
+
+impl Error for SqlParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    // Keyword, unquoted identifier or number
+    Word,
+    // "..." - a delimited identifier, or a string when DELIMIDENT is off
+    Quoted,
+    // '...'
+    String,
+    Symbol(char),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn is_word(&self, sql: &str, word: &str) -> bool {
+        self.kind == TokenKind::Word && sql[self.start..self.end].eq_ignore_ascii_case(word)
+    }
+    
+    fn is_identifier(&self) -> bool {
+        matches!(self.kind, TokenKind::Word | TokenKind::Quoted)
+    }
+    
+    fn text<'a>(&self, sql: &'a str) -> &'a str {
+        &sql[self.start..self.end]
+    }
+}
+
+// Split a statement into tokens, dropping whitespace and `--`, `/* */` and `{ }` comments
+fn tokenize(sql: &str) -> Result<Vec<Token>, SqlParseError> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        match c {
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            },
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map(|end| i + 2 + end + 2)
+                    .ok_or_else(|| SqlParseError::new("unterminated /* comment", start, sql))?;
+                continue;
+            },
+            b'{' => {
+                i = sql[i..].find('}').map(|end| i + end + 1)
+                    .ok_or_else(|| SqlParseError::new("unterminated { comment", start, sql))?;
+                continue;
+            },
+            b'\'' | b'"' => {
+                // A doubled quote inside the literal stands for the quote itself
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => {
+                            let what = if c == b'\'' { "string literal" } else { "quoted identifier" };
+                            return Err(SqlParseError::new(format!("unterminated {}", what), start, sql));
+                        },
+                        Some(&q) if q == c && bytes.get(i + 1) == Some(&c) => i += 2,
+                        Some(&q) if q == c => {
+                            i += 1;
+                            break;
+                        },
+                        Some(_) => i += 1,
+                    }
+                }
+                let kind = if c == b'\'' { TokenKind::String } else { TokenKind::Quoted };
+                tokens.push(Token { kind, start, end: i });
+            },
+            c if c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80 => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'$' || bytes[i] >= 0x80) {
+                    i += 1;
+                }
+                tokens.push(Token { kind: TokenKind::Word, start, end: i });
+            },
+            c => {
+                i += 1;
+                tokens.push(Token { kind: TokenKind::Symbol(c as char), start, end: i });
+            },
+        }
+    }
+    
+    Ok(tokens)
+}
+
+// One entry of a SELECT list
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectItem {
+    // The item as written, alias included
+    pub text: String,
+    // The item without its alias
+    pub expression: String,
+    pub alias: Option<String>,
+    // Byte offset of the item in the statement
+    pub position: usize,
+}
+
+impl SelectItem {
+    // `*` or `t.*`
+    pub fn is_wildcard(&self) -> bool {
+        self.expression == "*" || self.expression.ends_with(".*")
+    }
+    
+    // The normalized name the column is returned under
+    pub fn label(&self) -> String {
+        match &self.alias {
+            Some(alias) => normalize_identifier(alias),
+            None => column_label(&self.expression),
+        }
+    }
+}
+
+// The first item of a FROM clause
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableReference {
+    // The table as written, owner and database prefix included; None for a derived table
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    pub position: usize,
+}
+
+// The parts of a SELECT statement the batch processor relies on
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectStatement {
+    // DISTINCT or UNIQUE
+    pub distinct: bool,
+    pub items: Vec<SelectItem>,
+    pub table: TableReference,
+    // Byte offsets of the select list and of the FROM keyword
+    pub projection_start: usize,
+    pub from_position: usize,
+}
+
+impl SelectStatement {
+    // Parse a SELECT statement up to its first table reference. Subqueries, function calls and
+    // CASE expressions in the select list are kept whole; whatever follows the first table
+    // reference (joins, WHERE, ORDER BY) isn't examined.
+    pub fn parse(sql: &str) -> Result<Self, SqlParseError> {
+        let tokens = tokenize(sql)?;
+        let mut i = 0;
+        
+        match tokens.first() {
+            Some(token) if token.is_word(sql, "SELECT") => i += 1,
+            Some(token) => return Err(SqlParseError::new(
+                format!("expected SELECT, found '{}'", token.text(sql)), token.start, sql)),
+            None => return Err(SqlParseError::new("empty statement, expected SELECT", 0, sql)),
+        }
+        
+        // Informix SKIP/FIRST/LIMIT and SQL Server TOP take a count; DISTINCT/UNIQUE/ALL don't
+        let mut distinct = false;
+        while let Some(token) = tokens.get(i) {
+            if ["SKIP", "FIRST", "LIMIT", "TOP"].iter().any(|word| token.is_word(sql, word)) {
+                i += 2;
+            } else if token.is_word(sql, "DISTINCT") || token.is_word(sql, "UNIQUE") {
+                distinct = true;
+                i += 1;
+            } else if token.is_word(sql, "ALL") {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+        let projection_start = tokens.get(i).map_or(sql.len(), |token| token.start);
+        
+        // Split the select list on top-level commas until the top-level FROM
+        let mut items = Vec::new();
+        let mut item_start = i;
+        let mut depth = 0usize;
+        let from_index = loop {
+            let token = match tokens.get(i) {
+                Some(token) => token,
+                None if depth > 0 => return Err(SqlParseError::new("unclosed '(' in the select list", sql.len(), sql)),
+                None => return Err(SqlParseError::new("no FROM clause after the select list", sql.len(), sql)),
+            };
+            match token.kind {
+                TokenKind::Symbol('(') => depth += 1,
+                TokenKind::Symbol(')') if depth == 0 => {
+                    return Err(SqlParseError::new("unmatched ')' in the select list", token.start, sql));
+                },
+                TokenKind::Symbol(')') => depth -= 1,
+                TokenKind::Symbol(',') if depth == 0 => {
+                    items.push(select_item(sql, &tokens[item_start..i], token.start)?);
+                    item_start = i + 1;
+                },
+                TokenKind::Word if depth == 0 && token.is_word(sql, "FROM") => {
+                    items.push(select_item(sql, &tokens[item_start..i], token.start)?);
+                    break i;
+                },
+                _ => {}
+            }
+            i += 1;
+        };
+        
+        let from_position = tokens[from_index].start;
+        let table = table_reference(sql, &tokens, from_index + 1)?;
+        
+        Ok(SelectStatement { distinct, items, table, projection_start, from_position })
+    }
+}
+
+// Build a select item from its tokens; `end` is where the item stops (the comma or FROM)
+fn select_item(sql: &str, tokens: &[Token], end: usize) -> Result<SelectItem, SqlParseError> {
+    let (first, last) = match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(SqlParseError::new("empty item in the select list", end, sql)),
+    };
+    
+    // `expr AS alias`, or `expr alias` when the alias directly follows a complete operand
+    let n = tokens.len();
+    let alias_at = if n >= 3 && tokens[n - 2].is_word(sql, "AS") && last.is_identifier() {
+        Some(n - 2)
+    } else if n >= 2 && last.is_identifier()
+        && matches!(tokens[n - 2].kind, TokenKind::Word | TokenKind::Quoted | TokenKind::String | TokenKind::Symbol(')'))
+        && !NOT_COLUMN_ALIASES.iter().any(|word| last.is_word(sql, word)) {
+        Some(n - 1)
+    } else {
+        None
+    };
+    
+    let (expression, alias) = match alias_at {
+        Some(at) if at > 0 => (
+            sql[first.start..tokens[at - 1].end].to_string(),
+            Some(last.text(sql).to_string()),
+        ),
+        Some(_) => return Err(SqlParseError::new("select item has an alias but no expression", first.start, sql)),
+        None => (sql[first.start..last.end].to_string(), None),
+    };
+    
+    Ok(SelectItem {
+        text: sql[first.start..last.end].to_string(),
+        expression,
+        alias,
+        position: first.start,
+    })
+}
+
+// The table (or derived table) reference starting at `tokens[i]`
+fn table_reference(sql: &str, tokens: &[Token], mut i: usize) -> Result<TableReference, SqlParseError> {
+    let first = match tokens.get(i) {
+        Some(token) => *token,
+        None => return Err(SqlParseError::new("expected a table name after FROM", sql.len(), sql)),
+    };
+    
+    let name = match first.kind {
+        TokenKind::Symbol('(') => {
+            // A derived table: skip to the matching parenthesis
+            let mut depth = 0usize;
+            loop {
+                match tokens.get(i).map(|token| token.kind) {
+                    Some(TokenKind::Symbol('(')) => depth += 1,
+                    Some(TokenKind::Symbol(')')) => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    },
+                    Some(_) => {},
+                    None => return Err(SqlParseError::new("unclosed '(' in the FROM clause", first.start, sql)),
+                }
+                i += 1;
+            }
+            i += 1;
+            None
+        },
+        _ if first.is_identifier() && !NOT_ALIASES.iter().any(|word| first.is_word(sql, word)) => {
+            // Parts of `db@server:owner.table` are joined by punctuation with no space between
+            let mut end = first.end;
+            i += 1;
+            while let (Some(separator), Some(part)) = (tokens.get(i), tokens.get(i + 1)) {
+                let joins = matches!(separator.kind, TokenKind::Symbol('.') | TokenKind::Symbol(':') | TokenKind::Symbol('@'));
+                if !joins || separator.start != end || part.start != separator.end || !part.is_identifier() {
+                    break;
+                }
+                end = part.end;
+                i += 2;
+            }
+            Some(sql[first.start..end].to_string())
+        },
+        _ => return Err(SqlParseError::new(
+            format!("expected a table name after FROM, found '{}'", first.text(sql)), first.start, sql)),
+    };
+    
+    let alias = match tokens.get(i) {
+        Some(token) if token.is_word(sql, "AS") => match tokens.get(i + 1) {
+            Some(alias) if alias.is_identifier() => Some(alias.text(sql).to_string()),
+            Some(other) => return Err(SqlParseError::new("expected an alias after AS", other.start, sql)),
+            None => return Err(SqlParseError::new("expected an alias after AS", sql.len(), sql)),
+        },
+        Some(token) if token.is_identifier() && !NOT_ALIASES.iter().any(|word| token.is_word(sql, word)) => {
+            Some(token.text(sql).to_string())
+        },
+        _ => None,
+    };
+    
+    Ok(TableReference { name, alias, position: first.start })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn texts(sql: &str) -> Vec<&str> {
+        tokenize(sql).unwrap().iter().map(|token| token.text(sql)).collect()
+    }
+    
+    #[test]
+    fn quoted_semicolons_and_keywords_stay_in_their_literal() {
+        let sql = "SELECT 'a;b FROM c' FROM t;";
+        assert_eq!(texts(sql), vec!["SELECT", "'a;b FROM c'", "FROM", "t", ";"]);
+        
+        let statement = SelectStatement::parse(sql).unwrap();
+        assert_eq!(statement.items.len(), 1);
+        assert_eq!(statement.table.name.as_deref(), Some("t"));
+    }
+    
+    #[test]
+    fn doubled_quotes_are_escapes() {
+        assert_eq!(texts("'it''s'"), vec!["'it''s'"]);
+        assert_eq!(texts("\"say \"\"hi\"\"\" x"), vec!["\"say \"\"hi\"\"\"", "x"]);
+        assert_eq!(texts("'' ''"), vec!["''", "''"]);
+    }
+    
+    #[test]
+    fn comments_are_dropped() {
+        let sql = "SELECT a, -- b, c FROM x\n b /* , c FROM y */ { FROM z } FROM t";
+        assert_eq!(texts(sql), vec!["SELECT", "a", ",", "b", "FROM", "t"]);
+        
+        // Comment markers inside a literal are text
+        assert_eq!(texts("'--' '/*' '{'"), vec!["'--'", "'/*'", "'{'"]);
+    }
+    
+    #[test]
+    fn unterminated_literals_and_comments_report_where_they_start() {
+        let error = tokenize("SELECT 'abc FROM t").unwrap_err();
+        assert_eq!((error.message.as_str(), error.position), ("unterminated string literal", 7));
+        
+        let error = tokenize("SELECT \"abc FROM t").unwrap_err();
+        assert_eq!(error.message, "unterminated quoted identifier");
+        
+        let error = tokenize("SELECT a /* FROM t").unwrap_err();
+        assert_eq!((error.message.as_str(), error.position), ("unterminated /* comment", 9));
+        
+        let error = tokenize("SELECT a { FROM t").unwrap_err();
+        assert_eq!(error.message, "unterminated { comment");
+    }
+    
+    #[test]
+    fn select_items_keep_nested_commas_and_find_aliases() {
+        let sql = "SELECT FIRST 10 DISTINCT c.zip AS z, NVL(county, 'x, y') county_name, \
+                   CASE WHEN a = 1 THEN 'y' END, (SELECT MAX(b) FROM u, v) \"Max B\", price * 2 FROM t";
+        let statement = SelectStatement::parse(sql).unwrap();
+        
+        assert!(statement.distinct);
+        let summary: Vec<(&str, Option<&str>)> = statement.items.iter()
+            .map(|item| (item.expression.as_str(), item.alias.as_deref()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("c.zip", Some("z")),
+            ("NVL(county, 'x, y')", Some("county_name")),
+            ("CASE WHEN a = 1 THEN 'y' END", None),
+            ("(SELECT MAX(b) FROM u, v)", Some("\"Max B\"")),
+            ("price * 2", None),
+        ]);
+        assert_eq!(statement.items[3].label(), "Max B");
+        assert_eq!(&sql[statement.from_position..], "FROM t");
+    }
+    
+    #[test]
+    fn table_references_keep_qualifiers_and_aliases() {
+        let table = SelectStatement::parse("SELECT a FROM stores@srv:informix.customer c WHERE a = 1").unwrap().table;
+        assert_eq!(table.name.as_deref(), Some("stores@srv:informix.customer"));
+        assert_eq!(table.alias.as_deref(), Some("c"));
+        
+        let table = SelectStatement::parse("SELECT a FROM \"Owner\".\"Cust\" AS x").unwrap().table;
+        assert_eq!(table.name.as_deref(), Some("\"Owner\".\"Cust\""));
+        assert_eq!(table.alias.as_deref(), Some("x"));
+        
+        let table = SelectStatement::parse("SELECT a FROM customer WHERE a = 1").unwrap().table;
+        assert_eq!(table.alias, None);
+        
+        let table = SelectStatement::parse("SELECT a FROM (SELECT a FROM t) d").unwrap().table;
+        assert_eq!(table.name, None);
+        assert_eq!(table.alias.as_deref(), Some("d"));
+    }
+    
+    #[test]
+    fn malformed_statements_are_errors_not_panics() {
+        let cases = [
+            ("", "empty statement, expected SELECT"),
+            ("UPDATE t SET a = 1", "expected SELECT, found 'UPDATE'"),
+            ("SELECT a, b", "no FROM clause after the select list"),
+            ("SELECT (a FROM t", "unclosed '(' in the select list"),
+            ("SELECT a) FROM t", "unmatched ')' in the select list"),
+            ("SELECT a,, b FROM t", "empty item in the select list"),
+            ("SELECT a FROM", "expected a table name after FROM"),
+            ("SELECT a FROM t AS", "expected an alias after AS"),
+        ];
+        for (sql, message) in cases {
+            assert_eq!(SelectStatement::parse(sql).unwrap_err().message, message, "{}", sql);
+        }
+    }
+    
+    #[test]
+    fn errors_show_where_they_are() {
+        let error = SelectStatement::parse("SELECT a) FROM t").unwrap_err();
+        assert_eq!(error.to_string(), "unmatched ')' in the select list (character 9: SELECT a <here> ) FROM t)");
+    }
+}
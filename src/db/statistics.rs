@@ -0,0 +1,66 @@
+use odbc_api::Connection;
+use indicatif::ProgressBar;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::time::Instant;
+
+use crate::config::AppConfig;
+use crate::files::manifest::{RunManifest, StatisticsUpdate};
+use crate::ui;
+use crate::utils::{masking, run_id, time};
+
+// Refresh optimizer statistics for every table a run changed, once the run modified more than
+// `update_statistics_after_rows` rows. Each update's duration (or error) is recorded in the
+// run manifest; a failed update is reported but doesn't fail the run.
+pub fn update_statistics_after_run(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    tables: &BTreeSet<String>,
+    rows_modified: u64,
+    progress_bar: &ProgressBar,
+) -> Result<(), Box<dyn Error>> {
+    if config.update_statistics_after_rows == 0 || rows_modified <= config.update_statistics_after_rows || tables.is_empty() {
+        return Ok(());
+    }
+    
+    let mut manifest = RunManifest::load(results_dir).unwrap_or_default();
+    if manifest.run_id.is_empty() {
+        manifest.run_id = run_id::current().to_string();
+        manifest.created = time::now_rfc3339();
+    }
+    
+    for table in tables {
+        let statement = config.update_statistics_statement.replace("{{table}}", table);
+        let message = format!("{} rows modified; running {}", rows_modified, statement);
+        ui::progress::print_with_progress(progress_bar, &message);
+        log::info!("{}", message);
+        
+        let started = time::now_rfc3339();
+        let timer = Instant::now();
+        let error = match conn.execute(&statement, ()) {
+            Ok(_) => None,
+            Err(e) => Some(masking::mask(&e.to_string())),
+        };
+        let duration_seconds = timer.elapsed().as_secs_f64();
+        
+        match &error {
+            None => log::info!("Statistics updated for {} in {:.1}s", table, duration_seconds),
+            Some(e) => {
+                ui::progress::print_with_progress(progress_bar, &format!("\x1b[31mStatistics update for {} failed: {}\x1b[0m", table, e));
+                log::error!("Statistics update for {} failed after {:.1}s: {}", table, duration_seconds, e);
+            }
+        }
+        
+        manifest.statistics_updates.push(StatisticsUpdate {
+            table: table.clone(),
+            statement,
+            started,
+            duration_seconds,
+            rows_modified,
+            error,
+        });
+    }
+    
+    manifest.save(results_dir)
+}
@@ -0,0 +1,334 @@
+use odbc_api::{parameter::VarCharBox, Connection};
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::config::AppConfig;
+use crate::db::connection::{apply_session_settings, create_connection, execute_with_row_count};
+use crate::db::error_classes::{ErrorStatistics, OdbcDiagnostics};
+use crate::db::fetch::for_each_row;
+use crate::db::keys::{key_columns, row_key};
+use crate::db::lookups::LookupTables;
+use crate::db::preflight::run_preflight_checks;
+use crate::db::protection::ProtectedObjects;
+use crate::db::query_execution::{finish_transaction, zero_rows_error};
+use crate::db::query_generation::{build_query_record, check_selection_shape, confirm_selection_volume, GenerationCache, GenerationOptions};
+use crate::db::query_types::{QueryRecord, QueryStatus, ZeroRowsPolicy};
+use crate::db::result_columns::ResultColumns;
+use crate::db::success_criteria::RunMetrics;
+use crate::files::events::append_event;
+use crate::files::json_handler::save_error_file;
+use crate::files::ledger::{AppliedLedger, LedgerEntry};
+use crate::files::manifest::{save_selection_keys, RunManifest};
+use crate::files::processed::ProcessedRecords;
+use crate::ui;
+use crate::utils::{encoding, masking, operator, run_id, time, version};
+
+// Generated queries waiting for the executor; generation pauses when the executor falls behind
+const CHANNEL_CAPACITY: usize = 1000;
+
+// Outcome of a streaming cycle
+#[derive(Debug, Clone, Default)]
+pub struct StreamingSummary {
+    pub matched: usize,
+    pub generated: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub conflicts: usize,
+    // Changes an earlier run already applied
+    pub already_applied: usize,
+    // Rows a lookup had no value for, and statements touching protected objects
+    pub rejected: usize,
+    // Failures from unique, not-null and referential constraints
+    pub constraint_violations: usize,
+    // Rows changed by successful statements; a statement whose driver reports no count counts once
+    pub rows_affected: u64,
+    // Unguarded statements that affected no rows, whatever zero_rows_policy made of them
+    pub no_rows: usize,
+}
+
+impl StreamingSummary {
+    // The counts success_criteria judge
+    pub fn metrics(&self) -> RunMetrics {
+        RunMetrics {
+            succeeded: self.succeeded,
+            failed: self.failed,
+            conflicts: self.conflicts,
+            skipped: self.already_applied,
+            quarantined: 0,
+            constraint_violations: self.constraint_violations,
+            rows_affected: self.rows_affected,
+        }
+    }
+}
+
+// A generated query and the long values it binds instead of storing them in parameter files
+type Generated = (QueryRecord, Vec<String>);
+
+// Generate and execute in one pass: a generation thread with its own connection renders each
+// selected row and hands the record over a bounded channel to the executor on this thread,
+// so no query files are written or read. Outcomes still go to the log, the status event log,
+// errors.json, the applied ledger and the run manifest.
+pub fn stream_queries(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    processed_records: &mut ProcessedRecords,
+    options: &GenerationOptions,
+    progress_bar: &ProgressBar,
+) -> Result<StreamingSummary, Box<dyn Error>> {
+    run_preflight_checks(conn, config, "generate", results_dir, progress_bar)?;
+    run_preflight_checks(conn, config, "execute", results_dir, progress_bar)?;
+    confirm_selection_volume(conn, config, options, progress_bar)?;
+    
+    let columns = ResultColumns::resolve(conn, config, &config.selection_query, progress_bar)?;
+    check_selection_shape(config, &columns)?;
+    
+    let protected = ProtectedObjects::from_config(config);
+    let mut ledger = AppliedLedger::load(&config.data_path);
+    let mut error_statistics = ErrorStatistics::default();
+    let mut summary = StreamingSummary::default();
+    let manual_commit = !config.execution_autocommit;
+    let zero_rows_policy = ZeroRowsPolicy::from_name(&config.zero_rows_policy)?;
+    if config.commit_batch_size > 0 {
+        log::warn!("commit_batch_size is not used when streaming; each statement is committed on its own");
+    }
+    RunManifest::record_execution(results_dir, None)?;
+    
+    ui::progress::print_with_progress(progress_bar, "Streaming generated queries straight into execution...");
+    let (sender, receiver) = mpsc::sync_channel::<Generated>(CHANNEL_CAPACITY);
+    
+    let generation = thread::scope(|scope| {
+        let generator = scope.spawn(|| generate_into(config, &columns, options, progress_bar, sender));
+        
+        for (mut query_record, long_values) in receiver {
+            summary.generated += 1;
+            let outcome = execute_streamed(
+                conn, config, results_dir, &protected, manual_commit, zero_rows_policy, &mut query_record, &long_values,
+                processed_records, &mut ledger, &mut error_statistics, progress_bar,
+            );
+            match outcome {
+                Ok(StreamedOutcome::Succeeded(rows_affected)) => {
+                    summary.succeeded += 1;
+                    summary.rows_affected += rows_affected;
+                    if rows_affected == 0 {
+                        summary.no_rows += 1;
+                    }
+                    ui::progress::update_rows_affected(progress_bar, summary.rows_affected);
+                },
+                Ok(StreamedOutcome::NoRowsFailed) => {
+                    summary.failed += 1;
+                    summary.no_rows += 1;
+                },
+                Ok(StreamedOutcome::Failed) => summary.failed += 1,
+                Ok(StreamedOutcome::Conflict) => summary.conflicts += 1,
+                Ok(StreamedOutcome::AlreadyApplied) => summary.already_applied += 1,
+                Ok(StreamedOutcome::Rejected) => summary.rejected += 1,
+                Err(e) => {
+                    // Dropping the receiver stops the generator at its next send
+                    log::error!("Streaming stopped at key {}: {}", query_record.key, e);
+                    return Err(e.to_string());
+                }
+            }
+            progress_bar.set_position(summary.generated as u64);
+        }
+        generator.join().map_err(|_| "the generation thread panicked".to_string())?
+    });
+    let (matched_keys, lookup_rejected) = generation?;
+    summary.matched = matched_keys.len();
+    summary.rejected += lookup_rejected;
+    summary.constraint_violations = error_statistics.constraint_violations();
+    
+    save_selection_keys(results_dir, &matched_keys)?;
+    let mut manifest = RunManifest::load(results_dir).unwrap_or_default();
+    manifest.selection_query = config.selection_query.clone();
+    manifest.matched_keys = summary.matched;
+    manifest.generated = summary.generated;
+    manifest.delta_from = options.delta_from.clone();
+    manifest.ticket = Some(config.ticket.trim().to_string()).filter(|ticket| !ticket.is_empty());
+    manifest.generated_by = Some(operator::current().clone());
+    manifest.tool_version = Some(version::build());
+    manifest.save(results_dir)?;
+    
+    if !error_statistics.is_empty() {
+        ui::progress::print_with_progress(progress_bar, "Errors by class:");
+        for line in error_statistics.report() {
+            ui::progress::print_with_progress(progress_bar, &format!("  {}", line));
+            log::warn!("Error class {}", line);
+        }
+    }
+    
+    Ok(summary)
+}
+
+// Run the selection on a separate connection and send every rendered record; returns the
+// keys the selection matched and how many rows were rejected for missing lookup values
+fn generate_into(
+    config: &AppConfig,
+    columns: &ResultColumns,
+    options: &GenerationOptions,
+    progress_bar: &ProgressBar,
+    sender: mpsc::SyncSender<Generated>,
+) -> Result<(Vec<String>, usize), String> {
+    let run = || -> Result<(Vec<String>, usize), Box<dyn Error>> {
+        let conn = create_connection(config)?;
+        apply_session_settings(&conn, config, "Generation", config.generation_autocommit, &config.generation_isolation)?;
+        let lookups = LookupTables::load(&conn, config, progress_bar)?;
+        let mut cache = GenerationCache::default();
+        let key_count = key_columns(config).len();
+        let mut matched_keys = Vec::new();
+        let mut rejected = 0;
+        
+        for_each_row(&conn, &config.selection_query, config, progress_bar, |row| {
+            let key = row_key(row, key_count);
+            matched_keys.push(key.clone());
+            if options.previous_keys.as_ref().is_some_and(|previous| previous.contains(&key)) {
+                return Ok(());
+            }
+            
+            match build_query_record(config, columns, &lookups, &mut cache, row) {
+                Ok(generated) => sender.send(generated).map_err(|_| "execution stopped")?,
+                Err(reason) => {
+                    log::warn!("Skipping key {}: {}", key, reason);
+                    rejected += 1;
+                },
+            }
+            Ok(())
+        })?;
+        cache.log_metrics();
+        Ok((matched_keys, rejected))
+    };
+    run().map_err(|e| e.to_string())
+}
+
+// What happened to one streamed record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamedOutcome {
+    // With the rows the statement changed
+    Succeeded(u64),
+    Failed,
+    // Affected no rows under zero_rows_policy "fail"
+    NoRowsFailed,
+    Conflict,
+    AlreadyApplied,
+    Rejected,
+}
+
+// Execute one streamed record the way execute does a query file, recording its outcome
+// everywhere except a query file
+#[allow(clippy::too_many_arguments)]
+fn execute_streamed(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    protected: &ProtectedObjects,
+    manual_commit: bool,
+    zero_rows_policy: ZeroRowsPolicy,
+    query_record: &mut QueryRecord,
+    long_values: &[String],
+    processed_records: &mut ProcessedRecords,
+    ledger: &mut AppliedLedger,
+    error_statistics: &mut ErrorStatistics,
+    progress_bar: &ProgressBar,
+) -> Result<StreamedOutcome, Box<dyn Error>> {
+    let results_path = Path::new(results_dir);
+    let key = query_record.key.clone();
+    append_event(results_path, &key, None, QueryStatus::Pending)?;
+    
+    if let Some(reason) = protected.record_violation(query_record) {
+        log::error!("Protected object: key {}: {}", key, reason);
+        append_event(results_path, &key, Some(QueryStatus::Pending), QueryStatus::Skipped)?;
+        return Ok(StreamedOutcome::Rejected);
+    }
+    
+    let change_hash = query_record.change_hash();
+    if ledger.lookup(&change_hash).is_some() || processed_records.has_applied_hash(&change_hash) {
+        log::info!("Skipping key {}: identical change already applied", key);
+        append_event(results_path, &key, Some(QueryStatus::Pending), QueryStatus::Skipped)?;
+        return Ok(StreamedOutcome::AlreadyApplied);
+    }
+    
+    ui::progress::update_message(progress_bar, format!("Executing query for key: {}", key));
+    let values = if long_values.is_empty() { query_record.parameters.clone() } else { long_values.to_vec() };
+    let parameters: Vec<VarCharBox> = values.iter()
+        .map(|value| VarCharBox::from_vec(encoding::encode(value)))
+        .collect();
+    
+    let sent = |sql: &str| if config.sql_provenance_comments { query_record.with_provenance_comment(sql) } else { sql.to_string() };
+    let statement = sent(&query_record.query);
+    let insert_fallback = query_record.insert_fallback.as_deref().map(sent);
+    
+    let execution = config.execution_retry.run(
+        &format!("Query for key {}", key),
+        || {
+            let mut execution = if parameters.is_empty() {
+                execute_with_row_count(conn, &statement, ())
+            } else {
+                execute_with_row_count(conn, &statement, parameters.as_slice())
+            };
+            if let (Ok(Some(0)), Some(insert)) = (&execution, &insert_fallback) {
+                execution = execute_with_row_count(conn, insert, ());
+            }
+            if manual_commit {
+                finish_transaction(conn, execution)
+            } else {
+                execution
+            }
+        },
+        |err| {
+            let diagnostics = OdbcDiagnostics::from_odbc_error(err);
+            config.execution_retry.is_retryable(diagnostics.sqlstate.as_deref(), diagnostics.native_error, diagnostics.isam_error)
+        },
+    );
+    
+    let now = time::now_rfc3339();
+    let mut abort_reason: Option<String> = None;
+    let (status, outcome) = match execution {
+        Ok(Some(0)) if query_record.concurrency_guard => {
+            log::warn!("Concurrency conflict for key {}: row changed since selection, update not applied", key);
+            (QueryStatus::Conflict, StreamedOutcome::Conflict)
+        },
+        Ok(Some(0)) if zero_rows_policy == ZeroRowsPolicy::Fail => {
+            let error = "no rows affected (zero_rows_policy \"fail\")";
+            save_error_file(results_path.join("errors.json"), &zero_rows_error(&key, "", error, &now))?;
+            log::error!("Query execution failed for key {}{}: {}", key, query_record.ticket_suffix(), error);
+            (QueryStatus::Failed, StreamedOutcome::NoRowsFailed)
+        },
+        Ok(row_count) => {
+            processed_records.add_applied(key.clone(), now.clone(), change_hash.clone());
+            let entry = LedgerEntry {
+                hash: change_hash,
+                key: key.clone(),
+                results_dir: results_dir.to_string(),
+                run_id: run_id::current().to_string(),
+                timestamp: now.clone(),
+                ticket: query_record.ticket.clone(),
+                operator: Some(operator::current().to_string()),
+            };
+            if let Err(e) = ledger.record(entry) {
+                log::error!("Failed to record key {} in the applied ledger: {}", key, e);
+            }
+            if row_count == Some(0) && zero_rows_policy == ZeroRowsPolicy::Warn {
+                log::warn!("Query execution completed for key {}{} but no rows were affected", key, query_record.ticket_suffix());
+            } else {
+                log::info!("Query execution successful for key {}{} ({:?} rows affected)", key, query_record.ticket_suffix(), row_count);
+            }
+            (QueryStatus::Completed, StreamedOutcome::Succeeded(row_count.unwrap_or(1) as u64))
+        },
+        Err(err) => {
+            let diagnostics = OdbcDiagnostics::from_odbc_error(&err);
+            error_statistics.record(diagnostics.class(), &key, &masking::mask(&diagnostics.message));
+            save_error_file(results_path.join("errors.json"), &diagnostics.error_record(&key, "", &now))?;
+            log::error!("Query execution failed for key {}{}: {}", key, query_record.ticket_suffix(), masking::mask(&diagnostics.summary()));
+            abort_reason = diagnostics.abort_reason(&config.abort_sqlstates, &key);
+            (QueryStatus::Failed, StreamedOutcome::Failed)
+        },
+    };
+    append_event(results_path, &key, Some(QueryStatus::Pending), status)?;
+    if let Some(reason) = abort_reason {
+        return Err(reason.into());
+    }
+    Ok(outcome)
+}
@@ -0,0 +1,182 @@
+use regex::Regex;
+use std::error::Error;
+
+use crate::config::AppConfig;
+use crate::utils::notify;
+
+// Counts a finished execution is judged on
+#[derive(Debug, Clone, Default)]
+pub struct RunMetrics {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub conflicts: usize,
+    // Changes an earlier run already applied
+    pub skipped: usize,
+    pub quarantined: usize,
+    // Failures from unique, not-null and referential constraints
+    pub constraint_violations: usize,
+    pub rows_affected: u64,
+}
+
+impl RunMetrics {
+    // Statements sent to the database
+    pub fn executed(&self) -> usize {
+        self.succeeded + self.failed + self.conflicts
+    }
+    
+    // Value of a metric by the name criteria use; rates are percentages of the executed
+    // statements, and a run that executed none has failed none
+    fn value(&self, metric: &str) -> Option<f64> {
+        let executed = self.executed();
+        let rate = |count: usize| if executed == 0 { 0.0 } else { count as f64 * 100.0 / executed as f64 };
+        Some(match metric {
+            "executed" => executed as f64,
+            "succeeded" => self.succeeded as f64,
+            "failed" => self.failed as f64,
+            "conflicts" => self.conflicts as f64,
+            "skipped" => self.skipped as f64,
+            "quarantined" => self.quarantined as f64,
+            "constraint_violations" => self.constraint_violations as f64,
+            "rows_affected" => self.rows_affected as f64,
+            "success_rate" => if executed == 0 { 100.0 } else { rate(self.succeeded) },
+            "error_rate" => rate(self.failed),
+            "conflict_rate" => rate(self.conflicts),
+            _ => return None,
+        })
+    }
+}
+
+const METRICS: &[&str] = &[
+    "executed", "succeeded", "failed", "conflicts", "skipped", "quarantined",
+    "constraint_violations", "rows_affected", "success_rate", "error_rate", "conflict_rate",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    AtLeast,
+    AtMost,
+    Above,
+    Below,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn parse(operator: &str) -> Option<Self> {
+        match operator {
+            ">=" | "≥" => Some(Comparison::AtLeast),
+            "<=" | "≤" => Some(Comparison::AtMost),
+            ">" => Some(Comparison::Above),
+            "<" => Some(Comparison::Below),
+            "=" | "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            _ => None,
+        }
+    }
+    
+    fn holds(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::AtLeast => value >= threshold,
+            Comparison::AtMost => value <= threshold,
+            Comparison::Above => value > threshold,
+            Comparison::Below => value < threshold,
+            Comparison::Equal => (value - threshold).abs() < 1e-9,
+            Comparison::NotEqual => (value - threshold).abs() >= 1e-9,
+        }
+    }
+}
+
+// One `metric operator number` clause, kept as written for messages
+#[derive(Debug, Clone)]
+struct Criterion {
+    text: String,
+    metric: String,
+    comparison: Comparison,
+    threshold: f64,
+}
+
+// A job's success_criteria: clauses joined by `and`, all of which must hold, e.g.
+// "success_rate >= 99.5 and constraint_violations == 0"
+#[derive(Debug, Clone)]
+pub struct SuccessCriteria {
+    criteria: Vec<Criterion>,
+}
+
+impl SuccessCriteria {
+    // None when no criteria are configured
+    pub fn parse(text: &str) -> Result<Option<Self>, Box<dyn Error>> {
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        
+        let separator = Regex::new(r"(?i)\s+and\s+|&&|,")?;
+        let clause = Regex::new(r"^([A-Za-z_]+)\s*(>=|<=|==|!=|=|>|<|≥|≤)\s*(-?[0-9]+(?:\.[0-9]+)?)\s*%?$")?;
+        let mut criteria = Vec::new();
+        for part in separator.split(text.trim()) {
+            let part = part.trim();
+            let captures = clause.captures(part).ok_or_else(|| format!(
+                "success_criteria: can't read '{}' (expected e.g. success_rate >= 99.5)", part
+            ))?;
+            let metric = captures[1].to_lowercase();
+            if !METRICS.contains(&metric.as_str()) {
+                return Err(format!("success_criteria: unknown metric '{}' (known: {})", &captures[1], METRICS.join(", ")).into());
+            }
+            criteria.push(Criterion {
+                text: part.to_string(),
+                metric,
+                comparison: Comparison::parse(&captures[2]).ok_or("success_criteria: unknown comparison")?,
+                threshold: captures[3].parse()?,
+            });
+        }
+        Ok(Some(SuccessCriteria { criteria }))
+    }
+    
+    // The clauses that don't hold, each with the value the run reached
+    pub fn unmet(&self, metrics: &RunMetrics) -> Vec<String> {
+        self.criteria.iter()
+            .filter_map(|criterion| {
+                let value = metrics.value(&criterion.metric).unwrap_or_default();
+                (!criterion.comparison.holds(value, criterion.threshold))
+                    .then(|| format!("{} (was {})", criterion.text, format_value(value)))
+            })
+            .collect()
+    }
+}
+
+// Judge a finished execution by success_criteria. The verdict is printed and sent to
+// notify_command either way; an unmet criterion is an error, so the exit code tells a
+// scheduler whether the run passed without it parsing any counts.
+pub fn check_success_criteria(config: &AppConfig, metrics: &RunMetrics) -> Result<(), Box<dyn Error>> {
+    let criteria = match SuccessCriteria::parse(&config.success_criteria)? {
+        Some(criteria) => criteria,
+        None => return Ok(()),
+    };
+    
+    let unmet = criteria.unmet(metrics);
+    let counts = format!(
+        "{} executed, {} successful, {} failed, {} constraint violations",
+        metrics.executed(), metrics.succeeded, metrics.failed, metrics.constraint_violations
+    );
+    if unmet.is_empty() {
+        let message = format!("Success criteria met ({}): {}", config.success_criteria.trim(), counts);
+        println!("\x1b[32m{}\x1b[0m", message);
+        log::info!("{}", message);
+        notify::notify(config, "Success criteria met", &message);
+        return Ok(());
+    }
+    
+    let message = format!("Success criteria not met: {}; {}", unmet.join(", "), counts);
+    println!("\x1b[31m{}\x1b[0m", message);
+    log::error!("{}", message);
+    notify::notify(config, "Success criteria not met", &message);
+    Err(message.into())
+}
+
+// Whole numbers without a fraction, rates to two places
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
@@ -0,0 +1,135 @@
+use odbc_api::Connection;
+use chrono::{DateTime, Utc};
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::fs;
+
+use crate::config::AppConfig;
+use crate::db::query_types::{QueryRecord, QueryStatus};
+use crate::db::verification::{verify_query, VerificationOutcome};
+use crate::files::json_handler::{read_query_file, read_query_files, save_query_file};
+use crate::files::ledger::{AppliedLedger, LedgerEntry};
+use crate::files::manifest::RunManifest;
+use crate::files::processed::ProcessedRecords;
+use crate::ui;
+use crate::utils::{masking, operator, run_id, time};
+
+// Outcome of sweeping the runs under a directory
+#[derive(Debug, Clone, Default)]
+pub struct SweepSummary {
+    pub runs_scanned: usize,
+    // Pending or InProgress records older than the cutoff
+    pub stuck: usize,
+    // InProgress records whose row holds the change: completed and added to the ledger
+    pub completed: usize,
+    // Pending records whose row already holds the change: skipped
+    pub skipped: usize,
+    // Everything else: marked Stale
+    pub stale: usize,
+}
+
+// Find records of the runs under `root` left Pending or InProgress for more than
+// `older_than_hours` (by their last attempt, generation or the run's creation) and settle
+// them from a verification read. An interrupted statement whose row holds the change was
+// committed, so the record becomes Completed and is folded into the applied ledger. A pending
+// one whose row already holds it is Skipped. The rest are marked Stale for regeneration.
+pub fn sweep_stale_records(
+    conn: &Connection,
+    config: &AppConfig,
+    root: &str,
+    older_than_hours: u64,
+    processed_records: &mut ProcessedRecords,
+    progress_bar: &ProgressBar,
+) -> Result<SweepSummary, Box<dyn Error>> {
+    let cutoff = Utc::now() - chrono::Duration::hours(older_than_hours as i64);
+    let mut ledger = AppliedLedger::load(&config.data_path);
+    let mut summary = SweepSummary::default();
+    
+    let mut run_dirs: Vec<String> = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    run_dirs.sort();
+    
+    for results_dir in run_dirs {
+        let manifest = match RunManifest::load(&results_dir) {
+            Some(manifest) => manifest,
+            None => continue,
+        };
+        summary.runs_scanned += 1;
+        
+        for file_path in read_query_files(&results_dir)? {
+            let mut record = match read_query_file(&file_path) {
+                Ok(record) if matches!(record.status, QueryStatus::Pending | QueryStatus::InProgress) => record,
+                _ => continue,
+            };
+            let last_touched = record.timestamp.as_ref()
+                .or(record.generated_at.as_ref())
+                .unwrap_or(&manifest.created);
+            match DateTime::parse_from_rfc3339(last_touched) {
+                Ok(touched) if touched < cutoff => {},
+                _ => continue,
+            }
+            summary.stuck += 1;
+            ui::progress::update_message(progress_bar, format!("Verifying {:?} key {} in {}", record.status, record.key, results_dir));
+            
+            let outcome = match verify_query(conn, &record) {
+                Ok(outcome) => outcome,
+                Err(e) => VerificationOutcome::Unverifiable(format!("verification read failed: {}", e)),
+            };
+            let previous = record.status;
+            let now = time::now_rfc3339();
+            match (previous, outcome) {
+                (QueryStatus::InProgress, VerificationOutcome::Verified) => {
+                    record.status = QueryStatus::Completed;
+                    record.result = Some("success - interrupted statement found applied by sweep".to_string());
+                    fold_into_ledger(&mut ledger, processed_records, &results_dir, &record, &now);
+                    summary.completed += 1;
+                },
+                (_, VerificationOutcome::Verified) => {
+                    record.status = QueryStatus::Skipped;
+                    record.result = Some("skipped - row already held the change when swept".to_string());
+                    summary.skipped += 1;
+                },
+                (_, VerificationOutcome::Mismatch(reason)) | (_, VerificationOutcome::Unverifiable(reason)) => {
+                    record.status = QueryStatus::Stale;
+                    record.result = Some(masking::mask(&format!("stale - {:?} since {}: {}", previous, last_touched, reason)));
+                    summary.stale += 1;
+                },
+            }
+            record.timestamp = Some(now);
+            log::info!("Sweep: key {} in {} {:?} -> {:?}", record.key, results_dir, previous, record.status);
+            save_query_file(&file_path, &record)?;
+        }
+    }
+    
+    Ok(summary)
+}
+
+// Record a change the sweep found applied, so later runs never replay it
+fn fold_into_ledger(
+    ledger: &mut AppliedLedger,
+    processed_records: &mut ProcessedRecords,
+    results_dir: &str,
+    record: &QueryRecord,
+    timestamp: &str,
+) {
+    let change_hash = record.change_hash();
+    processed_records.add_applied(record.key.clone(), timestamp.to_string(), change_hash.clone());
+    if ledger.lookup(&change_hash).is_some() {
+        return;
+    }
+    let entry = LedgerEntry {
+        hash: change_hash,
+        key: record.key.clone(),
+        results_dir: results_dir.to_string(),
+        run_id: record.execution_run_id.clone().unwrap_or_else(|| run_id::current().to_string()),
+        timestamp: timestamp.to_string(),
+        ticket: record.ticket.clone(),
+        operator: Some(operator::current().to_string()),
+    };
+    if let Err(e) = ledger.record(entry) {
+        log::error!("Failed to record key {} in the applied ledger: {}", record.key, e);
+    }
+}
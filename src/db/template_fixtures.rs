@@ -0,0 +1,127 @@
+use config::{Config, File, FileFormat};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::config::AppConfig;
+use crate::db::lookups::LookupTables;
+use crate::db::query_generation::{build_query_record, selection_column_names, GenerationCache};
+use crate::db::result_columns::ResultColumns;
+
+// A YAML file of sample rows and the SQL the configured template must produce for them:
+//
+//   cases:
+//     - name: county fix
+//       row: ["42", "98101", "061"]
+//       expected: "UPDATE customers SET county = '033' WHERE key_field = '42'"
+//
+// `row` is positional like a selection row: the key first, then field1..fieldN. Entries for
+// configured `[lookups]` are given under `lookups`, e.g. `lookups: {carrier_id: {P100: "7"}}`,
+// the row's column names under `columns` when the selection is `SELECT *`, and the columns
+// the database reports as integers under `integer_columns`.
+#[derive(Debug, Deserialize)]
+pub struct TemplateFixture {
+    pub cases: Vec<TemplateCase>,
+    #[serde(default)]
+    pub lookups: HashMap<String, HashMap<String, String>>,
+    // Column names of the rows, required when the selection query is `SELECT *`
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    // Columns to treat as integer typed, as the driver would report them
+    #[serde(default)]
+    pub integer_columns: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TemplateCase {
+    #[serde(default)]
+    pub name: String,
+    pub row: Vec<String>,
+    pub expected: String,
+    // Values expected to be bound as parameters (long values or procedure arguments)
+    #[serde(default)]
+    pub expected_parameters: Option<Vec<String>>,
+}
+
+// Result of running a fixture file
+#[derive(Debug, Default)]
+pub struct TemplateTestSummary {
+    pub passed: usize,
+    // One description per failed case
+    pub failures: Vec<String>,
+}
+
+pub fn load_fixture(fixture_file: &str) -> Result<TemplateFixture, Box<dyn Error>> {
+    let fixture = Config::builder()
+        .add_source(File::new(fixture_file, FileFormat::Yaml))
+        .build()
+        .map_err(|e| format!("Failed to read template fixture {}: {}", fixture_file, e))?
+        .try_deserialize::<TemplateFixture>()
+        .map_err(|e| format!("Invalid template fixture {}: {}", fixture_file, e))?;
+    
+    if fixture.cases.is_empty() {
+        return Err(format!("Template fixture {} has no cases", fixture_file).into());
+    }
+    
+    Ok(fixture)
+}
+
+// Render every fixture row through the same code generation uses and compare the SQL,
+// ignoring differences in whitespace. No database connection is needed.
+pub fn run_template_tests(config: &AppConfig, fixture_file: &str) -> Result<TemplateTestSummary, Box<dyn Error>> {
+    let fixture = load_fixture(fixture_file)?;
+    let column_names = match &fixture.columns {
+        Some(columns) => columns.iter().map(|column| column.trim().to_lowercase()).collect(),
+        None if selection_column_names(&config.selection_query).iter().any(|name| name == "*") => {
+            return Err(format!("The selection query uses SELECT *; list the row's column names under `columns` in {}", fixture_file).into());
+        },
+        None => selection_column_names(&config.selection_query),
+    };
+    let lookups = LookupTables::from_maps(config, fixture.lookups.clone());
+    let mut cache = GenerationCache::default();
+    let mut columns = ResultColumns::from_names(column_names);
+    columns.integer = columns.names.iter()
+        .map(|name| fixture.integer_columns.iter().any(|column| column.trim().eq_ignore_ascii_case(name)))
+        .collect();
+    
+    let mut summary = TemplateTestSummary::default();
+    
+    for (index, case) in fixture.cases.iter().enumerate() {
+        let name = if case.name.is_empty() { format!("case {}", index + 1) } else { case.name.clone() };
+        let (record, long_values) = match build_query_record(config, &columns, &lookups, &mut cache, &case.row) {
+            Ok(built) => built,
+            Err(reason) => {
+                let failure = format!("{}\n    row skipped: {}", name, reason);
+                log::error!("Template test failed: {}", failure);
+                summary.failures.push(failure);
+                continue;
+            }
+        };
+        let parameters = if record.parameters.is_empty() { long_values } else { record.parameters.clone() };
+        
+        let mut problems = Vec::new();
+        if normalize_whitespace(&record.query) != normalize_whitespace(&case.expected) {
+            problems.push(format!("expected: {}\n    actual:   {}", case.expected.trim(), record.query));
+        }
+        if let Some(expected_parameters) = &case.expected_parameters {
+            if &parameters != expected_parameters {
+                problems.push(format!("expected parameters: {:?}\n    actual parameters:   {:?}", expected_parameters, parameters));
+            }
+        }
+        
+        if problems.is_empty() {
+            log::info!("Template test passed: {}", name);
+            summary.passed += 1;
+        } else {
+            let failure = format!("{}\n    {}", name, problems.join("\n    "));
+            log::error!("Template test failed: {}", failure);
+            summary.failures.push(failure);
+        }
+    }
+    
+    Ok(summary)
+}
+
+fn normalize_whitespace(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
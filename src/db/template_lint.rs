@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::error::Error;
+
+use crate::config::AppConfig;
+use crate::db::keys::key_columns;
+use crate::db::query_generation::{selection_column_names, withheld_columns};
+use crate::db::sql_helpers::find_keyword_outside_quotes;
+
+// Check update_query_template before anything is generated: every placeholder must be one
+// the selection provides, the WHERE clause must name the key, and quotes must balance. Every
+// problem is reported with its line and column in the template. Templates aren't used (and
+// aren't checked) when [upsert] or update_procedure is configured.
+pub fn lint_update_template(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    if config.upsert.is_some() || !config.update_procedure.is_empty() {
+        return Ok(());
+    }
+    let template = &config.update_query_template;
+    let mut problems = Vec::new();
+    
+    let known = known_placeholders(config);
+    let placeholders = placeholders(template, &mut problems);
+    for (position, name) in &placeholders {
+        if let Some(known) = &known {
+            if let Some(column) = known.withheld(name) {
+                problems.push((*position, format!("{{{{{}}}}} substitutes column {}, which isn't in substitution_columns", name, column)));
+            } else if !known.names.contains(&name.to_lowercase()) && !known.allows(name) {
+                problems.push((*position, format!("unknown placeholder {{{{{}}}}} (known: {})", name, known.describe())));
+            }
+        }
+    }
+    
+    if let Some((position, quote)) = unbalanced_quote(template) {
+        problems.push((position, format!("{} opened here is never closed", if quote == '\'' { "single quote" } else { "double quote" })));
+    }
+    
+    // The WHERE clause must name the key, or every row of the table would get the first row's change
+    match find_keyword_outside_quotes(template, "WHERE") {
+        None => problems.push((template.trim_end().len(), "no WHERE clause; the statement would change every row".to_string())),
+        Some(where_position) => {
+            let names_key = |name: &str| placeholders.iter().any(|(position, placeholder)| *position > where_position && placeholder == name);
+            let key_count = key_columns(config).len();
+            let keyed = names_key("key") || names_key("key_condition")
+                || (key_count > 1 && (1..=key_count).all(|index| names_key(&format!("key{}", index))));
+            if !keyed {
+                problems.push((where_position, "the WHERE clause doesn't use {{key}} (or {{key_condition}})".to_string()));
+            }
+        },
+    }
+    
+    if problems.is_empty() {
+        return Ok(());
+    }
+    problems.sort_by_key(|(position, _)| *position);
+    let report = problems.iter()
+        .map(|(position, problem)| {
+            let (line, column) = line_and_column(template, *position);
+            format!("  line {}, column {}: {}", line, column, problem)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(format!("update_query_template has {} problem(s):\n{}", problems.len(), report).into())
+}
+
+// Placeholder names the selection provides; field numbers are checked against its width
+struct KnownPlaceholders {
+    names: HashSet<String>,
+    fields: usize,
+    // Placeholders (lowercase, by name and as fieldN) of columns substitution_columns withholds
+    withheld: Vec<(String, String)>,
+}
+
+impl KnownPlaceholders {
+    // `fieldN` within the selection's columns
+    fn allows(&self, name: &str) -> bool {
+        name.strip_prefix("field")
+            .and_then(|number| number.parse::<usize>().ok())
+            .is_some_and(|number| number >= 1 && number <= self.fields)
+    }
+    
+    // The column a placeholder would substitute, when substitution_columns withholds it
+    fn withheld(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.withheld.iter().find(|(placeholder, _)| *placeholder == name).map(|(_, column)| column.as_str())
+    }
+    
+    fn describe(&self) -> String {
+        let mut names: Vec<&String> = self.names.iter().collect();
+        names.sort();
+        let mut described: Vec<String> = names.into_iter().cloned().collect();
+        if self.fields > 0 {
+            described.push(format!("field1..field{}", self.fields));
+        }
+        described.join(", ")
+    }
+}
+
+// None when the selection's columns aren't known before it runs (`SELECT *`, or a select list
+// the parser can't read); the driver names them then and unknown placeholders can't be told
+fn known_placeholders(config: &AppConfig) -> Option<KnownPlaceholders> {
+    let columns = selection_column_names(&config.selection_query);
+    if columns.is_empty() || columns.iter().any(|column| column == "*" || column.ends_with(".*")) {
+        return None;
+    }
+    
+    let mut names: HashSet<String> = columns.iter().map(|column| column.to_lowercase()).collect();
+    names.insert("key".to_string());
+    let key_count = key_columns(config).len();
+    if key_count > 1 {
+        names.insert("key_condition".to_string());
+        for index in 1..=key_count {
+            names.insert(format!("key{}", index));
+        }
+    }
+    names.extend(config.lookups.keys().map(|name| name.to_lowercase()));
+    
+    // A lookup named like a withheld column is the lookup's value, not the column's
+    let mut withheld = Vec::new();
+    for index in withheld_columns(config, &columns) {
+        let column = &columns[index];
+        withheld.push((format!("field{}", index), column.clone()));
+        if !config.lookups.keys().any(|name| name.eq_ignore_ascii_case(column)) {
+            withheld.push((column.to_lowercase(), column.clone()));
+        }
+    }
+    Some(KnownPlaceholders { names, fields: columns.len() - 1, withheld })
+}
+
+// Each `{{name}}` with the byte position of its opening braces; an unterminated one is a problem
+fn placeholders(template: &str, problems: &mut Vec<(usize, String)>) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = template[offset..].find("{{") {
+        let start = offset + start;
+        match template[start + 2..].find("}}") {
+            Some(end) => {
+                found.push((start, template[start + 2..start + 2 + end].to_string()));
+                offset = start + 2 + end + 2;
+            },
+            None => {
+                problems.push((start, "{{ is never closed with }}".to_string()));
+                break;
+            },
+        }
+    }
+    found
+}
+
+// The position and kind of the first quote left open; doubled quotes inside a string are escapes
+fn unbalanced_quote(template: &str) -> Option<(usize, char)> {
+    let mut open: Option<(usize, char)> = None;
+    let mut chars = template.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        match open {
+            Some((_, quote)) if c == quote => {
+                if chars.peek().map(|(_, next)| *next) == Some(quote) {
+                    chars.next();
+                } else {
+                    open = None;
+                }
+            },
+            Some(_) => {},
+            None if c == '\'' || c == '"' => open = Some((position, c)),
+            None => {},
+        }
+    }
+    open
+}
+
+// 1-based line and column of a byte position
+fn line_and_column(text: &str, position: usize) -> (usize, usize) {
+    let before = &text[..position.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
@@ -0,0 +1,234 @@
+use odbc_api::{parameter::VarCharBox, Connection};
+use indicatif::ProgressBar;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{AppConfig, TrialConfig};
+use crate::db::connection::{execute_with_row_count, query_first_row};
+use crate::db::error_classes::OdbcDiagnostics;
+use crate::db::query_types::{QueryRecord, QueryStatus};
+use crate::db::sql_helpers::{parse_set_assignments, parse_update_statement, QualifiedName, UpdateParts};
+use crate::db::verification::{unguarded_condition, values_match};
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::snapshot::csv_field;
+use crate::ui;
+use crate::utils::{encoding, masking};
+
+// Per-column comparison of a trial execution with production, written to the results directory
+pub const TRIAL_COMPARISON_FILE_NAME: &str = "trial_comparison.csv";
+
+// Outcome of a trial execution
+#[derive(Debug, Clone, Default)]
+pub struct TrialSummary {
+    // Statements executed against a staging table
+    pub executed: usize,
+    pub failed: usize,
+    // Pending records not rehearsed: no staging table mapped, not an UPDATE, ...
+    pub skipped: usize,
+    // Columns whose staging value isn't the literal the statement assigned
+    pub unexpected: usize,
+    // Columns compared between staging and production
+    pub compared: usize,
+}
+
+// One line of the comparison report
+struct ComparisonRow {
+    key: String,
+    status: &'static str,
+    rows_affected: String,
+    column: String,
+    production_value: String,
+    trial_value: String,
+    expected_value: String,
+    result: String,
+}
+
+impl ComparisonRow {
+    // A line for a record that has no column comparison, with the reason in `result`
+    fn outcome(key: &str, status: &'static str, result: String) -> Self {
+        ComparisonRow {
+            key: key.to_string(),
+            status,
+            rows_affected: String::new(),
+            column: String::new(),
+            production_value: String::new(),
+            trial_value: String::new(),
+            expected_value: String::new(),
+            result,
+        }
+    }
+}
+
+// Rehearse the run's pending queries on staging copies of their tables: each UPDATE is
+// rewritten to target the table [trial] maps its table to and executed there, then the row is
+// re-read from both tables and the assigned columns compared. Query files keep their status,
+// and a statement whose table has no staging copy is never executed.
+pub fn run_trial(
+    conn: &Connection,
+    config: &AppConfig,
+    results_dir: &str,
+    progress_bar: &ProgressBar,
+) -> Result<TrialSummary, Box<dyn Error>> {
+    let trial = config.trial.as_ref().ok_or("No [trial] section in the configuration")?;
+    check_mapping(trial)?;
+    
+    let query_files = read_query_files(results_dir)?;
+    progress_bar.set_length(query_files.len() as u64);
+    ui::progress::print_with_progress(progress_bar, &format!("Rehearsing {} queries on staging tables...", query_files.len()));
+    
+    let mut summary = TrialSummary::default();
+    let mut rows = Vec::new();
+    for (index, file_path) in query_files.iter().enumerate() {
+        progress_bar.set_position(index as u64);
+        let record = match read_query_file(file_path) {
+            Ok(record) if record.status == QueryStatus::Pending => record,
+            Ok(_) => continue,
+            Err(e) => {
+                log::error!("Failed to read {}: {}", file_path.display(), e);
+                continue;
+            },
+        };
+        ui::progress::update_message(progress_bar, format!("Rehearsing query for key: {}", record.key));
+        
+        let (parts, staging) = match rewrite_target(trial, &record) {
+            Ok(target) => target,
+            Err(reason) => {
+                log::info!("Not rehearsing key {}: {}", record.key, reason);
+                summary.skipped += 1;
+                rows.push(ComparisonRow::outcome(&record.key, "skipped", reason));
+                continue;
+            },
+        };
+        
+        match rehearse(conn, results_dir, &record, &parts, &staging) {
+            Ok(mut compared) => {
+                summary.executed += 1;
+                summary.compared += compared.iter().filter(|row| !row.column.is_empty()).count();
+                summary.unexpected += compared.iter().filter(|row| row.result == "unexpected").count();
+                rows.append(&mut compared);
+            },
+            Err(e) => {
+                log::warn!("Trial of key {} on {} failed: {}", record.key, staging, e);
+                summary.failed += 1;
+                rows.push(ComparisonRow::outcome(&record.key, "failed", masking::mask(&e.to_string())));
+            },
+        }
+    }
+    
+    write_comparison(results_dir, &rows)?;
+    Ok(summary)
+}
+
+// Refuse a mapping that would send a rehearsal to the production table itself
+fn check_mapping(trial: &TrialConfig) -> Result<(), Box<dyn Error>> {
+    if trial.tables.is_empty() {
+        return Err("[trial] maps no tables to staging copies".into());
+    }
+    for (production, staging) in &trial.tables {
+        let parse = |name: &str| QualifiedName::parse(name).ok_or_else(|| format!("[trial] table '{}' is not a valid table name", name));
+        if parse(production)?.normalized() == parse(staging)?.normalized() {
+            return Err(format!("[trial] maps {} onto itself; the staging copy must be another table", production).into());
+        }
+    }
+    Ok(())
+}
+
+// The record's UPDATE and the staging table to run it on, or why it can't be rehearsed
+fn rewrite_target(trial: &TrialConfig, record: &QueryRecord) -> Result<(UpdateParts, String), String> {
+    let parts = parse_update_statement(&record.query).ok_or_else(|| "not an UPDATE statement".to_string())?;
+    if parts.where_clause.is_none() {
+        return Err("UPDATE has no WHERE clause".to_string());
+    }
+    let table = QualifiedName::parse(&parts.table).ok_or_else(|| format!("can't read the table name {}", parts.table))?;
+    // An unqualified mapping matches the table under any owner
+    let staging = trial.tables.iter()
+        .find(|(production, _)| QualifiedName::parse(production).is_some_and(|production| {
+            production.name == table.name && production.owner.as_ref().is_none_or(|owner| Some(owner) == table.owner.as_ref())
+        }))
+        .map(|(_, staging)| staging.trim().to_string())
+        .ok_or_else(|| format!("no staging table mapped for {}", parts.table))?;
+    Ok((parts, staging))
+}
+
+// Execute the rewritten statement on the staging table and compare the assigned columns of
+// the row in staging and production
+fn rehearse(
+    conn: &Connection,
+    results_dir: &str,
+    record: &QueryRecord,
+    parts: &UpdateParts,
+    staging: &str,
+) -> Result<Vec<ComparisonRow>, Box<dyn Error>> {
+    let where_clause = parts.where_clause.as_deref().unwrap_or_default();
+    let statement = format!("UPDATE {} SET {} WHERE {}", staging, parts.set_clause, where_clause);
+    let values = record.bound_values(results_dir)?;
+    let parameters: Vec<VarCharBox> = values.iter()
+        .map(|value| VarCharBox::from_vec(encoding::encode(value)))
+        .collect();
+    let execution = if parameters.is_empty() {
+        execute_with_row_count(conn, &statement, ())
+    } else {
+        execute_with_row_count(conn, &statement, parameters.as_slice())
+    };
+    let rows_affected = execution
+        .map_err(|e| OdbcDiagnostics::from_odbc_error(&e).summary())?
+        .map(|count| count.to_string())
+        .unwrap_or_default();
+    log::info!("Trial of key {} on {}: {} rows affected", record.key, staging, if rows_affected.is_empty() { "?" } else { &rows_affected });
+    
+    let executed = |result: String| ComparisonRow { rows_affected: rows_affected.clone(), ..ComparisonRow::outcome(&record.key, "executed", result) };
+    if record.has_bound_values() {
+        return Ok(vec![executed("not compared: the statement binds parameter values".to_string())]);
+    }
+    let assignments = parse_set_assignments(&parts.set_clause);
+    if assignments.is_empty() {
+        return Ok(vec![executed("not compared: no SET assignments recognised".to_string())]);
+    }
+    
+    // The concurrency guards compare with the pre-update values, which staging no longer holds
+    let condition = if record.concurrency_guard { unguarded_condition(where_clause) } else { where_clause };
+    let columns = assignments.iter().map(|(column, _)| column.as_str()).collect::<Vec<_>>().join(", ");
+    let read = |table: &str| query_first_row(conn, &format!("SELECT {} FROM {} WHERE {}", columns, table, condition));
+    let production = read(&parts.table)?;
+    let trial = read(staging)?;
+    let (production, trial) = match (production, trial) {
+        (Some(production), Some(trial)) => (production, trial),
+        (None, _) => return Ok(vec![executed(format!("not compared: no row in {}", parts.table))]),
+        (_, None) => return Ok(vec![executed(format!("not compared: no row in {}", staging))]),
+    };
+    
+    Ok(assignments.iter().enumerate()
+        .map(|(index, (column, expected))| {
+            let production_value = production.get(index).map(|value| value.trim_end().to_string()).unwrap_or_default();
+            let trial_value = trial.get(index).map(|value| value.trim_end().to_string()).unwrap_or_default();
+            // With a literal assigned, the staging row must hold it; otherwise say whether the value moved
+            let result = match expected {
+                Some(expected) if values_match(&trial_value, expected) => "as expected",
+                Some(_) => "unexpected",
+                None if values_match(&trial_value, &production_value) => "unchanged",
+                None => "changed",
+            };
+            ComparisonRow {
+                column: column.clone(),
+                production_value: masking::mask_value(column, &production_value),
+                trial_value: masking::mask_value(column, &trial_value),
+                expected_value: masking::mask_value(column, expected.as_deref().unwrap_or_default()),
+                ..executed(result.to_string())
+            }
+        })
+        .collect())
+}
+
+fn write_comparison(results_dir: &str, rows: &[ComparisonRow]) -> Result<(), Box<dyn Error>> {
+    let mut content = String::from("\u{feff}key,status,rows_affected,column,production_value,trial_value,expected_value,result\r\n");
+    for row in rows {
+        content.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\r\n",
+            csv_field(&row.key), row.status, row.rows_affected, csv_field(&row.column),
+            csv_field(&row.production_value), csv_field(&row.trial_value), csv_field(&row.expected_value), csv_field(&row.result)
+        ));
+    }
+    fs::write(Path::new(results_dir).join(TRIAL_COMPARISON_FILE_NAME), content)?;
+    Ok(())
+}
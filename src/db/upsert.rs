@@ -0,0 +1,101 @@
+use std::error::Error;
+
+use crate::config::{AppConfig, UpsertConfig};
+use crate::db::dialect::SqlDialect;
+use crate::db::sql_helpers::{format_identifier, normalize_identifier, QualifiedName};
+
+// How an upsert is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertStrategy {
+    // One MERGE statement
+    Merge,
+    // An UPDATE, followed in the same transaction by an INSERT when it matched no row
+    UpdateInsert,
+}
+
+impl UpsertStrategy {
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "merge" => Ok(UpsertStrategy::Merge),
+            "update_insert" => Ok(UpsertStrategy::UpdateInsert),
+            _ => Err(format!("Unknown upsert strategy '{}' (expected 'merge' or 'update_insert')", name).into()),
+        }
+    }
+}
+
+// The statement for one selected row, and the INSERT to fall back to for update_insert.
+// `row` is the key followed by one value per configured column; empty values are NULL.
+pub fn build_upsert(
+    config: &AppConfig,
+    upsert: &UpsertConfig,
+    dialect: &dyn SqlDialect,
+    row: &[String],
+) -> Result<(String, Option<String>), String> {
+    if row.len() != upsert.columns.len() + 1 {
+        return Err(format!(
+            "the selection returned {} values but the upsert needs {} (the key and {} columns)",
+            row.len(), upsert.columns.len() + 1, upsert.columns.len()
+        ));
+    }
+    
+    if row[0].is_empty() {
+        return Err("the key is empty".to_string());
+    }
+    
+    let identifier = |name: &str| format_identifier(&normalize_identifier(name), config.delimident);
+    let table = QualifiedName::parse(&upsert.table)
+        .map(|name| name.to_sql(config.delimident))
+        .ok_or_else(|| format!("upsert table '{}' is not a valid table name", upsert.table))?;
+    let key_column = identifier(&upsert.key_column);
+    let columns: Vec<String> = upsert.columns.iter().map(|column| identifier(column)).collect();
+    let key = literal(&row[0]);
+    let values: Vec<String> = row[1..].iter().map(|value| literal(value)).collect();
+    
+    let strategy = UpsertStrategy::from_name(&upsert.strategy).map_err(|e| e.to_string())?;
+    match strategy {
+        UpsertStrategy::Merge => {
+            let select_list = std::iter::once(format!("{} AS {}", key, key_column))
+                .chain(values.iter().zip(&columns).map(|(value, column)| format!("{} AS {}", value, column)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let assignments = columns.iter()
+                .map(|column| format!("t.{} = s.{}", column, column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let all_columns = std::iter::once(&key_column).chain(&columns).cloned().collect::<Vec<_>>();
+            let statement = format!(
+                "MERGE INTO {} t USING {} s ON t.{key} = s.{key} \
+                 WHEN MATCHED THEN UPDATE SET {} \
+                 WHEN NOT MATCHED THEN INSERT ({}) VALUES ({})",
+                table,
+                dialect.single_row_source(&select_list),
+                assignments,
+                all_columns.join(", "),
+                all_columns.iter().map(|column| format!("s.{}", column)).collect::<Vec<_>>().join(", "),
+                key = key_column
+            );
+            Ok((statement, None))
+        },
+        UpsertStrategy::UpdateInsert => {
+            let assignments = columns.iter()
+                .zip(&values)
+                .map(|(column, value)| format!("{} = {}", column, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let update = format!("UPDATE {} SET {} WHERE {} = {}", table, assignments, key_column, key);
+            let insert = format!(
+                "INSERT INTO {} ({}, {}) VALUES ({}, {})",
+                table, key_column, columns.join(", "), key, values.join(", ")
+            );
+            Ok((update, Some(insert)))
+        },
+    }
+}
+
+fn literal(value: &str) -> String {
+    if value.is_empty() {
+        "NULL".to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+}
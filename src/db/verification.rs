@@ -0,0 +1,133 @@
+use odbc_api::{Connection, Cursor};
+use std::error::Error;
+
+use crate::db::fetch::{ensure_not_truncated, text_buffers, UTILITY_MAX_TEXT_LENGTH};
+use crate::db::query_types::QueryRecord;
+use crate::db::sql_helpers::{parse_set_assignments, parse_update_statement};
+use crate::utils::encoding;
+
+// Result of re-reading the rows a query changed
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationOutcome {
+    // Every row matched by the WHERE clause holds the expected values
+    Verified,
+    // At least one row doesn't hold the expected values
+    Mismatch(String),
+    // The query can't be verified automatically (not an UPDATE, or no literal values)
+    Unverifiable(String),
+}
+
+// Re-select the rows an UPDATE targeted and check they hold the values it assigned
+pub fn verify_query(conn: &Connection, query_record: &QueryRecord) -> Result<VerificationOutcome, Box<dyn Error>> {
+    if query_record.has_bound_values() {
+        return Ok(VerificationOutcome::Unverifiable("query binds parameter values".to_string()));
+    }
+    
+    let parts = match parse_update_statement(&query_record.query) {
+        Some(parts) => parts,
+        None => return Ok(VerificationOutcome::Unverifiable("not an UPDATE statement".to_string())),
+    };
+    
+    let where_clause = match &parts.where_clause {
+        Some(where_clause) if query_record.concurrency_guard => unguarded_condition(where_clause),
+        Some(where_clause) => where_clause.as_str(),
+        None => return Ok(VerificationOutcome::Unverifiable("UPDATE has no WHERE clause".to_string())),
+    };
+    
+    // Only assignments of literal values can be compared
+    let expected: Vec<(String, String)> = parse_set_assignments(&parts.set_clause)
+        .into_iter()
+        .filter_map(|(column, value)| value.map(|v| (column, v)))
+        .collect();
+    
+    if expected.is_empty() {
+        return Ok(VerificationOutcome::Unverifiable("no literal values assigned".to_string()));
+    }
+    
+    let columns = expected.iter().map(|(column, _)| column.as_str()).collect::<Vec<_>>().join(", ");
+    let select = format!("SELECT {} FROM {} WHERE {}", columns, parts.table, where_clause);
+    
+    let cursor = match conn.execute(&select, ())? {
+        Some(cursor) => cursor,
+        None => return Ok(VerificationOutcome::Mismatch("verification query returned no result set".to_string())),
+    };
+    
+    let mut buffers = text_buffers(&cursor, 100, UTILITY_MAX_TEXT_LENGTH)?;
+    let mut row_set_cursor = cursor.bind_buffer(&mut buffers)?;
+    let mut row_count = 0;
+    
+    while let Some(batch) = row_set_cursor.fetch()? {
+        for row_index in 0..batch.num_rows() {
+            row_count += 1;
+            // A truncated value can't be compared with what was assigned
+            if let Err(e) = ensure_not_truncated(batch, row_index) {
+                return Ok(VerificationOutcome::Unverifiable(e.to_string()));
+            }
+            
+            for (col_index, (column, expected_value)) in expected.iter().enumerate() {
+                let actual = encoding::decode(batch.at(col_index, row_index).unwrap_or(&[]));
+                
+                if !values_match(&actual, expected_value) {
+                    return Ok(VerificationOutcome::Mismatch(format!(
+                        "{} is '{}', expected '{}'", column, actual.trim_end(), expected_value
+                    )));
+                }
+            }
+        }
+    }
+    
+    if row_count == 0 {
+        return Ok(VerificationOutcome::Mismatch("no rows match the WHERE clause".to_string()));
+    }
+    
+    // A composite key identifies one row; more means the WHERE clause leaves out a key column
+    if query_record.key_values.len() > 1 && row_count > 1 {
+        return Ok(VerificationOutcome::Mismatch(format!(
+            "{} rows match the WHERE clause for composite key {}", row_count, query_record.key
+        )));
+    }
+    
+    Ok(VerificationOutcome::Verified)
+}
+
+// The original condition of a guarded WHERE clause, `(<condition>) AND <guards>`. The guards
+// compare against the pre-update values, so they must not be part of the re-select.
+pub fn unguarded_condition(where_clause: &str) -> &str {
+    if !where_clause.starts_with('(') {
+        return where_clause;
+    }
+    
+    let mut depth = 0;
+    let mut in_quote = false;
+    for (index, c) in where_clause.char_indices() {
+        match c {
+            '\'' => in_quote = !in_quote,
+            '(' if !in_quote => depth += 1,
+            ')' if !in_quote => {
+                depth -= 1;
+                if depth == 0 {
+                    return &where_clause[1..index];
+                }
+            },
+            _ => {}
+        }
+    }
+    
+    where_clause
+}
+
+// Compare a fetched value with an expected literal. CHAR columns come back
+// blank-padded and numeric columns may carry a different scale.
+pub fn values_match(actual: &str, expected: &str) -> bool {
+    let actual = actual.trim_end();
+    let expected = expected.trim_end();
+    
+    if actual == expected {
+        return true;
+    }
+    
+    match (actual.trim().parse::<f64>(), expected.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
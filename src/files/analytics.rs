@@ -0,0 +1,210 @@
+use chrono::DateTime;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::QueryStatus;
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::manifest::RunManifest;
+use crate::files::snapshot::csv_field;
+
+/// One row per historical run
+pub const ANALYTICS_RUNS_FILE_NAME: &str = "analytics_runs.csv";
+
+/// Runs aggregated by ISO week of generation
+pub const ANALYTICS_WEEKLY_FILE_NAME: &str = "analytics_weekly.csv";
+
+/// Keys that failed in more than one run
+pub const ANALYTICS_RECURRING_FILE_NAME: &str = "analytics_recurring_failures.csv";
+
+/// Everything above in one document
+pub const ANALYTICS_JSON_FILE_NAME: &str = "analytics.json";
+
+/// Outcome counts of one run, from its manifest and query files
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RunTrend {
+    pub results_dir: String,
+    pub run_id: String,
+    pub created: String,
+    /// ISO week the run was generated in, e.g. `2026-W41`
+    pub week: String,
+    pub ticket: Option<String>,
+    pub matched_keys: usize,
+    pub generated: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub conflicts: usize,
+    pub skipped: usize,
+    pub pending: usize,
+    /// Abandoned records settled as Stale by `sweep`
+    pub stale: usize,
+    /// Completed records undone by `rollback`
+    pub rolled_back: usize,
+}
+
+impl RunTrend {
+    /// Failed and conflicting queries as a share of those executed, 0.0 when none were
+    pub fn error_rate(&self) -> f64 {
+        let executed = self.completed + self.failed + self.conflicts;
+        if executed == 0 { 0.0 } else { (self.failed + self.conflicts) as f64 / executed as f64 }
+    }
+}
+
+/// Runs of one week added together
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct WeeklyTrend {
+    pub week: String,
+    pub runs: usize,
+    pub generated: usize,
+    /// Completed queries, i.e. rows corrected
+    pub completed: usize,
+    pub failed: usize,
+    pub conflicts: usize,
+    pub error_rate: f64,
+}
+
+/// A key that failed in several runs, which usually points at bad upstream data
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RecurringFailure {
+    pub key: String,
+    pub runs: Vec<String>,
+    /// The error of its most recent failure
+    pub last_error: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Analytics {
+    pub runs: Vec<RunTrend>,
+    pub weekly: Vec<WeeklyTrend>,
+    pub recurring_failures: Vec<RecurringFailure>,
+}
+
+/// Aggregate every results directory under `root` that has a manifest, oldest run first.
+/// Keys that failed in at least `min_failures` runs are reported as recurring.
+pub fn collect_analytics(root: &str, min_failures: usize) -> Result<Analytics, Box<dyn Error>> {
+    let mut runs = Vec::new();
+    // key -> (created, results dir, error) of each failure
+    let mut failures: BTreeMap<String, Vec<(String, String, String)>> = BTreeMap::new();
+    
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let results_dir = path.to_string_lossy().to_string();
+        let manifest = match RunManifest::load(&results_dir) {
+            Some(manifest) => manifest,
+            None => continue,
+        };
+        
+        let mut run = RunTrend {
+            results_dir: path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+            run_id: manifest.run_id,
+            week: iso_week(&manifest.created),
+            created: manifest.created,
+            ticket: manifest.ticket,
+            matched_keys: manifest.matched_keys,
+            generated: manifest.generated,
+            ..RunTrend::default()
+        };
+        for file_path in read_query_files(&results_dir)? {
+            let record = match read_query_file(&file_path) {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            match record.status {
+                QueryStatus::Completed => run.completed += 1,
+                QueryStatus::Failed => {
+                    run.failed += 1;
+                    failures.entry(record.key.clone()).or_default()
+                        .push((run.created.clone(), run.results_dir.clone(), record.result.clone().unwrap_or_default()));
+                },
+                QueryStatus::Conflict => run.conflicts += 1,
+                QueryStatus::Skipped => run.skipped += 1,
+                QueryStatus::Pending | QueryStatus::InProgress | QueryStatus::Quarantined => run.pending += 1,
+                QueryStatus::Stale => run.stale += 1,
+                QueryStatus::RolledBack => run.rolled_back += 1,
+            }
+        }
+        runs.push(run);
+    }
+    runs.sort_by(|a, b| a.created.cmp(&b.created));
+    
+    let mut weeks: BTreeMap<String, WeeklyTrend> = BTreeMap::new();
+    for run in &runs {
+        let week = weeks.entry(run.week.clone()).or_insert_with(|| WeeklyTrend { week: run.week.clone(), ..WeeklyTrend::default() });
+        week.runs += 1;
+        week.generated += run.generated;
+        week.completed += run.completed;
+        week.failed += run.failed;
+        week.conflicts += run.conflicts;
+    }
+    let weekly = weeks.into_values()
+        .map(|mut week| {
+            let executed = week.completed + week.failed + week.conflicts;
+            week.error_rate = if executed == 0 { 0.0 } else { (week.failed + week.conflicts) as f64 / executed as f64 };
+            week
+        })
+        .collect();
+    
+    let mut recurring_failures: Vec<RecurringFailure> = failures.into_iter()
+        .filter(|(_, failed)| failed.len() >= min_failures.max(1))
+        .map(|(key, mut failed)| {
+            failed.sort();
+            RecurringFailure {
+                key,
+                last_error: failed.last().map(|(_, _, error)| error.clone()).unwrap_or_default(),
+                runs: failed.into_iter().map(|(_, results_dir, _)| results_dir).collect(),
+            }
+        })
+        .collect();
+    recurring_failures.sort_by(|a, b| b.runs.len().cmp(&a.runs.len()).then_with(|| a.key.cmp(&b.key)));
+    
+    Ok(Analytics { runs, weekly, recurring_failures })
+}
+
+/// Write the three CSV files and the JSON document into `output_dir`
+pub fn write_analytics(output_dir: &str, analytics: &Analytics) -> Result<(), Box<dyn Error>> {
+    let mut runs = String::from("results_dir,run_id,created,week,ticket,matched_keys,generated,completed,failed,conflicts,skipped,pending,stale,rolled_back,error_rate\r\n");
+    for run in &analytics.runs {
+        runs.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{:.4}\r\n",
+            csv_field(&run.results_dir), run.run_id, run.created, run.week,
+            csv_field(run.ticket.as_deref().unwrap_or_default()),
+            run.matched_keys, run.generated, run.completed, run.failed, run.conflicts, run.skipped, run.pending, run.stale, run.rolled_back,
+            run.error_rate()
+        ));
+    }
+    
+    let mut weekly = String::from("week,runs,generated,completed,failed,conflicts,error_rate\r\n");
+    for week in &analytics.weekly {
+        weekly.push_str(&format!(
+            "{},{},{},{},{},{},{:.4}\r\n",
+            week.week, week.runs, week.generated, week.completed, week.failed, week.conflicts, week.error_rate
+        ));
+    }
+    
+    let mut recurring = String::from("key,failed_runs,runs,last_error\r\n");
+    for failure in &analytics.recurring_failures {
+        recurring.push_str(&format!(
+            "{},{},{},{}\r\n",
+            csv_field(&failure.key), failure.runs.len(), csv_field(&failure.runs.join(" ")), csv_field(&failure.last_error)
+        ));
+    }
+    
+    let output = Path::new(output_dir);
+    fs::write(output.join(ANALYTICS_RUNS_FILE_NAME), runs)?;
+    fs::write(output.join(ANALYTICS_WEEKLY_FILE_NAME), weekly)?;
+    fs::write(output.join(ANALYTICS_RECURRING_FILE_NAME), recurring)?;
+    fs::write(output.join(ANALYTICS_JSON_FILE_NAME), serde_json::to_string_pretty(analytics)?)?;
+    Ok(())
+}
+
+/// `2026-W41` for an RFC 3339 timestamp, or `unknown` when it can't be read
+fn iso_week(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|time| time.format("%G-W%V").to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
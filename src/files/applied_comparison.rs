@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::{QueryRecord, QueryStatus};
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::snapshot::csv_field;
+
+/// Keys a previous run corrected that the selection matched again, written by
+/// `generate --compare-applied`
+pub const APPLIED_COMPARISON_FILE_NAME: &str = "applied_comparison.csv";
+
+/// A change a previous run applied to a key
+#[derive(Debug, Clone)]
+struct AppliedChange {
+    query: String,
+    change_hash: String,
+    applied_at: String,
+}
+
+/// The changes a previous run applied, by key, to compare a new generation with
+#[derive(Debug, Clone, Default)]
+pub struct AppliedRun {
+    pub results_dir: String,
+    changes: HashMap<String, AppliedChange>,
+}
+
+/// A key the previous run corrected that needs a change again
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub key: String,
+    pub applied_at: String,
+    pub applied_query: String,
+    pub new_query: String,
+    /// The new change is the one already applied: the row went back to its old value
+    pub same_change: bool,
+}
+
+impl AppliedRun {
+    /// The Completed records of a results directory
+    pub fn load(results_dir: &str) -> Result<Self, Box<dyn Error>> {
+        if !Path::new(results_dir).is_dir() {
+            return Err(format!("Results directory {} to compare with does not exist", results_dir).into());
+        }
+        
+        let mut changes = HashMap::new();
+        for file_path in read_query_files(results_dir)? {
+            let record = match read_query_file(&file_path) {
+                Ok(record) if record.status == QueryStatus::Completed => record,
+                _ => continue,
+            };
+            changes.insert(record.key.clone(), AppliedChange {
+                change_hash: record.change_hash(),
+                applied_at: record.timestamp.clone().unwrap_or_default(),
+                query: record.query,
+            });
+        }
+        log::info!("Comparing generation with the {} changes {} applied", changes.len(), results_dir);
+        Ok(AppliedRun { results_dir: results_dir.to_string(), changes })
+    }
+    
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+    
+    /// The regression a newly generated record shows, if its key was corrected before
+    pub fn compare(&self, record: &QueryRecord) -> Option<Regression> {
+        let applied = self.changes.get(&record.key)?;
+        Some(Regression {
+            key: record.key.clone(),
+            applied_at: applied.applied_at.clone(),
+            applied_query: applied.query.clone(),
+            new_query: record.query.clone(),
+            same_change: applied.change_hash == record.change_hash(),
+        })
+    }
+}
+
+/// Write the regressions of a generation into its results directory
+pub fn write_applied_comparison(results_dir: &str, applied_run: &AppliedRun, regressions: &[Regression]) -> Result<(), Box<dyn Error>> {
+    let mut content = String::from("\u{feff}key,result,applied_at,applied_run,applied_query,new_query\r\n");
+    for regression in regressions {
+        content.push_str(&format!(
+            "{},{},{},{},{},{}\r\n",
+            csv_field(&regression.key),
+            if regression.same_change { "same change again" } else { "different change" },
+            regression.applied_at,
+            csv_field(&applied_run.results_dir),
+            csv_field(&regression.applied_query),
+            csv_field(&regression.new_query)
+        ));
+    }
+    fs::write(Path::new(results_dir).join(APPLIED_COMPARISON_FILE_NAME), content)?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::utils::time;
+
+/// Progress of an unfinished generation, rewritten every `generation_checkpoint_interval` rows
+pub const CHECKPOINT_FILE_NAME: &str = "generation_checkpoint.json";
+
+/// Keys the unfinished generation has handled, one per line, appended at each checkpoint
+pub const CHECKPOINT_KEYS_FILE_NAME: &str = "generation_checkpoint_keys.txt";
+
+/// Where an interrupted generation got to, so `generate --resume` can carry on from there
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GenerationCheckpoint {
+    /// Selection the checkpoint belongs to; resuming with a different one is refused
+    pub selection_query: String,
+    /// Last key handled, in selection order
+    pub last_key: Option<String>,
+    /// Keys handled so far, including those skipped
+    pub rows: usize,
+    pub generated: usize,
+    pub already_applied: usize,
+    pub lookup_skipped: usize,
+    pub delta_skipped: usize,
+    pub updated: String,
+}
+
+impl GenerationCheckpoint {
+    /// The checkpoint of a results directory, if its generation didn't finish
+    pub fn load(results_dir: &str) -> Option<Self> {
+        let content = fs::read_to_string(Path::new(results_dir).join(CHECKPOINT_FILE_NAME)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+    
+    /// Append the keys handled since the last checkpoint, then record the counts. The keys go
+    /// first so a crash in between can only leave keys the counts don't cover yet.
+    pub fn save(&mut self, results_dir: &str, new_keys: &[String]) -> Result<(), Box<dyn Error>> {
+        if !new_keys.is_empty() {
+            let mut content = new_keys.join("\n");
+            content.push('\n');
+            let mut file = OpenOptions::new().create(true).append(true).open(Path::new(results_dir).join(CHECKPOINT_KEYS_FILE_NAME))?;
+            file.write_all(content.as_bytes())?;
+        }
+        
+        self.updated = time::now_rfc3339();
+        fs::write(Path::new(results_dir).join(CHECKPOINT_FILE_NAME), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    
+    /// Keys handled before the interruption, in selection order
+    pub fn load_keys(results_dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let path = Path::new(results_dir).join(CHECKPOINT_KEYS_FILE_NAME);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+    
+    /// Remove the checkpoint once generation completes
+    pub fn remove(results_dir: &str) -> Result<(), Box<dyn Error>> {
+        for name in [CHECKPOINT_FILE_NAME, CHECKPOINT_KEYS_FILE_NAME] {
+            let path = Path::new(results_dir).join(name);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
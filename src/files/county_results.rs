@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::QueryStatus;
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::snapshot::csv_field;
+
+/// Per-record results of a county correction, written into the results directory
+pub const COUNTY_RESULTS_FILE_NAME: &str = "county_update_results.csv";
+
+/// Write one row per county correction in a results directory with its key, zip code, old
+/// and new county, current status and any error, for review in Excel.
+///
+/// Like the review sheet, the file starts with a UTF-8 byte order mark. Returns the number
+/// of rows written; nothing is written when the directory has no county corrections.
+pub fn write_county_results(results_dir: &str) -> Result<usize, Box<dyn Error>> {
+    let mut query_files = read_query_files(results_dir)?;
+    query_files.sort();
+    
+    let mut content = String::from("\u{feff}key,zip,old_county,new_county,status,error\r\n");
+    let mut count = 0;
+    for file_path in &query_files {
+        let record = match read_query_file(file_path) {
+            Ok(record) => record,
+            Err(e) => {
+                log::warn!("Skipping unreadable query file {}: {}", file_path.display(), e);
+                continue;
+            }
+        };
+        let change = match &record.county_change {
+            Some(change) => change,
+            None => continue,
+        };
+        
+        // Failures and conflicts carry their reason in the record's result
+        let error = match record.status {
+            QueryStatus::Failed | QueryStatus::Conflict | QueryStatus::Quarantined | QueryStatus::Stale => record.result.clone().unwrap_or_default(),
+            _ => String::new(),
+        };
+        content.push_str(&format!(
+            "{},{},{},{},{:?},{}\r\n",
+            csv_field(&record.key),
+            csv_field(&change.zip),
+            csv_field(&change.old_county),
+            csv_field(&change.new_county),
+            record.status,
+            csv_field(&error)
+        ));
+        count += 1;
+    }
+    
+    if count > 0 {
+        fs::write(Path::new(results_dir).join(COUNTY_RESULTS_FILE_NAME), content)?;
+    }
+    Ok(count)
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::db::query::{inline_parameters, parse_update_statement, QueryRecord, QueryStatus};
+use crate::files::json_handler::{read_query_file, read_query_files};
+
+/// Name of the dbaccess script written into the results directory
+pub const DBACCESS_SCRIPT_NAME: &str = "dbaccess_apply.sql";
+
+/// Write a dbaccess-ready script for all pending queries in a results directory.
+///
+/// Statements are grouped into `BEGIN WORK`/`COMMIT WORK` blocks of `batch_size`
+/// statements. Each block is preceded by an `UNLOAD` capturing the pre-image of
+/// the rows it is about to change, so the DBA can restore them with `LOAD`.
+/// Returns the number of statements written.
+pub fn write_dbaccess_script(results_dir: &str, batch_size: usize) -> Result<usize, Box<dyn Error>> {
+    let mut query_files = read_query_files(results_dir)?;
+    query_files.sort();
+
+    let mut records: Vec<QueryRecord> = Vec::new();
+    for file_path in &query_files {
+        let mut record = read_query_file(file_path)?;
+        if record.status == QueryStatus::Pending {
+            // dbaccess can't bind parameters, so bound values go back in as literals
+            if record.has_bound_values() {
+                let values = record.bound_values(results_dir)?;
+                record.query = inline_parameters(&record.query, &values);
+            }
+            records.push(record);
+        }
+    }
+
+    let script_path = Path::new(results_dir).join(DBACCESS_SCRIPT_NAME);
+    let mut writer = BufWriter::new(File::create(&script_path)?);
+
+    writeln!(writer, "-- Generated by informix-batch-processor")?;
+    writeln!(writer, "-- Apply with: dbaccess <database> {}", DBACCESS_SCRIPT_NAME)?;
+    writeln!(writer, "-- Pre-images are unloaded to preimage_NNNN.unl before each block")?;
+    writeln!(writer)?;
+
+    for (block_index, block) in records.chunks(batch_size.max(1)).enumerate() {
+        let block_number = block_index + 1;
+
+        // Collect the WHERE conditions per table so one UNLOAD covers the block
+        let mut conditions: Vec<(String, Vec<String>)> = Vec::new();
+        for record in block {
+            if let Some(parts) = parse_update_statement(&record.query) {
+                if let Some(where_clause) = parts.where_clause {
+                    match conditions.iter_mut().find(|(table, _)| *table == parts.table) {
+                        Some((_, table_conditions)) => table_conditions.push(where_clause),
+                        None => conditions.push((parts.table, vec![where_clause])),
+                    }
+                }
+            }
+        }
+
+        writeln!(writer, "-- Block {} ({} statements)", block_number, block.len())?;
+        for (table_index, (table, table_conditions)) in conditions.iter().enumerate() {
+            let unload_file = if conditions.len() == 1 {
+                format!("preimage_{:04}.unl", block_number)
+            } else {
+                format!("preimage_{:04}_{}.unl", block_number, table_index + 1)
+            };
+            let predicate = table_conditions.iter()
+                .map(|condition| format!("({})", condition))
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            writeln!(writer, "UNLOAD TO '{}' SELECT * FROM {} WHERE {};", unload_file, table, predicate)?;
+        }
+
+        writeln!(writer, "BEGIN WORK;")?;
+        for record in block {
+            writeln!(writer, "-- key: {}", record.key)?;
+            writeln!(writer, "{};", record.query.trim().trim_end_matches(';'))?;
+        }
+        writeln!(writer, "COMMIT WORK;")?;
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+
+    log::info!("Wrote {} statements to dbaccess script {}", records.len(), script_path.display());
+
+    Ok(records.len())
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::query::QueryStatus;
+use crate::files::json_handler::{query_file_exists, read_query_file, read_query_files, remove_query_file, save_query_file};
+
+/// Directory inside a results directory holding records that used up max_record_attempts.
+/// `execute` only reads the results directory itself, so they're never retried automatically.
+pub const DEAD_LETTER_DIR_NAME: &str = "dead_letter";
+
+/// Move a query file into the dead-letter directory of its results directory. A record of
+/// queries.jsonl is written there as a file of its own.
+pub fn move_to_dead_letter(file_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let results_dir = file_path.parent().unwrap_or_else(|| Path::new("."));
+    let dead_letter_dir = results_dir.join(DEAD_LETTER_DIR_NAME);
+    fs::create_dir_all(&dead_letter_dir)?;
+    
+    let target = dead_letter_dir.join(file_path.file_name().ok_or("query file has no name")?);
+    if file_path.is_file() {
+        fs::rename(file_path, &target)
+            .map_err(|e| format!("Failed to move {} to {}: {}", file_path.display(), dead_letter_dir.display(), e))?;
+    } else {
+        let record = read_query_file(file_path)?;
+        fs::write(&target, serde_json::to_string_pretty(&record)?)?;
+        remove_query_file(file_path)?;
+    }
+    Ok(target)
+}
+
+/// Query files in the dead-letter directory of a results directory; none when it has none
+pub fn dead_letter_files(results_dir: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let dead_letter_dir = Path::new(results_dir).join(DEAD_LETTER_DIR_NAME);
+    if !dead_letter_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    read_query_files(&dead_letter_dir.to_string_lossy())
+}
+
+/// Reset a run's Failed records to Pending so the next `execute` tries them again, with
+/// `include_dead_letter` also moving the dead-lettered ones back with a fresh attempt budget.
+/// Returns the number of records re-enqueued from each place.
+pub fn requeue_failed(results_dir: &str, include_dead_letter: bool) -> Result<(usize, usize), Box<dyn Error>> {
+    let mut failed = 0;
+    for file_path in read_query_files(results_dir)? {
+        let mut record = match read_query_file(&file_path) {
+            Ok(record) if record.status == QueryStatus::Failed => record,
+            _ => continue,
+        };
+        record.status = QueryStatus::Pending;
+        save_query_file(&file_path, &record)?;
+        failed += 1;
+    }
+    
+    let mut dead_lettered = 0;
+    if include_dead_letter {
+        for file_path in dead_letter_files(results_dir)? {
+            let mut record = read_query_file(&file_path)
+                .map_err(|e| format!("Failed to read dead-lettered {}: {}", file_path.display(), e))?;
+            let target = Path::new(results_dir).join(file_path.file_name().ok_or("query file has no name")?);
+            if query_file_exists(&target) {
+                log::warn!("Not re-enqueueing {}: {} already exists", file_path.display(), target.display());
+                continue;
+            }
+            // The history stays with the record; only the budget starts over
+            record.status = QueryStatus::Pending;
+            record.failed_attempts = 0;
+            save_query_file(&target, &record)?;
+            fs::remove_file(&file_path)?;
+            dead_lettered += 1;
+        }
+    }
+    Ok((failed, dead_lettered))
+}
@@ -0,0 +1,147 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::ErrorRecord;
+use crate::files::snapshot::csv_field;
+
+/// Error log every phase appends failed records to
+pub const ERRORS_FILE_NAME: &str = "errors.json";
+
+/// Keys listed per group in the printed summary
+const SAMPLE_KEYS: usize = 5;
+
+/// What the `errors` command groups error records by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorGrouping {
+    /// The message with quoted literals and numbers replaced, so errors differing only in
+    /// the row's values fall into one group
+    Message,
+    /// SQLCODE and ISAM error, as in the execution summary
+    Sqlcode,
+    /// The ODBC SQLSTATE
+    Sqlstate,
+    /// The first characters of the key
+    KeyPrefix(usize),
+}
+
+impl ErrorGrouping {
+    pub fn from_name(name: &str, prefix_length: usize) -> Result<Self, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "message" => Ok(ErrorGrouping::Message),
+            "sqlcode" => Ok(ErrorGrouping::Sqlcode),
+            "sqlstate" => Ok(ErrorGrouping::Sqlstate),
+            "key-prefix" | "key_prefix" => Ok(ErrorGrouping::KeyPrefix(prefix_length.max(1))),
+            _ => Err(format!("Unknown grouping '{}' (expected message, sqlcode, sqlstate or key-prefix)", name).into()),
+        }
+    }
+    
+    /// Short name used in the export file name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorGrouping::Message => "message",
+            ErrorGrouping::Sqlcode => "sqlcode",
+            ErrorGrouping::Sqlstate => "sqlstate",
+            ErrorGrouping::KeyPrefix(_) => "key_prefix",
+        }
+    }
+}
+
+/// Error records sharing one label
+#[derive(Debug, Clone, Default)]
+pub struct ErrorGroup {
+    pub label: String,
+    pub count: usize,
+    pub keys: Vec<String>,
+    /// The first error of the group, unaltered
+    pub example: String,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// Read a results directory's error log; a run without one has no errors
+pub fn read_error_records(results_dir: &str) -> Result<Vec<ErrorRecord>, Box<dyn Error>> {
+    let path = Path::new(results_dir).join(ERRORS_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e).into())
+}
+
+/// Group error records, largest group first
+pub fn group_errors(records: &[ErrorRecord], grouping: ErrorGrouping) -> Vec<ErrorGroup> {
+    let literals = Regex::new(r"'[^']*'|-?\b\d+(\.\d+)?\b").unwrap();
+    let mut groups: HashMap<String, ErrorGroup> = HashMap::new();
+    
+    for record in records {
+        let label = match grouping {
+            ErrorGrouping::Message => literals.replace_all(&message_text(record), "?").to_string(),
+            ErrorGrouping::Sqlcode => match (record.sqlcode, record.isam_error) {
+                (Some(sqlcode), Some(isam)) => format!("{} (ISAM {})", sqlcode, isam),
+                (Some(sqlcode), None) => sqlcode.to_string(),
+                (None, Some(isam)) => format!("ISAM {}", isam),
+                (None, None) => "unclassified".to_string(),
+            },
+            ErrorGrouping::Sqlstate => record.sqlstate.clone().unwrap_or_else(|| "unclassified".to_string()),
+            ErrorGrouping::KeyPrefix(length) => record.key.chars().take(length).collect(),
+        };
+        
+        let group = groups.entry(label.clone()).or_insert_with(|| ErrorGroup {
+            label,
+            example: record.error.clone(),
+            first_seen: record.timestamp.clone(),
+            last_seen: record.timestamp.clone(),
+            ..ErrorGroup::default()
+        });
+        group.count += 1;
+        group.keys.push(record.key.clone());
+        group.first_seen = group.first_seen.clone().min(record.timestamp.clone());
+        group.last_seen = group.last_seen.clone().max(record.timestamp.clone());
+    }
+    
+    let mut groups: Vec<ErrorGroup> = groups.into_values().collect();
+    groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+    groups
+}
+
+/// The driver's message inside a debug-formatted ODBC error, or the whole text
+fn message_text(record: &ErrorRecord) -> String {
+    if let Some(text) = record.message_chain.last() {
+        return text.clone();
+    }
+    // Records written before diagnostics were structured hold the ODBC error's debug format
+    Regex::new(r#"message: "((?:[^"\\]|\\.)*)""#)
+        .unwrap()
+        .captures(&record.error)
+        .map(|caps| caps[1].to_string())
+        .unwrap_or_else(|| record.error.clone())
+}
+
+/// Summary lines for one group, with a few of its keys and its example error
+pub fn group_lines(group: &ErrorGroup) -> Vec<String> {
+    let more = if group.keys.len() > SAMPLE_KEYS { format!(" and {} more", group.keys.len() - SAMPLE_KEYS) } else { String::new() };
+    vec![
+        format!("{:>6}  {}", group.count, group.label),
+        format!("        keys: {}{}", group.keys.iter().take(SAMPLE_KEYS).cloned().collect::<Vec<_>>().join(", "), more),
+        format!("        e.g.: {}", group.example),
+    ]
+}
+
+/// Write the groups as `errors_by_<grouping>.csv` in the results directory, returning its path
+pub fn export_error_groups(results_dir: &str, grouping: ErrorGrouping, groups: &[ErrorGroup]) -> Result<String, Box<dyn Error>> {
+    let mut csv = String::from("group,count,first_seen,last_seen,keys,example\r\n");
+    for group in groups {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\r\n",
+            csv_field(&group.label), group.count, group.first_seen, group.last_seen,
+            csv_field(&group.keys.join(" ")), csv_field(&group.example)
+        ));
+    }
+    
+    let path = Path::new(results_dir).join(format!("errors_by_{}.csv", grouping.name()));
+    fs::write(&path, csv)?;
+    Ok(path.to_string_lossy().to_string())
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::db::query::QueryStatus;
+use crate::utils::operator::{self, OperatorIdentity};
+use crate::utils::{run_id, time};
+
+/// Append-only log of status transitions, kept in the results directory
+pub const EVENTS_FILE_NAME: &str = "events.jsonl";
+
+/// Serializes appends from this process; each event is written with a single append so
+/// separate processes sharing a results directory don't interleave lines
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// One change of a query record's status
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatusEvent {
+    pub key: String,
+    /// None when the record was created
+    pub from: Option<QueryStatus>,
+    pub to: QueryStatus,
+    pub timestamp: String,
+    /// Process that made the change, as `pid@host`
+    pub worker: String,
+    pub run_id: String,
+    /// Who ran the process, for attributing each change to a person and machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<OperatorIdentity>,
+}
+
+/// Append a status transition to the results directory's event log
+pub fn append_event(results_dir: &Path, key: &str, from: Option<QueryStatus>, to: QueryStatus) -> Result<(), Box<dyn Error>> {
+    let event = StatusEvent {
+        key: key.to_string(),
+        from,
+        to,
+        timestamp: time::now_rfc3339(),
+        worker: worker_name(),
+        run_id: run_id::current().to_string(),
+        operator: Some(operator::current().clone()),
+    };
+    let mut line = serde_json::to_string(&event)?;
+    line.push('\n');
+    
+    let _guard = APPEND_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut file = OpenOptions::new().create(true).append(true).open(results_dir.join(EVENTS_FILE_NAME))?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Read a results directory's event log in order; unreadable lines are skipped
+pub fn read_events(results_dir: &str) -> Result<Vec<StatusEvent>, Box<dyn Error>> {
+    let path = Path::new(results_dir).join(EVENTS_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    
+    let mut events = Vec::new();
+    for (line_number, line) in fs::read_to_string(&path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<StatusEvent>(line) {
+            Ok(event) => events.push(event),
+            Err(e) => log::warn!("Ignoring unreadable line {} of {}: {}", line_number + 1, path.display(), e),
+        }
+    }
+    Ok(events)
+}
+
+/// The status of every key as of its last event, reconstructed without the query files
+pub fn replay_statuses(results_dir: &str) -> Result<BTreeMap<String, QueryStatus>, Box<dyn Error>> {
+    let mut statuses = BTreeMap::new();
+    for event in read_events(results_dir)? {
+        statuses.insert(event.key, event.to);
+    }
+    Ok(statuses)
+}
+
+/// Count the reconstructed statuses
+pub fn replayed_status_summary(results_dir: &str) -> Result<BTreeMap<String, usize>, Box<dyn Error>> {
+    let mut counts = BTreeMap::new();
+    for status in replay_statuses(results_dir)?.values() {
+        *counts.entry(format!("{:?}", status)).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
+
+fn worker_name() -> String {
+    format!("{}@{}", std::process::id(), operator::host_name())
+}
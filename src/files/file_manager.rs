@@ -20,25 +20,3 @@ pub fn setup_directories(results_dir: &str, clean: bool) -> Result<(), Box<dyn E
     
     Ok(())
 }
-
-/// Check if a file exists
-pub fn file_exists(file_path: &str) -> bool {
-    Path::new(file_path).exists()
-}
-
-/// Count number of files in a directory with specific extension
-pub fn count_files(dir_path: &str, extension: &str) -> Result<usize, Box<dyn Error>> {
-    let mut count = 0;
-    
-    for entry in fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.is_file() && 
-           path.extension().map_or(false, |ext| ext == extension) {
-            count += 1;
-        }
-    }
-    
-    Ok(count)
-}
\ No newline at end of file
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::prelude::*;
+
+use crate::config::AppConfig;
+use crate::db::load_monitor::LoadReading;
+use crate::utils::{run_id, time};
+
+/// Phase recorded once a command has finished normally
+pub const FINISHED_PHASE: &str = "finished";
+
+/// Heartbeat file in the results directory, unless `heartbeat_path` names another
+pub const HEARTBEAT_FILE_NAME: &str = "heartbeat.json";
+
+/// Liveness record written periodically so external monitors can tell slow from hung
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Heartbeat {
+    pub timestamp: String,
+    #[serde(default)]
+    pub run_id: String,
+    pub pid: u32,
+    pub phase: String,
+    pub position: u64,
+    pub total: u64,
+    pub message: String,
+    /// When the position or message last changed; the timestamp only shows the process is alive
+    #[serde(default)]
+    pub progress_at: String,
+    pub results_dir: String,
+    /// Last sysmaster reading of the load monitor, when it runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<LoadReading>,
+}
+
+impl Heartbeat {
+    /// Seconds since this heartbeat was written
+    pub fn age_seconds(&self) -> Option<i64> {
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .ok()
+            .map(|written| (Utc::now() - written.with_timezone(&Utc)).num_seconds())
+    }
+}
+
+struct HeartbeatWriter {
+    path: PathBuf,
+    interval: Duration,
+    results_dir: String,
+    phase: String,
+    position: u64,
+    total: u64,
+    message: String,
+    progress_at: String,
+    load: Option<LoadReading>,
+    last_write: Option<Instant>,
+}
+
+static HEARTBEAT: OnceLock<Mutex<HeartbeatWriter>> = OnceLock::new();
+
+/// Where a run's heartbeat is written: `heartbeat_path`, or the results directory, so
+/// concurrent runs never share one
+pub fn heartbeat_path(config: &AppConfig, results_dir: &str) -> PathBuf {
+    if config.heartbeat_path.trim().is_empty() {
+        Path::new(results_dir).join(HEARTBEAT_FILE_NAME)
+    } else {
+        PathBuf::from(config.heartbeat_path.trim())
+    }
+}
+
+/// Start writing heartbeats for this process. Later calls are ignored.
+///
+/// A background thread rewrites the last position every interval, so a long statement or
+/// fetch that reports no progress still shows the process is alive.
+pub fn init(config: &AppConfig, results_dir: &str) {
+    let interval = Duration::from_secs(config.heartbeat_interval_seconds.max(1));
+    let writer = HeartbeatWriter {
+        path: heartbeat_path(config, results_dir),
+        interval,
+        results_dir: results_dir.to_string(),
+        phase: "starting".to_string(),
+        position: 0,
+        total: 0,
+        message: String::new(),
+        progress_at: time::now_rfc3339(),
+        load: None,
+        last_write: None,
+    };
+    if HEARTBEAT.set(Mutex::new(writer)).is_err() {
+        return;
+    }
+    
+    let ticker = thread::Builder::new().name("heartbeat".to_string()).spawn(move || loop {
+        thread::sleep(interval);
+        tick();
+    });
+    if let Err(e) = ticker {
+        log::warn!("Failed to start the heartbeat thread; heartbeats are only written on progress: {}", e);
+    }
+}
+
+/// Record the start of a new phase, writing a heartbeat immediately
+pub fn set_phase(phase: &str) {
+    if let Some(writer) = HEARTBEAT.get() {
+        if let Ok(mut writer) = writer.lock() {
+            writer.phase = phase.to_string();
+            writer.progress(0, 0, phase);
+            writer.write();
+        }
+    }
+}
+
+/// Keep a server load reading for the heartbeats that follow
+pub fn record_load(reading: &LoadReading) {
+    if let Some(writer) = HEARTBEAT.get() {
+        if let Ok(mut writer) = writer.lock() {
+            writer.load = Some(reading.clone());
+        }
+    }
+}
+
+/// Record progress, writing a heartbeat if the configured interval has passed since the last one
+pub fn beat(position: u64, total: u64, message: &str) {
+    if let Some(writer) = HEARTBEAT.get() {
+        if let Ok(mut writer) = writer.lock() {
+            writer.progress(position, total, message);
+            if writer.is_due() {
+                writer.write();
+            }
+        }
+    }
+}
+
+/// Rewrite the last progress once the interval has passed without a beat
+fn tick() {
+    if let Some(writer) = HEARTBEAT.get() {
+        if let Ok(mut writer) = writer.lock() {
+            if writer.is_due() {
+                writer.write();
+            }
+        }
+    }
+}
+
+impl HeartbeatWriter {
+    fn progress(&mut self, position: u64, total: u64, message: &str) {
+        if position != self.position || total != self.total || message != self.message {
+            self.position = position;
+            self.total = total;
+            self.message = message.to_string();
+            self.progress_at = time::now_rfc3339();
+        }
+    }
+    
+    fn is_due(&self) -> bool {
+        self.last_write.is_none_or(|last| last.elapsed() >= self.interval)
+    }
+    
+    fn write(&mut self) {
+        let heartbeat = Heartbeat {
+            timestamp: time::now_rfc3339(),
+            run_id: run_id::current().to_string(),
+            pid: std::process::id(),
+            phase: self.phase.clone(),
+            position: self.position,
+            total: self.total,
+            message: self.message.clone(),
+            progress_at: self.progress_at.clone(),
+            results_dir: self.results_dir.clone(),
+            load: self.load.clone(),
+        };
+        
+        // Write to a temporary file and rename so monitors never read a partial file
+        let temp_path = self.path.with_extension("json.tmp");
+        let result = serde_json::to_string_pretty(&heartbeat)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&temp_path, json).map_err(|e| e.to_string()))
+            .and_then(|_| fs::rename(&temp_path, &self.path).map_err(|e| e.to_string()));
+        
+        match result {
+            Ok(()) => self.last_write = Some(Instant::now()),
+            Err(e) => log::warn!("Failed to write heartbeat {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Read the last heartbeat written to a file
+pub fn read_heartbeat<P: AsRef<Path>>(file_path: P) -> Result<Heartbeat, Box<dyn Error>> {
+    let content = fs::read_to_string(file_path)?;
+    Ok(serde_json::from_str(&content)?)
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, TimeZone};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::QueryStatus;
+use crate::files::events::read_events;
+use crate::files::snapshot::csv_field;
+
+/// Errors broken down by worker, chunk and time bucket, written by `report`
+pub const ERROR_ISOLATION_FILE_NAME: &str = "error_isolation.csv";
+
+/// A group is flagged when its error rate is this many times the run's
+const OUTLIER_FACTOR: f64 = 2.0;
+
+/// Groups with fewer executed queries than this are never flagged
+const OUTLIER_MIN_EXECUTED: usize = 20;
+
+/// Executed and failed queries of one worker, chunk or time bucket
+#[derive(Debug, Clone, Default)]
+pub struct IsolationGroup {
+    /// "worker", "chunk" or "time"
+    pub dimension: String,
+    pub label: String,
+    pub executed: usize,
+    pub failed: usize,
+    /// Error rate well above the run's, pointing at that worker or period rather than the data
+    pub outlier: bool,
+}
+
+impl IsolationGroup {
+    pub fn error_rate(&self) -> f64 {
+        if self.executed == 0 { 0.0 } else { self.failed as f64 / self.executed as f64 }
+    }
+}
+
+/// Break the executions in a run's event log down by worker (process and host), by chunk of
+/// `chunk_size` consecutive executions of each worker, and by `bucket_minutes` of wall time.
+/// Failures spread evenly are data-dependent; failures concentrated in one worker or period
+/// point at a degraded connection or server, and those groups are flagged.
+pub fn error_isolation(results_dir: &str, chunk_size: usize, bucket_minutes: i64) -> Result<Vec<IsolationGroup>, Box<dyn Error>> {
+    let mut groups: BTreeMap<(String, String), IsolationGroup> = BTreeMap::new();
+    let mut executed_by_worker: BTreeMap<String, usize> = BTreeMap::new();
+    let bucket_seconds = bucket_minutes.max(1) * 60;
+    let (mut executed, mut failed) = (0, 0);
+    
+    for event in read_events(results_dir)? {
+        let is_failure = match event.to {
+            QueryStatus::Failed => true,
+            QueryStatus::Completed | QueryStatus::Conflict => false,
+            _ => continue,
+        };
+        // Only the outcome of running a pending statement counts, not manual status changes
+        if event.from != Some(QueryStatus::InProgress) && event.from != Some(QueryStatus::Pending) {
+            continue;
+        }
+        executed += 1;
+        failed += is_failure as usize;
+        
+        let position = executed_by_worker.entry(event.worker.clone()).or_insert(0);
+        let chunk = *position / chunk_size.max(1);
+        *position += 1;
+        
+        let time = DateTime::parse_from_rfc3339(&event.timestamp)
+            .ok()
+            .and_then(|time| {
+                let floored = time.timestamp() - time.timestamp().rem_euclid(bucket_seconds);
+                time.offset().timestamp_opt(floored, 0).single()
+            })
+            .map(|time| time.format("%Y-%m-%d %H:%M %:z").to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        
+        let labels = [
+            ("worker", format!("{} (run {})", event.worker, event.run_id)),
+            ("chunk", format!("{} #{}", event.worker, chunk + 1)),
+            ("time", time),
+        ];
+        for (dimension, label) in labels {
+            let group = groups.entry((dimension.to_string(), label.clone())).or_insert_with(|| IsolationGroup {
+                dimension: dimension.to_string(),
+                label,
+                ..IsolationGroup::default()
+            });
+            group.executed += 1;
+            group.failed += is_failure as usize;
+        }
+    }
+    
+    let overall = if executed == 0 { 0.0 } else { failed as f64 / executed as f64 };
+    let mut groups: Vec<IsolationGroup> = groups.into_values().collect();
+    for group in &mut groups {
+        group.outlier = group.failed > 0
+            && group.executed >= OUTLIER_MIN_EXECUTED
+            && group.error_rate() > overall * OUTLIER_FACTOR;
+    }
+    Ok(groups)
+}
+
+/// Write the breakdown as CSV, one row per group
+pub fn write_error_isolation(results_dir: &str, groups: &[IsolationGroup]) -> Result<(), Box<dyn Error>> {
+    let mut csv = String::from("dimension,group,executed,failed,error_rate,outlier\r\n");
+    for group in groups {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.4},{}\r\n",
+            group.dimension, csv_field(&group.label), group.executed, group.failed, group.error_rate(), group.outlier
+        ));
+    }
+    fs::write(Path::new(results_dir).join(ERROR_ISOLATION_FILE_NAME), csv)?;
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::{run_id, time};
+
+/// Write-ahead journal of the statements execution sends, kept in the results directory
+pub const JOURNAL_FILE_NAME: &str = "execution_journal.jsonl";
+
+/// Serializes appends from this process; each entry is a single append, like the event log
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalPhase {
+    /// Written before the statement is sent
+    Intent,
+    /// Written once the statement's outcome is known and saved in its query file
+    Completion,
+}
+
+/// One entry of the execution journal
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub phase: JournalPhase,
+    pub key: String,
+    pub change_hash: String,
+    pub run_id: String,
+    pub timestamp: String,
+    /// How the statement ended, on completion entries: "committed", "failed", "rolled back", ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+}
+
+/// The execution journal of a results directory, with the intents earlier executions left
+/// without a completion. A statement whose intent is open may or may not have committed;
+/// execution decides which from a verification read instead of quarantining the record.
+#[derive(Debug)]
+pub struct ExecutionJournal {
+    path: PathBuf,
+    open_intents: HashMap<String, JournalEntry>,
+}
+
+impl ExecutionJournal {
+    /// Open the journal of a results directory, reading the intents still open
+    pub fn open(results_dir: &str) -> Result<Self, Box<dyn Error>> {
+        let path = Path::new(results_dir).join(JOURNAL_FILE_NAME);
+        let mut open_intents = HashMap::new();
+        if path.exists() {
+            for (line_number, line) in fs::read_to_string(&path)?.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<JournalEntry>(line) {
+                    Ok(entry) if entry.phase == JournalPhase::Intent => {
+                        open_intents.insert(entry.key.clone(), entry);
+                    },
+                    Ok(entry) => {
+                        open_intents.remove(&entry.key);
+                    },
+                    Err(e) => log::warn!("Ignoring unreadable line {} of {}: {}", line_number + 1, path.display(), e),
+                }
+            }
+        }
+        if !open_intents.is_empty() {
+            log::warn!("{} has {} statements whose outcome was never recorded", path.display(), open_intents.len());
+        }
+        Ok(ExecutionJournal { path, open_intents })
+    }
+    
+    /// The open intent an interrupted execution left for this change, if any
+    pub fn open_intent(&self, key: &str, change_hash: &str) -> Option<&JournalEntry> {
+        self.open_intents.get(key).filter(|entry| entry.change_hash == change_hash)
+    }
+    
+    /// Record that a statement is about to be sent
+    pub fn record_intent(&self, key: &str, change_hash: &str) -> Result<(), Box<dyn Error>> {
+        self.append(JournalPhase::Intent, key, change_hash, None)
+    }
+    
+    /// Record how a statement ended, after its query file was saved
+    pub fn record_completion(&self, key: &str, change_hash: &str, outcome: &str) -> Result<(), Box<dyn Error>> {
+        self.append(JournalPhase::Completion, key, change_hash, Some(outcome.to_string()))
+    }
+    
+    fn append(&self, phase: JournalPhase, key: &str, change_hash: &str, outcome: Option<String>) -> Result<(), Box<dyn Error>> {
+        let entry = JournalEntry {
+            phase,
+            key: key.to_string(),
+            change_hash: change_hash.to_string(),
+            run_id: run_id::current().to_string(),
+            timestamp: time::now_rfc3339(),
+            outcome,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        
+        let _guard = APPEND_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        // The intent must be on disk before the statement reaches the server
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn only_intents_without_a_completion_stay_open() {
+        let dir = std::env::temp_dir().join(format!("ibp-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let results_dir = dir.to_string_lossy().to_string();
+        
+        let journal = ExecutionJournal::open(&results_dir).unwrap();
+        journal.record_intent("1001", "hash-a").unwrap();
+        journal.record_completion("1001", "hash-a", "committed").unwrap();
+        journal.record_intent("1002", "hash-b").unwrap();
+        // A line torn by a crash mid-append is skipped, not fatal
+        OpenOptions::new().append(true).open(dir.join(JOURNAL_FILE_NAME)).unwrap().write_all(b"{\"phase\":\"int").unwrap();
+        
+        let reopened = ExecutionJournal::open(&results_dir).unwrap();
+        assert!(reopened.open_intent("1001", "hash-a").is_none());
+        assert_eq!(reopened.open_intent("1002", "hash-b").map(|entry| entry.run_id.as_str()), Some(run_id::current()));
+        // A different change to the same key isn't the interrupted statement
+        assert!(reopened.open_intent("1002", "hash-c").is_none());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
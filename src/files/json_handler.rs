@@ -1,22 +1,272 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufReader, Write};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use crate::db::query::{QueryRecord, ErrorRecord};
+use crate::db::query::{QueryRecord, ErrorRecord, PREFLIGHT_FILE_NAME, PURGE_SUMMARY_FILE_NAME};
+use crate::files::analytics::ANALYTICS_JSON_FILE_NAME;
+use crate::files::checkpoint::CHECKPOINT_FILE_NAME;
+use crate::files::errors::ERRORS_FILE_NAME;
+use crate::files::events::append_event;
+use crate::files::heartbeat::HEARTBEAT_FILE_NAME;
+use crate::files::manifest::MANIFEST_FILE_NAME;
 
-/// Save a query record to a JSON file
+/// Queries of a results directory in manifest mode, one JSON line per record in generation order
+pub const QUERIES_FILE_NAME: &str = "queries.jsonl";
+/// Later versions of manifest-mode records, appended as they change; a key's last line wins
+pub const QUERY_STATUS_FILE_NAME: &str = "query_status.jsonl";
+/// JSON files a results directory holds besides its query files. Every `.json` file written
+/// into a results directory belongs here, or it's read back as a (failing) query record.
+pub const AUXILIARY_JSON_FILE_NAMES: &[&str] = &[
+    ERRORS_FILE_NAME,
+    MANIFEST_FILE_NAME,
+    PREFLIGHT_FILE_NAME,
+    PURGE_SUMMARY_FILE_NAME,
+    CHECKPOINT_FILE_NAME,
+    ANALYTICS_JSON_FILE_NAME,
+    HEARTBEAT_FILE_NAME,
+];
+
+/// Whether a file name of a results directory is a query file's
+pub fn is_query_file_name(name: &str) -> bool {
+    name.ends_with(".json") && !AUXILIARY_JSON_FILE_NAMES.contains(&name)
+}
+
+/// Store for the query records of a results directory, from `state_backend`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateBackend {
+    /// One JSON file per key
+    Files,
+    /// queries.jsonl and query_status.jsonl
+    Jsonl,
+    /// One file per key, with the processed records in state.sqlite
+    Sqlite,
+}
+
+impl StateBackend {
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "files" => Ok(StateBackend::Files),
+            "jsonl" => Ok(StateBackend::Jsonl),
+            "sqlite" if cfg!(feature = "sqlite") => Ok(StateBackend::Sqlite),
+            "sqlite" => Err("state_backend \"sqlite\" needs a build with the sqlite feature (cargo build --release --features sqlite)".into()),
+            _ => Err(format!("Unknown state_backend '{}' (expected 'files', 'jsonl' or 'sqlite')", name).into()),
+        }
+    }
+}
+
+/// Indexes of the manifest-mode results directories this process has touched
+static MANIFESTS: Mutex<BTreeMap<PathBuf, ManifestIndex>> = Mutex::new(BTreeMap::new());
+
+/// A version of a manifest-mode record in the status file; no record means it was removed
+#[derive(Serialize, Deserialize)]
+struct StatusLine {
+    stem: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record: Option<QueryRecord>,
+}
+
+/// Where the latest version of a manifest-mode record is
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    in_status_file: bool,
+    offset: u64,
+    length: usize,
+}
+
+#[derive(Debug, Default)]
+struct ManifestIndex {
+    /// Bytes of each file indexed so far; lines other workers append are indexed on the next access
+    queries_indexed: u64,
+    status_indexed: u64,
+    /// Latest version of each record by file stem; None once removed
+    locations: HashMap<String, Option<Location>>,
+    /// File stems in the order they were generated
+    order: Vec<String>,
+}
+
+impl ManifestIndex {
+    /// Index the lines appended to either file since the last call
+    fn refresh(&mut self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let (lines, indexed) = complete_lines(&dir.join(QUERIES_FILE_NAME), self.queries_indexed)?;
+        for (offset, line) in lines {
+            let record: QueryRecord = serde_json::from_str(&line)
+                .map_err(|e| format!("Unreadable line at byte {} of {}/{}: {}", offset, dir.display(), QUERIES_FILE_NAME, e))?;
+            self.locate(record.file_stem(), Some(Location { in_status_file: false, offset, length: line.len() }));
+        }
+        self.queries_indexed = indexed;
+        
+        let (lines, indexed) = complete_lines(&dir.join(QUERY_STATUS_FILE_NAME), self.status_indexed)?;
+        for (offset, line) in lines {
+            let status: StatusLine = serde_json::from_str(&line)
+                .map_err(|e| format!("Unreadable line at byte {} of {}/{}: {}", offset, dir.display(), QUERY_STATUS_FILE_NAME, e))?;
+            let location = status.record.map(|_| Location { in_status_file: true, offset, length: line.len() });
+            self.locate(status.stem, location);
+        }
+        self.status_indexed = indexed;
+        Ok(())
+    }
+    
+    fn locate(&mut self, stem: String, location: Option<Location>) {
+        if !self.locations.contains_key(&stem) {
+            self.order.push(stem.clone());
+        }
+        self.locations.insert(stem, location);
+    }
+}
+
+/// Lines read from a file, each with its offset, and the offset where they end
+type OffsetLines = (Vec<(u64, String)>, u64);
+
+/// The complete lines of a file from an offset, with the offset of each, and where they end.
+/// A line a concurrent writer hasn't finished is left for the next call.
+fn complete_lines(path: &Path, from: u64) -> Result<OffsetLines, Box<dyn Error>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), from)),
+        Err(e) => return Err(e.into()),
+    };
+    if file.metadata()?.len() <= from {
+        return Ok((Vec::new(), from));
+    }
+    file.seek(SeekFrom::Start(from))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    
+    let mut lines = Vec::new();
+    let mut offset = from;
+    let mut rest = content.as_str();
+    while let Some(end) = rest.find('\n') {
+        let line = &rest[..end];
+        if !line.trim().is_empty() {
+            lines.push((offset, line.to_string()));
+        }
+        offset += end as u64 + 1;
+        rest = &rest[end + 1..];
+    }
+    Ok((lines, offset))
+}
+
+/// Run a closure on the refreshed index of a manifest-mode results directory
+fn with_manifest<T>(dir: &Path, action: impl FnOnce(&mut ManifestIndex) -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+    let mut manifests = MANIFESTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let index = manifests.entry(dir.to_path_buf()).or_default();
+    index.refresh(dir)?;
+    action(index)
+}
+
+/// Keep a results directory's queries in queries.jsonl from now on instead of one file per key
+pub fn enable_query_manifest(results_dir: &str) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(results_dir);
+    OpenOptions::new().create(true).append(true).open(dir.join(QUERIES_FILE_NAME))?;
+    MANIFESTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(dir);
+    Ok(())
+}
+
+/// Whether a results directory keeps its queries in queries.jsonl
+pub fn is_query_manifest(results_dir: &Path) -> bool {
+    results_dir.join(QUERIES_FILE_NAME).is_file()
+}
+
+/// The results directory and file stem of a query path that names a manifest-mode record rather
+/// than a file on disk
+fn manifest_record_path(file_path: &Path) -> Option<(&Path, String)> {
+    if file_path.is_file() {
+        return None;
+    }
+    let dir = file_path.parent().filter(|dir| is_query_manifest(dir))?;
+    let stem = file_path.file_stem()?.to_string_lossy().to_string();
+    Some((dir, stem))
+}
+
+/// Whether a query file, or the manifest-mode record a path names, exists
+pub fn query_file_exists<P: AsRef<Path>>(file_path: P) -> bool {
+    let file_path = file_path.as_ref();
+    match manifest_record_path(file_path) {
+        Some((dir, stem)) => with_manifest(dir, |index| Ok(matches!(index.locations.get(&stem), Some(Some(_))))).unwrap_or(false),
+        None => file_path.is_file(),
+    }
+}
+
+/// Remove a query file, or drop a manifest-mode record from its directory's listing
+pub fn remove_query_file<P: AsRef<Path>>(file_path: P) -> Result<(), Box<dyn Error>> {
+    let file_path = file_path.as_ref();
+    match manifest_record_path(file_path) {
+        Some((dir, stem)) => append_manifest_line(dir, QUERY_STATUS_FILE_NAME, &StatusLine { stem, record: None }),
+        None => Ok(fs::remove_file(file_path)?),
+    }
+}
+
+/// Append one line to a file of a manifest-mode directory and index it
+fn append_manifest_line<T: Serialize>(dir: &Path, file_name: &str, value: &T) -> Result<(), Box<dyn Error>> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    with_manifest(dir, |index| {
+        let mut file = OpenOptions::new().create(true).append(true).open(dir.join(file_name))?;
+        file.write_all(line.as_bytes())?;
+        index.refresh(dir)
+    })
+}
+
+fn read_manifest_record(dir: &Path, stem: &str) -> Result<QueryRecord, Box<dyn Error>> {
+    let location = with_manifest(dir, |index| Ok(index.locations.get(stem).copied().flatten()))?
+        .ok_or_else(|| format!("No query for {} in {}/{}", stem, dir.display(), QUERIES_FILE_NAME))?;
+    let file_name = if location.in_status_file { QUERY_STATUS_FILE_NAME } else { QUERIES_FILE_NAME };
+    let mut file = File::open(dir.join(file_name))?;
+    file.seek(SeekFrom::Start(location.offset))?;
+    let mut line = vec![0; location.length];
+    file.read_exact(&mut line)?;
+    if location.in_status_file {
+        let status: StatusLine = serde_json::from_slice(&line)?;
+        status.record.ok_or_else(|| format!("Query for {} was removed", stem).into())
+    } else {
+        Ok(serde_json::from_slice(&line)?)
+    }
+}
+
+/// Save a query record to a JSON file, logging a status change to the directory's event log.
+/// In a manifest-mode directory a new record is appended to queries.jsonl and a changed one to
+/// query_status.jsonl.
 pub fn save_query_file<P: AsRef<Path>>(file_path: P, query_record: &QueryRecord) -> Result<(), Box<dyn Error>> {
-    let json = serde_json::to_string_pretty(query_record)?;
+    let file_path = file_path.as_ref();
+    let previous_status = if query_file_exists(file_path) {
+        read_query_file(file_path).ok().map(|record| record.status)
+    } else {
+        None
+    };
     
-    let mut file = File::create(file_path)?;
-    file.write_all(json.as_bytes())?;
+    match manifest_record_path(file_path) {
+        Some((dir, stem)) => {
+            let known = with_manifest(dir, |index| Ok(index.locations.contains_key(&stem)))?;
+            if known {
+                append_manifest_line(dir, QUERY_STATUS_FILE_NAME, &StatusLine { stem, record: Some(query_record.clone()) })?;
+            } else {
+                append_manifest_line(dir, QUERIES_FILE_NAME, query_record)?;
+            }
+        },
+        None => {
+            let json = serde_json::to_string_pretty(query_record)?;
+            
+            let mut file = File::create(file_path)?;
+            file.write_all(json.as_bytes())?;
+        },
+    }
+    
+    if previous_status != Some(query_record.status) {
+        let results_dir = file_path.parent().unwrap_or_else(|| Path::new("."));
+        if let Err(e) = append_event(results_dir, &query_record.key, previous_status, query_record.status) {
+            log::warn!("Failed to record status event for key {}: {}", query_record.key, e);
+        }
+    }
     
     Ok(())
 }
 
-/// Read all query files from a directory
+/// Read all query files from a directory, followed by the records of its queries.jsonl in the
+/// order they were generated
 pub fn read_query_files(dir_path: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
     let mut query_files = Vec::new();
     
@@ -24,13 +274,24 @@ pub fn read_query_files(dir_path: &str) -> Result<Vec<PathBuf>, Box<dyn Error>>
         let entry = entry?;
         let path = entry.path();
         
-        if path.is_file() && 
-           path.extension().map_or(false, |ext| ext == "json") && 
-           path.file_name().map_or(false, |name| name != "errors.json") {
+        if path.is_file() && path.file_name().and_then(|name| name.to_str()).is_some_and(is_query_file_name) {
             query_files.push(path);
         }
     }
     
+    let dir = Path::new(dir_path);
+    if is_query_manifest(dir) {
+        let stems = with_manifest(dir, |index| {
+            Ok(index.order.iter().filter(|stem| matches!(index.locations.get(*stem), Some(Some(_)))).cloned().collect::<Vec<_>>())
+        })?;
+        for stem in stems {
+            let path = dir.join(format!("{}.json", stem));
+            if !path.is_file() {
+                query_files.push(path);
+            }
+        }
+    }
+    
     Ok(query_files)
 }
 
@@ -59,14 +320,17 @@ pub fn save_error_file<P: AsRef<Path>>(file_path: P, error_record: &ErrorRecord)
         .create(true)
         .truncate(true)
         .open(file_path)?;
-        
+    
     file.write_all(json.as_bytes())?;
     
     Ok(())
 }
 
-/// Read a query record from a file
+/// Read a query record from a file, or from queries.jsonl of a manifest-mode directory
 pub fn read_query_file<P: AsRef<Path>>(file_path: P) -> Result<QueryRecord, Box<dyn Error>> {
+    if let Some((dir, stem)) = manifest_record_path(file_path.as_ref()) {
+        return read_manifest_record(dir, &stem);
+    }
     let file = File::open(file_path)?;
     let reader = BufReader::new(file);
     let query_record = serde_json::from_reader(reader)?;
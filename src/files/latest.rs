@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Pointer file naming the results directory of the most recent generation run, written next
+/// to the results directories (a plain file rather than a symlink, which Windows restricts)
+pub const LATEST_FILE_NAME: &str = "latest";
+
+/// Point `latest` at a results directory
+pub fn update_latest(results_dir: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(LATEST_FILE_NAME, format!("{}\n", results_dir))
+        .map_err(|e| format!("Failed to update {}: {}", LATEST_FILE_NAME, e))?;
+    log::info!("{} now points to {}", LATEST_FILE_NAME, results_dir);
+    Ok(())
+}
+
+/// The results directory `latest` points to, if it still exists
+pub fn latest_results_dir() -> Option<String> {
+    let results_dir = fs::read_to_string(LATEST_FILE_NAME).ok()?.trim().to_string();
+    if !results_dir.is_empty() && Path::new(&results_dir).is_dir() {
+        Some(results_dir)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::utils::{operator, run_id, time};
+
+/// Directory of key leases, kept next to the processed records file so every results
+/// directory and every worker shares it
+pub const LEASE_DIR_NAME: &str = "key_leases";
+
+/// Claim of one worker on one key while it executes that key's statements
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyLease {
+    pub key: String,
+    pub run_id: String,
+    pub pid: u32,
+    pub operator: String,
+    pub results_dir: String,
+    pub acquired: String,
+    pub expires: String,
+}
+
+impl KeyLease {
+    /// Whether the lease ran out, so a worker that crashed holding it no longer blocks the key
+    fn is_expired(&self) -> bool {
+        DateTime::parse_from_rfc3339(&self.expires)
+            .map(|expires| expires.with_timezone(&Utc) <= Utc::now())
+            .unwrap_or(true)
+    }
+    
+    fn is_ours(&self) -> bool {
+        self.run_id == run_id::current() && self.pid == std::process::id()
+    }
+    
+    /// "run 3f2c... (pid 4242, jdoe@host) in results/2026-10-14 until 2026-10-14T10:15:00Z"
+    fn describe(&self) -> String {
+        format!("run {} (pid {}, {}) in {} until {}", self.run_id, self.pid, self.operator, self.results_dir, self.expires)
+    }
+}
+
+/// Result of asking for a key's lease
+pub enum LeaseOutcome<'a> {
+    /// This worker holds the lease until the value is dropped
+    Acquired(HeldLease<'a>),
+    /// Another live worker holds it, described for the log
+    HeldBy(String),
+}
+
+/// A lease this worker holds; dropping it releases the key
+#[derive(Debug)]
+pub struct HeldLease<'a> {
+    store: &'a LeaseStore,
+    path: PathBuf,
+    /// Half way to expiry, when `renew_if_due` extends the lease again
+    renew_after: Instant,
+}
+
+impl HeldLease<'_> {
+    /// Extend the lease by the lease duration once half of it has passed, so a key whose
+    /// batch is still open isn't taken over as if its worker had crashed
+    pub fn renew_if_due(&mut self) {
+        if Instant::now() < self.renew_after {
+            return;
+        }
+        self.store.renew(&self.path);
+        self.renew_after = Instant::now() + self.store.duration / 2;
+    }
+}
+
+impl Drop for HeldLease<'_> {
+    fn drop(&mut self) {
+        self.store.release(&self.path);
+    }
+}
+
+/// Lease files, one per key being executed. A lease is taken by creating its file, which
+/// only one process can do, so two workers never execute the same key at the same time.
+#[derive(Debug)]
+pub struct LeaseStore {
+    dir: PathBuf,
+    duration: Duration,
+    results_dir: String,
+}
+
+impl LeaseStore {
+    /// The store beside `data_path`, handing out leases that last `seconds`
+    pub fn open(data_path: &str, results_dir: &str, seconds: u64) -> Result<Self, Box<dyn Error>> {
+        let dir = Path::new(data_path).with_file_name(LEASE_DIR_NAME);
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create lease directory {}: {}", dir.display(), e))?;
+        Ok(LeaseStore {
+            dir,
+            duration: Duration::from_secs(seconds.max(1)),
+            results_dir: results_dir.to_string(),
+        })
+    }
+    
+    /// Take the lease on `key` unless another worker holds a lease on it that hasn't expired.
+    /// An expired lease, or one this process left behind, is taken over.
+    pub fn acquire(&self, key: &str) -> Result<LeaseOutcome<'_>, Box<dyn Error>> {
+        let path = self.lease_path(key);
+        let acquired = time::now();
+        let lease = KeyLease {
+            key: key.to_string(),
+            run_id: run_id::current().to_string(),
+            pid: std::process::id(),
+            operator: operator::current().to_string(),
+            results_dir: self.results_dir.clone(),
+            acquired: time::rfc3339(acquired),
+            expires: time::rfc3339(acquired + chrono::Duration::seconds(self.duration.as_secs() as i64)),
+        };
+        
+        // Two attempts: the second follows taking over an expired lease
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(serde_json::to_string(&lease)?.as_bytes())
+                        .map_err(|e| format!("Failed to write lease {}: {}", path.display(), e))?;
+                    let renew_after = Instant::now() + self.duration / 2;
+                    return Ok(LeaseOutcome::Acquired(HeldLease { store: self, path, renew_after }));
+                },
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {},
+                Err(e) => return Err(format!("Failed to create lease {}: {}", path.display(), e).into()),
+            }
+            
+            match read_lease(&path) {
+                Some(holder) if !holder.is_expired() && !holder.is_ours() => {
+                    return Ok(LeaseOutcome::HeldBy(holder.describe()));
+                },
+                Some(holder) if !holder.is_ours() => {
+                    log::warn!("Taking over the expired lease on key {} held by {}", key, holder.describe());
+                },
+                Some(_) => {},
+                // Released between the two checks
+                None if !path.exists() => continue,
+                // A lease file without content is being written right now, unless it's old
+                None if !self.is_older_than_lease(&path) => {
+                    return Ok(LeaseOutcome::HeldBy(format!("a worker writing {}", path.display())));
+                },
+                None => log::warn!("Taking over the unreadable lease {} on key {}", path.display(), key),
+            }
+            
+            // Renaming is atomic, so of several workers taking over only one removes the lease
+            let expired = path.with_extension(format!("expired.{}", std::process::id()));
+            match fs::rename(&path, &expired) {
+                Ok(()) => {
+                    let _ = fs::remove_file(&expired);
+                },
+                Err(e) if e.kind() == ErrorKind::NotFound => {},
+                Err(e) => return Err(format!("Failed to take over lease {}: {}", path.display(), e).into()),
+            }
+        }
+        Ok(LeaseOutcome::HeldBy(format!("another worker that took over {} first", path.display())))
+    }
+    
+    /// Push a held lease's expiry a full lease duration out. The new content replaces the
+    /// file by rename, so other workers never read a half-written lease.
+    fn renew(&self, path: &Path) {
+        let mut lease = match read_lease(path) {
+            Some(lease) if lease.is_ours() => lease,
+            Some(lease) => {
+                log::warn!("Lease on key {} expired during execution and is now held by {}", lease.key, lease.describe());
+                return;
+            },
+            None => return,
+        };
+        lease.expires = time::rfc3339(time::now() + chrono::Duration::seconds(self.duration.as_secs() as i64));
+        
+        let renewed = path.with_extension(format!("renewed.{}", std::process::id()));
+        let result = serde_json::to_string(&lease)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&renewed, json).map_err(|e| e.to_string()))
+            .and_then(|_| fs::rename(&renewed, path).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => log::debug!("Renewed the lease on key {} until {}", lease.key, lease.expires),
+            Err(e) => log::warn!("Failed to renew lease {}: {}", path.display(), e),
+        }
+    }
+    
+    /// Remove a lease, unless it expired and another worker took it over meanwhile
+    fn release(&self, path: &Path) {
+        match read_lease(path) {
+            Some(lease) if lease.is_ours() => {
+                if let Err(e) = fs::remove_file(path) {
+                    log::warn!("Failed to release lease {}: {}", path.display(), e);
+                }
+            },
+            Some(lease) => log::warn!("Lease on key {} expired during execution and is now held by {}", lease.key, lease.describe()),
+            None => {},
+        }
+    }
+    
+    /// Keys may hold characters file names can't, so lease files are named after a hash
+    fn lease_path(&self, key: &str) -> PathBuf {
+        let digest = Sha256::digest(key.as_bytes());
+        let hex: String = digest.iter().take(16).map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(format!("{}.json", hex))
+    }
+    
+    fn is_older_than_lease(&self, path: &Path) -> bool {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_none_or(|age| age > self.duration)
+    }
+}
+
+fn read_lease(path: &Path) -> Option<KeyLease> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn store(name: &str, seconds: u64) -> LeaseStore {
+        let dir = std::env::temp_dir().join(format!("ibp-leases-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        LeaseStore::open(&dir.join("processed.json").to_string_lossy(), "results_1", seconds).unwrap()
+    }
+    
+    // Write a lease on `key` as another worker, expiring `expires_in` seconds from now
+    fn lease_of_other_worker(store: &LeaseStore, key: &str, expires_in: i64) {
+        let lease = KeyLease {
+            key: key.to_string(),
+            run_id: "other-run".to_string(),
+            pid: 0,
+            operator: "someone@elsewhere".to_string(),
+            results_dir: "results_2".to_string(),
+            acquired: time::now_rfc3339(),
+            expires: time::rfc3339(time::now() + chrono::Duration::seconds(expires_in)),
+        };
+        fs::write(store.lease_path(key), serde_json::to_string(&lease).unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn a_lease_is_held_until_released() {
+        let store = store("release", 900);
+        let lease = match store.acquire("1001").unwrap() {
+            LeaseOutcome::Acquired(lease) => lease,
+            LeaseOutcome::HeldBy(holder) => panic!("lease held by {}", holder),
+        };
+        assert!(store.lease_path("1001").exists());
+        
+        drop(lease);
+        assert!(!store.lease_path("1001").exists());
+        assert!(matches!(store.acquire("1001").unwrap(), LeaseOutcome::Acquired(_)));
+        fs::remove_dir_all(store.dir.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn a_live_lease_of_another_worker_is_left_alone() {
+        let store = store("held", 900);
+        lease_of_other_worker(&store, "1002", 600);
+        match store.acquire("1002").unwrap() {
+            LeaseOutcome::HeldBy(holder) => assert!(holder.contains("other-run") && holder.contains("results_2"), "{}", holder),
+            LeaseOutcome::Acquired(_) => panic!("took a live lease"),
+        }
+        assert_eq!(read_lease(&store.lease_path("1002")).unwrap().run_id, "other-run");
+        fs::remove_dir_all(store.dir.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn an_expired_lease_is_taken_over() {
+        let store = store("expired", 900);
+        lease_of_other_worker(&store, "1003", -60);
+        let lease = match store.acquire("1003").unwrap() {
+            LeaseOutcome::Acquired(lease) => lease,
+            LeaseOutcome::HeldBy(holder) => panic!("expired lease still held by {}", holder),
+        };
+        assert!(read_lease(&store.lease_path("1003")).unwrap().is_ours());
+        drop(lease);
+        fs::remove_dir_all(store.dir.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn renewing_pushes_the_expiry_out() {
+        let store = store("renew", 900);
+        let mut lease = match store.acquire("1004").unwrap() {
+            LeaseOutcome::Acquired(lease) => lease,
+            LeaseOutcome::HeldBy(holder) => panic!("lease held by {}", holder),
+        };
+        let path = store.lease_path("1004");
+        let mut written = read_lease(&path).unwrap();
+        written.expires = time::rfc3339(time::now() + chrono::Duration::seconds(5));
+        fs::write(&path, serde_json::to_string(&written).unwrap()).unwrap();
+        
+        // Not due yet: the lease is left as it is
+        lease.renew_if_due();
+        assert_eq!(read_lease(&path).unwrap().expires, written.expires);
+        
+        lease.renew_after = Instant::now();
+        lease.renew_if_due();
+        let renewed = DateTime::parse_from_rfc3339(&read_lease(&path).unwrap().expires).unwrap();
+        assert!(renewed.with_timezone(&Utc) > Utc::now() + chrono::Duration::seconds(800));
+        drop(lease);
+        fs::remove_dir_all(store.dir.parent().unwrap()).unwrap();
+    }
+}
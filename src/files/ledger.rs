@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the global ledger of applied changes, kept next to the processed records file
+pub const LEDGER_FILE_NAME: &str = "applied_ledger.jsonl";
+
+/// Keys of a run corrected more often than recurrent_offender_threshold, written by `report`
+pub const RECURRENT_OFFENDERS_FILE_NAME: &str = "recurrent_offenders.csv";
+
+/// What `execute` does with a query whose change was already applied by an earlier run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayPolicy {
+    /// Mark the query Skipped and carry on
+    Skip,
+    /// Log a warning and execute it again
+    Warn,
+    /// Refuse to start the run at all
+    Refuse,
+}
+
+impl ReplayPolicy {
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "skip" => Ok(ReplayPolicy::Skip),
+            "warn" => Ok(ReplayPolicy::Warn),
+            "refuse" => Ok(ReplayPolicy::Refuse),
+            _ => Err(format!("Unknown replay_policy '{}' (expected 'skip', 'warn' or 'refuse')", name).into()),
+        }
+    }
+}
+
+/// One successfully applied change
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LedgerEntry {
+    pub hash: String,
+    pub key: String,
+    pub results_dir: String,
+    pub run_id: String,
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    /// Operator, user and host that applied the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+}
+
+/// Append-only ledger of every change ever applied, across all results directories.
+///
+/// Unlike the processed records file, which is rewritten at the end of a phase, each
+/// entry is appended as soon as its statement succeeds, so a crashed run still leaves
+/// a complete record of what it applied.
+#[derive(Debug, Default)]
+pub struct AppliedLedger {
+    path: PathBuf,
+    entries: HashMap<String, LedgerEntry>,
+}
+
+impl AppliedLedger {
+    /// Load the ledger that lives beside `data_path`; a missing ledger is empty
+    pub fn load(data_path: &str) -> Self {
+        let path = Path::new(data_path).with_file_name(LEDGER_FILE_NAME);
+        let mut entries = HashMap::new();
+        
+        if let Ok(content) = fs::read_to_string(&path) {
+            for (line_number, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<LedgerEntry>(line) {
+                    Ok(entry) => {
+                        entries.insert(entry.hash.clone(), entry);
+                    },
+                    Err(e) => log::warn!("Ignoring unreadable line {} of {}: {}", line_number + 1, path.display(), e),
+                }
+            }
+        }
+        
+        AppliedLedger { path, entries }
+    }
+    
+    /// The entry for a previously applied change, if any
+    pub fn lookup(&self, hash: &str) -> Option<&LedgerEntry> {
+        self.entries.get(hash)
+    }
+    
+    /// Append an applied change to the ledger file
+    pub fn record(&mut self, entry: LedgerEntry) -> Result<(), Box<dyn Error>> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        self.entries.insert(entry.hash.clone(), entry);
+        Ok(())
+    }
+}
+
+/// How often one key has been corrected, across every run in the ledger
+#[derive(Debug, Clone, Default)]
+pub struct CorrectionHistory {
+    pub key: String,
+    /// Runs that applied a change to the key, oldest first
+    pub runs: Vec<String>,
+    pub first_corrected: String,
+    pub last_corrected: String,
+}
+
+/// The correction history of each key in the ledger beside `data_path`. Every ledger line
+/// counts, including changes applied again under replay_policy "warn"; a run that changed
+/// a key more than once counts once.
+pub fn correction_histories(data_path: &str) -> BTreeMap<String, CorrectionHistory> {
+    let path = Path::new(data_path).with_file_name(LEDGER_FILE_NAME);
+    let mut entries: Vec<LedgerEntry> = fs::read_to_string(&path)
+        .map(|content| content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default();
+    entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    
+    let mut histories: BTreeMap<String, CorrectionHistory> = BTreeMap::new();
+    let mut seen: BTreeSet<(String, String)> = BTreeSet::new();
+    for entry in entries {
+        if !seen.insert((entry.key.clone(), entry.run_id.clone())) {
+            continue;
+        }
+        let history = histories.entry(entry.key.clone()).or_insert_with(|| CorrectionHistory {
+            key: entry.key.clone(),
+            first_corrected: entry.timestamp.clone(),
+            ..CorrectionHistory::default()
+        });
+        history.runs.push(entry.run_id);
+        history.last_corrected = entry.timestamp;
+    }
+    histories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn entry(hash: &str, key: &str, run_id: &str, timestamp: &str) -> LedgerEntry {
+        LedgerEntry {
+            hash: hash.to_string(),
+            key: key.to_string(),
+            results_dir: "results".to_string(),
+            run_id: run_id.to_string(),
+            timestamp: timestamp.to_string(),
+            ticket: None,
+            operator: None,
+        }
+    }
+    
+    #[test]
+    fn recorded_changes_survive_a_reload_and_count_once_per_run() {
+        let dir = std::env::temp_dir().join(format!("ibp-ledger-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let data_path = dir.join("processed_records.json").to_string_lossy().to_string();
+        
+        let mut ledger = AppliedLedger::load(&data_path);
+        assert!(ledger.lookup("h1").is_none());
+        ledger.record(entry("h1", "1001", "run-b", "2026-01-02T00:00:00Z")).unwrap();
+        ledger.record(entry("h2", "1001", "run-a", "2026-01-01T00:00:00Z")).unwrap();
+        ledger.record(entry("h3", "1001", "run-b", "2026-01-03T00:00:00Z")).unwrap();
+        ledger.record(entry("h4", "1002", "run-a", "2026-01-01T00:00:00Z")).unwrap();
+        
+        // A torn line from a crashed run is skipped
+        let mut file = OpenOptions::new().append(true).open(dir.join(LEDGER_FILE_NAME)).unwrap();
+        file.write_all(b"{\"hash\":\"h5\",\"ke").unwrap();
+        
+        let reloaded = AppliedLedger::load(&data_path);
+        assert_eq!(reloaded.lookup("h1").map(|entry| entry.key.as_str()), Some("1001"));
+        assert!(reloaded.lookup("h5").is_none());
+        
+        let histories = correction_histories(&data_path);
+        let history = &histories["1001"];
+        assert_eq!(history.runs, vec!["run-a".to_string(), "run-b".to_string()]);
+        assert_eq!(history.first_corrected, "2026-01-01T00:00:00Z");
+        assert_eq!(history.last_corrected, "2026-01-02T00:00:00Z");
+        assert_eq!(histories["1002"].runs.len(), 1);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
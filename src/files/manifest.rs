@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::utils::operator::{self, OperatorIdentity};
+use crate::utils::{run_id, time, version};
+
+/// Name of the run manifest written into the results directory
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Keys the selection query matched, one per line
+pub const SELECTION_KEYS_FILE_NAME: &str = "selection_keys.txt";
+
+/// Summary of a run, kept next to its query files
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct RunManifest {
+    pub run_id: String,
+    pub created: String,
+    pub selection_query: String,
+    /// Number of keys the selection query matched
+    pub matched_keys: usize,
+    /// Number of update queries generated
+    pub generated: usize,
+    /// Results directory this run was diffed against, for delta generation
+    #[serde(default)]
+    pub delta_from: Option<String>,
+    /// Matched keys skipped because the previous run already matched them
+    #[serde(default)]
+    pub delta_skipped: usize,
+    /// After this time `execute` refuses the run's queries unless `--allow-stale` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    /// Change request the run's queries were generated for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    /// Who generated the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_by: Option<OperatorIdentity>,
+    /// Each execution of the run, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub executions: Vec<ExecutionAttribution>,
+    /// File the queries were ingested from, for runs built by `ingest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingested_from: Option<String>,
+    /// File of authoritative values the table was compared with, for runs built by `reconcile`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconciled_from: Option<String>,
+    /// Statistics updates run after execution modified enough rows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub statistics_updates: Vec<StatisticsUpdate>,
+    /// Predicates of the selection or the generated statements that no index leads with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub index_advice: Vec<IndexAdvice>,
+    /// Version and commit of the build that created the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+}
+
+/// One execution of a run and who started it
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ExecutionAttribution {
+    pub run_id: String,
+    pub started: String,
+    pub operator: OperatorIdentity,
+    /// Partition (N/M) the execution was limited to with `execute --partition`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<String>,
+    /// Version and commit of the build that executed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+}
+
+/// One optimizer statistics update run after execution
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct StatisticsUpdate {
+    pub table: String,
+    pub statement: String,
+    pub started: String,
+    pub duration_seconds: f64,
+    /// Rows execution changed before the update was triggered
+    pub rows_modified: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Columns a run filters a table on that no index of the table starts with
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct IndexAdvice {
+    pub table: String,
+    /// "selection" for the selection query's WHERE clause, "update" for the generated statements'
+    pub source: String,
+    pub columns: Vec<String>,
+    /// CREATE INDEX statement that would serve the predicates
+    pub suggestion: String,
+}
+
+impl RunManifest {
+    /// Load the manifest of a results directory, if it has one
+    pub fn load(results_dir: &str) -> Option<Self> {
+        let content = fs::read_to_string(Path::new(results_dir).join(MANIFEST_FILE_NAME)).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                log::warn!("Ignoring unreadable manifest in {}: {}", results_dir, e);
+                None
+            }
+        }
+    }
+    
+    /// Save the manifest into a results directory
+    pub fn save(&self, results_dir: &str) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(Path::new(results_dir).join(MANIFEST_FILE_NAME), json)?;
+        Ok(())
+    }
+    
+    /// Add this process's execution, and who started it, to a results directory's manifest
+    pub fn record_execution(results_dir: &str, partition: Option<String>) -> Result<(), Box<dyn Error>> {
+        let mut manifest = RunManifest::load(results_dir).unwrap_or_default();
+        if manifest.run_id.is_empty() {
+            manifest.run_id = run_id::current().to_string();
+            manifest.created = time::now_rfc3339();
+            manifest.tool_version = Some(version::build());
+        }
+        manifest.executions.push(ExecutionAttribution {
+            run_id: run_id::current().to_string(),
+            started: time::now_rfc3339(),
+            operator: operator::current().clone(),
+            partition,
+            tool_version: Some(version::build()),
+        });
+        manifest.save(results_dir)
+    }
+    
+    /// Partition the last execution was limited to, if it was
+    pub fn last_partition(&self) -> Option<&str> {
+        self.executions.last().and_then(|execution| execution.partition.as_deref())
+    }
+}
+
+/// Save the keys a selection matched
+pub fn save_selection_keys(results_dir: &str, keys: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut content = keys.join("\n");
+    content.push('\n');
+    fs::write(Path::new(results_dir).join(SELECTION_KEYS_FILE_NAME), content)?;
+    Ok(())
+}
+
+/// Load the keys a previous run's selection matched. Runs made before the key list was
+/// recorded fall back to the keys of their query files.
+pub fn load_selection_keys(results_dir: &str) -> Result<HashSet<String>, Box<dyn Error>> {
+    if !Path::new(results_dir).is_dir() {
+        return Err(format!("Previous results directory {} does not exist", results_dir).into());
+    }
+    
+    let keys_path = Path::new(results_dir).join(SELECTION_KEYS_FILE_NAME);
+    if keys_path.exists() {
+        let content = fs::read_to_string(keys_path)?;
+        return Ok(content.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect());
+    }
+    
+    log::warn!("{} has no {}; using the keys of its query files", results_dir, SELECTION_KEYS_FILE_NAME);
+    let mut keys = HashSet::new();
+    for file_path in read_query_files(results_dir)? {
+        match read_query_file(&file_path) {
+            Ok(record) => {
+                keys.insert(record.key);
+            },
+            Err(e) => log::warn!("Skipping unreadable query file {}: {}", file_path.display(), e),
+        }
+    }
+    
+    Ok(keys)
+}
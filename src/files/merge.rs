@@ -0,0 +1,168 @@
+use chrono::DateTime;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::{ErrorRecord, QueryRecord, QueryStatus};
+use crate::files::json_handler::{query_file_exists, read_query_file, read_query_files, save_error_file, save_query_file};
+
+/// Outcome of merging partition results back into one results directory
+#[derive(Debug, Clone, Default)]
+pub struct MergeSummary {
+    /// Query records taken from a partition's copy
+    pub updated: usize,
+    /// Records present in a partition but not in the target, copied over
+    pub added: usize,
+    /// Error records appended to the target's errors.json
+    pub errors: usize,
+}
+
+/// Merge the statuses of partitioned executions into `target_dir`.
+///
+/// Each source is a copy of the same run executed with `execute --partition`. A source
+/// record replaces the target's when the target is still Pending, or when both progressed
+/// and the source's timestamp is later. Error records not yet in the target are appended.
+pub fn merge_statuses(target_dir: &str, source_dirs: &[String]) -> Result<MergeSummary, Box<dyn Error>> {
+    let mut summary = MergeSummary::default();
+    
+    for source_dir in source_dirs {
+        if Path::new(source_dir) == Path::new(target_dir) {
+            continue;
+        }
+        
+        for source_path in read_query_files(source_dir)? {
+            let source = match read_query_file(&source_path) {
+                Ok(record) => record,
+                Err(e) => {
+                    log::warn!("Skipping unreadable query file {}: {}", source_path.display(), e);
+                    continue;
+                }
+            };
+            let target_path = Path::new(target_dir).join(source_path.file_name().unwrap_or_default());
+            
+            if !query_file_exists(&target_path) {
+                save_query_file(&target_path, &source)?;
+                summary.added += 1;
+                continue;
+            }
+            
+            let target = read_query_file(&target_path)?;
+            if supersedes(&source, &target) {
+                log::info!("Merge: key {} {:?} -> {:?} from {}", source.key, target.status, source.status, source_dir);
+                save_query_file(&target_path, &source)?;
+                summary.updated += 1;
+            }
+        }
+        
+        summary.errors += merge_errors(target_dir, source_dir)?;
+    }
+    
+    log::info!(
+        "Merged {} partitions into {}: {} records updated, {} added, {} error records",
+        source_dirs.len(), target_dir, summary.updated, summary.added, summary.errors
+    );
+    
+    Ok(summary)
+}
+
+/// Whether a partition's copy of a record carries newer progress than the target's
+fn supersedes(source: &QueryRecord, target: &QueryRecord) -> bool {
+    if source.status == QueryStatus::Pending || (source.status == target.status && source.timestamp == target.timestamp) {
+        return false;
+    }
+    if target.status == QueryStatus::Pending {
+        return true;
+    }
+    
+    let parse = |timestamp: &Option<String>| timestamp.as_deref().and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    match (parse(&source.timestamp), parse(&target.timestamp)) {
+        (Some(source_time), Some(target_time)) => source_time > target_time,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Append the source's error records that the target doesn't have yet
+fn merge_errors(target_dir: &str, source_dir: &str) -> Result<usize, Box<dyn Error>> {
+    let read_errors = |dir: &str| -> Result<Vec<ErrorRecord>, Box<dyn Error>> {
+        let path = Path::new(dir).join("errors.json");
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    };
+    
+    let known: HashSet<(String, String)> = read_errors(target_dir)?
+        .into_iter()
+        .map(|error| (error.key, error.timestamp))
+        .collect();
+    
+    let mut added = 0;
+    for error in read_errors(source_dir)? {
+        if !known.contains(&(error.key.clone(), error.timestamp.clone())) {
+            save_error_file(Path::new(target_dir).join("errors.json"), &error)?;
+            added += 1;
+        }
+    }
+    
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn record(key: &str, status: QueryStatus, timestamp: Option<&str>) -> QueryRecord {
+        let mut record = QueryRecord::new(key.to_string(), format!("UPDATE customer SET county = '001' WHERE zip = '{}'", key));
+        record.status = status;
+        record.timestamp = timestamp.map(str::to_string);
+        record
+    }
+    
+    #[test]
+    fn newer_partition_progress_replaces_the_target() {
+        let dir = std::env::temp_dir().join(format!("ibp-merge-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (target, source) = (dir.join("target"), dir.join("partition"));
+        fs::create_dir_all(&target).unwrap();
+        fs::create_dir_all(&source).unwrap();
+        
+        save_query_file(target.join("1001.json"), &record("1001", QueryStatus::Pending, None)).unwrap();
+        save_query_file(target.join("1002.json"), &record("1002", QueryStatus::Completed, Some("2026-01-02T00:00:00Z"))).unwrap();
+        save_query_file(target.join("1003.json"), &record("1003", QueryStatus::Failed, Some("2026-01-01T00:00:00Z"))).unwrap();
+        save_query_file(source.join("1001.json"), &record("1001", QueryStatus::Completed, Some("2026-01-01T00:00:00Z"))).unwrap();
+        save_query_file(source.join("1002.json"), &record("1002", QueryStatus::Failed, Some("2026-01-01T00:00:00Z"))).unwrap();
+        save_query_file(source.join("1003.json"), &record("1003", QueryStatus::Completed, Some("2026-01-02T00:00:00Z"))).unwrap();
+        save_query_file(source.join("1004.json"), &record("1004", QueryStatus::Pending, None)).unwrap();
+        let error = ErrorRecord {
+            key: "1002".to_string(),
+            file: "1002.json".to_string(),
+            error: "lock timeout".to_string(),
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            run_id: None,
+            sqlcode: Some(-244),
+            isam_error: None,
+            sqlstate: None,
+            odbc_function: None,
+            message_chain: Vec::new(),
+        };
+        save_error_file(source.join("errors.json"), &error).unwrap();
+        
+        let (target_dir, sources) = (target.to_string_lossy().to_string(), vec![source.to_string_lossy().to_string()]);
+        let summary = merge_statuses(&target_dir, &sources).unwrap();
+        assert_eq!((summary.updated, summary.added, summary.errors), (2, 1, 1));
+        
+        let status = |key: &str| read_query_file(target.join(format!("{}.json", key))).unwrap().status;
+        assert_eq!(status("1001"), QueryStatus::Completed);
+        assert_eq!(status("1002"), QueryStatus::Completed);
+        assert_eq!(status("1003"), QueryStatus::Completed);
+        assert_eq!(status("1004"), QueryStatus::Pending);
+        
+        // Merging the same partition again changes nothing
+        let summary = merge_statuses(&target_dir, &sources).unwrap();
+        assert_eq!((summary.updated, summary.added, summary.errors), (0, 0, 0));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
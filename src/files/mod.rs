@@ -1,3 +1,28 @@
 pub mod json_handler;
 pub mod file_manager;
-pub mod processed;
\ No newline at end of file
+pub mod processed;
+pub mod dbaccess;
+pub mod parameter_values;
+pub mod heartbeat;
+pub mod snapshot;
+pub mod manifest;
+pub mod review;
+pub mod report;
+pub mod merge;
+pub mod ledger;
+pub mod events;
+pub mod county_results;
+pub mod latest;
+pub mod analytics;
+pub mod errors;
+pub mod checkpoint;
+pub mod isolation;
+pub mod xlsx;
+pub mod zip_mapping;
+pub mod refused_zips;
+pub mod leases;
+pub mod dead_letter;
+pub mod applied_comparison;
+pub mod journal;
+pub mod run_report;
+pub mod rebuild;
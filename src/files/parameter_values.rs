@@ -0,0 +1,36 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Directory inside a results directory holding out-of-line parameter values
+pub const PARAMETER_VALUES_DIR: &str = "values";
+
+/// Store long parameter values next to the query record, one file per value.
+/// Returns the file names relative to the results directory, in parameter order.
+pub fn save_parameter_values(results_dir: &str, key: &str, values: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    if values.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let values_dir = Path::new(results_dir).join(PARAMETER_VALUES_DIR);
+    fs::create_dir_all(&values_dir)?;
+    
+    let mut file_names = Vec::with_capacity(values.len());
+    for (index, value) in values.iter().enumerate() {
+        let file_name = format!("{}/{}_{}.txt", PARAMETER_VALUES_DIR, key, index + 1);
+        fs::write(Path::new(results_dir).join(&file_name), value)?;
+        file_names.push(file_name);
+    }
+    
+    Ok(file_names)
+}
+
+/// Read back the parameter values referenced by a query record
+pub fn load_parameter_values(results_dir: &str, file_names: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    file_names.iter()
+        .map(|file_name| {
+            fs::read_to_string(Path::new(results_dir).join(file_name))
+                .map_err(|e| format!("Failed to read parameter value {}: {}", file_name, e).into())
+        })
+        .collect()
+}
@@ -1,23 +1,96 @@
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-/// Struct to track processed records to avoid reprocessing
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[cfg(feature = "sqlite")]
+use std::error::Error;
+#[cfg(feature = "sqlite")]
+use std::path::PathBuf;
+
+#[cfg(feature = "sqlite")]
+use crate::utils::sqlite::Database;
+
+/// The SQLite state store, next to `data_path`, with `state_backend = "sqlite"`
+#[cfg(feature = "sqlite")]
+pub const SQLITE_STATE_FILE_NAME: &str = "state.sqlite";
+
+/// Whether processed records are kept in the SQLite state store instead of `data_path`
+static SQLITE_STATE: AtomicBool = AtomicBool::new(false);
+
+/// Keep processed records in state.sqlite from here on
+pub fn enable_sqlite_state() {
+    SQLITE_STATE.store(true, Ordering::Relaxed);
+}
+
+/// Struct to track processed records to avoid reprocessing. Saved as a list of records; a
+/// key's last entry in the file is its current one.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(from = "ProcessedFile", into = "ProcessedFile")]
 pub struct ProcessedRecords {
-    pub processed: Vec<ProcessedRecord>,
+    /// Latest record of each key
+    by_key: HashMap<String, ProcessedRecord>,
+    /// Every applied change by idempotency hash, since a key can be corrected more than once
+    applied: HashMap<String, ProcessedRecord>,
+}
+
+/// The processed records file
+#[derive(Serialize, Deserialize, Default)]
+struct ProcessedFile {
+    processed: Vec<ProcessedRecord>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ProcessedRecord {
     pub key: String,
     pub timestamp: String,
-    pub action: String, // "skipped" or "updated"
+    pub action: String, // "generated", "skipped" or "updated"
+    #[serde(default)]
+    pub hash: Option<String>, // idempotency hash of the applied change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>, // query file a generated key waits in
+}
+
+impl From<ProcessedFile> for ProcessedRecords {
+    fn from(file: ProcessedFile) -> Self {
+        let mut records = ProcessedRecords::default();
+        for record in file.processed {
+            records.insert(record);
+        }
+        records
+    }
+}
+
+impl From<ProcessedRecords> for ProcessedFile {
+    fn from(records: ProcessedRecords) -> Self {
+        // Applied changes first, then the keys whose latest record isn't one of them
+        let mut processed: Vec<ProcessedRecord> = records.applied.into_values().collect();
+        processed.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.key.cmp(&b.key)));
+        let mut latest: Vec<ProcessedRecord> = records.by_key.into_values()
+            .filter(|record| !(record.action == "updated" && record.hash.is_some()))
+            .collect();
+        latest.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.key.cmp(&b.key)));
+        processed.extend(latest);
+        ProcessedFile { processed }
+    }
 }
 
 impl ProcessedRecords {
-    /// Load processed records from file
+    /// Load processed records from file, or from the SQLite store beside it when that is enabled
     pub fn load(file_path: &str) -> Self {
+        #[cfg(feature = "sqlite")]
+        if SQLITE_STATE.load(Ordering::Relaxed) {
+            return Self::load_sqlite(file_path).unwrap_or_else(|e| {
+                eprintln!("Error reading processed records from {}: {}", Self::sqlite_path(file_path).display(), e);
+                Self::default()
+            });
+        }
+        
+        Self::load_json(file_path)
+    }
+    
+    fn load_json(file_path: &str) -> Self {
         if Path::new(file_path).exists() {
             match fs::read_to_string(file_path) {
                 Ok(content) => {
@@ -38,35 +111,257 @@ impl ProcessedRecords {
             Self::default()
         }
     }
-
-    /// Save processed records to file
+    
+    /// Save processed records to file, or to the SQLite store beside it when that is enabled
     pub fn save(&self, file_path: &str) -> Result<(), std::io::Error> {
+        #[cfg(feature = "sqlite")]
+        if SQLITE_STATE.load(Ordering::Relaxed) {
+            return self.save_sqlite(file_path).map_err(|e| std::io::Error::other(e.to_string()));
+        }
+        
+        self.save_json(file_path)
+    }
+    
+    fn save_json(&self, file_path: &str) -> Result<(), std::io::Error> {
         let json = serde_json::to_string_pretty(&self).expect("Failed to serialize processed records");
         fs::write(file_path, json)
     }
-
+    
+    fn insert(&mut self, record: ProcessedRecord) {
+        if record.action == "updated" {
+            if let Some(hash) = &record.hash {
+                self.applied.insert(hash.clone(), record.clone());
+            }
+        }
+        self.by_key.insert(record.key.clone(), record);
+    }
+    
     /// Add a processed record
     pub fn add(&mut self, key: String, timestamp: String, action: String) {
-        let record = ProcessedRecord {
+        self.insert(ProcessedRecord {
             key,
             timestamp,
             action,
-        };
-        
-        if !self.processed.contains(&record) {
-            self.processed.push(record);
+            hash: None,
+            file: None,
+        });
+    }
+    
+    /// Add a record for a key whose query file was generated and waits to be executed
+    pub fn add_generated(&mut self, key: String, timestamp: String, file: String) {
+        self.insert(ProcessedRecord {
+            key,
+            timestamp,
+            action: "generated".to_string(),
+            hash: None,
+            file: Some(file),
+        });
+    }
+    
+    /// Add a record for a change that was applied, identified by its idempotency hash
+    pub fn add_applied(&mut self, key: String, timestamp: String, hash: String) {
+        if self.has_applied_hash(&hash) {
+            return;
         }
+        
+        self.insert(ProcessedRecord {
+            key,
+            timestamp,
+            action: "updated".to_string(),
+            hash: Some(hash),
+            file: None,
+        });
     }
-
+    
+    /// Check if a change with this idempotency hash has already been applied
+    pub fn has_applied_hash(&self, hash: &str) -> bool {
+        self.applied.contains_key(hash)
+    }
+    
     /// Check if a record has been processed
     pub fn is_processed(&self, key: &str) -> bool {
-        self.processed.iter().any(|r| r.key == key)
+        self.by_key.contains_key(key)
     }
     
     /// Get action for a record if it has been processed
     pub fn get_action(&self, key: &str) -> Option<String> {
-        self.processed.iter()
-            .find(|r| r.key == key)
-            .map(|r| r.action.clone())
+        self.by_key.get(key).map(|r| r.action.clone())
     }
-}
\ No newline at end of file
+    
+    /// The query file a generated key's latest record points at
+    pub fn generated_file(&self, key: &str) -> Option<&str> {
+        self.by_key.get(key)
+            .filter(|record| record.action == "generated")
+            .and_then(|record| record.file.as_deref())
+    }
+}
+
+/// The SQLite state store: one `processed_records` table holding what the JSON file holds, in
+/// file order, looked up by key and unique by applied hash. `PRAGMA user_version` is the schema
+/// version; version 0 is a new store.
+#[cfg(feature = "sqlite")]
+impl ProcessedRecords {
+    fn sqlite_path(file_path: &str) -> PathBuf {
+        Path::new(file_path).with_file_name(SQLITE_STATE_FILE_NAME)
+    }
+    
+    fn load_sqlite(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let database = Self::open_sqlite(file_path)?;
+        let mut processed = Vec::new();
+        database.prepare("SELECT key, timestamp, action, hash, file FROM processed_records ORDER BY seq")?
+            .query(&[], |row| {
+                let mut columns = row.into_iter();
+                let mut text = || columns.next().flatten();
+                processed.push(ProcessedRecord {
+                    key: text().unwrap_or_default(),
+                    timestamp: text().unwrap_or_default(),
+                    action: text().unwrap_or_default(),
+                    hash: text(),
+                    file: text(),
+                });
+            })?;
+        Ok(ProcessedFile { processed }.into())
+    }
+    
+    fn save_sqlite(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let database = Self::open_sqlite(file_path)?;
+        in_transaction(&database, || write_records(&database, ProcessedFile::from(self.clone())))
+    }
+    
+    // Open the store, creating its table on first use. A new store imports the JSON file at
+    // `file_path`, so switching state_backend keeps what earlier runs recorded; the file is
+    // left in place.
+    fn open_sqlite(file_path: &str) -> Result<Database, Box<dyn Error>> {
+        let path = Self::sqlite_path(file_path);
+        let database = Database::open(&path)?;
+        if user_version(&database)? > 0 {
+            return Ok(database);
+        }
+        
+        in_transaction(&database, || {
+            // Another worker may have created the store while this one waited for the lock
+            if user_version(&database)? > 0 {
+                return Ok(());
+            }
+            database.execute_batch(
+                "CREATE TABLE IF NOT EXISTS processed_records (\
+                     seq INTEGER PRIMARY KEY, \
+                     key TEXT NOT NULL, \
+                     timestamp TEXT NOT NULL, \
+                     action TEXT NOT NULL, \
+                     hash TEXT UNIQUE, \
+                     file TEXT); \
+                 CREATE INDEX IF NOT EXISTS processed_records_key ON processed_records (key);"
+            )?;
+            let imported = Self::load_json(file_path);
+            if !imported.by_key.is_empty() {
+                log::info!("Importing {} processed keys from {} into {}", imported.by_key.len(), file_path, path.display());
+            }
+            write_records(&database, ProcessedFile::from(imported))?;
+            database.execute_batch("PRAGMA user_version = 1")
+        })?;
+        Ok(database)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn user_version(database: &Database) -> Result<i64, Box<dyn Error>> {
+    let mut version = 0;
+    database.prepare("PRAGMA user_version")?.query(&[], |row| {
+        version = row.first().cloned().flatten().and_then(|value| value.parse().ok()).unwrap_or(0);
+    })?;
+    Ok(version)
+}
+
+// Replace the table's content with the records of a file, in its order
+#[cfg(feature = "sqlite")]
+fn write_records(database: &Database, file: ProcessedFile) -> Result<(), Box<dyn Error>> {
+    database.execute_batch("DELETE FROM processed_records")?;
+    let mut insert = database.prepare("INSERT INTO processed_records (key, timestamp, action, hash, file) VALUES (?, ?, ?, ?, ?)")?;
+    for record in &file.processed {
+        insert.execute(&[
+            Some(record.key.as_str()),
+            Some(record.timestamp.as_str()),
+            Some(record.action.as_str()),
+            record.hash.as_deref(),
+            record.file.as_deref(),
+        ])?;
+    }
+    Ok(())
+}
+
+// Run `write` in a transaction that holds the write lock from the start, rolled back on failure
+#[cfg(feature = "sqlite")]
+fn in_transaction(database: &Database, write: impl FnOnce() -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    database.execute_batch("BEGIN IMMEDIATE")?;
+    match write() {
+        Ok(()) => database.execute_batch("COMMIT"),
+        Err(e) => {
+            let _ = database.execute_batch("ROLLBACK");
+            Err(e)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    
+    fn data_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("ibp-processed-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("processed_records.json").to_string_lossy().to_string()
+    }
+    
+    fn sample() -> ProcessedRecords {
+        let mut records = ProcessedRecords::default();
+        records.add_generated("1001".to_string(), "2026-01-01T00:00:00Z".to_string(), "results_1/1001.json".to_string());
+        records.add_applied("1002".to_string(), "2026-01-01T00:01:00Z".to_string(), "hash-a".to_string());
+        records.add_applied("1002".to_string(), "2026-01-02T00:01:00Z".to_string(), "hash-b".to_string());
+        records.add("1003".to_string(), "2026-01-01T00:02:00Z".to_string(), "skipped".to_string());
+        records
+    }
+    
+    fn assert_sample(records: &ProcessedRecords) {
+        assert_eq!(records.generated_file("1001"), Some("results_1/1001.json"));
+        assert!(records.has_applied_hash("hash-a") && records.has_applied_hash("hash-b"));
+        assert_eq!(records.get_action("1002").as_deref(), Some("updated"));
+        assert_eq!(records.get_action("1003").as_deref(), Some("skipped"));
+        assert!(!records.is_processed("1004"));
+    }
+    
+    #[test]
+    fn records_round_trip_through_the_sqlite_store() {
+        let data_path = data_path("round-trip");
+        sample().save_sqlite(&data_path).unwrap();
+        assert!(!Path::new(&data_path).exists(), "the JSON file isn't written");
+        
+        let mut loaded = ProcessedRecords::load_sqlite(&data_path).unwrap();
+        assert_sample(&loaded);
+        
+        // Saving again replaces the rows rather than adding to them
+        loaded.add_applied("1001".to_string(), "2026-01-03T00:00:00Z".to_string(), "hash-c".to_string());
+        loaded.save_sqlite(&data_path).unwrap();
+        let reloaded = ProcessedRecords::load_sqlite(&data_path).unwrap();
+        assert_eq!(reloaded.get_action("1001").as_deref(), Some("updated"));
+        assert_eq!(ProcessedFile::from(reloaded).processed.len(), 4);
+        
+        fs::remove_dir_all(Path::new(&data_path).parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn a_new_sqlite_store_imports_the_json_file() {
+        let data_path = data_path("import");
+        sample().save_json(&data_path).unwrap();
+        
+        let imported = ProcessedRecords::load_sqlite(&data_path).unwrap();
+        assert_sample(&imported);
+        
+        // Once imported, later changes to the JSON file are not read again
+        ProcessedRecords::default().save_json(&data_path).unwrap();
+        assert_sample(&ProcessedRecords::load_sqlite(&data_path).unwrap());
+        
+        fs::remove_dir_all(Path::new(&data_path).parent().unwrap()).unwrap();
+    }
+}
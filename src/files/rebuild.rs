@@ -0,0 +1,169 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::db::query::{QueryRecord, QueryStatus};
+use crate::files::dead_letter::dead_letter_files;
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::manifest::{save_selection_keys, RunManifest};
+use crate::files::processed::ProcessedRecords;
+use crate::utils::time;
+
+/// What `rebuild-state` reconstructed
+#[derive(Debug, Default)]
+pub struct RebuildSummary {
+    /// Query records read, dead-lettered ones included
+    pub records: usize,
+    /// Files that couldn't be read as a query record
+    pub unreadable: usize,
+    pub keys: usize,
+    /// Keys given a processed-records entry they didn't have
+    pub processed_added: usize,
+    /// Whether there was no manifest, so a new one was written
+    pub manifest_created: bool,
+}
+
+/// Reconstruct a results directory's auxiliary state from its query files alone: the selection
+/// key index (`selection_keys.txt`), the manifest's counts, and processed-records entries for
+/// keys that lost theirs. A Completed record is entered as applied under its change hash; any
+/// other record as generated, a Pending one with its file so generation leaves it to run. Entries that already exist are kept. Keys the selection matched
+/// but generation skipped have no query file, so they aren't recovered.
+pub fn rebuild_state(results_dir: &str, processed_records: &mut ProcessedRecords) -> Result<RebuildSummary, Box<dyn Error>> {
+    let mut summary = RebuildSummary::default();
+    let mut records: Vec<(PathBuf, QueryRecord)> = Vec::new();
+    for file_path in read_query_files(results_dir)?.into_iter().chain(dead_letter_files(results_dir)?) {
+        match read_query_file(&file_path) {
+            Ok(record) => records.push((file_path, record)),
+            Err(e) => {
+                summary.unreadable += 1;
+                log::warn!("rebuild-state skipped {}: {}", file_path.display(), e);
+            },
+        }
+    }
+    summary.records = records.len();
+    
+    // Each key once, in the order its files were read
+    let mut seen = BTreeSet::new();
+    let keys: Vec<String> = records.iter()
+        .filter(|(_, record)| seen.insert(record.key.clone()))
+        .map(|(_, record)| record.key.clone())
+        .collect();
+    summary.keys = keys.len();
+    save_selection_keys(results_dir, &keys)?;
+    
+    for (file_path, record) in &records {
+        let timestamp = record.timestamp.clone()
+            .or_else(|| record.generated_at.clone())
+            .unwrap_or_else(time::now_rfc3339);
+        if record.status == QueryStatus::Completed {
+            let change_hash = record.change_hash();
+            if !processed_records.has_applied_hash(&change_hash) {
+                processed_records.add_applied(record.key.clone(), timestamp, change_hash);
+                summary.processed_added += 1;
+            }
+        } else if !processed_records.is_processed(&record.key) {
+            // A pending file keeps its key from being generated again until it runs
+            if record.status == QueryStatus::Pending {
+                processed_records.add_generated(record.key.clone(), timestamp, file_path.to_string_lossy().to_string());
+            } else {
+                processed_records.add(record.key.clone(), timestamp, "generated".to_string());
+            }
+            summary.processed_added += 1;
+        }
+    }
+    
+    let mut manifest = match RunManifest::load(results_dir) {
+        Some(manifest) => manifest,
+        None => {
+            summary.manifest_created = true;
+            RunManifest::default()
+        },
+    };
+    manifest.matched_keys = keys.len();
+    manifest.generated = records.len();
+    if manifest.run_id.is_empty() {
+        manifest.run_id = most_common(records.iter().filter_map(|(_, record)| record.run_id.clone())).unwrap_or_default();
+    }
+    if manifest.created.is_empty() {
+        manifest.created = records.iter().filter_map(|(_, record)| record.generated_at.clone()).min().unwrap_or_else(time::now_rfc3339);
+    }
+    if manifest.ticket.is_none() {
+        manifest.ticket = most_common(records.iter().filter_map(|(_, record)| record.ticket.clone()));
+    }
+    if manifest.tool_version.is_none() {
+        manifest.tool_version = most_common(records.iter().filter_map(|(_, record)| record.tool_version.clone()));
+    }
+    manifest.save(results_dir)?;
+    
+    Ok(summary)
+}
+
+/// The value that occurs most often, the first of them on a tie
+fn most_common(values: impl Iterator<Item = String>) -> Option<String> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for value in values {
+        match positions.get(&value) {
+            Some(&position) => counts[position].1 += 1,
+            None => {
+                positions.insert(value.clone(), counts.len());
+                counts.push((value, 1));
+            },
+        }
+    }
+    let max = counts.iter().map(|(_, count)| *count).max()?;
+    counts.into_iter().find(|(_, count)| *count == max).map(|(value, _)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::files::json_handler::save_query_file;
+    use crate::files::manifest::load_selection_keys;
+    
+    fn record(key: &str, status: QueryStatus) -> QueryRecord {
+        let mut record = QueryRecord::new(key.to_string(), format!("UPDATE customer SET county = '001' WHERE zip = '{}'", key));
+        record.status = status;
+        record.generated_at = Some("2026-01-01T00:00:00Z".to_string());
+        record
+    }
+    
+    #[test]
+    fn state_is_rebuilt_from_the_query_files() {
+        let dir = std::env::temp_dir().join(format!("ibp-rebuild-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let results_dir = dir.to_string_lossy().to_string();
+        
+        let completed = record("1001", QueryStatus::Completed);
+        save_query_file(dir.join("1001.json"), &completed).unwrap();
+        save_query_file(dir.join("1002.json"), &record("1002", QueryStatus::Pending)).unwrap();
+        save_query_file(dir.join("1003.json"), &record("1003", QueryStatus::Failed)).unwrap();
+        fs::write(dir.join("1004.json"), "{ not a query record").unwrap();
+        
+        // An entry the processed records still have is kept as it is
+        let mut processed = ProcessedRecords::default();
+        processed.add("1003".to_string(), "2025-12-31T00:00:00Z".to_string(), "skipped".to_string());
+        
+        let summary = rebuild_state(&results_dir, &mut processed).unwrap();
+        assert_eq!((summary.records, summary.unreadable, summary.keys, summary.processed_added), (3, 1, 3, 2));
+        assert!(summary.manifest_created);
+        
+        assert!(processed.has_applied_hash(&completed.change_hash()));
+        assert_eq!(processed.generated_file("1002"), Some(dir.join("1002.json").to_string_lossy().as_ref()));
+        assert_eq!(processed.get_action("1003").as_deref(), Some("skipped"));
+        
+        let keys = load_selection_keys(&results_dir).unwrap();
+        assert!(["1001", "1002", "1003"].iter().all(|key| keys.contains(*key)));
+        let manifest = RunManifest::load(&results_dir).unwrap();
+        assert_eq!((manifest.generated, manifest.matched_keys), (3, 3));
+        
+        // Rebuilding again finds nothing missing
+        let again = rebuild_state(&results_dir, &mut processed).unwrap();
+        assert_eq!(again.processed_added, 0);
+        assert!(!again.manifest_created);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
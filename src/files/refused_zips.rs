@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::files::snapshot::csv_field;
+
+/// Zip codes the county commands refused to correct, written into the results directory
+pub const REFUSED_ZIPS_FILE_NAME: &str = "refused_zips.csv";
+
+#[derive(Debug, Clone, Default)]
+struct RefusedZip {
+    state: String,
+    records: usize,
+    example_key: String,
+}
+
+/// Selected records whose zip maps to a county outside the configured states. Their 2-digit
+/// county codes belong to another state's namespace, so writing them would corrupt the row.
+#[derive(Debug, Default)]
+pub struct RefusedZips {
+    zips: BTreeMap<String, RefusedZip>,
+}
+
+impl RefusedZips {
+    pub fn record(&mut self, zip: &str, state: &str, key: &str) {
+        let refused = self.zips.entry(zip.to_string()).or_insert_with(|| RefusedZip {
+            state: state.to_string(),
+            example_key: key.to_string(),
+            ..RefusedZip::default()
+        });
+        refused.records += 1;
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.zips.is_empty()
+    }
+    
+    pub fn records(&self) -> usize {
+        self.zips.values().map(|refused| refused.records).sum()
+    }
+    
+    /// Write one row per refused zip with its state, how many records have it and one of
+    /// their keys; nothing is written when no zip was refused
+    pub fn write(&self, results_dir: &str) -> Result<(), Box<dyn Error>> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut content = String::from("\u{feff}zip,state,records,example_key\r\n");
+        for (zip, refused) in &self.zips {
+            content.push_str(&format!(
+                "{},{},{},{}\r\n",
+                csv_field(zip), csv_field(&refused.state), refused.records, csv_field(&refused.example_key)
+            ));
+        }
+        fs::write(Path::new(results_dir).join(REFUSED_ZIPS_FILE_NAME), content)?;
+        Ok(())
+    }
+}
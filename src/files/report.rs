@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::{parse_set_assignments, parse_update_statement, Partition, QueryRecord, QueryStatus};
+use crate::files::dead_letter::dead_letter_files;
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::manifest::RunManifest;
+use crate::files::snapshot::csv_field;
+use crate::utils::masking;
+
+/// Per-column value transitions, written by `report --diff`
+pub const DIFF_REPORT_CSV_NAME: &str = "diff_report.csv";
+
+/// Readable version of the diff report for review documents
+pub const DIFF_REPORT_TEXT_NAME: &str = "diff_report.txt";
+
+/// How many of the most common transitions the text report lists per column
+const TOP_TRANSITIONS: usize = 20;
+
+/// Before/after statistics for one changed column across the completed queries
+#[derive(Debug, Clone, Default)]
+pub struct ColumnDiff {
+    pub column: String,
+    /// Completed queries that changed this column to a different value
+    pub changed_rows: usize,
+    /// Completed queries that assigned the value the row already held
+    pub unchanged_rows: usize,
+    /// Number of rows per (before, after) pair
+    pub transitions: HashMap<(String, String), usize>,
+    /// Number of rows per value length bucket, before and after the change
+    pub lengths_before: BTreeMap<usize, usize>,
+    pub lengths_after: BTreeMap<usize, usize>,
+}
+
+impl ColumnDiff {
+    /// Transitions ordered from most to least common
+    pub fn sorted_transitions(&self) -> Vec<(&(String, String), &usize)> {
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        transitions
+    }
+    
+    /// Report lines for this column
+    pub fn report_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Column {}: {} rows changed, {} already held the new value",
+            self.column, self.changed_rows, self.unchanged_rows
+        )];
+        
+        let transitions = self.sorted_transitions();
+        for ((before, after), rows) in transitions.iter().take(TOP_TRANSITIONS) {
+            lines.push(format!("  {} -> {}: {} rows", display_value(before), display_value(after), rows));
+        }
+        if transitions.len() > TOP_TRANSITIONS {
+            lines.push(format!("  ... {} more transitions in {}", transitions.len() - TOP_TRANSITIONS, DIFF_REPORT_CSV_NAME));
+        }
+        
+        lines.push(format!("  Length before: {}", format_lengths(&self.lengths_before)));
+        lines.push(format!("  Length after:  {}", format_lengths(&self.lengths_after)));
+        lines
+    }
+}
+
+/// Count the query records of a results directory by status. Executed statements that affected
+/// no rows are counted apart, as "Completed (0 rows)" or "Failed (0 rows)".
+pub fn status_summary(results_dir: &str) -> Result<BTreeMap<String, usize>, Box<dyn Error>> {
+    let mut counts = BTreeMap::new();
+    for file_path in read_query_files(results_dir)? {
+        match read_query_file(&file_path) {
+            Ok(record) => *counts.entry(status_label(&record)).or_insert(0) += 1,
+            Err(e) => log::warn!("Skipping unreadable query file {}: {}", file_path.display(), e),
+        }
+    }
+    Ok(counts)
+}
+
+/// A record's status, with zero-row executions told apart
+pub fn status_label(record: &QueryRecord) -> String {
+    match record.status {
+        QueryStatus::Completed | QueryStatus::Failed if record.rows_affected == Some(0) => format!("{:?} (0 rows)", record.status),
+        status => format!("{:?}", status),
+    }
+}
+
+/// Count the query records of a results directory by ticket and status
+pub fn ticket_summary(results_dir: &str) -> Result<BTreeMap<(String, String), usize>, Box<dyn Error>> {
+    let mut counts = BTreeMap::new();
+    for file_path in read_query_files(results_dir)? {
+        if let Ok(record) = read_query_file(&file_path) {
+            let ticket = record.ticket.unwrap_or_else(|| "(no ticket)".to_string());
+            *counts.entry((ticket, format!("{:?}", record.status))).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Status counts of one results directory in a consolidated report
+#[derive(Debug, Clone, Default)]
+pub struct RunStatus {
+    pub results_dir: String,
+    /// Run ID from the manifest; empty for a directory without one
+    pub run_id: String,
+    /// Partition (N/M) the directory's last execution was limited to
+    pub partition: Option<String>,
+    /// Records by status, dead-lettered ones as "DeadLetter"
+    pub statuses: BTreeMap<String, usize>,
+}
+
+impl RunStatus {
+    pub fn total(&self) -> usize {
+        self.statuses.values().sum()
+    }
+    
+    /// "3f2c9a1e-... 2/4", or the directory name for a run without a manifest
+    pub fn label(&self) -> String {
+        let run = if self.run_id.is_empty() { self.results_dir.clone() } else { self.run_id.clone() };
+        match &self.partition {
+            Some(partition) => format!("{} {}", run, partition),
+            None => run,
+        }
+    }
+}
+
+/// Status counts of several results directories, such as the phases of a pipeline or the
+/// copies of a partitioned run, one entry per directory in the order given, and the number
+/// of distinct keys among them. A partition's copy holds every record of the run but only
+/// executed its own, so only the records of its partition are counted there.
+pub fn consolidated_status(results_dirs: &[String]) -> Result<(Vec<RunStatus>, usize), Box<dyn Error>> {
+    let mut runs = Vec::new();
+    let mut keys = HashSet::new();
+    for results_dir in results_dirs {
+        if !Path::new(results_dir).is_dir() {
+            return Err(format!("Results directory {} does not exist", results_dir).into());
+        }
+        let manifest = RunManifest::load(results_dir).unwrap_or_default();
+        let partition_name = manifest.last_partition().map(str::to_string);
+        let partition = match partition_name.as_deref().map(Partition::parse) {
+            Some(Ok(partition)) => Some(partition),
+            Some(Err(e)) => {
+                log::warn!("Counting every record of {}: {}", results_dir, e);
+                None
+            },
+            None => None,
+        };
+        
+        let mut run = RunStatus {
+            results_dir: results_dir.clone(),
+            run_id: manifest.run_id.clone(),
+            partition: partition_name,
+            statuses: BTreeMap::new(),
+        };
+        let in_partition = |path: &Path| partition.is_none_or(|partition| {
+            path.file_stem().is_some_and(|stem| partition.contains(&stem.to_string_lossy()))
+        });
+        for file_path in read_query_files(results_dir)?.iter().filter(|path| in_partition(path)) {
+            match read_query_file(file_path) {
+                Ok(record) => {
+                    *run.statuses.entry(format!("{:?}", record.status)).or_insert(0) += 1;
+                    keys.insert(record.key);
+                },
+                Err(e) => log::warn!("Skipping unreadable query file {}: {}", file_path.display(), e),
+            }
+        }
+        for file_path in dead_letter_files(results_dir)?.iter().filter(|path| in_partition(path)) {
+            if let Ok(record) = read_query_file(file_path) {
+                *run.statuses.entry("DeadLetter".to_string()).or_insert(0) += 1;
+                keys.insert(record.key);
+            }
+        }
+        runs.push(run);
+    }
+    Ok((runs, keys.len()))
+}
+
+/// Build per-column before/after statistics from the completed queries of a run.
+///
+/// The "before" value is the one the selection returned, stored with each query at
+/// generation; the "after" value is the literal the UPDATE assigned. Queries without
+/// stored originals (older runs, procedure calls, expressions) are left out.
+pub fn diff_report(results_dir: &str) -> Result<Vec<ColumnDiff>, Box<dyn Error>> {
+    let mut diffs: BTreeMap<String, ColumnDiff> = BTreeMap::new();
+    
+    for file_path in read_query_files(results_dir)? {
+        let record = match read_query_file(&file_path) {
+            Ok(record) => record,
+            Err(e) => {
+                log::warn!("Skipping unreadable query file {}: {}", file_path.display(), e);
+                continue;
+            }
+        };
+        if record.status != QueryStatus::Completed {
+            continue;
+        }
+        
+        for (column, before, after) in column_changes(&record) {
+            let diff = diffs.entry(column.to_lowercase()).or_insert_with(|| ColumnDiff {
+                column: column.clone(),
+                ..ColumnDiff::default()
+            });
+            
+            if before == after {
+                diff.unchanged_rows += 1;
+            } else {
+                diff.changed_rows += 1;
+            }
+            *diff.lengths_before.entry(length_bucket(before.chars().count())).or_insert(0) += 1;
+            *diff.lengths_after.entry(length_bucket(after.chars().count())).or_insert(0) += 1;
+            
+            // Sensitive columns are counted but their values never reach the report
+            let transition = (masking::mask_value(&column, &before), masking::mask_value(&column, &after));
+            *diff.transitions.entry(transition).or_insert(0) += 1;
+        }
+    }
+    
+    Ok(diffs.into_values().collect())
+}
+
+/// (column, before, after) for each literal assignment with a stored original value
+fn column_changes(record: &QueryRecord) -> Vec<(String, String, String)> {
+    let parts = match parse_update_statement(&record.query) {
+        Some(parts) => parts,
+        None => return Vec::new(),
+    };
+    
+    parse_set_assignments(&parts.set_clause)
+        .into_iter()
+        .filter_map(|(column, after)| {
+            let before = record.original_values.get(&column)?;
+            Some((column, before.clone(), after?))
+        })
+        .collect()
+}
+
+/// Write the diff report as CSV (every transition) and text (summary) into the results directory
+pub fn write_diff_report(results_dir: &str, diffs: &[ColumnDiff]) -> Result<(), Box<dyn Error>> {
+    let mut csv = String::from("column,before,after,rows\r\n");
+    let mut text = String::new();
+    
+    for diff in diffs {
+        for ((before, after), rows) in diff.sorted_transitions() {
+            csv.push_str(&format!("{},{},{},{}\r\n", csv_field(&diff.column), csv_field(before), csv_field(after), rows));
+        }
+        for line in diff.report_lines() {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    
+    fs::write(Path::new(results_dir).join(DIFF_REPORT_CSV_NAME), csv)?;
+    fs::write(Path::new(results_dir).join(DIFF_REPORT_TEXT_NAME), text)?;
+    Ok(())
+}
+
+/// Exact lengths up to 20 characters, wider buckets above that
+fn length_bucket(length: usize) -> usize {
+    match length {
+        0..=20 => length,
+        21..=50 => 21,
+        51..=100 => 51,
+        101..=255 => 101,
+        _ => 256,
+    }
+}
+
+fn bucket_label(bucket: usize) -> String {
+    match bucket {
+        21 => "21-50".to_string(),
+        51 => "51-100".to_string(),
+        101 => "101-255".to_string(),
+        256 => "256+".to_string(),
+        _ => bucket.to_string(),
+    }
+}
+
+fn format_lengths(lengths: &BTreeMap<usize, usize>) -> String {
+    lengths.iter()
+        .map(|(bucket, rows)| format!("{} chars x{}", bucket_label(*bucket), rows))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_value(value: &str) -> String {
+    if value.is_empty() {
+        "(empty)".to_string()
+    } else {
+        format!("'{}'", value)
+    }
+}
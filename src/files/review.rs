@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::QueryStatus;
+use crate::files::json_handler::{read_query_file, read_query_files, save_query_file};
+use crate::files::snapshot::csv_field;
+use crate::utils::time;
+
+/// Name of the review sheet written into the results directory
+pub const REVIEW_FILE_NAME: &str = "review.csv";
+
+/// Column reviewers fill in to approve a query
+const APPROVE_COLUMN: &str = "approve";
+
+/// What importing a review sheet did to the pending queries
+#[derive(Debug, Clone, Default)]
+pub struct ReviewSummary {
+    /// Pending queries approved in the sheet
+    pub approved: usize,
+    /// Pending queries marked Skipped because they weren't approved
+    pub skipped: usize,
+    /// Pending queries missing from the sheet, also marked Skipped
+    pub missing: usize,
+    /// Sheet rows whose key has no pending query
+    pub unknown: usize,
+}
+
+/// Write a CSV sheet of all pending queries with an empty `approve` column.
+///
+/// The file starts with a UTF-8 byte order mark so Excel opens it with the right
+/// encoding. Returns the number of queries written.
+pub fn export_review(results_dir: &str) -> Result<usize, Box<dyn Error>> {
+    let mut query_files = read_query_files(results_dir)?;
+    query_files.sort();
+    
+    let mut content = String::from("\u{feff}");
+    content.push_str(&format!("key,status,ticket,note,query,{}\r\n", APPROVE_COLUMN));
+    
+    let mut count = 0;
+    for file_path in &query_files {
+        let record = read_query_file(file_path)?;
+        if record.status != QueryStatus::Pending {
+            continue;
+        }
+        
+        content.push_str(&format!(
+            "{},{:?},{},{},{},\r\n",
+            csv_field(&record.key),
+            record.status,
+            csv_field(record.ticket.as_deref().unwrap_or("")),
+            csv_field(record.note.as_deref().unwrap_or("")),
+            csv_field(&record.query)
+        ));
+        count += 1;
+    }
+    
+    let sheet_path = Path::new(results_dir).join(REVIEW_FILE_NAME);
+    fs::write(&sheet_path, content)?;
+    
+    log::info!("Wrote {} pending queries to review sheet {}", count, sheet_path.display());
+    
+    Ok(count)
+}
+
+/// Apply a filled-in review sheet to a results directory.
+///
+/// Pending queries whose `approve` cell says yes (`y`, `yes`, `x`, `true`, `1`,
+/// `approved`) stay Pending. Every other pending query, including any the sheet
+/// doesn't mention, is marked Skipped so nothing runs without approval.
+pub fn import_review(results_dir: &str, sheet_file: &str) -> Result<ReviewSummary, Box<dyn Error>> {
+    let content = fs::read_to_string(sheet_file)
+        .map_err(|e| format!("Failed to read review sheet {}: {}", sheet_file, e))?;
+    let rows = parse_csv(content.trim_start_matches('\u{feff}'));
+    
+    let header = rows.first().ok_or("Review sheet is empty")?;
+    let column = |name: &str| header.iter().position(|cell| cell.trim().eq_ignore_ascii_case(name));
+    let key_index = column("key").ok_or("Review sheet has no 'key' column")?;
+    let approve_index = column(APPROVE_COLUMN).ok_or("Review sheet has no 'approve' column")?;
+    
+    let mut decisions: HashMap<String, bool> = HashMap::new();
+    for row in rows.iter().skip(1) {
+        let key = match row.get(key_index) {
+            Some(key) if !key.trim().is_empty() => key.trim().to_string(),
+            _ => continue,
+        };
+        let approved = row.get(approve_index).is_some_and(|cell| is_approval(cell));
+        decisions.insert(key, approved);
+    }
+    
+    let mut summary = ReviewSummary::default();
+    let mut pending_keys = HashSet::new();
+    
+    for file_path in read_query_files(results_dir)? {
+        let mut record = read_query_file(&file_path)?;
+        if record.status != QueryStatus::Pending {
+            continue;
+        }
+        pending_keys.insert(record.key.clone());
+        
+        let reason = match decisions.get(&record.key) {
+            Some(true) => {
+                summary.approved += 1;
+                continue;
+            },
+            Some(false) => {
+                summary.skipped += 1;
+                "skipped - not approved in review"
+            },
+            None => {
+                summary.missing += 1;
+                "skipped - missing from review sheet"
+            },
+        };
+        
+        log::info!("Review: {} for key {}", reason, record.key);
+        record.status = QueryStatus::Skipped;
+        record.result = Some(reason.to_string());
+        record.timestamp = Some(time::now_rfc3339());
+        save_query_file(&file_path, &record)?;
+    }
+    
+    summary.unknown = decisions.keys()
+        .filter(|key| !pending_keys.contains(*key))
+        .inspect(|key| log::warn!("Review sheet key {} has no pending query in {}", key, results_dir))
+        .count();
+    
+    log::info!(
+        "Imported review {}: {} approved, {} not approved, {} missing from sheet, {} unknown keys",
+        sheet_file, summary.approved, summary.skipped, summary.missing, summary.unknown
+    );
+    
+    Ok(summary)
+}
+
+/// Whether a reviewer's cell means "approved"
+fn is_approval(cell: &str) -> bool {
+    matches!(cell.trim().to_lowercase().as_str(), "y" | "yes" | "x" | "true" | "1" | "approved" | "approve")
+}
+
+/// Parse CSV text into rows of fields, honouring quoted fields with embedded
+/// delimiters, doubled quotes and line breaks
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                },
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        
+        match c {
+            '"' => in_quotes = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\r' | '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            _ => field.push(c),
+        }
+    }
+    
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::query::QueryRecord;
+    
+    #[test]
+    fn only_approved_queries_stay_pending_after_review() {
+        let dir = std::env::temp_dir().join(format!("ibp-review-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let results_dir = dir.to_string_lossy().to_string();
+        for key in ["1001", "1002", "1003"] {
+            let record = QueryRecord::new(key.to_string(), format!("UPDATE customer SET note = 'a, \"b\"' WHERE zip = '{}'", key));
+            save_query_file(dir.join(format!("{}.json", key)), &record).unwrap();
+        }
+        
+        assert_eq!(export_review(&results_dir).unwrap(), 3);
+        let sheet = fs::read_to_string(dir.join(REVIEW_FILE_NAME)).unwrap();
+        let rows = parse_csv(sheet.trim_start_matches('\u{feff}'));
+        assert_eq!(rows[1][4], "UPDATE customer SET note = 'a, \"b\"' WHERE zip = '1001'");
+        
+        // The reviewer approves 1001, rejects 1002, drops 1003 and adds a key that isn't pending
+        let filled = "key,approve\r\n1001,Yes\r\n1002,\r\n9999,x\r\n";
+        let filled_path = dir.join("filled.csv");
+        fs::write(&filled_path, filled).unwrap();
+        let summary = import_review(&results_dir, &filled_path.to_string_lossy()).unwrap();
+        assert_eq!((summary.approved, summary.skipped, summary.missing, summary.unknown), (1, 1, 1, 1));
+        
+        let status = |key: &str| read_query_file(dir.join(format!("{}.json", key))).unwrap().status;
+        assert_eq!(status("1001"), QueryStatus::Pending);
+        assert_eq!(status("1002"), QueryStatus::Skipped);
+        assert_eq!(status("1003"), QueryStatus::Skipped);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use chrono::{DateTime, FixedOffset};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::db::query::QueryStatus;
+use crate::files::dead_letter::dead_letter_files;
+use crate::files::errors::{group_errors, read_error_records, ErrorGrouping};
+use crate::files::events::read_events;
+use crate::files::json_handler::{read_query_file, read_query_files};
+use crate::files::manifest::RunManifest;
+use crate::files::report::status_summary;
+
+/// Human-readable run reports, written by `report --format`
+pub const MARKDOWN_REPORT_FILE_NAME: &str = "report.md";
+pub const HTML_REPORT_FILE_NAME: &str = "report.html";
+
+/// How many of the slowest statements a report lists
+const SLOWEST_QUERIES: usize = 10;
+
+/// Format of a written run report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!("unknown report format '{}' (expected md or html)", name)),
+        }
+    }
+    
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => MARKDOWN_REPORT_FILE_NAME,
+            ReportFormat::Html => HTML_REPORT_FILE_NAME,
+        }
+    }
+}
+
+/// One table of a report
+struct Section {
+    title: String,
+    /// Shown above the table
+    summary: Option<String>,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Write a report of a results directory for people who weren't at the console: totals by
+/// status, the execution's duration, errors by SQLCODE, the slowest statements, the index
+/// advisory and, for the county commands, the corrections per county. Returns the path written.
+pub fn write_run_report(results_dir: &str, format: ReportFormat) -> Result<String, Box<dyn Error>> {
+    let manifest = RunManifest::load(results_dir);
+    let title = format!("Run report: {}", results_dir);
+    let mut overview = vec![vec!["Results directory".to_string(), results_dir.to_string()]];
+    if let Some(manifest) = &manifest {
+        overview.push(vec!["Run".to_string(), manifest.run_id.clone()]);
+        overview.push(vec!["Generated".to_string(), manifest.created.clone()]);
+        overview.push(vec!["Matched keys".to_string(), manifest.matched_keys.to_string()]);
+        if let Some(ticket) = &manifest.ticket {
+            overview.push(vec!["Ticket".to_string(), ticket.clone()]);
+        }
+    }
+    
+    let timings = execution_timings(results_dir)?;
+    if let (Some(started), Some(finished)) = (timings.started, timings.finished) {
+        overview.push(vec!["Execution started".to_string(), started.to_rfc3339()]);
+        overview.push(vec!["Execution finished".to_string(), finished.to_rfc3339()]);
+        overview.push(vec!["Duration".to_string(), format_duration((finished - started).num_seconds())]);
+    }
+    
+    let mut sections = vec![Section { title: "Overview".to_string(), summary: None, headers: vec!["", "Value"], rows: overview }];
+    
+    let mut statuses: Vec<Vec<String>> = status_summary(results_dir)?.into_iter()
+        .map(|(status, count)| vec![status, count.to_string()])
+        .collect();
+    let dead_letters = dead_letter_files(results_dir)?.len();
+    if dead_letters > 0 {
+        statuses.push(vec!["DeadLetter".to_string(), dead_letters.to_string()]);
+    }
+    let total: usize = statuses.iter().filter_map(|row| row[1].parse::<usize>().ok()).sum();
+    statuses.push(vec!["Total".to_string(), total.to_string()]);
+    sections.push(Section { title: "Queries by status".to_string(), summary: None, headers: vec!["Status", "Queries"], rows: statuses });
+    
+    let groups = group_errors(&read_error_records(results_dir)?, ErrorGrouping::Sqlcode);
+    if !groups.is_empty() {
+        sections.push(Section {
+            title: "Errors by SQLCODE".to_string(),
+            summary: Some(format!("{} errors in {} groups", groups.iter().map(|group| group.count).sum::<usize>(), groups.len())),
+            headers: vec!["SQLCODE", "Errors", "First seen", "Last seen", "Example"],
+            rows: groups.iter()
+                .map(|group| vec![group.label.clone(), group.count.to_string(), group.first_seen.clone(), group.last_seen.clone(), group.example.clone()])
+                .collect(),
+        });
+    }
+    
+    if !timings.durations.is_empty() {
+        let mut slowest = timings.durations.clone();
+        slowest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sections.push(Section {
+            title: "Slowest queries".to_string(),
+            summary: Some(format!("From sending the statement to recording its outcome, {} of {} executions", slowest.len().min(SLOWEST_QUERIES), slowest.len())),
+            headers: vec!["Key", "Seconds", "Outcome"],
+            rows: slowest.iter().take(SLOWEST_QUERIES)
+                .map(|(key, milliseconds, outcome)| vec![key.clone(), format!("{:.3}", *milliseconds as f64 / 1000.0), format!("{:?}", outcome)])
+                .collect(),
+        });
+    }
+    
+    let index_advice = manifest.as_ref().map(|manifest| manifest.index_advice.as_slice()).unwrap_or_default();
+    if !index_advice.is_empty() {
+        sections.push(Section {
+            title: "Index advisory".to_string(),
+            summary: Some("Predicates no index of their table starts with, found when the queries were generated".to_string()),
+            headers: vec!["Table", "Filtered by", "Columns", "Suggestion"],
+            rows: index_advice.iter()
+                .map(|advice| vec![advice.table.clone(), advice.source.clone(), advice.columns.join(", "), advice.suggestion.clone()])
+                .collect(),
+        });
+    }
+    
+    let counties = county_changes(results_dir)?;
+    if !counties.is_empty() {
+        sections.push(Section {
+            title: "County corrections".to_string(),
+            summary: Some("Corrections per new county code".to_string()),
+            headers: vec!["County", "Completed", "Pending", "Failed", "Other"],
+            rows: counties.into_iter()
+                .map(|(county, counts)| {
+                    let mut row = vec![county];
+                    row.extend(counts.iter().map(|count| count.to_string()));
+                    row
+                })
+                .collect(),
+        });
+    }
+    
+    let content = match format {
+        ReportFormat::Markdown => render_markdown(&title, &sections),
+        ReportFormat::Html => render_html(&title, &sections),
+    };
+    let path = Path::new(results_dir).join(format.file_name());
+    fs::write(&path, content)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// When execution ran, and how long each statement took, from the event log
+#[derive(Default)]
+struct ExecutionTimings {
+    started: Option<DateTime<FixedOffset>>,
+    finished: Option<DateTime<FixedOffset>>,
+    /// Key, milliseconds from InProgress to its outcome, and the outcome
+    durations: Vec<(String, i64, QueryStatus)>,
+}
+
+fn execution_timings(results_dir: &str) -> Result<ExecutionTimings, Box<dyn Error>> {
+    let mut timings = ExecutionTimings::default();
+    let mut in_progress: HashMap<String, DateTime<FixedOffset>> = HashMap::new();
+    for event in read_events(results_dir)? {
+        let time = match DateTime::parse_from_rfc3339(&event.timestamp) {
+            Ok(time) => time,
+            Err(_) => continue,
+        };
+        if event.to == QueryStatus::InProgress {
+            timings.started = Some(timings.started.map_or(time, |started| started.min(time)));
+            in_progress.insert(event.key, time);
+        } else if event.from == Some(QueryStatus::InProgress) {
+            timings.finished = Some(timings.finished.map_or(time, |finished| finished.max(time)));
+            if let Some(sent) = in_progress.remove(&event.key) {
+                timings.durations.push((event.key, (time - sent).num_milliseconds(), event.to));
+            }
+        }
+    }
+    Ok(timings)
+}
+
+/// Completed, Pending, Failed and other corrections per new county code
+fn county_changes(results_dir: &str) -> Result<BTreeMap<String, [usize; 4]>, Box<dyn Error>> {
+    let mut counties: BTreeMap<String, [usize; 4]> = BTreeMap::new();
+    for file_path in read_query_files(results_dir)? {
+        let record = match read_query_file(&file_path) {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if let Some(change) = &record.county_change {
+            let column = match record.status {
+                QueryStatus::Completed => 0,
+                QueryStatus::Pending => 1,
+                QueryStatus::Failed => 2,
+                _ => 3,
+            };
+            counties.entry(change.new_county.clone()).or_default()[column] += 1;
+        }
+    }
+    Ok(counties)
+}
+
+fn format_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    format!("{}h {:02}m {:02}s", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn render_markdown(title: &str, sections: &[Section]) -> String {
+    let cell = |value: &str| value.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let mut out = format!("# {}\n", cell(title));
+    for section in sections {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if let Some(summary) = &section.summary {
+            out.push_str(&format!("{}\n\n", cell(summary)));
+        }
+        out.push_str(&format!("| {} |\n", section.headers.iter().map(|header| cell(header)).collect::<Vec<_>>().join(" | ")));
+        out.push_str(&format!("|{}|\n", vec!["---"; section.headers.len()].join("|")));
+        for row in &section.rows {
+            out.push_str(&format!("| {} |\n", row.iter().map(|value| cell(value)).collect::<Vec<_>>().join(" | ")));
+        }
+    }
+    out
+}
+
+fn render_html(title: &str, sections: &[Section]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>\n\
+         body {{ font-family: sans-serif; margin: 2em; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1.5em; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}\n\
+         th {{ background: #f0f0f0; }}\n\
+         </style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(title)
+    );
+    for section in sections {
+        out.push_str(&format!("<h2>{}</h2>\n", escape_html(&section.title)));
+        if let Some(summary) = &section.summary {
+            out.push_str(&format!("<p>{}</p>\n", escape_html(summary)));
+        }
+        out.push_str("<table>\n<tr>");
+        for header in &section.headers {
+            out.push_str(&format!("<th>{}</th>", escape_html(header)));
+        }
+        out.push_str("</tr>\n");
+        for row in &section.rows {
+            out.push_str("<tr>");
+            for value in row {
+                out.push_str(&format!("<td>{}</td>", escape_html(value)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
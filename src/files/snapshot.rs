@@ -0,0 +1,199 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Name of the selection snapshot written into the results directory
+pub const SNAPSHOT_FILE_NAME: &str = "selection_snapshot.csv.gz";
+
+// Largest payload of a single stored deflate block
+const MAX_STORED_BLOCK: usize = 65535;
+
+/// Gzip writer producing a standard `.gz` stream.
+///
+/// The deflate payload uses stored (uncompressed) blocks, which need no compression crate.
+/// Any gzip reader (`zcat`, `gunzip`, pandas, Excel via 7-Zip) accepts the file; it is
+/// simply no smaller than the CSV.
+struct GzipWriter<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+    crc: u32,
+    size: u32,
+    crc_table: [u32; 256],
+}
+
+impl<W: Write> GzipWriter<W> {
+    fn new(mut inner: W) -> Result<Self, Box<dyn Error>> {
+        // ID1 ID2, CM = deflate, no flags, no mtime, no extra flags, OS = unknown
+        inner.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255])?;
+        
+        let mut crc_table = [0u32; 256];
+        for (index, entry) in crc_table.iter_mut().enumerate() {
+            let mut value = index as u32;
+            for _ in 0..8 {
+                value = if value & 1 != 0 { 0xEDB8_8320 ^ (value >> 1) } else { value >> 1 };
+            }
+            *entry = value;
+        }
+        
+        Ok(GzipWriter { inner, pending: Vec::with_capacity(MAX_STORED_BLOCK), crc: 0xFFFF_FFFF, size: 0, crc_table })
+    }
+    
+    fn write_bytes(&mut self, mut bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+        for &byte in bytes {
+            self.crc = self.crc_table[((self.crc ^ byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+        self.size = self.size.wrapping_add(bytes.len() as u32);
+        
+        while !bytes.is_empty() {
+            let room = MAX_STORED_BLOCK - self.pending.len();
+            let take = room.min(bytes.len());
+            self.pending.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            if self.pending.len() == MAX_STORED_BLOCK {
+                self.write_block(false)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn write_block(&mut self, last: bool) -> Result<(), Box<dyn Error>> {
+        let len = self.pending.len() as u16;
+        self.inner.write_all(&[if last { 1 } else { 0 }])?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner.write_all(&(!len).to_le_bytes())?;
+        self.inner.write_all(&self.pending)?;
+        self.pending.clear();
+        Ok(())
+    }
+    
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.write_block(true)?;
+        self.inner.write_all(&(self.crc ^ 0xFFFF_FFFF).to_le_bytes())?;
+        self.inner.write_all(&self.size.to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes every row a selection query returned to a gzipped CSV file
+pub struct SelectionSnapshot {
+    writer: GzipWriter<BufWriter<File>>,
+    rows: usize,
+}
+
+impl SelectionSnapshot {
+    /// Create the snapshot file in a results directory and write the header row
+    pub fn create(results_dir: &str, columns: &[String]) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(Path::new(results_dir).join(SNAPSHOT_FILE_NAME))?;
+        let mut snapshot = SelectionSnapshot {
+            writer: GzipWriter::new(BufWriter::new(file))?,
+            rows: 0,
+        };
+        snapshot.write_record(columns)?;
+        Ok(snapshot)
+    }
+    
+    /// Append one fetched row
+    pub fn write_row(&mut self, values: &[String]) -> Result<(), Box<dyn Error>> {
+        self.write_record(values)?;
+        self.rows += 1;
+        Ok(())
+    }
+    
+    fn write_record(&mut self, values: &[String]) -> Result<(), Box<dyn Error>> {
+        let line = values.iter()
+            .map(|value| csv_field(value))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.writer.write_bytes(line.as_bytes())?;
+        self.writer.write_bytes(b"\r\n")
+    }
+    
+    /// Complete the gzip stream. Returns the number of data rows written.
+    pub fn finish(self) -> Result<usize, Box<dyn Error>> {
+        self.writer.finish()?;
+        Ok(self.rows)
+    }
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break
+pub fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') || value.contains('\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::inflate::inflate;
+    use std::process::{Command, Stdio};
+    
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut writer = GzipWriter::new(&mut output).unwrap();
+        // In pieces, so blocks fill across writes
+        for chunk in data.chunks(1000) {
+            writer.write_bytes(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        output
+    }
+    
+    fn trailer(stream: &[u8]) -> (u32, u32) {
+        let trailer = &stream[stream.len() - 8..];
+        (
+            u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]),
+            u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]),
+        )
+    }
+    
+    #[test]
+    fn writes_the_standard_check_value() {
+        let stream = gzip(b"123456789");
+        assert_eq!(&stream[..3], &[0x1f, 0x8b, 8]);
+        assert_eq!(trailer(&stream), (0xCBF4_3926, 9));
+        assert_eq!(inflate(&stream[10..stream.len() - 8]).unwrap(), b"123456789");
+    }
+    
+    #[test]
+    fn round_trips_data_spanning_several_blocks() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let stream = gzip(&data);
+        assert_eq!(trailer(&stream).1, data.len() as u32);
+        assert_eq!(inflate(&stream[10..stream.len() - 8]).unwrap(), data);
+    }
+    
+    #[test]
+    fn round_trips_an_empty_stream() {
+        let stream = gzip(b"");
+        assert_eq!(trailer(&stream), (0, 0));
+        assert!(inflate(&stream[10..stream.len() - 8]).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn gzip_accepts_the_stream() {
+        // Skipped where no gzip binary is installed
+        let mut child = match Command::new("gzip").args(["-dc"]).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(_) => return,
+        };
+        let data = "key,zip_code\r\n1,98101\r\n".repeat(5000);
+        child.stdin.take().unwrap().write_all(&gzip(data.as_bytes())).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, data.as_bytes());
+    }
+    
+    #[test]
+    fn quotes_fields_that_need_it() {
+        assert_eq!(csv_field("98101"), "98101");
+        assert_eq!(csv_field("King, WA"), "\"King, WA\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
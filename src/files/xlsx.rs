@@ -0,0 +1,287 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+
+use crate::utils::inflate::inflate;
+
+/// Rows of one worksheet of an .xlsx workbook as text, the first sheet unless `sheet` names
+/// one. Shared and inline strings are resolved and numbers keep the text Excel stored, with
+/// whole numbers written without a decimal point. Cells a row skips are empty strings.
+pub fn read_xlsx_rows(path: &str, sheet: Option<&str>) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let archive = ZipArchive::parse(&bytes).map_err(|e| format!("{} is not an .xlsx workbook: {}", path, e))?;
+    
+    let sheet_path = worksheet_path(&archive, sheet).map_err(|e| format!("{}: {}", path, e))?;
+    let shared_strings = match archive.entry("xl/sharedStrings.xml")? {
+        Some(xml) => shared_strings(&xml),
+        None => Vec::new(),
+    };
+    let xml = archive.entry(&sheet_path)?.ok_or_else(|| format!("{} has no worksheet {}", path, sheet_path))?;
+    Ok(worksheet_rows(&xml, &shared_strings))
+}
+
+/// The entries of a zip file, read from its central directory
+struct ZipArchive<'a> {
+    bytes: &'a [u8],
+    /// name -> (compression method, compressed size, local header offset)
+    entries: HashMap<String, (u16, usize, usize)>,
+}
+
+impl<'a> ZipArchive<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, Box<dyn Error>> {
+        // The end of central directory record is the last thing in the file, before an optional comment
+        let end = (0..bytes.len().saturating_sub(21)).rev()
+            .find(|&offset| read_u32(bytes, offset) == Some(0x0605_4b50))
+            .ok_or("no zip end of central directory record")?;
+        let count = read_u16(bytes, end + 10).ok_or("truncated zip directory")? as usize;
+        let mut offset = read_u32(bytes, end + 16).ok_or("truncated zip directory")? as usize;
+        
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            if read_u32(bytes, offset) != Some(0x0201_4b50) {
+                return Err("corrupt zip central directory".into());
+            }
+            let field = |at: usize| read_u16(bytes, offset + at).ok_or("truncated zip directory");
+            let method = field(10)?;
+            let compressed_size = read_u32(bytes, offset + 20).ok_or("truncated zip directory")?;
+            let name_length = field(28)? as usize;
+            let extra_length = field(30)? as usize;
+            let comment_length = field(32)? as usize;
+            let local_offset = read_u32(bytes, offset + 42).ok_or("truncated zip directory")?;
+            if compressed_size == u32::MAX || local_offset == u32::MAX {
+                return Err("zip64 archives are not supported".into());
+            }
+            let name = bytes.get(offset + 46..offset + 46 + name_length).ok_or("truncated zip directory")?;
+            entries.insert(
+                String::from_utf8_lossy(name).replace('\\', "/"),
+                (method, compressed_size as usize, local_offset as usize),
+            );
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+        Ok(ZipArchive { bytes, entries })
+    }
+    
+    /// The decompressed content of an entry, `None` when the archive has no such entry
+    fn entry(&self, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let (method, size, offset) = match self.entries.get(name) {
+            Some(entry) => *entry,
+            None => return Ok(None),
+        };
+        if read_u32(self.bytes, offset) != Some(0x0403_4b50) {
+            return Err(format!("corrupt zip entry {}", name).into());
+        }
+        let name_length = read_u16(self.bytes, offset + 26).ok_or("truncated zip entry")? as usize;
+        let extra_length = read_u16(self.bytes, offset + 28).ok_or("truncated zip entry")? as usize;
+        let start = offset + 30 + name_length + extra_length;
+        let data = self.bytes.get(start..start + size).ok_or_else(|| format!("truncated zip entry {}", name))?;
+        
+        let content = match method {
+            0 => data.to_vec(),
+            8 => inflate(data).map_err(|e| format!("zip entry {}: {}", name, e))?,
+            other => return Err(format!("zip entry {} uses unsupported compression method {}", name, other).into()),
+        };
+        Ok(Some(String::from_utf8_lossy(&content).to_string()))
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Path inside the archive of the named worksheet, or of the first one in workbook order
+fn worksheet_path(archive: &ZipArchive, sheet: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let workbook = archive.entry("xl/workbook.xml")?.ok_or("no xl/workbook.xml")?;
+    let relationships = archive.entry("xl/_rels/workbook.xml.rels")?.ok_or("no xl/_rels/workbook.xml.rels")?;
+    
+    let sheets: Vec<(String, String)> = Regex::new(r"<sheet\b[^>]*>").unwrap()
+        .find_iter(&workbook)
+        .filter_map(|tag| Some((xml_attribute(tag.as_str(), "name")?, xml_attribute(tag.as_str(), "r:id")?)))
+        .collect();
+    let (name, relationship) = match sheet.map(str::trim).filter(|sheet| !sheet.is_empty()) {
+        Some(wanted) => sheets.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .ok_or_else(|| format!(
+                "no sheet named '{}' (sheets: {})",
+                wanted, sheets.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", ")
+            ))?,
+        None => sheets.first().ok_or("the workbook has no sheets")?,
+    };
+    
+    let target = Regex::new(r"<Relationship\b[^>]*>").unwrap()
+        .find_iter(&relationships)
+        .find(|tag| xml_attribute(tag.as_str(), "Id").as_deref() == Some(relationship.as_str()))
+        .and_then(|tag| xml_attribute(tag.as_str(), "Target"))
+        .ok_or_else(|| format!("sheet '{}' has no worksheet part", name))?;
+    // Targets are relative to xl/ unless absolute
+    Ok(match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("xl/{}", target),
+    })
+}
+
+/// The shared string table, joining the runs of rich text strings
+fn shared_strings(xml: &str) -> Vec<String> {
+    let text = Regex::new(r"(?s)<t\b[^>]*?(?:/>|>(.*?)</t>)").unwrap();
+    Regex::new(r"(?s)<si\b[^>]*?(?:/>|>(.*?)</si>)").unwrap()
+        .captures_iter(xml)
+        .map(|item| {
+            let content = item.get(1).map_or("", |content| content.as_str());
+            // Phonetic runs (<rPh>) are reading aids, not part of the value
+            let content = Regex::new(r"(?s)<rPh\b.*?</rPh>").unwrap().replace_all(content, "");
+            text.captures_iter(&content)
+                .map(|run| unescape_xml(run.get(1).map_or("", |run| run.as_str())))
+                .collect()
+        })
+        .collect()
+}
+
+fn worksheet_rows(xml: &str, shared_strings: &[String]) -> Vec<Vec<String>> {
+    let row_pattern = Regex::new(r"(?s)<row\b[^>]*?(?:/>|>(.*?)</row>)").unwrap();
+    let cell_pattern = Regex::new(r"(?s)<c\b([^>]*?)(?:/>|>(.*?)</c>)").unwrap();
+    let value_pattern = Regex::new(r"(?s)<v>(.*?)</v>").unwrap();
+    let inline_pattern = Regex::new(r"(?s)<t\b[^>]*>(.*?)</t>").unwrap();
+    
+    let mut rows = Vec::new();
+    for row in row_pattern.captures_iter(xml) {
+        let mut values: Vec<String> = Vec::new();
+        let content = row.get(1).map_or("", |content| content.as_str());
+        for cell in cell_pattern.captures_iter(content) {
+            let attributes = &cell[1];
+            let inner = cell.get(2).map_or("", |inner| inner.as_str());
+            let raw = value_pattern.captures(inner).map(|value| unescape_xml(&value[1]));
+            
+            let value = match xml_attribute(attributes, "t").as_deref() {
+                Some("s") => raw.and_then(|index| index.trim().parse::<usize>().ok())
+                    .and_then(|index| shared_strings.get(index).cloned())
+                    .unwrap_or_default(),
+                Some("inlineStr") => inline_pattern.captures_iter(inner).map(|run| unescape_xml(&run[1])).collect(),
+                Some("b") => match raw.as_deref() {
+                    Some("1") => "TRUE".to_string(),
+                    Some(_) => "FALSE".to_string(),
+                    None => String::new(),
+                },
+                Some("str") | Some("e") => raw.unwrap_or_default(),
+                _ => raw.map(|number| whole_number(&number)).unwrap_or_default(),
+            };
+            
+            // Place the value in its column; cells without a reference follow the previous one
+            let column = xml_attribute(attributes, "r")
+                .and_then(|reference| column_index(&reference))
+                .unwrap_or(values.len());
+            if column >= values.len() {
+                values.resize(column + 1, String::new());
+            }
+            values[column] = value;
+        }
+        rows.push(values);
+    }
+    rows
+}
+
+/// Zero-based column of a cell reference such as `AB12`
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<char> = reference.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    if letters.is_empty() {
+        return None;
+    }
+    let number = letters.iter().fold(0usize, |number, letter| number * 26 + (letter.to_ascii_uppercase() as usize - 'A' as usize + 1));
+    Some(number - 1)
+}
+
+/// `98101` for a stored `98101.0` or `9.8101E4`; anything else unchanged
+fn whole_number(text: &str) -> String {
+    let trimmed = text.trim();
+    match trimmed.parse::<f64>() {
+        Ok(number) if number.fract() == 0.0 && number.abs() < 1e15 && trimmed.chars().any(|c| matches!(c, '.' | 'e' | 'E')) => {
+            format!("{}", number as i64)
+        },
+        _ => trimmed.to_string(),
+    }
+}
+
+fn xml_attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(r#"(?:^|\s){}\s*=\s*(?:"([^"]*)"|'([^']*)')"#, regex::escape(name));
+    let captures = Regex::new(&pattern).ok()?.captures(tag)?;
+    captures.get(1).or_else(|| captures.get(2)).map(|value| unescape_xml(value.as_str()))
+}
+
+fn unescape_xml(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    Regex::new(r"&(#x[0-9A-Fa-f]+|#[0-9]+|amp|lt|gt|quot|apos);").unwrap()
+        .replace_all(text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let code = if let Some(hex) = entity.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok()
+            } else {
+                None
+            };
+            match (entity, code) {
+                (_, Some(code)) => char::from_u32(code).map(String::from).unwrap_or_default(),
+                ("amp", _) => "&".to_string(),
+                ("lt", _) => "<".to_string(),
+                ("gt", _) => ">".to_string(),
+                ("quot", _) => "\"".to_string(),
+                _ => "'".to_string(),
+            }
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/zip_mapping.xlsx");
+    
+    #[test]
+    fn reads_first_sheet_in_workbook_order() {
+        let rows = read_xlsx_rows(FIXTURE, None).unwrap();
+        assert_eq!(rows, vec![vec!["Exported from the county GIS".to_string(), "TRUE".to_string()]]);
+    }
+    
+    #[test]
+    fn reads_named_sheet() {
+        let rows = read_xlsx_rows(FIXTURE, Some("counties")).unwrap();
+        assert_eq!(rows, vec![
+            vec!["ZIP", "County", "FIPS"],
+            vec!["98101", "King County", "33"],
+            vec!["99153", "Pend Oreille & Co", "051"],
+            vec!["99362", "", "071"],
+        ]);
+    }
+    
+    #[test]
+    fn names_the_sheets_when_one_is_missing() {
+        let error = read_xlsx_rows(FIXTURE, Some("Zips")).unwrap_err().to_string();
+        assert!(error.contains("no sheet named 'Zips' (sheets: Notes, Counties)"), "{}", error);
+    }
+    
+    #[test]
+    fn rejects_files_that_are_not_zip_archives() {
+        assert!(ZipArchive::parse(b"ZIP,County\n98101,King\n").is_err());
+    }
+    
+    #[test]
+    fn whole_numbers_lose_their_decimal_point() {
+        assert_eq!(whole_number("98101.0"), "98101");
+        assert_eq!(whole_number("9.8101E4"), "98101");
+        assert_eq!(whole_number("033"), "033");
+        assert_eq!(whole_number("1.5"), "1.5");
+    }
+    
+    #[test]
+    fn column_index_of_references() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("AB12"), Some(27));
+        assert_eq!(column_index("12"), None);
+    }
+}
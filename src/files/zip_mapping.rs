@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::config::ZipMappingConfig;
+use crate::files::review::parse_csv;
+use crate::files::xlsx::read_xlsx_rows;
+use crate::zip_county_map::{fips_for_state, state_for_fips, ZipCountyInfo, BUILT_IN_STATE};
+
+/// Rows searched for the header, since published workbooks often start with a title block
+const HEADER_SEARCH_ROWS: usize = 20;
+
+/// Values sampled to tell a county code column from a FIPS or name column
+const SAMPLE_ROWS: usize = 50;
+
+/// What a column of the mapping file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnRole {
+    Zip,
+    CountyCode,
+    Fips,
+    CountyName,
+    Division,
+    State,
+}
+
+impl ColumnRole {
+    /// Header names recognised for each role, compared without case, spaces or punctuation.
+    /// A plain "County" column is asked of its values instead (see `classify_county_column`).
+    fn from_header(header: &str) -> Option<Self> {
+        match normalize_header(header).as_str() {
+            "zip" | "zipcode" | "zip5" | "postalcode" | "zcta" | "zcta5" => Some(ColumnRole::Zip),
+            "countycode" | "countycd" | "countyno" | "countynumber" | "cnty" => Some(ColumnRole::CountyCode),
+            "fips" | "fipscode" | "countyfips" | "cofips" | "fipscounty" | "stcofips" | "geoid" => Some(ColumnRole::Fips),
+            "countyname" | "cntyname" | "name" => Some(ColumnRole::CountyName),
+            "division" | "div" => Some(ColumnRole::Division),
+            "state" | "st" | "stateabbr" | "stateabbreviation" | "stusps" | "usps" => Some(ColumnRole::State),
+            _ => None,
+        }
+    }
+    
+    fn name(&self) -> &'static str {
+        match self {
+            ColumnRole::Zip => "zip",
+            ColumnRole::CountyCode => "county code",
+            ColumnRole::Fips => "FIPS code",
+            ColumnRole::CountyName => "county name",
+            ColumnRole::Division => "division",
+            ColumnRole::State => "state",
+        }
+    }
+}
+
+/// Where the header is and which column plays each role
+#[derive(Debug, Default)]
+struct MappingColumns {
+    header_row: usize,
+    roles: Vec<(ColumnRole, usize)>,
+}
+
+impl MappingColumns {
+    fn index(&self, role: ColumnRole) -> Option<usize> {
+        self.roles.iter().find(|(candidate, _)| *candidate == role).map(|(_, index)| *index)
+    }
+    
+    /// "zip = ZIP Code, county code = County" for the log
+    fn describe(&self, header: &[String]) -> String {
+        self.roles.iter()
+            .map(|(role, index)| format!("{} = {}", role.name(), header.get(*index).map(|name| name.trim()).unwrap_or("?")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Load the zip-county mapping from a .csv or .xlsx file. Columns named in the config are
+/// used as given; the rest are inferred from the header and, for an ambiguous "County"
+/// column, from its values. Rows without a state column are in the state of their 5-digit FIPS
+/// code, or else in `state` of the config. Missing FIPS codes, names and county codes of the
+/// built-in state are completed from `counties` (county code -> FIPS code, name); other states
+/// have no county codes, so the 3-digit county FIPS code stands in.
+pub fn load_zip_mapping(
+    config: &ZipMappingConfig,
+    counties: &HashMap<String, (String, String)>,
+) -> Result<HashMap<String, ZipCountyInfo>, Box<dyn Error>> {
+    let rows = read_mapping_rows(config)?;
+    let columns = infer_columns(&rows, config).map_err(|e| format!("{}: {}", config.file, e))?;
+    let cell = |row: &[String], role: ColumnRole| {
+        columns.index(role).and_then(|index| row.get(index)).map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+    };
+    
+    let mut map: HashMap<String, ZipCountyInfo> = HashMap::new();
+    let no_counties = HashMap::new();
+    let mut skipped = 0;
+    let mut conflicts = 0;
+    for (line, row) in rows.iter().enumerate().skip(columns.header_row + 1) {
+        if row.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let zip = match cell(row, ColumnRole::Zip).and_then(|zip| normalize_zip(&zip)) {
+            Some(zip) => zip,
+            None => {
+                log::warn!("Zip mapping row {}: no valid 5-digit zip code, skipped", line + 1);
+                skipped += 1;
+                continue;
+            },
+        };
+        
+        let fips_value = cell(row, ColumnRole::Fips);
+        let fips = fips_value.as_deref().and_then(county_fips);
+        let fips_state = fips_value.as_deref().and_then(state_fips).and_then(|state_fips| state_for_fips(&state_fips));
+        let state = cell(row, ColumnRole::State)
+            .or_else(|| fips_state.map(str::to_string))
+            .unwrap_or_else(|| config.state.clone())
+            .trim()
+            .to_uppercase();
+        // The county table only knows the built-in state's codes
+        let counties = if state == BUILT_IN_STATE { counties } else { &no_counties };
+        
+        let name = cell(row, ColumnRole::CountyName);
+        let county_code = cell(row, ColumnRole::CountyCode)
+            .map(|code| if code.chars().all(|c| c.is_ascii_digit()) { format!("{:0>2}", code) } else { code })
+            .or_else(|| fips.as_ref().and_then(|fips| counties.iter().find(|(_, (known, _))| known == fips)).map(|(code, _)| code.clone()))
+            .or_else(|| name.as_ref().and_then(|name| county_by_name(counties, name)))
+            .or_else(|| if state == BUILT_IN_STATE { None } else { fips.clone() });
+        let county_code = match county_code {
+            Some(code) => code,
+            None => {
+                log::warn!("Zip mapping row {}: zip {} has no recognisable county, skipped", line + 1, zip);
+                skipped += 1;
+                continue;
+            },
+        };
+        
+        let known = counties.get(&county_code);
+        let info = ZipCountyInfo {
+            fips_code: fips.or_else(|| known.map(|(fips, _)| fips.clone())).unwrap_or_default(),
+            county_name: name.or_else(|| known.map(|(_, name)| name.clone())).unwrap_or_default(),
+            division: cell(row, ColumnRole::Division).unwrap_or_default(),
+            state_fips: fips_for_state(&state).unwrap_or_default().to_string(),
+            state,
+            county_code,
+        };
+        match map.get(&zip) {
+            Some(existing) if existing.namespaced_code() != info.namespaced_code() => {
+                // A zip spanning counties keeps its first row; the steward decides which is primary
+                log::warn!(
+                    "Zip mapping row {}: zip {} maps to county {} but an earlier row maps it to {}, keeping the earlier",
+                    line + 1, zip, info.namespaced_code(), existing.namespaced_code()
+                );
+                conflicts += 1;
+            },
+            Some(_) => {},
+            None => {
+                map.insert(zip, info);
+            },
+        }
+    }
+    
+    if map.is_empty() {
+        return Err(format!("{} holds no usable zip-county rows", config.file).into());
+    }
+    log::info!(
+        "Loaded {} zip codes from {} ({}); {} rows skipped, {} conflicting duplicates",
+        map.len(), config.file, columns.describe(&rows[columns.header_row]), skipped, conflicts
+    );
+    Ok(map)
+}
+
+/// The file's rows as text, by extension
+pub fn read_mapping_rows(config: &ZipMappingConfig) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let extension = Path::new(&config.file).extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "xlsx" | "xlsm" => read_xlsx_rows(&config.file, Some(config.sheet.as_str())),
+        "csv" | "txt" => {
+            let content = fs::read_to_string(&config.file).map_err(|e| format!("Failed to read {}: {}", config.file, e))?;
+            Ok(parse_csv(content.trim_start_matches('\u{feff}')))
+        },
+        "xls" => Err(format!("{}: the old .xls format can't be read; save it as .xlsx or .csv", config.file).into()),
+        _ => Err(format!("{}: expected a .xlsx or .csv file", config.file).into()),
+    }
+}
+
+/// Find the header row and the column of each role
+fn infer_columns(rows: &[Vec<String>], config: &ZipMappingConfig) -> Result<MappingColumns, Box<dyn Error>> {
+    let configured = [
+        (ColumnRole::Zip, &config.zip_column),
+        (ColumnRole::CountyCode, &config.county_column),
+        (ColumnRole::Fips, &config.fips_column),
+        (ColumnRole::CountyName, &config.name_column),
+        (ColumnRole::Division, &config.division_column),
+        (ColumnRole::State, &config.state_column),
+    ];
+    let find = |header: &[String], name: &str| header.iter().position(|cell| normalize_header(cell) == normalize_header(name));
+    
+    // The header is the first row naming the zip column, configured or recognised
+    let header_row = rows.iter()
+        .take(HEADER_SEARCH_ROWS)
+        .position(|row| match config.zip_column.trim() {
+            "" => row.iter().any(|cell| ColumnRole::from_header(cell) == Some(ColumnRole::Zip)),
+            name => find(row, name).is_some(),
+        })
+        .ok_or_else(|| format!(
+            "no header row with a zip column in the first {} rows; set zip_column in [zip_mapping]",
+            HEADER_SEARCH_ROWS
+        ))?;
+    let header = &rows[header_row];
+    let mut columns = MappingColumns { header_row, roles: Vec::new() };
+    
+    for (role, name) in configured {
+        if name.trim().is_empty() {
+            continue;
+        }
+        let index = find(header, name).ok_or_else(|| format!("no '{}' column for the {}", name.trim(), role.name()))?;
+        columns.roles.push((role, index));
+    }
+    
+    for (index, cell) in header.iter().enumerate() {
+        if columns.roles.iter().any(|(_, taken)| *taken == index) {
+            continue;
+        }
+        let role = match ColumnRole::from_header(cell) {
+            Some(role) => Some(role),
+            None if normalize_header(cell) == "county" => Some(classify_county_column(&rows[header_row + 1..], index)),
+            None => None,
+        };
+        if let Some(role) = role {
+            if columns.index(role).is_none() {
+                columns.roles.push((role, index));
+            }
+        }
+    }
+    
+    if columns.index(ColumnRole::CountyCode).is_none() && columns.index(ColumnRole::Fips).is_none()
+        && columns.index(ColumnRole::CountyName).is_none() {
+        return Err(format!(
+            "no county column among [{}]; set county_column, fips_column or name_column in [zip_mapping]",
+            header.join(", ")
+        ).into());
+    }
+    Ok(columns)
+}
+
+/// A "County" column holds codes (1-2 digits), FIPS codes (3 or 5 digits) or names
+fn classify_county_column(rows: &[Vec<String>], index: usize) -> ColumnRole {
+    let samples: Vec<&str> = rows.iter()
+        .filter_map(|row| row.get(index).map(|value| value.trim()))
+        .filter(|value| !value.is_empty())
+        .take(SAMPLE_ROWS)
+        .collect();
+    if samples.is_empty() || !samples.iter().all(|value| value.chars().all(|c| c.is_ascii_digit())) {
+        ColumnRole::CountyName
+    } else if samples.iter().all(|value| value.len() <= 2) {
+        ColumnRole::CountyCode
+    } else {
+        ColumnRole::Fips
+    }
+}
+
+pub fn normalize_header(header: &str) -> String {
+    header.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+/// The 5-digit zip of `98101`, `98101-1234` or a `1234` whose leading zero a spreadsheet dropped
+pub fn normalize_zip(value: &str) -> Option<String> {
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    match digits.len() {
+        3 | 4 => Some(format!("{:0>5}", digits)),
+        5 => Some(digits),
+        9 if !value.contains('-') => Some(digits[..5].to_string()),
+        _ => None,
+    }
+}
+
+/// The 3-digit county part of a 3-digit or 5-digit (state + county) FIPS code
+fn county_fips(value: &str) -> Option<String> {
+    if !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match value.len() {
+        1..=3 => Some(format!("{:0>3}", value)),
+        4 | 5 => Some(value[value.len() - 3..].to_string()),
+        _ => None,
+    }
+}
+
+/// The 2-digit state part of a 4-digit or 5-digit FIPS code
+fn state_fips(value: &str) -> Option<String> {
+    match value.len() {
+        4 | 5 if value.chars().all(|c| c.is_ascii_digit()) => Some(format!("{:0>2}", &value[..value.len() - 3])),
+        _ => None,
+    }
+}
+
+/// The county code of "King", "King County" or "KING COUNTY"
+fn county_by_name(counties: &HashMap<String, (String, String)>, name: &str) -> Option<String> {
+    let wanted = normalize_header(name);
+    let wanted = wanted.strip_suffix("county").unwrap_or(&wanted).to_string();
+    counties.iter()
+        .find(|(_, (_, known))| {
+            let known = normalize_header(known);
+            known.strip_suffix("county").unwrap_or(&known) == wanted
+        })
+        .map(|(code, _)| code.clone())
+}
@@ -10,20 +10,18 @@ use db::query::prompt_user;
 use std::error::Error;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use crossterm::event::{self, Event, KeyCode};
-use std::io::{stdout, Write};
+use std::io::Write;
 use std::fs::File;
-use indicatif::ProgressBar;
-use chrono::prelude::*;
 
 use crate::config::AppConfig;
-use crate::db::connection::create_connection;
-use crate::db::query::{generate_queries, execute_queries};
+use crate::db::connection::{apply_session_settings, create_connection, test_connection};
+use crate::db::query::{generate_queries, execute_queries, load_preflight_results, ExecutionOptions, GenerationOptions, Partition};
 use crate::files::file_manager::setup_directories;
 use crate::files::processed::ProcessedRecords;
 use crate::ui::progress::create_progress_bar;
 
 #[derive(Parser)]
-#[clap(author, version, about = "Informix Batch Processor CLI")]
+#[clap(author, version = utils::version::BUILD, about = "Informix Batch Processor CLI")]
 struct Cli {
     #[clap(subcommand)]
     command: Option<Commands>,
@@ -31,21 +29,229 @@ struct Cli {
     /// Clean existing query files before starting
     #[clap(short, long)]
     clean: bool,
+
+    /// Don't ask for confirmation when the selection exceeds confirm_row_threshold or a chunk exceeds confirm_chunk_error_rate
+    #[clap(short, long)]
+    yes: bool,
+
+    /// Generate queries for keys the processed records already hold (generated or applied before)
+    #[clap(long)]
+    reprocess: bool,
+    
+    /// Change request the generated queries are traced to (overrides `ticket` in config)
+    #[clap(long, value_name = "TICKET")]
+    ticket: Option<String>,
+
+    /// Note stored with each generated query (overrides `note` in config)
+    #[clap(long)]
+    note: Option<String>,
+
+    /// Person running the tool, recorded with the OS user and host (overrides `operator` in config)
+    #[clap(long, value_name = "NAME")]
+    operator: Option<String>,
+
+    /// Environment the configuration is expected to connect to; required to change a protected one
+    #[clap(long = "env", value_name = "ENVIRONMENT", global = true)]
+    env: Option<String>,
+    
+    /// Use this results directory instead of a new one (`latest` for the one the last generation wrote)
+    #[clap(long, value_name = "DIR", global = true)]
+    results_dir: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Generate SQL queries based on selection criteria
-    Generate,
+    Generate {
+        /// Also write a dbaccess-ready script with pre-image UNLOAD statements
+        #[clap(long)]
+        dbaccess: bool,
+        
+        /// Only generate queries for keys the given previous results directory didn't match
+        #[clap(long, value_name = "PREVIOUS_RUN_DIR")]
+        delta: Option<String>,
+        
+        /// Carry on with the interrupted generation of the run `latest` points to
+        #[clap(long)]
+        resume: bool,
+        
+        /// Flag keys the given earlier results directory corrected that need a change again
+        #[clap(long, value_name = "OLD_RUN_DIR")]
+        compare_applied: Option<String>,
+    },
     
     /// Execute previously generated queries
-    Execute,
+    Execute {
+        /// Execute and verify N random queries first; abort the run if verification fails
+        #[clap(long)]
+        canary: Option<usize>,
+        
+        /// Stop cleanly after executing this many queries, leaving the rest Pending
+        #[clap(long)]
+        max_rows: Option<usize>,
+        
+        /// Stop cleanly after this long (e.g. 90m, 2h, 1h30m), leaving the rest Pending
+        #[clap(long, value_parser = utils::time::parse_duration)]
+        max_duration: Option<Duration>,
+        
+        /// Only execute partition N of M (e.g. 2/4), chosen by a hash of each query's key
+        #[clap(long, value_parser = Partition::parse)]
+        partition: Option<Partition>,
+        
+        /// Execute queries generated more than max_query_age_hours ago
+        #[clap(long)]
+        allow_stale: bool,
+        
+        /// Execute each pending query in a transaction that is rolled back, recording what it would do
+        #[clap(long, conflicts_with = "canary")]
+        dry_run: bool,
+    },
+    
+    /// Reset a run's failed queries to Pending so the next execute tries them again
+    RetryFailed {
+        /// Also move the queries that used up max_record_attempts back from dead_letter/
+        #[clap(long)]
+        include_dead_letter: bool,
+    },
+    
+    /// Merge query statuses from partitioned executions back into one results directory
+    Merge {
+        /// Results directory to merge into
+        #[clap(long, value_name = "RESULTS_DIR")]
+        into: String,
+        
+        /// Results directory of a partition (repeat for each)
+        #[clap(long = "from", value_name = "RESULTS_DIR", required = true)]
+        from: Vec<String>,
+    },
     
     /// Test queries for syntax errors without executing them
     Test,
     
+    /// Write a CSV sheet of a run's pending queries with an "approve" column for reviewers
+    ExportReview {
+        /// Results directory holding the queries to review
+        #[clap(long, value_name = "RESULTS_DIR")]
+        run: String,
+    },
+    
+    /// Read a filled-in review sheet and mark every pending query not approved as Skipped
+    ImportReview {
+        /// Review sheet to import
+        #[clap(long)]
+        file: String,
+        
+        /// Results directory the sheet was exported from (defaults to the sheet's directory)
+        #[clap(long, value_name = "RESULTS_DIR")]
+        run: Option<String>,
+    },
+    
+    /// Summarize a run's query statuses, optionally with a per-column before/after diff
+    Report {
+        /// Results directory to report on; repeat to consolidate several (a pipeline's phases,
+        /// a partitioned run's copies) into one table by run and partition
+        #[clap(long, value_name = "RESULTS_DIR", required = true)]
+        run: Vec<String>,
+        
+        /// Also write diff_report.csv/.txt with per-column value transitions and length distributions
+        #[clap(long)]
+        diff: bool,
+        
+        /// Consecutive executions of a worker that form one chunk in the error isolation breakdown
+        #[clap(long, default_value = "1000")]
+        chunk_size: usize,
+        
+        /// Width of the time buckets in the error isolation breakdown
+        #[clap(long, default_value = "15")]
+        bucket_minutes: i64,
+        
+        /// Also write a readable report into the results directory: md (report.md) or html (report.html)
+        #[clap(long, value_name = "FORMAT", value_parser = files::run_report::ReportFormat::from_name)]
+        format: Option<files::run_report::ReportFormat>,
+    },
+    
+    /// Aggregate the manifests and query statuses of past runs into trends (CSV and JSON)
+    Analytics {
+        /// Directory holding the results directories to aggregate
+        #[clap(long, value_name = "DIR", default_value = ".")]
+        root: String,
+        
+        /// Report keys that failed in at least this many runs
+        #[clap(long, default_value = "2")]
+        min_failures: usize,
+    },
+    
+    /// Group a run's errors.json by message, SQLCODE or key prefix, with example errors
+    Errors {
+        /// message, sqlcode, sqlstate or key-prefix
+        #[clap(long, default_value = "message")]
+        group_by: String,
+        
+        /// Characters of the key that form its prefix, for --group-by key-prefix
+        #[clap(long, default_value = "3")]
+        prefix_length: usize,
+        
+        /// Also write the groups to the results directory; only csv is supported
+        #[clap(long, value_name = "FORMAT")]
+        export: Option<String>,
+    },
+    
+    /// Re-read the rows of a run's completed queries and check they hold the new values
+    Verify,
+    
+    /// Undo a run's completed updates from the rollback scripts capture_rollback wrote
+    Rollback {
+        /// Only roll back this key (repeat for several); all completed queries by default
+        #[clap(long = "key", value_name = "KEY")]
+        keys: Vec<String>,
+    },
+    
+    /// Reconstruct a run's key index, manifest counts and processed records from its query files
+    RebuildState,
+    
+    /// Settle records abandoned Pending or InProgress by old runs from a verification read
+    Sweep {
+        /// Directory holding the results directories to sweep
+        #[clap(long, value_name = "DIR", default_value = ".")]
+        root: String,
+        
+        /// Only records untouched for longer than this are swept
+        #[clap(long, default_value = "72")]
+        older_than_hours: u64,
+    },
+    
+    /// Check the update template against sample rows without a database
+    Template {
+        #[clap(subcommand)]
+        action: TemplateCommands,
+    },
+    
+    /// Validate a JSON array of {key, query} objects from other tooling and place it into a run
+    Ingest {
+        /// JSON file to ingest
+        #[clap(long)]
+        file: String,
+    },
+    
+    /// Compare a CSV of authoritative values with the [reconcile] table and generate updates for differences
+    Reconcile {
+        /// CSV file with a key_field_name column and one column per reconciled column
+        #[clap(long)]
+        file: String,
+    },
+    
+    /// Delete the rows matching the [purge] condition in committed chunks until none remain
+    Purge,
+    
+    /// Rehearse the pending queries on the [trial] staging tables and compare with production
+    Trial,
+    
     /// Run both generation and execution phases
-    Run,
+    Run {
+        /// Hand generated queries straight to execution in memory instead of writing query files
+        #[clap(long)]
+        streaming: bool,
+    },
     
     /// Setup test data with county and zip code mappings
     SetupTest {
@@ -62,6 +268,14 @@ enum Commands {
     
     /// Update county codes based on zip codes (using 2-digit county codes)
     UpdateCountyCodeFromCountyfp,
+    
+    /// Show the last heartbeat of a running batch and fail if it has gone stale
+    Status {
+        /// Show the record counts of these results directories by run and partition instead
+        /// (repeat for each)
+        #[clap(long, value_name = "RESULTS_DIR")]
+        run: Vec<String>,
+    },
 }
 
 fn setup_logger(log_file: &str) -> Result<(), Box<dyn Error>> {
@@ -85,11 +299,13 @@ fn setup_logger(log_file: &str) -> Result<(), Box<dyn Error>> {
         .format(|buf, record| {
             writeln!(
                 buf,
-                "[{}] {} - {}: {}",
-                Local::now().format("%Y-%m-%dT%H:%M:%SZ"),
+                "[{}] [{}]{} {} - {}: {}",
+                utils::time::rfc3339(utils::time::now()),
+                utils::run_id::current(),
+                utils::span::context(),
                 record.level(),
                 record.target(),
-                record.args()
+                utils::masking::mask(&record.args().to_string())
             )
         })
         .init();
@@ -98,6 +314,16 @@ fn setup_logger(log_file: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// Render the configured template for each row of a YAML fixture and compare with the expected SQL
+    Test {
+        /// YAML fixture with a list of cases (row, expected)
+        #[clap(long)]
+        fixture: String,
+    },
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     // Parse command line arguments
     let cli = Cli::parse();
@@ -107,40 +333,250 @@ fn main() -> Result<(), Box<dyn Error>> {
         .duration_since(UNIX_EPOCH)?
         .as_secs();
     
-    let results_dir = format!("results_{}", timestamp);
+    // --results-dir names the run to work in; otherwise execute and status carry on with the
+    // run the last generation pointed `latest` at
+    let latest = match cli.results_dir.as_deref().map(str::trim) {
+        Some(files::latest::LATEST_FILE_NAME) => Some(files::latest::latest_results_dir().ok_or_else(|| format!(
+            "--results-dir {}: {} doesn't name an existing results directory", files::latest::LATEST_FILE_NAME, files::latest::LATEST_FILE_NAME
+        ))?),
+        Some(results_dir) => Some(results_dir.trim_end_matches(['/', '\\']).to_string()),
+        None => match cli.command {
+            Some(Commands::Execute { .. }) | Some(Commands::Trial) | Some(Commands::RetryFailed { .. }) | Some(Commands::Status { .. })
+            | Some(Commands::Verify) | Some(Commands::Rollback { .. }) | Some(Commands::RebuildState) | Some(Commands::Generate { resume: true, .. })
+            | Some(Commands::Errors { .. }) => {
+                files::latest::latest_results_dir()
+            },
+            _ => None,
+        },
+    };
+    
+    // Commands that work on generated query files would find none in a fresh directory
+    let needs_queries = matches!(cli.command, Some(Commands::Execute { .. }) | Some(Commands::Trial) | Some(Commands::RetryFailed { .. }) | Some(Commands::Verify) | Some(Commands::Rollback { .. }) | Some(Commands::RebuildState))
+        || (cli.results_dir.is_some() && matches!(cli.command, None | Some(Commands::Test)));
+    match &latest {
+        Some(results_dir) if needs_queries && !std::path::Path::new(results_dir).is_dir() => {
+            return Err(format!("Results directory {} does not exist", results_dir).into());
+        },
+        None if needs_queries => {
+            return Err(format!(
+                "No results directory to work on: {} names none; generate first or pass --results-dir",
+                files::latest::LATEST_FILE_NAME
+            ).into());
+        },
+        _ => {},
+    }
+    let results_dir = latest.clone().unwrap_or_else(|| format!("results_{}", timestamp));
     
     // Setup log file in the results directory
     let log_file = format!("{}/batch_process.log", results_dir);
     
-    // Setup directories and clean if requested
-    setup_directories(&results_dir, cli.clean)?;
+    // Setup directories and clean if requested; a run carried on from `latest` is only cleaned
+    // when named with --results-dir
+    setup_directories(&results_dir, cli.clean && (latest.is_none() || cli.results_dir.is_some()))?;
     
     // Setup logger after directory is created
     setup_logger(&log_file)?;
     
-    log::info!("Starting Informix Batch Processor");
+    log::info!("Starting Informix Batch Processor {} (run {})", utils::version::BUILD, utils::run_id::current());
+    if cli.results_dir.is_some() {
+        println!("Using results directory {}", results_dir);
+        log::info!("Using results directory {} from --results-dir", results_dir);
+    } else if latest.is_some() {
+        println!("Using results directory {} ({})", results_dir, files::latest::LATEST_FILE_NAME);
+        log::info!("Using results directory {} from {}", results_dir, files::latest::LATEST_FILE_NAME);
+    }
     
     // Load configuration
-    let app_config = AppConfig::from_env_or_file()
+    let mut app_config = AppConfig::from_env_or_file()
         .expect("Failed to load configuration");
     
+    // Command-line annotations take precedence over the configured ones
+    if let Some(ticket) = cli.ticket.clone() {
+        app_config.ticket = ticket;
+    }
+    if let Some(note) = cli.note.clone() {
+        app_config.note = note;
+    }
+    if let Some(operator) = cli.operator.clone() {
+        app_config.operator = operator;
+    }
+    
+    // Quote identifiers and page selections the way the configured database expects
+    db::dialect::init(&app_config)?;
+    
+    // Read the selection in key order, so every run sees its rows in the same sequence
+    if app_config.order_selection_by_key {
+        let ordered = db::query::ordered_selection(&app_config);
+        if ordered != app_config.selection_query {
+            log::info!("Selection ordered by key: {}", ordered);
+            app_config.selection_query = ordered;
+        }
+    }
+    
+    // Attribute everything this run does to its operator, user and machine
+    utils::operator::init(&app_config);
+    log::info!("Operator: {}", utils::operator::current());
+    
+    // Express timestamps in the configured timezone from here on
+    utils::time::init(&app_config)?;
+    
+    // Mask sensitive column values from here on
+    utils::masking::init(&app_config)?;
+    
+    // Per-record progress messages, unless quiet_progress is set
+    ui::progress::init(&app_config);
+    
+    // The zip-county mapping from [zip_mapping], if configured
+    zip_county_map::init(&app_config)?;
+    
+    // Decode fetched text using the database's character set
+    utils::encoding::init(&app_config)?;
+    
+    // Log per-record spans when configured
+    utils::span::init(&app_config)?;
+    
+    // A typo in success_criteria should fail now, not after the run it was meant to judge
+    db::query::SuccessCriteria::parse(&app_config.success_criteria)?;
+    db::query::ZeroRowsPolicy::from_name(&app_config.zero_rows_policy)?;
+    
+    // The jsonl state backend is the query manifest; the sqlite one keeps the processed records
+    match files::json_handler::StateBackend::from_name(&app_config.state_backend)? {
+        files::json_handler::StateBackend::Jsonl => app_config.query_manifest = true,
+        files::json_handler::StateBackend::Sqlite => files::processed::enable_sqlite_state(),
+        files::json_handler::StateBackend::Files => {},
+    }
+    
     // Determine which command to run - default to Test command if none specified
     let command = cli.command.unwrap_or(Commands::Test);
     
+    // Catch a broken template before thousands of query files are generated from it
+    let generates = match command {
+        Commands::Generate { .. } | Commands::Run { .. } => true,
+        Commands::Test => cli.results_dir.is_none(),
+        _ => false,
+    };
+    if generates {
+        db::query::lint_update_template(&app_config)?;
+    }
+    
+    // Refuse a mismatched --env, and changes to a protected environment without it or from an unlisted host
+    let changes_data = match command {
+        Commands::Execute { .. } => Some("execute"),
+        Commands::Rollback { .. } => Some("rollback"),
+        Commands::Run { .. } => Some("run"),
+        Commands::Purge => Some("purge"),
+        Commands::Trial => Some("trial"),
+        Commands::SetupTest { .. } => Some("setup-test"),
+        Commands::CleanTest => Some("clean-test"),
+        Commands::UpdateCountyCodes => Some("update-county-codes"),
+        Commands::UpdateCountyCodeFromCountyfp => Some("update-county-code-from-countyfp"),
+        _ => None,
+    };
+    utils::environment_guard::check(&app_config, cli.env.as_deref(), changes_data)?;
+    
+    // Report liveness for everything except the status check itself
+    if !matches!(command, Commands::Status { .. }) {
+        files::heartbeat::init(&app_config, &results_dir);
+    }
+    
     match command {
-        Commands::Generate => {
-            generate_query_phase(&app_config, &results_dir)?;
+        Commands::Generate { dbaccess, delta, resume, compare_applied } => {
+            if resume && latest.is_none() {
+                return Err("Nothing to resume: no latest run to carry on with".into());
+            }
+            let mut options = match delta {
+                Some(previous_dir) => GenerationOptions::delta_from(&previous_dir)?,
+                None => GenerationOptions::default(),
+            };
+            options.assume_yes = cli.yes;
+            options.reprocess = cli.reprocess;
+            options.resume = resume;
+            if let Some(old_run_dir) = compare_applied {
+                options.compare_applied = Some(files::applied_comparison::AppliedRun::load(&old_run_dir)?);
+            }
+            generate_query_phase(&app_config, &results_dir, &options)?;
+            if dbaccess {
+                export_dbaccess_script(&app_config, &results_dir)?;
+            }
         },
-        Commands::Execute => {
-            execute_query_phase(&app_config, &results_dir)?;
+        Commands::Execute { canary, max_rows, max_duration, partition, allow_stale, dry_run } => {
+            let options = ExecutionOptions { canary, max_rows, max_duration, partition, allow_stale, interactive: !cli.yes };
+            if dry_run {
+                dry_run_phase(&app_config, &results_dir, &options)?;
+            } else {
+                execute_query_phase(&app_config, &results_dir, &options)?;
+            }
         },
         Commands::Test => {
-            // Run the generation phase first, then test
-            generate_query_phase(&app_config, &results_dir)?;
+            // A usable connection first, then the generation phase, then test; a named results
+            // directory is tested as it is
+            test_connection(&app_config)?;
+            if cli.results_dir.is_none() {
+                let options = GenerationOptions { assume_yes: cli.yes, reprocess: cli.reprocess, ..GenerationOptions::default() };
+                generate_query_phase(&app_config, &results_dir, &options)?;
+            }
             test_query_phase(&app_config, &results_dir)?;
         },
-        Commands::Run => {
-            run_continuous_mode(&app_config, &results_dir)?;
+        Commands::ExportReview { run } => {
+            export_review(&run)?;
+        },
+        Commands::ImportReview { file, run } => {
+            import_review(&file, run)?;
+        },
+        Commands::RetryFailed { include_dead_letter } => {
+            retry_failed(&results_dir, include_dead_letter)?;
+        },
+        Commands::Merge { into, from } => {
+            merge_partitions(&into, &from)?;
+        },
+        Commands::Report { run, diff, chunk_size, bucket_minutes, format } => {
+            if let [run] = run.as_slice() {
+                show_report(&app_config, run, diff, chunk_size, bucket_minutes)?;
+                if let Some(format) = format {
+                    let path = files::run_report::write_run_report(run, format)?;
+                    println!("Wrote {}", path);
+                }
+            } else if diff || format.is_some() {
+                return Err("--diff and --format report on one results directory at a time".into());
+            } else {
+                show_consolidated_report(&run)?;
+            }
+        },
+        Commands::Analytics { root, min_failures } => {
+            show_analytics(&root, min_failures, &results_dir)?;
+        },
+        Commands::Errors { group_by, prefix_length, export } => {
+            show_errors(latest.clone(), &group_by, prefix_length, export.as_deref())?;
+        },
+        Commands::Verify => {
+            verify_phase(&app_config, &results_dir)?;
+        },
+        Commands::Rollback { keys } => {
+            rollback_phase(&app_config, &results_dir, &keys, cli.yes)?;
+        },
+        Commands::RebuildState => {
+            rebuild_state(&app_config, &results_dir)?;
+        },
+        Commands::Sweep { root, older_than_hours } => {
+            sweep_phase(&app_config, &root, older_than_hours)?;
+        },
+        Commands::Template { action: TemplateCommands::Test { fixture } } => {
+            test_template(&app_config, &fixture)?;
+        },
+        Commands::Ingest { file } => {
+            ingest_query_phase(&app_config, &results_dir, &file)?;
+        },
+        Commands::Reconcile { file } => {
+            reconcile_phase(&app_config, &results_dir, &file)?;
+        },
+        Commands::Purge => {
+            purge_phase(&app_config, &results_dir)?;
+        },
+        Commands::Trial => {
+            trial_phase(&app_config, &results_dir)?;
+        },
+        Commands::Run { streaming } => {
+            run_continuous_mode(&app_config, &results_dir, cli.yes, cli.reprocess, streaming)?;
         },
         Commands::SetupTest { count } => {
             setup_test_data(&app_config, count)?;
@@ -154,7 +590,17 @@ fn main() -> Result<(), Box<dyn Error>> {
         Commands::UpdateCountyCodeFromCountyfp => {  
             update_county_code_from_countyfp(&app_config, &results_dir)?;  
         },
+        Commands::Status { run } => {
+            if run.is_empty() {
+                show_status(&app_config, &results_dir)?;
+            } else {
+                show_consolidated_report(&run)?;
+            }
+            return Ok(());
+        },
     }
+    
+    files::heartbeat::set_phase(files::heartbeat::FINISHED_PHASE);
 
     log::info!("Batch processing completed successfully");
     println!("Batch processing completed successfully");
@@ -162,21 +608,26 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn generate_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+fn generate_query_phase(config: &AppConfig, results_dir: &str, options: &GenerationOptions) -> Result<(), Box<dyn Error>> {
     println!("Starting Query Generation Phase");
     log::info!("Starting Query Generation Phase");
+    files::heartbeat::set_phase("generate");
     
     // Load processed records
     let mut processed_records = ProcessedRecords::load(&config.data_path);
     
     // Create database connection
     let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Generation", config.generation_autocommit, &config.generation_isolation)?;
     
     // Create progress bar for query generation
     let progress_bar = create_progress_bar("Generating Queries");
     
+    // Point `latest` here before the selection runs so an interrupted generation can be resumed
+    files::latest::update_latest(results_dir)?;
+    
     // Generate queries
-    let count = generate_queries(&connection, config, results_dir, &progress_bar)?;
+    let count = generate_queries(&connection, config, results_dir, &mut processed_records, options, &progress_bar)?;
     
     // Save processed records
     processed_records.save(&config.data_path)?;
@@ -186,21 +637,35 @@ fn generate_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box
     Ok(())
 }
 
-fn execute_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+fn export_dbaccess_script(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Writing dbaccess script");
+    log::info!("Writing dbaccess script");
+    
+    let count = files::dbaccess::write_dbaccess_script(results_dir, config.batch_size)?;
+    
+    println!("Wrote {} statements to {}/{}", count, results_dir, files::dbaccess::DBACCESS_SCRIPT_NAME);
+    
+    Ok(())
+}
+
+fn execute_query_phase(config: &AppConfig, results_dir: &str, options: &ExecutionOptions) -> Result<(), Box<dyn Error>> {
     println!("Starting Query Execution Phase");
     log::info!("Starting Query Execution Phase");
+    files::heartbeat::set_phase("execute");
     
     // Load processed records
     let mut processed_records = ProcessedRecords::load(&config.data_path);
     
     // Create database connection
     let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Execution", config.execution_autocommit, &config.execution_isolation)?;
     
     // Create progress bar for query execution
     let progress_bar = create_progress_bar("Executing Queries");
     
     // Execute queries
-    let (success_count, error_count) = execute_queries(&connection, results_dir, &progress_bar)?;
+    let metrics = execute_queries(&connection, config, results_dir, &mut processed_records, options, &progress_bar)?;
+    let (success_count, error_count) = (metrics.succeeded, metrics.failed);
     
     // Save processed records
     processed_records.save(&config.data_path)?;
@@ -210,8 +675,65 @@ fn execute_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<
                 success_count + error_count, success_count, error_count)
     );
     
-    println!("Executed {} queries ({} successful, {} failed)", 
-             success_count + error_count, success_count, error_count);
+    println!("Executed {} queries ({} successful, {} failed, {} rows affected)", 
+             success_count + error_count, success_count, error_count, metrics.rows_affected);
+    
+    db::query::check_success_criteria(config, &metrics)?;
+    
+    Ok(())
+}
+
+fn dry_run_phase(config: &AppConfig, results_dir: &str, options: &ExecutionOptions) -> Result<(), Box<dyn Error>> {
+    println!("Starting Dry Run Execution Phase");
+    log::info!("Starting Dry Run Execution Phase");
+    files::heartbeat::set_phase("dry-run");
+    
+    // Autocommit stays off whatever execution_autocommit says, so every statement can be rolled back
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Dry run", false, &config.execution_isolation)?;
+    
+    let progress_bar = create_progress_bar("Dry Run");
+    
+    let summary = db::query::dry_run_queries(&connection, config, results_dir, options, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Dry run of {} queries", summary.executed));
+    println!("Dry run of {} queries: {} would fail, {} would conflict, {} would change no rows; nothing was committed (results in each query file's dry_run)",
+             summary.executed, summary.failed, summary.conflicts, summary.no_rows);
+    
+    Ok(())
+}
+
+fn streaming_phase(config: &AppConfig, results_dir: &str, options: &GenerationOptions) -> Result<(), Box<dyn Error>> {
+    println!("Starting Streaming Generation and Execution");
+    log::info!("Starting Streaming Generation and Execution");
+    files::heartbeat::set_phase("stream");
+    
+    // Load processed records
+    let mut processed_records = ProcessedRecords::load(&config.data_path);
+    
+    // This connection executes; generation opens its own
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Execution", config.execution_autocommit, &config.execution_isolation)?;
+    
+    let progress_bar = create_progress_bar("Streaming Queries");
+    
+    let summary = db::query::stream_queries(&connection, config, results_dir, &mut processed_records, options, &progress_bar)?;
+    
+    // Save processed records
+    processed_records.save(&config.data_path)?;
+    
+    let message = format!(
+        "Streamed {} queries from {} matched rows ({} successful, {} failed, {} conflicts, {} skipped as already applied, {} rejected; {} rows affected, {} statements affected no rows)",
+        summary.generated, summary.matched, summary.succeeded, summary.failed, summary.conflicts, summary.already_applied, summary.rejected,
+        summary.rows_affected, summary.no_rows
+    );
+    progress_bar.finish_with_message(format!("Streamed {} queries", summary.generated));
+    println!("{}", message);
+    log::info!("{}", message);
+    
+    files::latest::update_latest(results_dir)?;
+    
+    db::query::check_success_criteria(config, &summary.metrics())?;
     
     Ok(())
 }
@@ -219,6 +741,7 @@ fn execute_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<
 fn test_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
     println!("Starting Query Test Phase");
     log::info!("Starting Query Test Phase");
+    files::heartbeat::set_phase("test");
     
     // Create database connection
     let connection = create_connection(config)?;
@@ -227,7 +750,7 @@ fn test_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn
     let progress_bar = create_progress_bar("Testing Queries");
     
     // Call the test_queries function that we'll create in db/query.rs
-    let (valid_count, invalid_count) = db::query::test_queries(&connection, results_dir, &progress_bar)?;
+    let (valid_count, invalid_count) = db::query::test_queries(&connection, config, results_dir, &progress_bar)?;
     
     progress_bar.finish_with_message(
         format!("Tested {} queries ({} valid, {} invalid)", 
@@ -240,19 +763,602 @@ fn test_query_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn
     Ok(())
 }
 
-fn run_continuous_mode(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+fn retry_failed(results_dir: &str, include_dead_letter: bool) -> Result<(), Box<dyn Error>> {
+    println!("Re-enqueueing failed queries");
+    log::info!("Re-enqueueing failed queries of {}", results_dir);
+    
+    let (failed, dead_lettered) = files::dead_letter::requeue_failed(results_dir, include_dead_letter)?;
+    
+    println!("Reset {} failed queries to Pending", failed);
+    if include_dead_letter {
+        println!("Moved {} queries back from {}/", dead_lettered, files::dead_letter::DEAD_LETTER_DIR_NAME);
+    }
+    println!("Run execute to try them again");
+    log::info!("Re-enqueued {} failed and {} dead-lettered queries", failed, dead_lettered);
+    
+    Ok(())
+}
+
+fn rebuild_state(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Rebuilding the state of {} from its query files", results_dir);
+    log::info!("Rebuilding the state of {} from its query files", results_dir);
+    
+    let mut processed_records = ProcessedRecords::load(&config.data_path);
+    let summary = files::rebuild::rebuild_state(results_dir, &mut processed_records)?;
+    processed_records.save(&config.data_path)?;
+    
+    println!("Read {} query records ({} unreadable) for {} keys", summary.records, summary.unreadable, summary.keys);
+    println!("Rewrote {}/{}", results_dir, files::manifest::SELECTION_KEYS_FILE_NAME);
+    println!(
+        "{} {}/{} with the counts",
+        if summary.manifest_created { "Created" } else { "Updated" }, results_dir, files::manifest::MANIFEST_FILE_NAME
+    );
+    println!("Added {} processed-records entries", summary.processed_added);
+    log::info!("Rebuilt state: {:?}", summary);
+    
+    Ok(())
+}
+
+fn export_review(run_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Writing review sheet");
+    log::info!("Writing review sheet for {}", run_dir);
+    
+    let count = files::review::export_review(run_dir)?;
+    
+    println!("Wrote {} pending queries to {}/{}", count, run_dir, files::review::REVIEW_FILE_NAME);
+    println!("Mark approved rows with Y in the approve column, then run import-review");
+    
+    Ok(())
+}
+
+fn import_review(sheet_file: &str, run_dir: Option<String>) -> Result<(), Box<dyn Error>> {
+    // The sheet normally sits in the results directory it was exported from
+    let run_dir = match run_dir {
+        Some(run_dir) => run_dir,
+        None => std::path::Path::new(sheet_file)
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
+            .filter(|parent| !parent.is_empty())
+            .unwrap_or_else(|| ".".to_string()),
+    };
+    
+    println!("Importing review sheet {} into {}", sheet_file, run_dir);
+    log::info!("Importing review sheet {} into {}", sheet_file, run_dir);
+    
+    let summary = files::review::import_review(&run_dir, sheet_file)?;
+    
+    println!("{} approved, {} not approved, {} missing from the sheet (both marked Skipped), {} unknown keys",
+             summary.approved, summary.skipped, summary.missing, summary.unknown);
+    
+    Ok(())
+}
+
+fn merge_partitions(target_dir: &str, source_dirs: &[String]) -> Result<(), Box<dyn Error>> {
+    println!("Merging {} partition results into {}", source_dirs.len(), target_dir);
+    log::info!("Merging partition results {:?} into {}", source_dirs, target_dir);
+    
+    let summary = files::merge::merge_statuses(target_dir, source_dirs)?;
+    
+    println!("{} records updated, {} added, {} error records merged", summary.updated, summary.added, summary.errors);
+    
+    Ok(())
+}
+
+fn show_report(config: &AppConfig, run_dir: &str, diff: bool, chunk_size: usize, bucket_minutes: i64) -> Result<(), Box<dyn Error>> {
+    println!("Report for {}", run_dir);
+    log::info!("Writing report for {}", run_dir);
+    
+    for (status, count) in files::report::status_summary(run_dir)? {
+        println!("  {:<12} {}", status, count);
+    }
+    
+    // Records that used up their attempts wait in dead_letter/ until re-enqueued
+    let dead_letters = files::dead_letter::dead_letter_files(run_dir)?;
+    if !dead_letters.is_empty() {
+        println!("  {:<12} {} (in {}/; retry-failed --include-dead-letter re-enqueues them)",
+                 "DeadLetter", dead_letters.len(), files::dead_letter::DEAD_LETTER_DIR_NAME);
+    }
+    
+    // The event log reconstructs the run independently of the query files
+    let replayed = files::events::replay_statuses(run_dir)?;
+    if !replayed.is_empty() {
+        println!("From {}:", files::events::EVENTS_FILE_NAME);
+        for (status, count) in files::events::replayed_status_summary(run_dir)? {
+            println!("  {:<12} {}", status, count);
+        }
+        
+        // Composite keys name their files by hash, so compare with the keys the files hold
+        let file_keys: std::collections::HashSet<String> = files::json_handler::read_query_files(run_dir)?
+            .iter()
+            .chain(&dead_letters)
+            .filter_map(|path| files::json_handler::read_query_file(path).ok())
+            .map(|record| record.key)
+            .collect();
+        let missing: Vec<&String> = replayed.keys()
+            .filter(|key| !file_keys.contains(*key))
+            .collect();
+        if !missing.is_empty() {
+            println!("\x1b[33m{} keys in the event log have no query file (e.g. {})\x1b[0m", missing.len(), missing[0]);
+            log::warn!("{} keys in the event log of {} have no query file", missing.len(), run_dir);
+        }
+    }
+    
+    // Refresh the county results sheet, e.g. after executing county queries later
+    write_county_results(run_dir)?;
+    
+    // Break the statuses down by change request when queries carry tickets
+    let tickets = files::report::ticket_summary(run_dir)?;
+    if tickets.keys().any(|(ticket, _)| ticket != "(no ticket)") {
+        println!("By ticket:");
+        for ((ticket, status), count) in tickets {
+            println!("  {:<16} {:<12} {}", ticket, status, count);
+        }
+    }
+    
+    // Checks that held up (or let through) the last phase
+    let preflight = load_preflight_results(run_dir);
+    if !preflight.is_empty() {
+        println!("Pre-flight checks ({}):", preflight[0].phase);
+        for result in &preflight {
+            match &result.reason {
+                Some(reason) => println!("  \x1b[31m{:<24} FAILED\x1b[0m {}", result.name, reason),
+                None => println!("  {:<24} passed", result.name),
+            }
+        }
+    }
+    
+    // Keys an upstream process keeps breaking again
+    if config.recurrent_offender_threshold > 0 {
+        show_recurrent_offenders(config, run_dir)?;
+    }
+    
+    // Systemic failures concentrate in one worker, chunk or period; data-dependent ones don't
+    let isolation = files::isolation::error_isolation(run_dir, chunk_size, bucket_minutes)?;
+    if isolation.iter().any(|group| group.failed > 0) {
+        files::isolation::write_error_isolation(run_dir, &isolation)?;
+        let workers = isolation.iter().filter(|group| group.dimension == "worker").count();
+        println!("Errors by worker, chunk and time written to {} ({} workers)", files::isolation::ERROR_ISOLATION_FILE_NAME, workers);
+        for group in isolation.iter().filter(|group| group.outlier) {
+            println!("  \x1b[33m{} {}: {} of {} failed ({:.1}%)\x1b[0m",
+                     group.dimension, group.label, group.failed, group.executed, group.error_rate() * 100.0);
+            log::warn!("Error outlier in {} {}: {} of {} failed", group.dimension, group.label, group.failed, group.executed);
+        }
+    }
+    
+    if diff {
+        let diffs = files::report::diff_report(run_dir)?;
+        if diffs.is_empty() {
+            println!("No completed queries with stored original values to diff");
+            return Ok(());
+        }
+        
+        println!();
+        for column_diff in &diffs {
+            for line in column_diff.report_lines() {
+                println!("{}", line);
+            }
+        }
+        
+        files::report::write_diff_report(run_dir, &diffs)?;
+        println!("Wrote {}/{} and {}/{}", run_dir, files::report::DIFF_REPORT_CSV_NAME, run_dir, files::report::DIFF_REPORT_TEXT_NAME);
+    }
+    
+    Ok(())
+}
+
+// Record counts of several results directories in one table, a row per run/partition and a
+// total; the full report of each directory is `report --run` with that directory alone
+fn show_consolidated_report(run_dirs: &[String]) -> Result<(), Box<dyn Error>> {
+    println!("Consolidated report for {} results directories", run_dirs.len());
+    log::info!("Writing consolidated report for {:?}", run_dirs);
+    
+    let (runs, distinct_keys) = files::report::consolidated_status(run_dirs)?;
+    let mut totals: std::collections::BTreeMap<String, usize> = std::collections::BTreeMap::new();
+    for run in &runs {
+        for (status, count) in &run.statuses {
+            *totals.entry(status.clone()).or_insert(0) += count;
+        }
+    }
+    
+    let label_width = runs.iter().map(|run| run.label().len()).chain(["Run/partition".len()]).max().unwrap_or_default();
+    let dir_width = runs.iter().map(|run| run.results_dir.len()).chain(["Results dir".len()]).max().unwrap_or_default();
+    let mut header = format!("  {:<label_width$}  {:<dir_width$}", "Run/partition", "Results dir");
+    for status in totals.keys() {
+        header.push_str(&format!(" {:>10}", status));
+    }
+    println!("{} {:>10}", header, "Total");
+    
+    for run in &runs {
+        let mut line = format!("  {:<label_width$}  {:<dir_width$}", run.label(), run.results_dir);
+        for status in totals.keys() {
+            line.push_str(&format!(" {:>10}", run.statuses.get(status).copied().unwrap_or_default()));
+        }
+        println!("{} {:>10}", line, run.total());
+    }
+    
+    let mut line = format!("  {:<label_width$}  {:<dir_width$}", "Total", "");
+    for count in totals.values() {
+        line.push_str(&format!(" {:>10}", count));
+    }
+    let total: usize = totals.values().sum();
+    println!("{} {:>10}", line, total);
+    
+    // Phases of a pipeline may work on the same keys; partitions of one run never should
+    if distinct_keys < total {
+        println!("{} distinct keys; {} records share a key with a record in another directory", distinct_keys, total - distinct_keys);
+    }
+    
+    // The same run and partition in two directories is usually the same copy named twice
+    let mut seen = std::collections::HashSet::new();
+    for run in runs.iter().filter(|run| !run.run_id.is_empty()) {
+        if !seen.insert((run.run_id.as_str(), run.partition.as_deref())) {
+            println!("\x1b[33m{} appears in more than one results directory (again in {})\x1b[0m", run.label(), run.results_dir);
+            log::warn!("{} appears in more than one of the consolidated results directories", run.label());
+        }
+    }
+    
+    Ok(())
+}
+
+// List the keys of a run that the applied ledger shows corrected in more than
+// recurrent_offender_threshold runs, and write them to recurrent_offenders.csv
+fn show_recurrent_offenders(config: &AppConfig, run_dir: &str) -> Result<(), Box<dyn Error>> {
+    let run_keys: std::collections::HashSet<String> = files::json_handler::read_query_files(run_dir)?
+        .iter()
+        .filter_map(|path| files::json_handler::read_query_file(path).ok())
+        .map(|record| record.key)
+        .collect();
+    let mut offenders: Vec<files::ledger::CorrectionHistory> = files::ledger::correction_histories(&config.data_path)
+        .into_values()
+        .filter(|history| history.runs.len() > config.recurrent_offender_threshold && run_keys.contains(&history.key))
+        .collect();
+    if offenders.is_empty() {
+        return Ok(());
+    }
+    offenders.sort_by(|a, b| b.runs.len().cmp(&a.runs.len()).then_with(|| a.key.cmp(&b.key)));
+    
+    println!("\x1b[33m{} keys corrected in more than {} runs (an upstream process may keep re-breaking them):\x1b[0m",
+             offenders.len(), config.recurrent_offender_threshold);
+    let mut csv = String::from("key,corrections,first_corrected,last_corrected,runs\r\n");
+    for (index, history) in offenders.iter().enumerate() {
+        if index < 10 {
+            println!("  {:<16} {} corrections, last {}", history.key, history.runs.len(), history.last_corrected);
+        }
+        csv.push_str(&format!(
+            "{},{},{},{},{}\r\n",
+            files::snapshot::csv_field(&history.key), history.runs.len(), history.first_corrected,
+            history.last_corrected, history.runs.join(" ")
+        ));
+    }
+    log::warn!("{} keys of {} corrected in more than {} runs", offenders.len(), run_dir, config.recurrent_offender_threshold);
+    
+    let path = std::path::Path::new(run_dir).join(files::ledger::RECURRENT_OFFENDERS_FILE_NAME);
+    std::fs::write(&path, csv)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn test_template(config: &AppConfig, fixture_file: &str) -> Result<(), Box<dyn Error>> {
+    println!("Testing update template against {}", fixture_file);
+    log::info!("Testing update template against {}", fixture_file);
+    
+    let summary = db::query::run_template_tests(config, fixture_file)?;
+    
+    for failure in &summary.failures {
+        println!("\x1b[31mFAILED\x1b[0m {}", failure);
+    }
+    println!("{} template cases passed, {} failed", summary.passed, summary.failures.len());
+    
+    // A failing case fails the command so CI catches template regressions
+    if !summary.failures.is_empty() {
+        return Err(format!("{} template cases failed", summary.failures.len()).into());
+    }
+    
+    Ok(())
+}
+
+fn show_analytics(root: &str, min_failures: usize, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Aggregating runs under {}", root);
+    log::info!("Aggregating runs under {}", root);
+    
+    let analytics = files::analytics::collect_analytics(root, min_failures)?;
+    files::analytics::write_analytics(results_dir, &analytics)?;
+    
+    for week in &analytics.weekly {
+        println!("  {}  {} runs, {} corrected, {} failed ({:.1}% errors)",
+                 week.week, week.runs, week.completed, week.failed + week.conflicts, week.error_rate * 100.0);
+    }
+    println!("{} runs, {} keys failed in {} or more runs", analytics.runs.len(), analytics.recurring_failures.len(), min_failures);
+    println!("Wrote {}, {}, {} and {} to {}",
+             files::analytics::ANALYTICS_RUNS_FILE_NAME, files::analytics::ANALYTICS_WEEKLY_FILE_NAME,
+             files::analytics::ANALYTICS_RECURRING_FILE_NAME, files::analytics::ANALYTICS_JSON_FILE_NAME, results_dir);
+    log::info!("Aggregated {} runs into {}", analytics.runs.len(), results_dir);
+    
+    Ok(())
+}
+
+fn show_errors(run: Option<String>, group_by: &str, prefix_length: usize, export: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let run_dir = run.ok_or("No results directory given and no latest run; pass --results-dir")?;
+    let grouping = files::errors::ErrorGrouping::from_name(group_by, prefix_length)?;
+    if let Some(format) = export {
+        if !format.eq_ignore_ascii_case("csv") {
+            return Err(format!("Unknown export format '{}' (expected csv)", format).into());
+        }
+    }
+    log::info!("Grouping errors of {} by {}", run_dir, grouping.name());
+    
+    let records = files::errors::read_error_records(&run_dir)?;
+    if records.is_empty() {
+        println!("No errors recorded in {}", run_dir);
+        return Ok(());
+    }
+    
+    let groups = files::errors::group_errors(&records, grouping);
+    println!("{} errors in {}, {} groups by {}:", records.len(), run_dir, groups.len(), group_by);
+    for group in &groups {
+        for line in files::errors::group_lines(group) {
+            println!("{}", line);
+        }
+    }
+    
+    if export.is_some() {
+        let path = files::errors::export_error_groups(&run_dir, grouping, &groups)?;
+        println!("Wrote {}", path);
+        log::info!("Wrote {} error groups to {}", groups.len(), path);
+    }
+    
+    Ok(())
+}
+
+fn verify_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Starting Verification Phase");
+    log::info!("Starting Verification Phase");
+    files::heartbeat::set_phase("verify");
+    
+    // Secondaries only allow reads, and may not accept every isolation level, so the replica
+    // keeps its driver default
+    let (connection, on_replica) = db::connection::create_verify_connection(config)?;
+    let isolation = if on_replica { "" } else { config.execution_isolation.as_str() };
+    apply_session_settings(&connection, config, "Verification", true, isolation)?;
+    
+    let progress_bar = create_progress_bar("Verifying Queries");
+    
+    let summary = db::query::verify_run(&connection, config, results_dir, on_replica, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Verified {} queries", summary.verified + summary.mismatched + summary.unverifiable));
+    
+    let message = format!(
+        "Verification: {} verified, {} don't hold their change, {} can't be verified automatically (see {}/{})",
+        summary.verified, summary.mismatched, summary.unverifiable, results_dir, db::query::VERIFICATION_FILE_NAME
+    );
+    println!("{}", message);
+    log::info!("{}", message);
+    if let Some(lag) = summary.replica_lag_seconds {
+        println!("Verified on the read replica ({}) with a lag of {}s", config.verify_odbc_dsn.trim(), lag);
+    }
+    if summary.mismatched > 0 {
+        return Err(format!("{} rows don't hold the change their query applied", summary.mismatched).into());
+    }
+    
+    Ok(())
+}
+
+fn rollback_phase(config: &AppConfig, results_dir: &str, keys: &[String], assume_yes: bool) -> Result<(), Box<dyn Error>> {
+    println!("Starting Rollback Phase");
+    log::info!("Starting Rollback Phase for {}", results_dir);
+    files::heartbeat::set_phase("rollback");
+    
+    let scope = if keys.is_empty() { "every completed query".to_string() } else { format!("{} keys", keys.len()) };
+    if !assume_yes && !prompt_user(&format!("Restore the previous values of {} in {}?", scope, results_dir)).to_uppercase().starts_with('Y') {
+        println!("Rollback cancelled");
+        log::info!("Rollback cancelled by the operator");
+        return Ok(());
+    }
+    
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Rollback", config.execution_autocommit, &config.execution_isolation)?;
+    
+    let progress_bar = create_progress_bar("Rolling Back");
+    
+    let summary = db::query::apply_rollback(&connection, config, results_dir, keys, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Rolled back {} queries", summary.rolled_back));
+    
+    let message = format!(
+        "Rolled back {} queries: {} left alone because their row changed since, {} failed, {} completed queries have no rollback script",
+        summary.rolled_back, summary.conflicts, summary.failed, summary.without_script
+    );
+    println!("{}", message);
+    log::info!("{}", message);
+    
+    Ok(())
+}
+
+fn sweep_phase(config: &AppConfig, root: &str, older_than_hours: u64) -> Result<(), Box<dyn Error>> {
+    println!("Sweeping runs under {} for records untouched for {} hours", root, older_than_hours);
+    log::info!("Starting sweep of {} (older than {} hours)", root, older_than_hours);
+    files::heartbeat::set_phase("sweep");
+    
+    // Records found applied are added to the processed records as well as the ledger
+    let mut processed_records = ProcessedRecords::load(&config.data_path);
+    
+    // Verification only reads, like execution's canary check
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Sweep", config.execution_autocommit, &config.execution_isolation)?;
+    
+    let progress_bar = create_progress_bar("Sweeping Runs");
+    
+    let summary = db::query::sweep_stale_records(&connection, config, root, older_than_hours, &mut processed_records, &progress_bar)?;
+    
+    processed_records.save(&config.data_path)?;
+    
+    progress_bar.finish_with_message(format!("Swept {} records", summary.stuck));
+    
+    println!("Scanned {} runs: {} stuck records, {} completed and added to the ledger, {} skipped as already applied, {} marked Stale",
+             summary.runs_scanned, summary.stuck, summary.completed, summary.skipped, summary.stale);
+    log::info!("Sweep finished: {:?}", summary);
+    
+    Ok(())
+}
+
+fn ingest_query_phase(config: &AppConfig, results_dir: &str, file: &str) -> Result<(), Box<dyn Error>> {
+    println!("Starting Query Ingestion Phase");
+    log::info!("Starting Query Ingestion Phase");
+    files::heartbeat::set_phase("ingest");
+    
+    // Load processed records so changes applied by earlier runs are recognised
+    let processed_records = ProcessedRecords::load(&config.data_path);
+    
+    // Create progress bar for ingestion
+    let progress_bar = create_progress_bar("Ingesting Queries");
+    
+    let summary = db::query::ingest_queries(file, results_dir, &processed_records, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Ingested {} queries", summary.accepted));
+    
+    println!("Ingested {} queries into {} ({} quarantined, {} already applied, {} rejected)",
+             summary.accepted, results_dir, summary.quarantined, summary.already_applied, summary.rejected);
+    
+    files::latest::update_latest(results_dir)?;
+    
+    Ok(())
+}
+
+fn reconcile_phase(config: &AppConfig, results_dir: &str, file: &str) -> Result<(), Box<dyn Error>> {
+    println!("Starting Reconciliation Phase");
+    log::info!("Starting Reconciliation Phase");
+    files::heartbeat::set_phase("reconcile");
+    
+    // Load processed records so changes applied by earlier runs are recognised
+    let processed_records = ProcessedRecords::load(&config.data_path);
+    
+    // Create database connection
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Generation", config.generation_autocommit, &config.generation_isolation)?;
+    
+    // Create progress bar for reconciliation
+    let progress_bar = create_progress_bar("Reconciling");
+    
+    let summary = db::query::reconcile_file(&connection, config, file, results_dir, &processed_records, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Generated {} updates", summary.generated));
+    
+    println!("Compared {} keys from {}: {} differ (updates generated), {} already match, {} not in the table, {} already applied",
+             summary.file_keys, file, summary.generated, summary.unchanged, summary.missing, summary.already_applied);
+    log::info!("Reconciled {} keys from {}: {} updates generated, {} unchanged, {} missing, {} already applied",
+               summary.file_keys, file, summary.generated, summary.unchanged, summary.missing, summary.already_applied);
+    
+    files::latest::update_latest(results_dir)?;
+    
+    Ok(())
+}
+
+fn purge_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Starting Purge Phase");
+    log::info!("Starting Purge Phase");
+    files::heartbeat::set_phase("purge");
+    
+    let purge = config.purge.as_ref().ok_or("No [purge] section in the configuration")?;
+    
+    // Create database connection
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Purge", config.execution_autocommit, &config.execution_isolation)?;
+    
+    // Create progress bar for the purge
+    let progress_bar = create_progress_bar("Purging Rows");
+    
+    let summary = db::query::purge_rows(&connection, config, purge, results_dir, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Purged {} rows", summary.rows_deleted));
+    
+    println!("Purged {} rows from {} in {} chunks", summary.rows_deleted, summary.table, summary.chunks);
+    
+    Ok(())
+}
+
+fn trial_phase(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    println!("Starting Trial Phase");
+    log::info!("Starting Trial Phase");
+    files::heartbeat::set_phase("trial");
+    
+    // Each rehearsed statement commits on its own so both tables can be read back
+    let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Trial", true, &config.execution_isolation)?;
+    
+    // Create progress bar for the rehearsal
+    let progress_bar = create_progress_bar("Rehearsing Queries");
+    
+    let summary = db::query::run_trial(&connection, config, results_dir, &progress_bar)?;
+    
+    progress_bar.finish_with_message(format!("Rehearsed {} queries", summary.executed));
+    
+    println!("Rehearsed {} queries on staging tables: {} failed, {} not rehearsed; {} of {} compared columns not as expected (see {}/{})",
+             summary.executed, summary.failed, summary.skipped, summary.unexpected, summary.compared,
+             results_dir, db::query::TRIAL_COMPARISON_FILE_NAME);
+    log::info!("Trial: {} executed, {} failed, {} skipped, {} of {} columns unexpected",
+               summary.executed, summary.failed, summary.skipped, summary.unexpected, summary.compared);
+    
+    Ok(())
+}
+
+fn run_continuous_mode(config: &AppConfig, results_dir: &str, assume_yes: bool, reprocess: bool, streaming: bool) -> Result<(), Box<dyn Error>> {
+    let mut consecutive_failures = 0;
+    let mut completed_cycles = 0;
+    
     loop {
-        // Run both phases
-        generate_query_phase(config, results_dir)?;
-        execute_query_phase(config, results_dir)?;
+        // After the first cycle, optionally only generate for keys that newly match the selection
+        let generation_options = if config.continuous_delta && completed_cycles > 0 {
+            GenerationOptions::delta_from(results_dir)
+        } else {
+            Ok(GenerationOptions::default())
+        };
+        
+        // Run both phases; a failed cycle is retried on the next schedule instead of ending the daemon
+        let cycle_result = generation_options
+            .map(|options| GenerationOptions { assume_yes, reprocess, unattended: true, ..options })
+            .and_then(|options| if streaming {
+                streaming_phase(config, results_dir, &options)
+            } else {
+                generate_query_phase(config, results_dir, &options)
+                    .and_then(|_| execute_query_phase(config, results_dir, &ExecutionOptions::default()))
+            });
+        
+        match cycle_result {
+            Ok(()) => {
+                completed_cycles += 1;
+                if consecutive_failures > 0 {
+                    utils::notify::notify(config, "Batch cycle recovered", &format!(
+                        "Cycle succeeded after {} consecutive failures", consecutive_failures
+                    ));
+                }
+                consecutive_failures = 0;
+            },
+            Err(e) => {
+                consecutive_failures += 1;
+                let message = format!(
+                    "Cycle failed ({} of {} consecutive failures allowed): {}",
+                    consecutive_failures, config.max_consecutive_failures, e
+                );
+                println!("\x1b[31m{}\x1b[0m", message);
+                log::error!("{}", message);
+                utils::notify::notify(config, "Batch cycle failed", &message);
+                
+                if consecutive_failures >= config.max_consecutive_failures {
+                    utils::notify::notify(config, "Continuous mode stopped", &format!(
+                        "Giving up after {} consecutive failed cycles", consecutive_failures
+                    ));
+                    return Err(format!("Continuous mode stopped after {} consecutive failed cycles: {}", consecutive_failures, e).into());
+                }
+            },
+        }
         
         // Disconnect from the database (will be reconnected in the next phase)
         
         let next_check_time = SystemTime::now() + Duration::from_secs(config.check_again_after);
-        let datetime = chrono::DateTime::<chrono::Local>::from(next_check_time);
+        let datetime = utils::time::from_system_time(next_check_time);
         println!(
             "Batch processing complete, checking again at: {}",
-            datetime.format("%Y-%m-%d %H:%M:%S")
+            datetime.format("%Y-%m-%d %H:%M:%S %:z")
         );
         println!("(press 'R' to check again now)");
 
@@ -261,7 +1367,13 @@ fn run_continuous_mode(config: &AppConfig, results_dir: &str) -> Result<(), Box<
         let mut time_passed = Duration::from_secs(0);
         let interval = Duration::from_millis(100); // Polling interval for keypress
 
+        files::heartbeat::set_phase("waiting");
         while time_passed < sleep_duration {
+            files::heartbeat::beat(
+                time_passed.as_secs(),
+                sleep_duration.as_secs(),
+                &format!("waiting until {}", datetime.format("%Y-%m-%d %H:%M:%S")),
+            );
             if event::poll(interval)? {
                 if let Event::Key(key_event) = event::read()? {
                     if key_event.code == KeyCode::Char('r') || key_event.code == KeyCode::Char('R') {
@@ -277,6 +1389,64 @@ fn run_continuous_mode(config: &AppConfig, results_dir: &str) -> Result<(), Box<
     }
 }
 
+fn show_status(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
+    let heartbeat_path = files::heartbeat::heartbeat_path(config, results_dir);
+    let heartbeat = match files::heartbeat::read_heartbeat(&heartbeat_path) {
+        Ok(heartbeat) => heartbeat,
+        Err(e) => return show_latest_run_status(results_dir)
+            .ok_or_else(|| format!("No heartbeat found at {}: {}", heartbeat_path.display(), e).into()),
+    };
+    
+    let age = heartbeat.age_seconds().unwrap_or(i64::MAX);
+    
+    println!("Phase:       {}", heartbeat.phase);
+    println!("Progress:    {}/{}", heartbeat.position, heartbeat.total);
+    println!("Message:     {}", heartbeat.message);
+    println!("Run ID:      {}", heartbeat.run_id);
+    println!("Process:     {}", heartbeat.pid);
+    println!("Results dir: {}", heartbeat.results_dir);
+    println!("Last beat:   {} ({}s ago)", heartbeat.timestamp, age);
+    if !heartbeat.progress_at.is_empty() {
+        println!("Progressed:  {}", heartbeat.progress_at);
+    }
+    if let Some(load) = &heartbeat.load {
+        println!("Server load: {} (read {})", load.summary(), load.read_at);
+    }
+    
+    // Record counts come from the event log, so they're right even while query files are being rewritten
+    let statuses = files::events::replayed_status_summary(&heartbeat.results_dir).unwrap_or_default();
+    if !statuses.is_empty() {
+        let counts: Vec<String> = statuses.iter().map(|(status, count)| format!("{} {}", count, status)).collect();
+        println!("Records:     {}", counts.join(", "));
+    }
+    
+    if heartbeat.phase == files::heartbeat::FINISHED_PHASE {
+        println!("\x1b[32mLast batch finished normally\x1b[0m");
+    } else if age > config.heartbeat_stale_seconds as i64 {
+        println!("\x1b[31mHeartbeat is stale: not written for {}s (limit {}s)\x1b[0m", age, config.heartbeat_stale_seconds);
+        log::warn!("Heartbeat stale for {}s in phase {}", age, heartbeat.phase);
+        return Err(format!("Heartbeat stale for {}s", age).into());
+    } else {
+        println!("\x1b[32mBatch is alive\x1b[0m");
+    }
+    
+    Ok(())
+}
+
+// Record counts of the run `latest` points to, for status without a heartbeat to read
+fn show_latest_run_status(results_dir: &str) -> Option<()> {
+    if files::latest::latest_results_dir().as_deref() != Some(results_dir) {
+        return None;
+    }
+    
+    println!("No heartbeat; showing the latest run");
+    println!("Results dir: {}", results_dir);
+    let statuses = files::events::replayed_status_summary(results_dir).unwrap_or_default();
+    let counts: Vec<String> = statuses.iter().map(|(status, count)| format!("{} {}", count, status)).collect();
+    println!("Records:     {}", if counts.is_empty() { "none recorded".to_string() } else { counts.join(", ") });
+    Some(())
+}
+
 // Add to main.rs
 
 fn setup_test_data(config: &AppConfig, count: usize) -> Result<(), Box<dyn Error>> {
@@ -314,24 +1484,33 @@ fn clean_test_data(config: &AppConfig) -> Result<(), Box<dyn Error>> {
 fn update_county_codes(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
     println!("Starting County Code Update Phase");
     log::info!("Starting County Code Update Phase");
+    files::heartbeat::set_phase("update-county-codes");
+    
+    // Load processed records so applied changes are tracked
+    let mut processed_records = ProcessedRecords::load(&config.data_path);
     
     // Create database connection
     let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Execution", config.execution_autocommit, &config.execution_isolation)?;
     
     // Create progress bar
     let progress_bar = create_progress_bar("Updating County Codes");
     
     // First, find records with mismatched county codes and generate update queries
-    let (checked_count, mismatch_count) = db::query::update_county_by_zip(
+    let (_, mismatch_count) = db::query::update_county_by_zip(
         &connection, config, results_dir, &progress_bar
     )?;
     
+    // Counts of the execution, when there was one, for success_criteria
+    let mut executed = None;
     if mismatch_count > 0 {
         println!("Found {} records with mismatched county codes", mismatch_count);
         log::info!("Found {} records with mismatched county codes", mismatch_count);
         
         // Execute the update queries
-        let (success_count, error_count) = execute_queries(&connection, results_dir, &progress_bar)?;
+        let metrics = execute_queries(&connection, config, results_dir, &mut processed_records, &ExecutionOptions::default(), &progress_bar)?;
+        let (success_count, error_count) = (metrics.succeeded, metrics.failed);
+        processed_records.save(&config.data_path)?;
         
         progress_bar.finish_with_message(
             format!("Updated county codes: {} successful, {} failed", success_count, error_count)
@@ -339,21 +1518,34 @@ fn update_county_codes(config: &AppConfig, results_dir: &str) -> Result<(), Box<
         
         println!("Updated county codes: {} successful, {} failed", success_count, error_count);
         log::info!("Updated county codes: {} successful, {} failed", success_count, error_count);
+        executed = Some(metrics);
     } else {
         progress_bar.finish_with_message("No county code updates needed");
         println!("No county code updates needed. All records have correct county codes.");
         log::info!("No county code updates needed. All records have correct county codes.");
     }
     
+    write_county_results(results_dir)?;
+    
+    // Judged once the sheet is written, so a failing run still leaves it for review
+    if let Some(metrics) = executed {
+        db::query::check_success_criteria(config, &metrics)?;
+    }
+    
     Ok(())
 }
 
 fn update_county_code_from_countyfp(config: &AppConfig, results_dir: &str) -> Result<(), Box<dyn Error>> {
     println!("Starting County Code Update from FIPS Phase");
     log::info!("Starting County Code Update from FIPS Phase");
+    files::heartbeat::set_phase("update-county-code-from-countyfp");
+    
+    // Load processed records so applied changes are tracked
+    let mut processed_records = ProcessedRecords::load(&config.data_path);
     
     // Create database connection
     let connection = create_connection(config)?;
+    apply_session_settings(&connection, config, "Execution", config.execution_autocommit, &config.execution_isolation)?;
     
     // Create progress bar
     let progress_bar = create_progress_bar("Updating County Codes from FIPS");
@@ -363,6 +1555,8 @@ fn update_county_code_from_countyfp(config: &AppConfig, results_dir: &str) -> Re
         &connection, config, results_dir, &progress_bar
     )?;
     
+    // Counts of the execution, when there was one, for success_criteria
+    let mut executed = None;
     if updated_count > 0 {
         println!("Generated {} county code update queries from {} records", updated_count, checked_count);
         log::info!("Generated {} county code update queries from {} records", updated_count, checked_count);
@@ -371,7 +1565,9 @@ fn update_county_code_from_countyfp(config: &AppConfig, results_dir: &str) -> Re
         let response = prompt_user("Do you want to execute the update queries now?");
         if response.to_uppercase().starts_with('Y') {
             // Execute the update queries
-            let (success_count, error_count) = execute_queries(&connection, results_dir, &progress_bar)?;
+            let metrics = execute_queries(&connection, config, results_dir, &mut processed_records, &ExecutionOptions::default(), &progress_bar)?;
+            let (success_count, error_count) = (metrics.succeeded, metrics.failed);
+            processed_records.save(&config.data_path)?;
             
             progress_bar.finish_with_message(
                 format!("Updated county codes: {} successful, {} failed", success_count, error_count)
@@ -379,6 +1575,7 @@ fn update_county_code_from_countyfp(config: &AppConfig, results_dir: &str) -> Re
             
             println!("Updated county codes: {} successful, {} failed", success_count, error_count);
             log::info!("Updated county codes: {} successful, {} failed", success_count, error_count);
+            executed = Some(metrics);
         } else {
             progress_bar.finish_with_message("Update queries generated but not executed");
             println!("Update queries have been generated but not executed. You can run 'execute' command later to apply them.");
@@ -390,5 +1587,41 @@ fn update_county_code_from_countyfp(config: &AppConfig, results_dir: &str) -> Re
         log::info!("No county code updates generated");
     }
     
+    write_county_results(results_dir)?;
+    
+    // Judged once the sheet is written, so a failing run still leaves it for review
+    if let Some(metrics) = executed {
+        db::query::check_success_criteria(config, &metrics)?;
+    }
+    
+    Ok(())
+}
+
+// Write the county results sheet for business owners, who review outcomes in Excel
+fn write_county_results(results_dir: &str) -> Result<(), Box<dyn Error>> {
+    let rows = files::county_results::write_county_results(results_dir)?;
+    if rows > 0 {
+        println!("Wrote {} county results to {}/{}", rows, results_dir, files::county_results::COUNTY_RESULTS_FILE_NAME);
+        log::info!("Wrote {} county results to {}/{}", rows, results_dir, files::county_results::COUNTY_RESULTS_FILE_NAME);
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    
+    #[test]
+    fn command_line_definition_is_consistent() {
+        // Catches flags that clash between the global options and a subcommand
+        Cli::command().debug_assert();
+    }
+    
+    #[test]
+    fn errors_takes_the_global_results_dir() {
+        let cli = Cli::try_parse_from(["informix-batch-processor", "errors", "--results-dir", "results_1", "--group-by", "sqlcode"]).unwrap();
+        assert_eq!(cli.results_dir.as_deref(), Some("results_1"));
+        assert!(matches!(cli.command, Some(Commands::Errors { ref group_by, .. }) if group_by == "sqlcode"));
+    }
+}
@@ -1,5 +1,18 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::AppConfig;
+use crate::files::heartbeat;
+use crate::utils::masking;
+
+/// Set from `quiet_progress`: per-record messages are skipped and only the position moves
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Apply the configured progress settings
+pub fn init(config: &AppConfig) {
+    QUIET.store(config.quiet_progress, Ordering::Relaxed);
+}
 
 /// Create a progress bar with the specified message
 pub fn create_progress_bar(message: &str) -> ProgressBar {
@@ -7,7 +20,7 @@ pub fn create_progress_bar(message: &str) -> ProgressBar {
     
     progress_bar.set_style(
         ProgressStyle::default_bar()
-            .template("{msg} [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
+            .template("{msg} [{bar:40.cyan/blue}] {pos}/{len} {prefix}({eta})")
             .unwrap()
             .progress_chars("=> ")
     );
@@ -17,32 +30,29 @@ pub fn create_progress_bar(message: &str) -> ProgressBar {
     progress_bar
 }
 
-/// Update the progress bar with a new message and position
-pub fn update_progress(
-    progress_bar: &ProgressBar,
-    message: &str,
-    position: u64,
-    length: u64
-) {
-    progress_bar.set_message(message.to_string());
-    progress_bar.set_position(position);
-    progress_bar.set_length(length);
+/// Update only the message of the progress bar without printing to console.
+/// With quiet_progress the bar keeps its phase message, which avoids a redraw per record.
+pub fn update_message(progress_bar: &ProgressBar, message: impl Display) {
+    if QUIET.load(Ordering::Relaxed) {
+        heartbeat::beat(progress_bar.position(), progress_bar.length().unwrap_or(0), &progress_bar.message());
+        return;
+    }
+    
+    let message = masking::mask(&message.to_string());
+    heartbeat::beat(progress_bar.position(), progress_bar.length().unwrap_or(0), &message);
+    progress_bar.set_message(message);
 }
 
-/// Update only the message of the progress bar without printing to console
-pub fn update_message(progress_bar: &ProgressBar, message: impl Display) {
-    progress_bar.set_message(message.to_string());
+/// Show the running total of rows changed next to the statement count, since a consolidated
+/// IN-list statement changes many rows
+pub fn update_rows_affected(progress_bar: &ProgressBar, rows_affected: u64) {
+    progress_bar.set_prefix(format!("{} rows affected ", rows_affected));
 }
 
 /// Print a message while temporarily suspending the progress bar
 /// Only use this for important summary messages, not for individual records
 pub fn print_with_progress(progress_bar: &ProgressBar, message: &str) {
     progress_bar.suspend(|| {
-        println!("{}", message);
+        println!("{}", masking::mask(message));
     });
 }
-
-/// Log an error message to the log file without printing to console
-pub fn log_error(message: &str) {
-    log::error!("{}", message);
-}
\ No newline at end of file
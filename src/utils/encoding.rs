@@ -0,0 +1,99 @@
+// src/utils/encoding.rs
+
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::config::AppConfig;
+
+// Character set of narrow (SQL_C_CHAR) text exchanged with the driver. Statements are
+// sent as UTF-16, so the driver converts them to the database locale on the way in; fetched
+// bytes and bound text parameters are converted here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientEncoding {
+    Utf8,
+    Latin1,
+    Windows1252,
+}
+
+static ENCODING: OnceLock<ClientEncoding> = OnceLock::new();
+static INVALID_UTF8_REPORTED: AtomicBool = AtomicBool::new(false);
+
+// CP1252 characters for bytes 0x80-0x9F; undefined positions keep their C1 code point
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl ClientEncoding {
+    // Parse a `client_encoding` setting, accepting the usual Informix and IANA names
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "" | "utf-8" | "utf8" => Some(ClientEncoding::Utf8),
+            "iso-8859-1" | "iso8859-1" | "8859-1" | "latin1" | "latin-1" => Some(ClientEncoding::Latin1),
+            "cp1252" | "windows-1252" | "1252" => Some(ClientEncoding::Windows1252),
+            _ => None,
+        }
+    }
+    
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            ClientEncoding::Utf8 => match std::str::from_utf8(bytes) {
+                Ok(text) => text.to_string(),
+                Err(_) => {
+                    if !INVALID_UTF8_REPORTED.swap(true, Ordering::Relaxed) {
+                        log::warn!("Fetched text is not valid UTF-8; set client_encoding to match the database locale");
+                    }
+                    String::from_utf8_lossy(bytes).to_string()
+                }
+            },
+            ClientEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            ClientEncoding::Windows1252 => bytes.iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect(),
+        }
+    }
+    
+    // Encode text for a narrow parameter; characters the encoding lacks become '?'
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            ClientEncoding::Utf8 => text.as_bytes().to_vec(),
+            ClientEncoding::Latin1 => text.chars()
+                .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+                .collect(),
+            ClientEncoding::Windows1252 => text.chars()
+                .map(|c| match WINDOWS_1252_HIGH.iter().position(|&high| high == c) {
+                    Some(index) => 0x80 + index as u8,
+                    None if (c as u32) < 0x80 || ((c as u32) >= 0xA0 && (c as u32) < 0x100) => c as u8,
+                    None => b'?',
+                })
+                .collect(),
+        }
+    }
+}
+
+// Install the process-wide client encoding from config. Later calls are ignored.
+pub fn init(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let encoding = ClientEncoding::from_name(&config.client_encoding)
+        .ok_or_else(|| format!(
+            "Unknown client_encoding '{}' (expected 'utf-8', 'iso-8859-1' or 'cp1252')",
+            config.client_encoding
+        ))?;
+    let _ = ENCODING.set(encoding);
+    Ok(())
+}
+
+// Decode fetched column bytes with the configured encoding (UTF-8 if not initialized)
+pub fn decode(bytes: &[u8]) -> String {
+    ENCODING.get().copied().unwrap_or(ClientEncoding::Utf8).decode(bytes)
+}
+
+// Encode text for a bound parameter with the configured encoding
+pub fn encode(text: &str) -> Vec<u8> {
+    ENCODING.get().copied().unwrap_or(ClientEncoding::Utf8).encode(text)
+}
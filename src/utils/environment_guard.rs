@@ -0,0 +1,63 @@
+// src/utils/environment_guard.rs
+
+use std::error::Error;
+
+use crate::config::AppConfig;
+use crate::utils::operator;
+
+// Refuse a command that changes data when the config belongs to another environment than --env
+// names, or to a protected environment without --env naming it, or on a host it doesn't allow.
+// `command` names the command when it changes data. Catches the production config run from a
+// laptop before anything connects.
+pub fn check(config: &AppConfig, requested: Option<&str>, command: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let requested = requested.map(str::trim).filter(|requested| !requested.is_empty());
+    let environment = match &config.environment {
+        Some(environment) => environment,
+        None => {
+            if let Some(requested) = requested {
+                return Err(format!(
+                    "--env {} given, but the configuration doesn't name its environment; add an [environment] section",
+                    requested
+                ).into());
+            }
+            return Ok(());
+        },
+    };
+    log::info!("Configured environment: {}", environment.name);
+    
+    if let Some(requested) = requested {
+        if !requested.eq_ignore_ascii_case(environment.name.trim()) {
+            return Err(format!(
+                "--env {} given, but the configuration connects to {}; refusing to run",
+                requested, environment.name
+            ).into());
+        }
+    }
+    let command = match command {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    
+    if environment.is_protected() && requested.is_none() {
+        return Err(format!(
+            "The configuration connects to {}, a protected environment; run {} with --env {} to confirm",
+            environment.name, command, environment.name
+        ).into());
+    }
+    
+    let host = operator::host_name();
+    let allowed = environment.allowed_hosts.iter().any(|allowed| allowed.trim().eq_ignore_ascii_case(&host));
+    // A protected environment allows no host until some are listed
+    if !allowed && (environment.is_protected() || !environment.allowed_hosts.is_empty()) {
+        return Err(format!(
+            "{} may not change {} from host {} (allowed_hosts: {})",
+            command,
+            environment.name,
+            host,
+            if environment.allowed_hosts.is_empty() { "none".to_string() } else { environment.allowed_hosts.join(", ") }
+        ).into());
+    }
+    
+    log::info!("Running {} against {} from host {}", command, environment.name, host);
+    Ok(())
+}
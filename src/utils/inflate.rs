@@ -0,0 +1,257 @@
+// src/utils/inflate.rs
+
+use std::error::Error;
+
+// Decoder for raw DEFLATE data (RFC 1951), for reading the zip containers .xlsx workbooks
+// come in. Decompression is all the xlsx reader needs, so it stays this small rather than
+// pulling in a compression crate; speed isn't a concern for files of a few megabytes.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order in which a dynamic block lists the code lengths of its code length alphabet
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// Reads the stream least significant bit first, as DEFLATE packs it
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32, Box<dyn Error>> {
+        while self.count < count {
+            let byte = *self.data.get(self.position).ok_or("compressed data ends unexpectedly")?;
+            self.position += 1;
+            self.buffer |= (byte as u64) << self.count;
+            self.count += 8;
+        }
+        let value = (self.buffer & ((1u64 << count) - 1)) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+    
+    // Stored blocks start at the next byte boundary
+    fn align_to_byte(&mut self) {
+        let partial = self.count % 8;
+        self.buffer >>= partial;
+        self.count -= partial;
+    }
+}
+
+// A canonical Huffman code: how many codes each length has, and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        
+        let mut offsets = [0u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+    
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Box<dyn Error>> {
+        // First code and symbol index of the current length
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code in compressed data".into())
+    }
+}
+
+// Decompress a raw DEFLATE stream, as stored in zip entries with method 8
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut reader = BitReader { data, position: 0, buffer: 0, count: 0 };
+    let mut output = Vec::with_capacity(data.len() * 4);
+    
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let length = reader.bits(16)?;
+                if length != !reader.bits(16)? & 0xFFFF {
+                    return Err("corrupt stored block in compressed data".into());
+                }
+                for _ in 0..length {
+                    output.push(reader.bits(8)? as u8);
+                }
+            },
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            },
+            _ => return Err("invalid block type in compressed data".into()),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5u8; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), Box<dyn Error>> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    
+    let mut code_lengths = [0u8; 19];
+    for &position in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[position] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+    
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or("repeated code length without a previous one")?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            18 => (0, 11 + reader.bits(7)?),
+            _ => return Err("invalid code length symbol in compressed data".into()),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err("code lengths overrun the alphabets in compressed data".into());
+    }
+    if lengths[256] == 0 {
+        return Err("compressed block has no end-of-block code".into());
+    }
+    
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), Box<dyn Error>> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = (symbol - 257) as usize;
+                if index >= LENGTH_BASE.len() {
+                    return Err("invalid length symbol in compressed data".into());
+                }
+                let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("invalid distance symbol in compressed data".into());
+                }
+                let distance = DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err("distance reaches before the start of compressed data".into());
+                }
+                
+                // Copies may overlap their own output, e.g. a run of one repeated byte
+                let start = output.len() - distance;
+                for offset in 0..length {
+                    let byte = output[start + offset];
+                    output.push(byte);
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Streams below were produced by zlib (raw DEFLATE, windowBits -15)
+    
+    #[test]
+    fn inflates_stored_block() {
+        let data = [
+            0x01, 0x0f, 0x00, 0xf0, 0xff, 0x4b, 0x69, 0x6e, 0x67, 0x20, 0x43, 0x6f, 0x75, 0x6e, 0x74, 0x79, 0x2c, 0x20, 0x57, 0x41,
+        ];
+        assert_eq!(inflate(&data).unwrap(), b"King County, WA");
+    }
+    
+    #[test]
+    fn inflates_fixed_block_with_back_references() {
+        let data = [0x4b, 0x4c, 0x4a, 0x4e, 0x44, 0x42, 0xba, 0xc9, 0xf9, 0xa5, 0x79, 0x25, 0x95, 0x00];
+        assert_eq!(inflate(&data).unwrap(), b"abcabcabcabcabc-county");
+    }
+    
+    #[test]
+    fn inflates_back_reference_overlapping_its_output() {
+        // One literal, then copies of length 258 and 41 at distance 1
+        let data = [0xab, 0xaa, 0x1a, 0x05, 0xc4, 0x02, 0x00];
+        assert_eq!(inflate(&data).unwrap(), vec![b'z'; 300]);
+    }
+    
+    #[test]
+    fn inflates_dynamic_block() {
+        let data = [
+            0x4d, 0xcc, 0xab, 0x0d, 0x03, 0x51, 0x0c, 0x45, 0x41, 0x9e, 0x5a, 0x16, 0xf8, 0xda, 0xef, 0xe7, 0x1a, 0xb6, 0x90, 0x55,
+            0x48, 0xfa, 0x87, 0x89, 0x6c, 0x45, 0x3a, 0x70, 0xc8, 0xe4, 0x31, 0xb3, 0xeb, 0x7e, 0x7f, 0x9e, 0xcb, 0x5e, 0xf9, 0x83,
+            0x1a, 0x2a, 0x78, 0xc3, 0x0b, 0xd1, 0x88, 0xc2, 0x68, 0x8c, 0xc2, 0x6c, 0xcc, 0xc2, 0x6a, 0xac, 0xc2, 0x66, 0x7d, 0x58,
+            0x27, 0x6a, 0x19, 0x6a, 0x09, 0xb5, 0x1c, 0xb5, 0x02, 0xb5, 0x06, 0x6a, 0x4d, 0xd4, 0x5a, 0xac, 0x37, 0xeb, 0xc3, 0x3a,
+            0x51, 0xbb, 0xa1, 0x76, 0xa1, 0x76, 0x47, 0xed, 0x81, 0xda, 0x07, 0x6a, 0x9f, 0xa8, 0x7d, 0xb1, 0xde, 0xac, 0x0f, 0xeb,
+            0x44, 0x1d, 0x86, 0x3a, 0x84, 0x3a, 0x1c, 0x75, 0x04, 0xea, 0x18, 0xa8, 0x63, 0xa2, 0x8e, 0xc5, 0x7a, 0xb3, 0x3e, 0xac,
+            0xf3, 0x5f, 0x7f, 0x01,
+        ];
+        assert_eq!(data[0] >> 1 & 3, 2, "fixture is a dynamic block");
+        let expected: String = (0..40).map(|i| format!("98{:03},King,{}\n", i, i % 7)).collect();
+        assert_eq!(inflate(&data).unwrap(), expected.as_bytes());
+    }
+    
+    #[test]
+    fn rejects_truncated_and_invalid_streams() {
+        assert!(inflate(&[0x4b, 0x4c, 0x4a]).is_err());
+        // Final block of the reserved type 3
+        assert!(inflate(&[0x07]).is_err());
+        // Stored block whose length and its complement disagree
+        assert!(inflate(&[0x01, 0x0f, 0x00, 0x00, 0x00]).is_err());
+        assert!(inflate(&[]).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+// src/utils/masking.rs
+
+use regex::Regex;
+use std::error::Error;
+use std::sync::OnceLock;
+
+use crate::config::AppConfig;
+
+const MASK: &str = "***";
+
+// Masks values of sensitive columns before they reach logs, progress output or error records
+pub struct Masker {
+    column_patterns: Vec<String>,
+    assignment_regex: Regex,
+    // Anything matching one of these is masked wherever it appears, e.g. a driver message
+    // echoing a literal
+    redaction_patterns: Vec<Regex>,
+}
+
+static MASKER: OnceLock<Masker> = OnceLock::new();
+
+impl Masker {
+    pub fn new(column_patterns: &[String]) -> Self {
+        Masker {
+            column_patterns: column_patterns.iter()
+                .map(|p| p.trim().to_lowercase())
+                .filter(|p| !p.is_empty())
+                .collect(),
+            // <column> <comparison> <quoted or numeric literal>
+            assignment_regex: Regex::new(
+                r"(?i)([A-Za-z_][A-Za-z0-9_.]*)(\s*(?:=|<>|!=|<=|>=|<|>)\s*|\s+LIKE\s+)('(?:[^']|'')*'|-?[0-9][0-9.\-/]*)"
+            ).expect("Invalid masking regex"),
+            redaction_patterns: Vec::new(),
+        }
+    }
+    
+    // Also mask every match of these regular expressions
+    pub fn with_redaction_patterns(mut self, patterns: &[String]) -> Result<Self, Box<dyn Error>> {
+        for pattern in patterns.iter().filter(|pattern| !pattern.trim().is_empty()) {
+            let regex = Regex::new(pattern).map_err(|e| format!("Invalid redaction pattern '{}': {}", pattern, e))?;
+            self.redaction_patterns.push(regex);
+        }
+        Ok(self)
+    }
+    
+    // Whether a column name matches one of the sensitive patterns
+    pub fn is_sensitive_column(&self, column: &str) -> bool {
+        let column = column.to_lowercase();
+        let column = column.rsplit('.').next().unwrap_or(&column);
+        self.column_patterns.iter().any(|pattern| column.contains(pattern.as_str()))
+    }
+    
+    // Mask a single value if it belongs to a sensitive column
+    pub fn mask_value(&self, column: &str, value: &str) -> String {
+        if self.is_sensitive_column(column) {
+            MASK.to_string()
+        } else {
+            value.to_string()
+        }
+    }
+    
+    // Replace literals compared against or assigned to sensitive columns in free text or SQL,
+    // then anything matching a redaction pattern
+    pub fn mask_text(&self, text: &str) -> String {
+        let mut text = if self.column_patterns.is_empty() {
+            text.to_string()
+        } else {
+            self.assignment_regex.replace_all(text, |caps: &regex::Captures| {
+                if self.is_sensitive_column(&caps[1]) {
+                    let masked = if caps[3].starts_with('\'') { format!("'{}'", MASK) } else { MASK.to_string() };
+                    format!("{}{}{}", &caps[1], &caps[2], masked)
+                } else {
+                    caps[0].to_string()
+                }
+            }).to_string()
+        };
+        
+        for pattern in &self.redaction_patterns {
+            text = pattern.replace_all(&text, MASK).to_string();
+        }
+        text
+    }
+}
+
+// Install the process-wide masker from config. Later calls are ignored.
+pub fn init(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let masker = Masker::new(&config.mask_column_patterns).with_redaction_patterns(&config.redaction_patterns)?;
+    let _ = MASKER.set(masker);
+    Ok(())
+}
+
+// Mask free text with the configured masker (unchanged if masking isn't initialized)
+pub fn mask(text: &str) -> String {
+    match MASKER.get() {
+        Some(masker) => masker.mask_text(text),
+        None => text.to_string(),
+    }
+}
+
+// Mask a column value with the configured masker
+pub fn mask_value(column: &str, value: &str) -> String {
+    match MASKER.get() {
+        Some(masker) => masker.mask_value(column, value),
+        None => value.to_string(),
+    }
+}
@@ -0,0 +1,62 @@
+// src/utils/memo.rs
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Memoized results of a per-row derivation, with hit and miss counts so the end-of-phase
+// log shows whether the cache earns its memory
+#[derive(Debug)]
+pub struct Memo<K, V> {
+    name: &'static str,
+    entries: HashMap<K, V>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<K: Eq + Hash, V> Memo<K, V> {
+    pub fn new(name: &'static str) -> Self {
+        Memo { name, entries: HashMap::new(), hits: 0, misses: 0 }
+    }
+    
+    // The cached value for `key`, computing and storing it on first use
+    pub fn get_or_insert_with(&mut self, key: K, compute: impl FnOnce(&K) -> V) -> &V {
+        if self.entries.contains_key(&key) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        self.entries.entry(key).or_insert_with_key(|key| compute(key))
+    }
+    
+    // Like get_or_insert_with, for a derivation that can fail; failures aren't cached
+    pub fn try_get_or_insert_with<E>(&mut self, key: K, compute: impl FnOnce(&K) -> Result<V, E>) -> Result<&V, E> {
+        if self.entries.contains_key(&key) {
+            self.hits += 1;
+            return Ok(&self.entries[&key]);
+        }
+        self.misses += 1;
+        let value = compute(&key)?;
+        Ok(self.entries.entry(key).or_insert(value))
+    }
+    
+    // Share of lookups answered from the cache, 0.0 before the first lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+    }
+    
+    // "zip lookups: 9950 hits, 50 misses (99.5% hit rate, 50 entries)"
+    pub fn summary(&self) -> String {
+        format!(
+            "{}: {} hits, {} misses ({:.1}% hit rate, {} entries)",
+            self.name, self.hits, self.misses, self.hit_rate() * 100.0, self.entries.len()
+        )
+    }
+    
+    // Log the counts, if the cache was used at all
+    pub fn log_metrics(&self) {
+        if self.hits + self.misses > 0 {
+            log::info!("Cache {}", self.summary());
+        }
+    }
+}
@@ -1,2 +1,16 @@
 // src/utils/mod.rs
-pub mod test_data;
\ No newline at end of file
+pub mod test_data;
+pub mod masking;
+pub mod encoding;
+pub mod notify;
+pub mod run_id;
+pub mod time;
+pub mod span;
+pub mod retry;
+pub mod operator;
+pub mod memo;
+pub mod version;
+pub mod inflate;
+pub mod environment_guard;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
@@ -0,0 +1,50 @@
+// src/utils/notify.rs
+
+use std::process::Command;
+
+use crate::config::AppConfig;
+use crate::utils::run_id;
+
+// Send an operator notification. It is always logged; when `notify_command` is set the
+// command is run through the shell with the subject, message and run ID in
+// IBP_NOTIFY_SUBJECT, IBP_NOTIFY_MESSAGE and IBP_RUN_ID. Failures to notify are logged and never interrupt the batch.
+pub fn notify(config: &AppConfig, subject: &str, message: &str) {
+    log::warn!("Notification: {}: {}", subject, message);
+    
+    if config.notify_command.trim().is_empty() {
+        return;
+    }
+    
+    let result = config.notification_retry.run(
+        "Notification command",
+        || run_notify_command(config, subject, message),
+        |_| true,
+    );
+    if let Err(e) = result {
+        log::warn!("{}", e);
+    }
+}
+
+// Run `notify_command` once; a non-zero exit counts as a failure
+fn run_notify_command(config: &AppConfig, subject: &str, message: &str) -> Result<(), String> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&config.notify_command);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&config.notify_command);
+        command
+    };
+    
+    match command
+        .env("IBP_NOTIFY_SUBJECT", subject)
+        .env("IBP_NOTIFY_MESSAGE", message)
+        .env("IBP_RUN_ID", run_id::current())
+        .status()
+    {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Notification command exited with {}", status)),
+        Err(e) => Err(format!("Failed to run notification command: {}", e)),
+    }
+}
@@ -0,0 +1,61 @@
+// src/utils/operator.rs
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::config::AppConfig;
+
+static OPERATOR: OnceLock<OperatorIdentity> = OnceLock::new();
+
+// Who ran this process and where, stamped into the manifest, the event log and executed records
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperatorIdentity {
+    // OS account the process runs as
+    pub user: String,
+    pub host: String,
+    // Person named with --operator (or `operator` in config), when it isn't the account owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+}
+
+impl fmt::Display for OperatorIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.operator {
+            Some(operator) => write!(f, "{} ({}@{})", operator, self.user, self.host),
+            None => write!(f, "{}@{}", self.user, self.host),
+        }
+    }
+}
+
+// Capture the identity once configuration (and --operator) is known
+pub fn init(config: &AppConfig) {
+    let operator = Some(config.operator.trim().to_string()).filter(|operator| !operator.is_empty());
+    let _ = OPERATOR.set(OperatorIdentity { user: os_user(), host: host_name(), operator });
+}
+
+// The identity of this run; without init, the OS user and host alone
+pub fn current() -> &'static OperatorIdentity {
+    OPERATOR.get_or_init(|| OperatorIdentity { user: os_user(), host: host_name(), operator: None })
+}
+
+// Name of the machine, from the environment the shells on both platforms set
+pub fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+fn os_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .or_else(|_| std::env::var("LOGNAME"))
+        .map(|user| user.trim().to_string())
+        .ok()
+        .filter(|user| !user.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
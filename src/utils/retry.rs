@@ -0,0 +1,127 @@
+// src/utils/retry.rs
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::thread;
+use std::time::Duration;
+
+// How an operation that can fail transiently is retried: up to `max_attempts` tries in
+// total, waiting `base_delay_ms` doubled after each failure (capped at `max_delay_ms`),
+// with up to `jitter` of each wait randomized so parallel workers don't retry in step.
+// Connection, execution and notification each have their own policy in config.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    // Fraction (0.0-1.0) of each delay that is randomized
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+    // SQLCODE or ISAM error numbers worth retrying; only consulted for database errors
+    #[serde(default = "default_retryable_errors")]
+    pub retryable_errors: Vec<i32>,
+    // SQLSTATEs worth retrying, matched as prefixes so "40" covers the whole class
+    #[serde(default = "default_retryable_sqlstates")]
+    pub retryable_sqlstates: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: default_max_attempts(),
+            base_delay_ms: default_base_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+            jitter: default_jitter(),
+            retryable_errors: default_retryable_errors(),
+            retryable_sqlstates: default_retryable_sqlstates(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // A policy with a different number of attempts and the default timing
+    pub fn with_attempts(max_attempts: u32) -> Self {
+        RetryPolicy { max_attempts, ..RetryPolicy::default() }
+    }
+    
+    // Whether a database error with this SQLSTATE or SQLCODE/ISAM error is worth retrying
+    pub fn is_retryable(&self, sqlstate: Option<&str>, sqlcode: Option<i32>, isam_error: Option<i32>) -> bool {
+        let state_retryable = sqlstate.is_some_and(|state| {
+            self.retryable_sqlstates.iter()
+                .any(|prefix| !prefix.trim().is_empty() && state.to_uppercase().starts_with(&prefix.trim().to_uppercase()))
+        });
+        state_retryable || [sqlcode, isam_error].iter()
+            .flatten()
+            .any(|code| self.retryable_errors.contains(code))
+    }
+    
+    // How long to wait after the given failed attempt (1-based)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(20);
+        let delay_ms = self.base_delay_ms.saturating_mul(1 << exponent).min(self.max_delay_ms) as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + jitter * rand::thread_rng().gen::<f64>();
+        Duration::from_millis((delay_ms * factor) as u64)
+    }
+    
+    // Run `operation` until it succeeds, fails with an error `is_retryable` rejects, or the
+    // attempts are used up. Each retry is logged with `description`.
+    pub fn run<T, E: Display>(
+        &self,
+        description: &str,
+        mut operation: impl FnMut() -> Result<T, E>,
+        is_retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Ok(value) => {
+                    if attempt > 1 {
+                        log::info!("{} succeeded on attempt {}", description, attempt);
+                    }
+                    return Ok(value);
+                },
+                Err(e) if attempt < self.max_attempts && is_retryable(&e) => {
+                    let delay = self.delay(attempt);
+                    log::warn!(
+                        "{} failed (attempt {} of {}), retrying in {:.1}s: {}",
+                        description, attempt, self.max_attempts, delay.as_secs_f64(), e
+                    );
+                    thread::sleep(delay);
+                    attempt += 1;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_max_delay_ms() -> u64 {
+    30000
+}
+
+fn default_jitter() -> f64 {
+    0.5
+}
+
+// Lock conflicts and deadlocks clear up on their own; other errors won't change on retry
+fn default_retryable_errors() -> Vec<i32> {
+    vec![-243, -244, -245, -246, -263, -107, -113, -143, -154]
+}
+
+// Transaction rollbacks (deadlocks, serialization failures) and timeouts
+fn default_retryable_sqlstates() -> Vec<String> {
+    vec!["40".to_string(), "HYT00".to_string()]
+}
@@ -0,0 +1,21 @@
+// src/utils/run_id.rs
+
+use rand::Rng;
+use std::sync::OnceLock;
+
+static RUN_ID: OnceLock<String> = OnceLock::new();
+
+// Generate a random (version 4) UUID
+fn new_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+// The ID of this run, generated on first use and stamped into every artifact it writes
+pub fn current() -> &'static str {
+    RUN_ID.get_or_init(new_uuid)
+}
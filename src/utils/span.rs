@@ -0,0 +1,95 @@
+// src/utils/span.rs
+
+use serde_json::json;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::AppConfig;
+use crate::utils::run_id;
+
+// The span log finished records are appended to, when `span_log_path` is set
+static SPAN_LOG: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+
+thread_local! {
+    // Key and table of the record being executed on this thread, added to every log line
+    static CURRENT: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+// Open the span log from config. Later calls are ignored.
+pub fn init(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let span_log = if config.span_log_path.is_empty() {
+        None
+    } else {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.span_log_path)
+            .map_err(|e| format!("Failed to open span log {}: {}", config.span_log_path, e))?;
+        Some(Mutex::new(file))
+    };
+    let _ = SPAN_LOG.set(span_log);
+    Ok(())
+}
+
+// " key=... table=..." for the record being executed, or an empty string outside a span
+pub fn context() -> String {
+    CURRENT.with(|current| match &*current.borrow() {
+        Some((key, table)) => format!(" [key={} table={}]", key, table),
+        None => String::new(),
+    })
+}
+
+// The execution of one query record. While it is open, log lines carry its key and table;
+// finishing it records the duration and, when configured, appends a line to the span log.
+pub struct RecordSpan {
+    key: String,
+    table: String,
+    started: Instant,
+    started_at: SystemTime,
+}
+
+impl RecordSpan {
+    pub fn enter(key: &str, table: &str) -> Self {
+        CURRENT.with(|current| *current.borrow_mut() = Some((key.to_string(), table.to_string())));
+        RecordSpan {
+            key: key.to_string(),
+            table: table.to_string(),
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+        }
+    }
+    
+    // Close the span with the record's outcome
+    pub fn finish(self, status: &str) {
+        let duration = self.started.elapsed();
+        log::debug!("Record {} finished as {} in {:.3}s", self.key, status, duration.as_secs_f64());
+        
+        if let Some(Some(span_log)) = SPAN_LOG.get() {
+            let start_nanos = self.started_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+            let span = json!({
+                "run_id": run_id::current(),
+                "key": self.key,
+                "table": self.table,
+                "status": status,
+                "start_time_unix_nano": start_nanos.to_string(),
+                "end_time_unix_nano": (start_nanos + duration.as_nanos()).to_string(),
+                "duration_ms": duration.as_secs_f64() * 1000.0,
+            });
+            if let Ok(mut file) = span_log.lock() {
+                if let Err(e) = writeln!(file, "{}", span) {
+                    log::warn!("Failed to write the span log line for key {}: {}", self.key, e);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RecordSpan {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = None);
+    }
+}
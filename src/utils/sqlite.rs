@@ -0,0 +1,175 @@
+// src/utils/sqlite.rs
+
+use std::error::Error;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::path::Path;
+use std::ptr;
+
+// The few entry points of the system SQLite library the state store needs
+#[allow(non_camel_case_types)]
+enum sqlite3 {}
+#[allow(non_camel_case_types)]
+enum sqlite3_stmt {}
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut sqlite3, flags: c_int, vfs: *const c_char) -> c_int;
+    fn sqlite3_close(db: *mut sqlite3) -> c_int;
+    fn sqlite3_busy_timeout(db: *mut sqlite3, milliseconds: c_int) -> c_int;
+    fn sqlite3_errmsg(db: *mut sqlite3) -> *const c_char;
+    fn sqlite3_exec(
+        db: *mut sqlite3,
+        sql: *const c_char,
+        callback: *mut c_void,
+        argument: *mut c_void,
+        error: *mut *mut c_char,
+    ) -> c_int;
+    fn sqlite3_free(pointer: *mut c_void);
+    fn sqlite3_prepare_v2(db: *mut sqlite3, sql: *const c_char, bytes: c_int, statement: *mut *mut sqlite3_stmt, tail: *mut *const c_char) -> c_int;
+    fn sqlite3_bind_text(statement: *mut sqlite3_stmt, index: c_int, text: *const c_char, bytes: c_int, destructor: isize) -> c_int;
+    fn sqlite3_bind_null(statement: *mut sqlite3_stmt, index: c_int) -> c_int;
+    fn sqlite3_step(statement: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_column_count(statement: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_column_text(statement: *mut sqlite3_stmt, column: c_int) -> *const u8;
+    fn sqlite3_column_bytes(statement: *mut sqlite3_stmt, column: c_int) -> c_int;
+    fn sqlite3_reset(statement: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_finalize(statement: *mut sqlite3_stmt) -> c_int;
+}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x02;
+const SQLITE_OPEN_CREATE: c_int = 0x04;
+// Tells SQLite to copy bound text before the call returns
+const SQLITE_TRANSIENT: isize = -1;
+
+// How long a statement waits for another process's write lock before failing
+const BUSY_TIMEOUT_MS: c_int = 30_000;
+
+// An open SQLite database file, closed when dropped
+pub struct Database {
+    handle: *mut sqlite3,
+}
+
+impl Database {
+    // Open a database file, creating it when it doesn't exist
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let filename = CString::new(path.to_string_lossy().as_bytes())?;
+        let mut handle = ptr::null_mut();
+        let result = unsafe {
+            sqlite3_open_v2(filename.as_ptr(), &mut handle, SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE, ptr::null())
+        };
+        // A handle comes back even when opening fails, to carry the message
+        let database = Database { handle };
+        if result != SQLITE_OK {
+            return Err(database.error(&format!("Failed to open {}", path.display())));
+        }
+        unsafe { sqlite3_busy_timeout(database.handle, BUSY_TIMEOUT_MS) };
+        Ok(database)
+    }
+    
+    // Run one or more statements separated by semicolons, without parameters or results
+    pub fn execute_batch(&self, sql: &str) -> Result<(), Box<dyn Error>> {
+        let sql = CString::new(sql)?;
+        let mut message = ptr::null_mut();
+        let result = unsafe { sqlite3_exec(self.handle, sql.as_ptr(), ptr::null_mut(), ptr::null_mut(), &mut message) };
+        if result == SQLITE_OK {
+            return Ok(());
+        }
+        
+        let error = if message.is_null() {
+            "unknown error".to_string()
+        } else {
+            let text = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+            unsafe { sqlite3_free(message as *mut c_void) };
+            text
+        };
+        Err(format!("SQLite statement failed: {}", error).into())
+    }
+    
+    pub fn prepare(&self, sql: &str) -> Result<Statement<'_>, Box<dyn Error>> {
+        let sql = CString::new(sql)?;
+        let mut handle = ptr::null_mut();
+        let result = unsafe { sqlite3_prepare_v2(self.handle, sql.as_ptr(), -1, &mut handle, ptr::null_mut()) };
+        if result != SQLITE_OK {
+            return Err(self.error("Failed to prepare SQLite statement"));
+        }
+        Ok(Statement { database: self, handle })
+    }
+    
+    fn error(&self, context: &str) -> Box<dyn Error> {
+        let message = if self.handle.is_null() {
+            "out of memory".to_string()
+        } else {
+            unsafe { CStr::from_ptr(sqlite3_errmsg(self.handle)) }.to_string_lossy().into_owned()
+        };
+        format!("{}: {}", context, message).into()
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        unsafe { sqlite3_close(self.handle) };
+    }
+}
+
+// A prepared statement; parameters are bound as text or NULL
+pub struct Statement<'a> {
+    database: &'a Database,
+    handle: *mut sqlite3_stmt,
+}
+
+impl Statement<'_> {
+    // Run the statement once with `parameters`, discarding any rows
+    pub fn execute(&mut self, parameters: &[Option<&str>]) -> Result<(), Box<dyn Error>> {
+        self.query(parameters, |_| {})
+    }
+    
+    // Run the statement with `parameters` and call `on_row` with each row's columns as text
+    pub fn query(&mut self, parameters: &[Option<&str>], mut on_row: impl FnMut(Vec<Option<String>>)) -> Result<(), Box<dyn Error>> {
+        self.bind(parameters)?;
+        let columns = unsafe { sqlite3_column_count(self.handle) };
+        let outcome = loop {
+            match unsafe { sqlite3_step(self.handle) } {
+                SQLITE_ROW => on_row((0..columns).map(|column| self.column_text(column)).collect()),
+                SQLITE_DONE => break Ok(()),
+                _ => break Err(self.database.error("SQLite statement failed")),
+            }
+        };
+        unsafe { sqlite3_reset(self.handle) };
+        outcome
+    }
+    
+    fn bind(&mut self, parameters: &[Option<&str>]) -> Result<(), Box<dyn Error>> {
+        for (index, parameter) in parameters.iter().enumerate() {
+            let index = index as c_int + 1;
+            let result = match parameter {
+                Some(text) => unsafe {
+                    sqlite3_bind_text(self.handle, index, text.as_ptr() as *const c_char, text.len() as c_int, SQLITE_TRANSIENT)
+                },
+                None => unsafe { sqlite3_bind_null(self.handle, index) },
+            };
+            if result != SQLITE_OK {
+                return Err(self.database.error(&format!("Failed to bind SQLite parameter {}", index)));
+            }
+        }
+        Ok(())
+    }
+    
+    fn column_text(&self, column: c_int) -> Option<String> {
+        let text = unsafe { sqlite3_column_text(self.handle, column) };
+        if text.is_null() {
+            return None;
+        }
+        let length = unsafe { sqlite3_column_bytes(self.handle, column) } as usize;
+        let bytes = unsafe { std::slice::from_raw_parts(text, length) };
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        unsafe { sqlite3_finalize(self.handle) };
+    }
+}
@@ -1,13 +1,13 @@
 // src/utils/test_data.rs
 
-use crate::zip_county_map::{load_zip_county_map, ZipCountyInfo};
-use odbc_api::{Connection, Environment};
+use crate::zip_county_map::zip_county_map;
+use odbc_api::Connection;
 use rand::prelude::*;
 use std::error::Error;
 
 pub fn generate_test_data(conn: &Connection, count: usize) -> Result<(), Box<dyn Error>> {
     // Load the zip-county mapping data
-    let zip_county_map = load_zip_county_map();
+    let zip_county_map = zip_county_map();
     let zip_codes: Vec<String> = zip_county_map.keys().cloned().collect();
     
     if zip_codes.is_empty() {
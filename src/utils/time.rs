@@ -0,0 +1,135 @@
+// src/utils/time.rs
+
+use chrono::prelude::*;
+use chrono::SecondsFormat;
+use std::error::Error;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use crate::config::AppConfig;
+
+// Timezone every timestamp the tool writes is expressed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timezone {
+    Utc,
+    Local,
+    Fixed(FixedOffset),
+}
+
+static TIMEZONE: OnceLock<Timezone> = OnceLock::new();
+
+impl Timezone {
+    // Parse a `timezone` setting: "utc", "local", or a fixed offset such as "-08:00"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        match name.to_lowercase().as_str() {
+            "" | "local" => Some(Timezone::Local),
+            "utc" | "z" | "gmt" => Some(Timezone::Utc),
+            _ => parse_offset(name).map(Timezone::Fixed),
+        }
+    }
+    
+    // Convert a UTC instant into this timezone
+    pub fn convert(&self, instant: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::Utc => instant.fixed_offset(),
+            Timezone::Local => instant.with_timezone(&Local).fixed_offset(),
+            Timezone::Fixed(offset) => instant.with_timezone(offset),
+        }
+    }
+}
+
+// Parse "+05:30", "-0800" or "+5" into an offset east of UTC
+fn parse_offset(text: &str) -> Option<FixedOffset> {
+    let (sign, digits) = match text.chars().next()? {
+        '+' => (1, &text[1..]),
+        '-' => (-1, &text[1..]),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    if digits.is_empty() || digits.len() > 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    
+    let (hours, minutes) = if digits.len() <= 2 {
+        (digits.parse::<i32>().ok()?, 0)
+    } else {
+        let split = digits.len() - 2;
+        (digits[..split].parse::<i32>().ok()?, digits[split..].parse::<i32>().ok()?)
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+// Install the process-wide timezone from config. Later calls are ignored.
+pub fn init(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let timezone = Timezone::from_name(&config.timezone)
+        .ok_or_else(|| format!(
+            "Unknown timezone '{}' (expected 'utc', 'local' or an offset like '-08:00')",
+            config.timezone
+        ))?;
+    let _ = TIMEZONE.set(timezone);
+    Ok(())
+}
+
+// The configured timezone (local time if not initialized)
+pub fn timezone() -> Timezone {
+    TIMEZONE.get().copied().unwrap_or(Timezone::Local)
+}
+
+// The current time in the configured timezone
+pub fn now() -> DateTime<FixedOffset> {
+    timezone().convert(Utc::now())
+}
+
+// The current time as RFC 3339 with an explicit offset ("Z" for UTC), for records and files
+pub fn now_rfc3339() -> String {
+    rfc3339(now())
+}
+
+// Format a timestamp as RFC 3339 to whole seconds, using "Z" for a zero offset
+pub fn rfc3339(datetime: DateTime<FixedOffset>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// A system time in the configured timezone, for scheduling messages and reports
+pub fn from_system_time(time: SystemTime) -> DateTime<FixedOffset> {
+    timezone().convert(DateTime::<Utc>::from(time))
+}
+
+// Parse a duration such as "90s", "45m", "2h" or "1h30m"; a bare number is seconds
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim().to_lowercase();
+    if let Ok(seconds) = text.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+    
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        
+        let unit = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(format!("invalid duration '{}': unexpected '{}'", text, c)),
+        };
+        let value: u64 = number.parse().map_err(|_| format!("invalid duration '{}': missing number before '{}'", text, c))?;
+        total += value * unit;
+        number.clear();
+    }
+    
+    if !number.is_empty() || total == 0 {
+        return Err(format!("invalid duration '{}' (expected e.g. 90s, 45m, 2h or 1h30m)", text));
+    }
+    
+    Ok(Duration::from_secs(total))
+}
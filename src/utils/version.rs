@@ -0,0 +1,9 @@
+// src/utils/version.rs
+
+// Crate version and the commit it was built from (see build.rs), e.g. "0.1.0 (3ff2016a9c1e)",
+// stamped into manifests, query records and the log so old results identify their build
+pub const BUILD: &str = concat!(env!("CARGO_PKG_VERSION"), " (", env!("GIT_HASH"), ")");
+
+pub fn build() -> String {
+    BUILD.to_string()
+}
@@ -1,16 +1,188 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::sync::OnceLock;
+
+use crate::config::{AppConfig, ZipMappingConfig};
+use crate::files::zip_mapping::{load_zip_mapping, normalize_header, normalize_zip, read_mapping_rows};
+
+static ZIP_COUNTY_MAP: OnceLock<HashMap<String, ZipCountyInfo>> = OnceLock::new();
+
+// State of the built-in mapping and county table; its 2-digit county codes mean nothing elsewhere
+pub const BUILT_IN_STATE: &str = "WA";
+
+// 2-digit state FIPS code -> postal code, for the states, DC and the territories
+const STATE_FIPS: &[(&str, &str)] = &[
+    ("01", "AL"), ("02", "AK"), ("04", "AZ"), ("05", "AR"), ("06", "CA"), ("08", "CO"), ("09", "CT"),
+    ("10", "DE"), ("11", "DC"), ("12", "FL"), ("13", "GA"), ("15", "HI"), ("16", "ID"), ("17", "IL"),
+    ("18", "IN"), ("19", "IA"), ("20", "KS"), ("21", "KY"), ("22", "LA"), ("23", "ME"), ("24", "MD"),
+    ("25", "MA"), ("26", "MI"), ("27", "MN"), ("28", "MS"), ("29", "MO"), ("30", "MT"), ("31", "NE"),
+    ("32", "NV"), ("33", "NH"), ("34", "NJ"), ("35", "NM"), ("36", "NY"), ("37", "NC"), ("38", "ND"),
+    ("39", "OH"), ("40", "OK"), ("41", "OR"), ("42", "PA"), ("44", "RI"), ("45", "SC"), ("46", "SD"),
+    ("47", "TN"), ("48", "TX"), ("49", "UT"), ("50", "VT"), ("51", "VA"), ("53", "WA"), ("54", "WV"),
+    ("55", "WI"), ("56", "WY"), ("60", "AS"), ("66", "GU"), ("69", "MP"), ("72", "PR"), ("78", "VI"),
+];
 
 #[derive(Debug)]
 pub struct ZipCountyInfo {
+    // Postal code of the state the zip is in; county codes are only unique within a state
+    pub state: String,
+    // 2-digit FIPS code of the state; empty when unknown
+    pub state_fips: String,
     pub county_code: String,
+    // Read from the mapping file and shown in debug output; no command uses them yet
+    #[allow(dead_code)]
     pub division: String,
     pub fips_code: String,  // This will now store just the 3-digit county FIPS code
+    #[allow(dead_code)]
     pub county_name: String, // Added county name field
 }
 
-pub fn load_zip_county_map() -> HashMap<String, ZipCountyInfo> {
+impl ZipCountyInfo {
+    // The county code qualified by its state, e.g. "WA-17"
+    pub fn namespaced_code(&self) -> String {
+        format!("{}-{}", self.state, self.county_code)
+    }
+    
+    // The 5-digit state + county FIPS code, e.g. "53033"; just the county's 3 digits when the
+    // state's FIPS code is unknown
+    pub fn full_fips_code(&self) -> String {
+        format!("{}{}", self.state_fips, self.fips_code)
+    }
+}
+
+// The zip-county mapping, built on first use and shared for the rest of the process
+pub fn zip_county_map() -> &'static HashMap<String, ZipCountyInfo> {
+    ZIP_COUNTY_MAP.get_or_init(load_zip_county_map)
+}
+
+// Replace the built-in mapping with the [zip_mapping] file, when one is configured
+pub fn init(config: &AppConfig) -> Result<(), Box<dyn Error>> {
+    let mapping = match &config.zip_mapping {
+        Some(mapping) => mapping,
+        None => return Ok(()),
+    };
+    let counties = county_to_info();
+    let map = match mapping.format.trim().to_lowercase().as_str() {
+        "" | "auto" => match load_hud_crosswalk(mapping, &counties)? {
+            Some(map) => map,
+            None => load_zip_mapping(mapping, &counties)?,
+        },
+        "hud" => load_hud_crosswalk(mapping, &counties)?.ok_or_else(|| format!(
+            "{}: not a HUD ZIP-county crosswalk (expected ZIP, COUNTY and RES_RATIO columns)", mapping.file
+        ))?,
+        "table" => load_zip_mapping(mapping, &counties)?,
+        other => return Err(format!("Unknown zip_mapping format '{}' (expected 'hud' or 'table')", other).into()),
+    };
+    ZIP_COUNTY_MAP.set(map).map_err(|_| "the zip-county mapping was used before it was loaded")?;
+    Ok(())
+}
+
+// Postal code of the state with this 2-digit FIPS code
+pub fn state_for_fips(state_fips: &str) -> Option<&'static str> {
+    STATE_FIPS.iter().find(|(fips, _)| *fips == state_fips).map(|(_, state)| *state)
+}
+
+// 2-digit FIPS code of the state with this postal code
+pub fn fips_for_state(state: &str) -> Option<&'static str> {
+    STATE_FIPS.iter().find(|(_, postal)| postal.eq_ignore_ascii_case(state.trim())).map(|(fips, _)| *fips)
+}
+
+// Load HUD's quarterly USPS ZIP-county crosswalk as published. It has a row per zip and county
+// it reaches, with the share of the zip's residential addresses in that county (RES_RATIO); each
+// zip goes to its dominant county, the one with the largest share, then the largest TOT_RATIO.
+// COUNTY is the 5-digit state + county FIPS code. The state comes from USPS_ZIP_PREF_STATE, or
+// from the FIPS code. Built-in state zips get their county code and name from `counties`; other
+// states have no county codes, so their 3-digit county FIPS code stands in. Returns None when
+// the file's header isn't the crosswalk's.
+pub fn load_hud_crosswalk(
+    config: &ZipMappingConfig,
+    counties: &HashMap<String, (String, String)>,
+) -> Result<Option<HashMap<String, ZipCountyInfo>>, Box<dyn Error>> {
+    let rows = read_mapping_rows(config)?;
+    let header = match rows.first() {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let column = |name: &str| header.iter().position(|cell| normalize_header(cell) == name);
+    let (zip_column, county_column, residential_column) = match (column("zip"), column("county"), column("resratio")) {
+        (Some(zip), Some(county), Some(residential)) => (zip, county, residential),
+        _ => return Ok(None),
+    };
+    let total_column = column("totratio");
+    let state_column = column("uspszipprefstate");
+    let cell = |row: &[String], index: Option<usize>| index.and_then(|index| row.get(index)).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+    let ratio = |row: &[String], index: Option<usize>| cell(row, index).and_then(|value| value.parse::<f64>().ok()).unwrap_or(0.0);
+    
+    // Zip -> (residential share, total share, 5-digit county FIPS, state) of its dominant county
+    let mut dominant: HashMap<String, (f64, f64, String, Option<String>)> = HashMap::new();
+    let mut counties_per_zip: HashMap<String, usize> = HashMap::new();
+    let mut skipped = 0;
+    for (line, row) in rows.iter().enumerate().skip(1) {
+        if row.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let zip = cell(row, Some(zip_column)).and_then(|zip| normalize_zip(&zip));
+        // A spreadsheet stores the FIPS code as a number and drops the state's leading zero
+        let county = cell(row, Some(county_column))
+            .filter(|county| county.len() <= 5 && county.chars().all(|c| c.is_ascii_digit()))
+            .map(|county| format!("{:0>5}", county));
+        let (zip, county) = match (zip, county) {
+            (Some(zip), Some(county)) => (zip, county),
+            _ => {
+                log::warn!("HUD crosswalk row {}: no valid zip or 5-digit county FIPS code, skipped", line + 1);
+                skipped += 1;
+                continue;
+            },
+        };
+        *counties_per_zip.entry(zip.clone()).or_default() += 1;
+        let candidate = (ratio(row, Some(residential_column)), ratio(row, total_column), county, cell(row, state_column));
+        match dominant.get(&zip) {
+            Some(current) if (current.0, current.1) >= (candidate.0, candidate.1) => {},
+            _ => {
+                dominant.insert(zip, candidate);
+            },
+        }
+    }
+    
     let mut map = HashMap::new();
+    for (zip, (_, _, county, state)) in dominant {
+        let state = match state.map(|state| state.to_uppercase()).or_else(|| state_for_fips(&county[..2]).map(str::to_string)) {
+            Some(state) => state,
+            None => {
+                log::warn!("HUD crosswalk: zip {} is in county {}, whose state FIPS code is unknown, skipped", zip, county);
+                skipped += 1;
+                continue;
+            },
+        };
+        let fips_code = county[2..].to_string();
+        let known = if state == BUILT_IN_STATE {
+            counties.iter().find(|(_, (fips, _))| *fips == fips_code)
+        } else {
+            None
+        };
+        map.insert(zip, ZipCountyInfo {
+            county_code: known.map(|(code, _)| code.clone()).unwrap_or_else(|| fips_code.clone()),
+            county_name: known.map(|(_, (_, name))| name.clone()).unwrap_or_default(),
+            division: String::new(),
+            state,
+            state_fips: county[..2].to_string(),
+            fips_code,
+        });
+    }
     
+    if map.is_empty() {
+        return Err(format!("{} holds no usable HUD crosswalk rows", config.file).into());
+    }
+    let split = counties_per_zip.values().filter(|count| **count > 1).count();
+    log::info!(
+        "Loaded {} zip codes from the HUD crosswalk {}; {} zips span several counties and went to the one with most residential addresses; {} rows skipped",
+        map.len(), config.file, split, skipped
+    );
+    Ok(Some(map))
+}
+
+// County code -> (3-digit county FIPS code, county name) for Washington's 39 counties (BUILT_IN_STATE)
+pub fn county_to_info() -> HashMap<String, (String, String)> {
     // Create a mapping of county_code to county name and FIPS code
     let mut county_to_info = HashMap::new();
     
@@ -61,6 +233,12 @@ pub fn load_zip_county_map() -> HashMap<String, ZipCountyInfo> {
     for (county_code, county_fips, county_name) in county_codes {
         county_to_info.insert(county_code, (county_fips, county_name));
     }
+    county_to_info
+}
+
+pub fn load_zip_county_map() -> HashMap<String, ZipCountyInfo> {
+    let mut map = HashMap::new();
+    let county_to_info = county_to_info();
     
     // Load the hardcoded zip:county_code:division data
     let zip_data = vec![
@@ -134,6 +312,8 @@ pub fn load_zip_county_map() -> HashMap<String, ZipCountyInfo> {
             };
             
             map.insert(zip, ZipCountyInfo { 
+                state: BUILT_IN_STATE.to_string(),
+                state_fips: fips_for_state(BUILT_IN_STATE).unwrap_or_default().to_string(),
                 county_code,
                 division,
                 fips_code,
@@ -144,39 +324,3 @@ pub fn load_zip_county_map() -> HashMap<String, ZipCountyInfo> {
     
     map
 }
-
-// Get the county code for a zip plus 4 by matching first 5 digits
-pub fn get_county_code_for_zip(zip_plus_4: &str, zip_county_map: &HashMap<String, ZipCountyInfo>) -> Option<String> {
-    // Extract first 5 digits of zip_plus_4
-    if zip_plus_4.len() >= 5 {
-        let zip5 = &zip_plus_4[0..5];
-        if let Some(info) = zip_county_map.get(zip5) {
-            return Some(info.county_code.clone());
-        }
-    }
-    None
-}
-
-// Get the FIPS code for a zip plus 4 by matching first 5 digits
-pub fn get_fips_code_for_zip(zip_plus_4: &str, zip_county_map: &HashMap<String, ZipCountyInfo>) -> Option<String> {
-    // Extract first 5 digits of zip_plus_4
-    if zip_plus_4.len() >= 5 {
-        let zip5 = &zip_plus_4[0..5];
-        if let Some(info) = zip_county_map.get(zip5) {
-            return Some(info.fips_code.clone());
-        }
-    }
-    None
-}
-
-// Get the county name for a zip plus 4 by matching first 5 digits
-pub fn get_county_name_for_zip(zip_plus_4: &str, zip_county_map: &HashMap<String, ZipCountyInfo>) -> Option<String> {
-    // Extract first 5 digits of zip_plus_4
-    if zip_plus_4.len() >= 5 {
-        let zip5 = &zip_plus_4[0..5];
-        if let Some(info) = zip_county_map.get(zip5) {
-            return Some(info.county_name.clone());
-        }
-    }
-    None
-}
\ No newline at end of file